@@ -170,6 +170,7 @@ static jmethodID method_onServerPhyUpdate;
 static jmethodID method_onServerPhyRead;
 static jmethodID method_onServerConnUpdate;
 static jmethodID method_onServerSubrateChange;
+static jmethodID method_onServerLinkDropped;
 
 /**
  * Advertiser callback methods
@@ -664,8 +665,9 @@ void btgatts_service_added_cb(int status, int server_if,
       auto& curr_service = service[i];
       service_records.push_back(bluetooth::gatt::GattRecord{
           curr_service.uuid, (bluetooth::gatt::GattRecordType)curr_service.type,
-          curr_service.attribute_handle, curr_service.properties,
-          curr_service.extended_properties, curr_service.permissions});
+          curr_service.attribute_handle, curr_service.start_handle,
+          curr_service.properties, curr_service.extended_properties,
+          curr_service.permissions});
     }
     bluetooth::gatt::add_service(server_if, std::move(service_records));
   }
@@ -853,6 +855,15 @@ void btgatts_subrate_change_cb(int conn_id, uint16_t subrate_factor,
                                timeout, status);
 }
 
+void btgatts_link_dropped_cb(int conn_id, uint8_t status) {
+  std::shared_lock<std::shared_mutex> lock(callbacks_mutex);
+  CallbackEnv sCallbackEnv(__func__);
+  if (!sCallbackEnv.valid() || !mCallbacksObj) return;
+
+  sCallbackEnv->CallVoidMethod(mCallbacksObj, method_onServerLinkDropped,
+                               conn_id, status);
+}
+
 static const btgatt_server_callbacks_t sGattServerCallbacks = {
     btgatts_register_app_cb,
     btgatts_connection_cb,
@@ -871,6 +882,7 @@ static const btgatt_server_callbacks_t sGattServerCallbacks = {
     btgatts_phy_updated_cb,
     btgatts_conn_updated_cb,
     btgatts_subrate_change_cb,
+    btgatts_link_dropped_cb,
 };
 
 /**
@@ -1344,6 +1356,8 @@ static void classInitNative(JNIEnv* env, jclass clazz) {
       env->GetMethodID(clazz, "onServerConnUpdate", "(IIIII)V");
   method_onServerSubrateChange =
       env->GetMethodID(clazz, "onServerSubrateChange", "(IIIIII)V");
+  method_onServerLinkDropped =
+      env->GetMethodID(clazz, "onServerLinkDropped", "(II)V");
 
   info("classInitNative: Success!");
 }
@@ -2019,6 +2033,10 @@ static void gattServerConnectNative(JNIEnv* env, jobject object, jint server_if,
   if (!sGattIf) return;
 
   RawAddress bd_addr = str2addr(env, address);
+  // a server reaching out over BR/EDR accepts ATT bearers on classic links
+  if (transport == BT_TRANSPORT_BR_EDR) {
+    bluetooth::gatt::expose_server_over_br_edr(server_if);
+  }
   sGattIf->server->connect(server_if, bd_addr, is_direct, transport);
 }
 
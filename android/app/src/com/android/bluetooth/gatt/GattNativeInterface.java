@@ -304,6 +304,10 @@ public class GattNativeInterface {
         getGattService().onMtuChanged(connId, mtu);
     }
 
+    void onServerLinkDropped(int connId, int status) {
+        getGattService().onServerLinkDropped(connId, status);
+    }
+
     /* Native methods */
     private static native void classInitNative();
     private native void initializeNative();
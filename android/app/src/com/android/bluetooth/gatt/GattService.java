@@ -272,6 +272,12 @@ public class GattService extends ProfileService {
      */
     private final Map<Integer, Set<Integer>> mRestrictedHandles = new HashMap<>();
 
+    /**
+     * Status to report with the disconnection of a server connection whose link was dropped by the
+     * server itself (e.g. on an ATT timeout), per connectionId.
+     */
+    private final Map<Integer, Integer> mServerLinkDropStatus = new HashMap<>();
+
     /**
      * HashMap used to synchronize writeCharacteristic calls mapping remote device address to
      * available permit (connectId or -1).
@@ -4281,6 +4287,7 @@ public class GattService extends ProfileService {
                             + connected);
         }
 
+        Integer linkDropStatus = connected ? null : mServerLinkDropStatus.remove(connId);
         ServerMap.App app = mServerMap.getById(serverIf);
         if (app == null) {
             return;
@@ -4303,7 +4310,8 @@ public class GattService extends ProfileService {
           Log.d(TAG, "onClientConnected() uid_not_found=" + app.name);
         }
 
-        app.callback.onServerConnectionState((byte) 0, serverIf, connected, address);
+        int status = linkDropStatus != null ? linkDropStatus : BluetoothGatt.GATT_SUCCESS;
+        app.callback.onServerConnectionState(status, serverIf, connected, address);
         statsLogAppPackage(address, applicationUid, serverIf);
         statsLogGattConnectionStateChange(
                 BluetoothProfile.GATT_SERVER, address, serverIf, connectionState, -1);
@@ -4486,6 +4494,15 @@ public class GattService extends ProfileService {
         app.callback.onMtuChanged(address, mtu);
     }
 
+    void onServerLinkDropped(int connId, int status) {
+        if (DBG) {
+            Log.d(TAG, "onServerLinkDropped() - connId=" + connId + ", status=" + status);
+        }
+
+        // reported once the disconnection itself arrives through onClientConnected()
+        mServerLinkDropStatus.put(connId, status);
+    }
+
     /**************************************************************************
      * GATT Service functions - SERVER
      *************************************************************************/
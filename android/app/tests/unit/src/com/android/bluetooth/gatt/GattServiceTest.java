@@ -649,6 +649,29 @@ public class GattServiceTest {
         verify(mNativeInterface).gattServerDisconnect(serverIf, address, connId);
     }
 
+    @Test
+    public void serverLinkDropped_statusReportedOnDisconnection() throws Exception {
+        int serverIf = 1;
+        int connId = 2;
+        int status = 0x08; // GATT_CONN_TIMEOUT
+        String address = REMOTE_DEVICE_ADDRESS;
+        GattService.ServerMap.App app = mock(GattService.ServerMap.App.class);
+        IBluetoothGattServerCallback callback = mock(IBluetoothGattServerCallback.class);
+        app.callback = callback;
+        app.name = "com.android.test";
+        doReturn(app).when(mServerMap).getById(serverIf);
+
+        mService.onServerLinkDropped(connId, status);
+        mService.onClientConnected(address, false, connId, serverIf);
+        verify(callback).onServerConnectionState(status, serverIf, false, address);
+
+        // only the disconnection of the dropped link carries the status
+        mService.onClientConnected(address, true, connId, serverIf);
+        mService.onClientConnected(address, false, connId, serverIf);
+        verify(callback).onServerConnectionState(
+                BluetoothGatt.GATT_SUCCESS, serverIf, false, address);
+    }
+
     @Test
     public void serverSetPreferredPhy() throws Exception {
         int serverIf = 1;
@@ -590,6 +590,7 @@ static void bta_dm_process_remove_device_no_callback(
   } else {
     BTM_SecDeleteDevice(bd_addr);
   }
+  gatt_notify_bond_removed(bd_addr);
 
   /* remove all cached GATT information */
   BTA_GATTC_Refresh(bd_addr);
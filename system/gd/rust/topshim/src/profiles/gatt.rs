@@ -1870,6 +1870,8 @@ impl Gatt {
             phy_updated_cb: Some(gs_phy_updated_cb),
             conn_updated_cb: Some(gs_conn_updated_cb),
             subrate_chg_cb: Some(gs_subrate_chg_cb),
+            // Only sent by the Rust GATT server, which is not used on Floss.
+            link_dropped_cb: None,
         });
 
         let gatt_scanner_callbacks = Box::new(btgatt_scanner_callbacks_t {
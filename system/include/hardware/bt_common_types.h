@@ -63,7 +63,8 @@ typedef struct {
   /*
    * If |type| is |BTGATT_DB_PRIMARY_SERVICE|, or
    * |BTGATT_DB_SECONDARY_SERVICE|, this contains the start and end attribute
-   * handles.
+   * handles. If |type| is |BTGATT_DB_INCLUDED_SERVICE|, once the service is
+   * added, this contains those of the included service.
    */
   uint16_t start_handle;
   uint16_t end_handle;
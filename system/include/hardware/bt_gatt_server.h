@@ -123,6 +123,11 @@ typedef void (*conn_updated_callback)(int conn_id, uint16_t interval,
 typedef void (*subrate_change_callback)(int conn_id, uint16_t subrate_factor,
                                         uint16_t latency, uint16_t cont_num,
                                         uint16_t timeout, uint8_t status);
+
+/** Callback invoked when the server itself drops the link of a connection
+ * (e.g. on an ATT timeout), before the disconnection is reported through
+ * connection_cb. |status| is the tGATT_DISCONN_REASON explaining why. */
+typedef void (*link_dropped_callback)(int conn_id, uint8_t status);
 typedef struct {
   register_server_callback register_server_cb;
   connection_callback connection_cb;
@@ -141,6 +146,7 @@ typedef struct {
   phy_updated_callback phy_updated_cb;
   conn_updated_callback conn_updated_cb;
   subrate_change_callback subrate_chg_cb;
+  link_dropped_callback link_dropped_cb;
 } btgatt_server_callbacks_t;
 
 /** Represents the standard BT-GATT server interface. */
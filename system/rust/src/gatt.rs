@@ -4,6 +4,7 @@
 pub mod arbiter;
 pub mod callbacks;
 pub mod channel;
+pub mod client;
 pub mod ffi;
 pub mod ids;
 pub mod mocks;
@@ -1,7 +1,10 @@
 //! This module handles "arbitration" of ATT packets, to determine whether they
 //! should be handled by the primary stack or by the "Private GATT" stack
 
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
 
 use log::{error, info};
 
@@ -12,25 +15,48 @@ use crate::{
 
 use super::{
     ffi::{InterceptAction, StoreCallbacksFromRust},
-    ids::{AdvertiserId, ConnectionId, ServerId, TransportIndex},
+    ids::{
+        AdvertiserId, BearerId, ConnectionId, EnhancedChannelId, PeerIdentity, ServerId,
+        TransportIndex,
+    },
+    server::gatt_database::ConnectionSecurity,
 };
 
 static ARBITER: Mutex<Option<Arbiter>> = Mutex::new(None);
 
 /// This class is responsible for tracking which connections and advertising we
 /// own, and using this information to decide what packets should be
-/// intercepted, and which should be forwarded to the legacy stack.
+/// intercepted, and which should be forwarded to the legacy stack. Connections
+/// are tracked by transport, so LE and BR/EDR links are isolated alike, and
+/// packets are routed to the bearer (fixed or enhanced channel) they came on.
+///
+/// A BR/EDR link carries a single ATT bearer with no advertiser to tell
+/// servers apart, so only one server at a time can be linked to BR/EDR, and
+/// only once it opts in. Otherwise BR/EDR links stay with the legacy stack.
 #[derive(Default)]
 pub struct Arbiter {
     advertiser_to_server: HashMap<AdvertiserId, ServerId>,
     transport_to_owned_connection: HashMap<TransportIndex, ConnectionId>,
+    owned_enhanced_channels: HashSet<(TransportIndex, EnhancedChannelId)>,
+    pending_indications: HashSet<TransportIndex>,
+    br_edr_server: Option<ServerId>,
 }
 
 /// Initialize the Arbiter
 pub fn initialize_arbiter() {
     *ARBITER.lock().unwrap() = Some(Arbiter::new());
 
-    StoreCallbacksFromRust(on_le_connect, on_le_disconnect, intercept_packet);
+    StoreCallbacksFromRust(
+        on_le_connect,
+        on_le_disconnect,
+        on_mtu_exchanged,
+        on_br_edr_connect,
+        on_br_edr_disconnect,
+        on_security_changed,
+        on_bonded_peer,
+        on_bond_removed,
+        intercept_packet,
+    );
 }
 
 /// Acquire the mutex holding the Arbiter and provide a mutable reference to the
@@ -45,6 +71,9 @@ impl Arbiter {
         Arbiter {
             advertiser_to_server: HashMap::new(),
             transport_to_owned_connection: HashMap::new(),
+            owned_enhanced_channels: HashSet::new(),
+            pending_indications: HashSet::new(),
+            br_edr_server: None,
         }
     }
 
@@ -63,10 +92,25 @@ impl Arbiter {
         }
     }
 
-    /// Remove all linked advertising sets from the provided server
+    /// Link a given GATT server to BR/EDR, so incoming ATT channels on classic
+    /// links will be visible only by the linked server
+    pub fn associate_server_with_br_edr(&mut self, server_id: ServerId) {
+        info!("associating server {server_id:?} with BR/EDR");
+        let old = self.br_edr_server.replace(server_id);
+        if let Some(old) = old.filter(|old| *old != server_id) {
+            error!(
+                "new server {server_id:?} associated with BR/EDR, displacing old server {old:?}"
+            );
+        }
+    }
+
+    /// Remove all linked advertising sets (and BR/EDR) from the provided server
     pub fn clear_server(&mut self, server_id: ServerId) {
         info!("clearing advertisers associated with {server_id:?}");
         self.advertiser_to_server.retain(|_, server| *server != server_id);
+        if self.br_edr_server == Some(server_id) {
+            self.br_edr_server = None;
+        }
     }
 
     /// Clear the server associated with this advertiser, if one exists
@@ -80,26 +124,85 @@ impl Arbiter {
         self.transport_to_owned_connection.values().any(|owned_conn_id| *owned_conn_id == conn_id)
     }
 
-    /// Test to see if a buffer contains a valid ATT packet with an opcode we
-    /// are interested in intercepting
+    /// Get the conn_id owned by the Rust stack on this transport, if any
+    pub fn get_owned_connection(&self, tcb_idx: TransportIndex) -> Option<ConnectionId> {
+        self.transport_to_owned_connection.get(&tcb_idx).copied()
+    }
+
+    /// Track an enhanced channel opened on this transport and return the
+    /// owning conn_id, if the link is owned by the Rust stack
+    pub fn on_enhanced_channel_opened(
+        &mut self,
+        tcb_idx: TransportIndex,
+        cid: EnhancedChannelId,
+    ) -> Option<ConnectionId> {
+        let conn_id = self.get_owned_connection(tcb_idx)?;
+        info!("enhanced channel {cid:?} opened on {conn_id:?}");
+        self.owned_enhanced_channels.insert((tcb_idx, cid));
+        Some(conn_id)
+    }
+
+    /// Stop tracking an enhanced channel and return the owning conn_id, if
+    /// the channel was owned by the Rust stack
+    pub fn on_enhanced_channel_closed(
+        &mut self,
+        tcb_idx: TransportIndex,
+        cid: EnhancedChannelId,
+    ) -> Option<ConnectionId> {
+        if !self.owned_enhanced_channels.remove(&(tcb_idx, cid)) {
+            return None;
+        }
+        info!("enhanced channel {cid:?} closed on transport {tcb_idx:?}");
+        self.get_owned_connection(tcb_idx)
+    }
+
+    /// Record that the GATT server sent an indication on the fixed channel of
+    /// the given transport, so the confirmation should be intercepted
+    pub fn on_indication_sent(&mut self, tcb_idx: TransportIndex) {
+        self.pending_indications.insert(tcb_idx);
+    }
+
+    /// Test to see if a buffer received on the given bearer contains a valid
+    /// ATT packet with an opcode we are interested in intercepting
     pub fn try_parse_att_server_packet(
-        &self,
+        &mut self,
         tcb_idx: TransportIndex,
+        bearer: BearerId,
         packet: Box<[u8]>,
     ) -> Option<(OwnedAttView, ConnectionId)> {
         let conn_id = *self.transport_to_owned_connection.get(&tcb_idx)?;
+        if let BearerId::Enhanced(cid) = bearer {
+            if !self.owned_enhanced_channels.contains(&(tcb_idx, cid)) {
+                return None;
+            }
+        }
 
         let att = OwnedAttView::try_parse(packet).ok()?;
 
         match att.view().get_opcode() {
+            // the MTU exchange is left to the legacy stack, which keeps its own
+            // copy of the ATT_MTU and reports the result via on_mtu_exchanged
             AttOpcode::FIND_INFORMATION_REQUEST
             | AttOpcode::FIND_BY_TYPE_VALUE_REQUEST
             | AttOpcode::READ_BY_TYPE_REQUEST
             | AttOpcode::READ_REQUEST
             | AttOpcode::READ_BLOB_REQUEST
             | AttOpcode::READ_MULTIPLE_REQUEST
+            | AttOpcode::READ_MULTIPLE_VARIABLE_REQUEST
             | AttOpcode::READ_BY_GROUP_TYPE_REQUEST
-            | AttOpcode::WRITE_REQUEST => Some((att, conn_id)),
+            | AttOpcode::WRITE_REQUEST
+            | AttOpcode::PREPARE_WRITE_REQUEST
+            | AttOpcode::EXECUTE_WRITE_REQUEST
+            | AttOpcode::WRITE_COMMAND
+            | AttOpcode::SIGNED_WRITE_COMMAND => Some((att, conn_id)),
+            // the legacy stack also indicates on the fixed channel (e.g. Service
+            // Changed), so only confirmations of our own indications are taken
+            AttOpcode::HANDLE_VALUE_CONFIRMATION
+                if matches!(bearer, BearerId::Enhanced(_))
+                    || self.pending_indications.remove(&tcb_idx) =>
+            {
+                Some((att, conn_id))
+            }
             _ => None,
         }
     }
@@ -128,6 +231,31 @@ impl Arbiter {
     /// Handle a disconnection and return the disconnected conn_id, if any
     pub fn on_le_disconnect(&mut self, tcb_idx: TransportIndex) -> Option<ConnectionId> {
         info!("processing disconnection on transport {tcb_idx:?}");
+        self.owned_enhanced_channels.retain(|(idx, _)| *idx != tcb_idx);
+        self.pending_indications.remove(&tcb_idx);
+        self.transport_to_owned_connection.remove(&tcb_idx)
+    }
+
+    /// Check if an incoming BR/EDR ATT channel should be intercepted and, if
+    /// so, on what conn_id
+    pub fn on_br_edr_connect(&mut self, tcb_idx: TransportIndex) -> Option<ConnectionId> {
+        info!("processing incoming BR/EDR connection on transport {tcb_idx:?}");
+        let server_id = self.br_edr_server?;
+        info!("connection is isolated to server {server_id:?}");
+
+        let conn_id = ConnectionId::new(tcb_idx, server_id);
+        let old = self.transport_to_owned_connection.insert(tcb_idx, conn_id);
+        if old.is_some() {
+            error!("new server {server_id:?} on transport {tcb_idx:?} displacing existing registered connection {conn_id:?}")
+        }
+        Some(conn_id)
+    }
+
+    /// Handle a BR/EDR ATT channel closing and return the disconnected
+    /// conn_id, if any
+    pub fn on_br_edr_disconnect(&mut self, tcb_idx: TransportIndex) -> Option<ConnectionId> {
+        info!("processing BR/EDR disconnection on transport {tcb_idx:?}");
+        self.pending_indications.remove(&tcb_idx);
         self.transport_to_owned_connection.remove(&tcb_idx)
     }
 }
@@ -153,13 +281,103 @@ fn on_le_disconnect(tcb_idx: u8) {
     }
 }
 
+fn on_mtu_exchanged(tcb_idx: u8, mtu: u16) {
+    if let Some(conn_id) =
+        with_arbiter(|arbiter| arbiter.get_owned_connection(TransportIndex(tcb_idx)))
+    {
+        do_in_rust_thread(move |modules| {
+            if let Err(err) = modules.gatt_module.on_mtu_exchanged(conn_id, mtu.into()) {
+                error!("{err:?}")
+            }
+        })
+    }
+}
+
+fn on_br_edr_connect(tcb_idx: u8, mtu: u16) {
+    if let Some(conn_id) =
+        with_arbiter(|arbiter| arbiter.on_br_edr_connect(TransportIndex(tcb_idx)))
+    {
+        do_in_rust_thread(move |modules| {
+            if let Err(err) = modules.gatt_module.on_br_edr_connect(conn_id, mtu.into()) {
+                error!("{err:?}")
+            }
+        })
+    }
+}
+
+fn on_br_edr_disconnect(tcb_idx: u8) {
+    if let Some(conn_id) =
+        with_arbiter(|arbiter| arbiter.on_br_edr_disconnect(TransportIndex(tcb_idx)))
+    {
+        do_in_rust_thread(move |modules| {
+            modules.gatt_module.on_br_edr_disconnect(conn_id);
+        })
+    }
+}
+
+fn on_security_changed(
+    tcb_idx: u8,
+    encrypted: bool,
+    key_size: u8,
+    authenticated: bool,
+    bonded: bool,
+) {
+    if let Some(conn_id) =
+        with_arbiter(|arbiter| arbiter.get_owned_connection(TransportIndex(tcb_idx)))
+    {
+        let security = ConnectionSecurity {
+            encrypted,
+            key_size,
+            bonded,
+            authenticated,
+            // the stack has no notion of authorization, which is left to the
+            // upper layer when it handles each access
+            authorized: false,
+        };
+        do_in_rust_thread(move |modules| {
+            if let Err(err) = modules.gatt_module.on_security_changed(conn_id, security) {
+                error!("{err:?}")
+            }
+        })
+    }
+}
+
+fn on_bonded_peer(tcb_idx: u8, peer: [u8; 6]) {
+    if let Some(conn_id) =
+        with_arbiter(|arbiter| arbiter.get_owned_connection(TransportIndex(tcb_idx)))
+    {
+        do_in_rust_thread(move |modules| {
+            if let Err(err) = modules.gatt_module.on_bonded_peer(conn_id, PeerIdentity(peer)) {
+                error!("{err:?}")
+            }
+        })
+    }
+}
+
+fn on_bond_removed(peer: [u8; 6]) {
+    do_in_rust_thread(move |modules| {
+        if let Err(err) = modules.gatt_module.on_bond_removed(PeerIdentity(peer)) {
+            error!("{err:?}")
+        }
+    })
+}
+
 fn intercept_packet(tcb_idx: u8, packet: Vec<u8>) -> InterceptAction {
+    // the legacy stack only hands us packets from the fixed channel, as
+    // enhanced channels are still owned by its EATT module
+    let bearer = BearerId::Unenhanced;
     if let Some((att, conn_id)) = with_arbiter(|arbiter| {
-        arbiter.try_parse_att_server_packet(TransportIndex(tcb_idx), packet.into_boxed_slice())
+        arbiter.try_parse_att_server_packet(
+            TransportIndex(tcb_idx),
+            bearer,
+            packet.into_boxed_slice(),
+        )
     }) {
         do_in_rust_thread(move |modules| {
-            info!("pushing packet to GATT");
-            if let Err(err) = modules.gatt_module.handle_packet(conn_id, att.view()) {
+            info!("pushing packet to GATT on {bearer:?}");
+            if let Err(err) =
+                modules.gatt_module.handle_packet_on_bearer(conn_id, bearer, att.view())
+            {
                 error!("{:?}", err.context("failed to push packet to GATT"))
             }
         });
@@ -175,7 +393,12 @@ mod test {
 
     use crate::{
         gatt::ids::AttHandle,
-        packets::{AttBuilder, AttReadRequestBuilder, Serializable},
+        packets::{
+            AttAttributeDataChild, AttBuilder, AttExchangeMtuRequestBuilder,
+            AttHandleValueConfirmationBuilder, AttReadRequestBuilder, AttWriteCommandBuilder,
+            Serializable,
+        },
+        utils::packet::build_att_data,
     };
 
     const TCB_IDX: TransportIndex = TransportIndex(1);
@@ -186,6 +409,11 @@ mod test {
 
     const ANOTHER_ADVERTISER_ID: AdvertiserId = AdvertiserId(4);
 
+    const BR_EDR_TCB_IDX: TransportIndex = TransportIndex(5);
+    const ANOTHER_SERVER_ID: ServerId = ServerId(6);
+
+    const CID: EnhancedChannelId = EnhancedChannelId(0x40);
+
     #[test]
     fn test_non_isolated_connect() {
         let mut arbiter = Arbiter::new();
@@ -300,6 +528,27 @@ mod test {
         assert!(is_isolated)
     }
 
+    #[test]
+    fn test_owned_connection() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+
+        let conn_id = arbiter.get_owned_connection(TCB_IDX);
+
+        assert_eq!(conn_id, Some(CONN_ID));
+    }
+
+    #[test]
+    fn test_no_owned_connection_when_not_isolated() {
+        let mut arbiter = Arbiter::new();
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+
+        let conn_id = arbiter.get_owned_connection(TCB_IDX);
+
+        assert!(conn_id.is_none());
+    }
+
     #[test]
     fn test_packet_capture_when_isolated() {
         let mut arbiter = Arbiter::new();
@@ -310,11 +559,125 @@ mod test {
             _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
         };
 
-        let out = arbiter.try_parse_att_server_packet(TCB_IDX, packet.to_vec().unwrap().into());
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
 
         assert!(matches!(out, Some((_, CONN_ID))));
     }
 
+    #[test]
+    fn test_mtu_exchange_bypass_when_isolated() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::EXCHANGE_MTU_REQUEST,
+            _child_: AttExchangeMtuRequestBuilder { mtu: 64 }.into(),
+        };
+
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(out.is_none());
+    }
+
+    #[test]
+    fn test_write_command_capture_when_isolated() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::WRITE_COMMAND,
+            _child_: AttWriteCommandBuilder {
+                handle: AttHandle(1).into(),
+                value: build_att_data(AttAttributeDataChild::RawData([1, 2].into())),
+            }
+            .into(),
+        };
+
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(matches!(out, Some((_, CONN_ID))));
+    }
+
+    #[test]
+    fn test_confirmation_bypass_without_pending_indication() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::HANDLE_VALUE_CONFIRMATION,
+            _child_: AttHandleValueConfirmationBuilder {}.into(),
+        };
+
+        // a confirmation of an indication sent by the legacy stack
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(out.is_none());
+    }
+
+    #[test]
+    fn test_confirmation_capture_with_pending_indication() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        arbiter.on_indication_sent(TCB_IDX);
+        let packet = AttBuilder {
+            opcode: AttOpcode::HANDLE_VALUE_CONFIRMATION,
+            _child_: AttHandleValueConfirmationBuilder {}.into(),
+        };
+
+        let first = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+        let second = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(matches!(first, Some((_, CONN_ID))));
+        assert!(second.is_none());
+    }
+
+    #[test]
+    fn test_pending_indication_cleared_on_disconnection() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        arbiter.on_indication_sent(TCB_IDX);
+        arbiter.on_le_disconnect(TCB_IDX);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::HANDLE_VALUE_CONFIRMATION,
+            _child_: AttHandleValueConfirmationBuilder {}.into(),
+        };
+
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(out.is_none());
+    }
+
     #[test]
     fn test_packet_bypass_when_not_isolated() {
         let mut arbiter = Arbiter::new();
@@ -325,7 +688,11 @@ mod test {
             _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
         };
 
-        let out = arbiter.try_parse_att_server_packet(TCB_IDX, packet.to_vec().unwrap().into());
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
 
         assert!(out.is_none());
     }
@@ -341,7 +708,11 @@ mod test {
         };
         arbiter.clear_advertiser(ADVERTISER_ID);
 
-        let out = arbiter.try_parse_att_server_packet(TCB_IDX, packet.to_vec().unwrap().into());
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
 
         assert!(matches!(out, Some((_, CONN_ID))));
     }
@@ -357,7 +728,11 @@ mod test {
         };
         arbiter.clear_server(SERVER_ID);
 
-        let out = arbiter.try_parse_att_server_packet(TCB_IDX, packet.to_vec().unwrap().into());
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
 
         assert!(matches!(out, Some((_, CONN_ID))));
     }
@@ -387,4 +762,203 @@ mod test {
         assert!(conn_id.is_none());
         assert!(!arbiter.is_connection_isolated(CONN_ID));
     }
+
+    #[test]
+    fn test_non_isolated_br_edr_connect() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+
+        let conn_id = arbiter.on_br_edr_connect(BR_EDR_TCB_IDX);
+
+        assert!(conn_id.is_none())
+    }
+
+    #[test]
+    fn test_isolated_br_edr_connect() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_br_edr(SERVER_ID);
+
+        let conn_id = arbiter.on_br_edr_connect(BR_EDR_TCB_IDX);
+
+        assert_eq!(conn_id, Some(ConnectionId::new(BR_EDR_TCB_IDX, SERVER_ID)));
+    }
+
+    #[test]
+    fn test_br_edr_packet_bypass_without_opt_in() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.associate_server_with_advertiser(ANOTHER_SERVER_ID, ANOTHER_ADVERTISER_ID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::READ_REQUEST,
+            _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
+        };
+
+        let conn_id = arbiter.on_br_edr_connect(BR_EDR_TCB_IDX);
+        let out = arbiter.try_parse_att_server_packet(
+            BR_EDR_TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(conn_id.is_none());
+        assert!(out.is_none());
+    }
+
+    #[test]
+    fn test_br_edr_exposed_by_second_server() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.associate_server_with_br_edr(ANOTHER_SERVER_ID);
+
+        let conn_id = arbiter.on_br_edr_connect(BR_EDR_TCB_IDX);
+
+        assert_eq!(conn_id, Some(ConnectionId::new(BR_EDR_TCB_IDX, ANOTHER_SERVER_ID)));
+    }
+
+    #[test]
+    fn test_br_edr_server_closed() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_br_edr(SERVER_ID);
+        arbiter.clear_server(SERVER_ID);
+
+        let conn_id = arbiter.on_br_edr_connect(BR_EDR_TCB_IDX);
+
+        assert!(conn_id.is_none())
+    }
+
+    #[test]
+    fn test_isolation_across_transports() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.associate_server_with_br_edr(ANOTHER_SERVER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let br_edr_conn_id = arbiter.on_br_edr_connect(BR_EDR_TCB_IDX).unwrap();
+        let packet = AttBuilder {
+            opcode: AttOpcode::READ_REQUEST,
+            _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
+        };
+
+        let le_out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+        let br_edr_out = arbiter.try_parse_att_server_packet(
+            BR_EDR_TCB_IDX,
+            BearerId::Unenhanced,
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(matches!(le_out, Some((_, CONN_ID))));
+        assert!(matches!(br_edr_out, Some((_, conn_id)) if conn_id == br_edr_conn_id));
+        assert_eq!(br_edr_conn_id.get_server_id(), ANOTHER_SERVER_ID);
+    }
+
+    #[test]
+    fn test_not_isolated_after_br_edr_disconnection() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_br_edr(SERVER_ID);
+        let conn_id = arbiter.on_br_edr_connect(BR_EDR_TCB_IDX).unwrap();
+
+        let disconnected = arbiter.on_br_edr_disconnect(BR_EDR_TCB_IDX);
+
+        assert_eq!(disconnected, Some(conn_id));
+        assert!(!arbiter.is_connection_isolated(conn_id));
+    }
+
+    #[test]
+    fn test_packet_capture_on_owned_enhanced_channel() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let opened = arbiter.on_enhanced_channel_opened(TCB_IDX, CID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::READ_REQUEST,
+            _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
+        };
+
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Enhanced(CID),
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert_eq!(opened, Some(CONN_ID));
+        assert!(matches!(out, Some((_, CONN_ID))));
+    }
+
+    #[test]
+    fn test_packet_bypass_on_unknown_enhanced_channel() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::READ_REQUEST,
+            _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
+        };
+
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Enhanced(CID),
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(out.is_none());
+    }
+
+    #[test]
+    fn test_enhanced_channel_not_owned_when_not_isolated() {
+        let mut arbiter = Arbiter::new();
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+
+        let opened = arbiter.on_enhanced_channel_opened(TCB_IDX, CID);
+        let closed = arbiter.on_enhanced_channel_closed(TCB_IDX, CID);
+
+        assert!(opened.is_none());
+        assert!(closed.is_none());
+    }
+
+    #[test]
+    fn test_enhanced_channel_closed() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        arbiter.on_enhanced_channel_opened(TCB_IDX, CID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::READ_REQUEST,
+            _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
+        };
+
+        let closed = arbiter.on_enhanced_channel_closed(TCB_IDX, CID);
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Enhanced(CID),
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert_eq!(closed, Some(CONN_ID));
+        assert!(out.is_none());
+    }
+
+    #[test]
+    fn test_enhanced_channels_dropped_after_disconnection() {
+        let mut arbiter = Arbiter::new();
+        arbiter.associate_server_with_advertiser(SERVER_ID, ADVERTISER_ID);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        arbiter.on_enhanced_channel_opened(TCB_IDX, CID);
+        arbiter.on_le_disconnect(TCB_IDX);
+        arbiter.on_le_connect(TCB_IDX, ADVERTISER_ID);
+        let packet = AttBuilder {
+            opcode: AttOpcode::READ_REQUEST,
+            _child_: AttReadRequestBuilder { attribute_handle: AttHandle(1).into() }.into(),
+        };
+
+        let out = arbiter.try_parse_att_server_packet(
+            TCB_IDX,
+            BearerId::Enhanced(CID),
+            packet.to_vec().unwrap().into(),
+        );
+
+        assert!(out.is_none());
+    }
 }
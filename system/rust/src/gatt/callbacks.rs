@@ -10,7 +10,10 @@ use async_trait::async_trait;
 
 use crate::packets::{AttAttributeDataChild, AttAttributeDataView, AttErrorCode};
 
-use super::ids::{AttHandle, ConnectionId, TransactionId};
+use super::{
+    channel::DisconnectReason,
+    ids::{AttHandle, ConnectionId, TransactionId},
+};
 
 /// These callbacks are expected to be made available to the GattModule from
 /// JNI.
@@ -39,6 +42,47 @@ pub trait GattCallbacks {
         is_prepare: bool,
         value: AttAttributeDataView,
     );
+
+    /// Invoked when a client writes a descriptor owned by the GattDatabase
+    /// (e.g. a CCCD), once the write has been applied. If need_response is
+    /// unset, no response is expected.
+    #[allow(clippy::too_many_arguments)] // needed to match the C++ interface
+    fn on_server_write_descriptor(
+        &self,
+        conn_id: ConnectionId,
+        trans_id: TransactionId,
+        handle: AttHandle,
+        offset: u32,
+        need_response: bool,
+        is_prepare: bool,
+        value: AttAttributeDataView,
+    );
+
+    /// Invoked when a client executes or cancels the writes previously
+    /// prepared on a connection (i.e. sent with is_prepare set). Expects a
+    /// response using bluetooth::gatt::send_response();
+    fn on_server_execute_write(
+        &self,
+        conn_id: ConnectionId,
+        trans_id: TransactionId,
+        decision: TransactionDecision,
+    );
+
+    /// Invoked when the ATT_MTU of a connection changes
+    fn on_mtu_changed(&self, conn_id: ConnectionId, mtu: usize);
+
+    /// Invoked when the server drops the link of a connection, before the
+    /// disconnection itself is reported
+    fn on_link_dropped(&self, conn_id: ConnectionId, reason: DisconnectReason);
+}
+
+/// Whether the writes prepared on a connection should be applied or discarded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TransactionDecision {
+    /// Apply all the prepared writes
+    Execute,
+    /// Discard all the prepared writes
+    Cancel,
 }
 
 /// This interface is an "async" version of the above, and is passed directly
@@ -52,11 +96,23 @@ pub trait GattDatastore {
     /// pending transactions can be dropped.
     fn remove_connection(&self, conn_id: ConnectionId);
 
-    /// Read a characteristic from the specified connection at the given handle.
+    /// Invoked when an MTU exchange changes the ATT_MTU of a connection
+    fn on_mtu_changed(&self, conn_id: ConnectionId, mtu: usize);
+
+    /// Invoked when the server drops the link of a connection, e.g. after an
+    /// ATT timeout. The connection is removed once the link disconnects.
+    fn on_link_dropped(&self, conn_id: ConnectionId, reason: DisconnectReason);
+
+    /// Read a characteristic from the specified connection at the given handle,
+    /// starting at the given offset into its value. is_long is set for
+    /// ATT_READ_BLOB_REQ, in which case only the part of the value from the
+    /// offset is expected.
     async fn read_characteristic(
         &self,
         conn_id: ConnectionId,
         handle: AttHandle,
+        offset: usize,
+        is_long: bool,
     ) -> Result<AttAttributeDataChild, AttErrorCode>;
 
     /// Write data to a given characteristic on the specified connection.
@@ -66,4 +122,41 @@ pub trait GattDatastore {
         handle: AttHandle,
         data: AttAttributeDataView<'_>,
     ) -> Result<(), AttErrorCode>;
+
+    /// Write data to a given characteristic on the specified connection,
+    /// without waiting for the result (e.g. for ATT_WRITE_CMD).
+    fn write_no_response_characteristic(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    );
+
+    /// Report a write to a descriptor owned by the GattDatabase (e.g. a CCCD)
+    /// on the specified connection, once it has been applied. The upper
+    /// layer cannot reject it.
+    fn write_no_response_descriptor(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    );
+
+    /// Prepare a write of data at the given offset into a characteristic on
+    /// the specified connection. It should only be applied once
+    /// execute_write() is invoked with TransactionDecision::Execute.
+    async fn prepare_write_characteristic(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode>;
+
+    /// Apply or discard all the writes prepared on the specified connection.
+    async fn execute_write(
+        &self,
+        conn_id: ConnectionId,
+        decision: TransactionDecision,
+    ) -> Result<(), AttErrorCode>;
 }
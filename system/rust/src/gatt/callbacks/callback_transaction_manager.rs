@@ -6,13 +6,14 @@ use tokio::sync::oneshot;
 
 use crate::{
     gatt::{
+        channel::DisconnectReason,
         ids::{AttHandle, ConnectionId, TransactionId},
         GattCallbacks,
     },
     packets::{AttAttributeDataChild, AttAttributeDataView, AttErrorCode},
 };
 
-use super::GattDatastore;
+use super::{GattDatastore, TransactionDecision};
 
 struct PendingTransaction {
     response: oneshot::Sender<Result<AttAttributeDataChild, AttErrorCode>>,
@@ -121,15 +122,31 @@ impl GattDatastore for CallbackTransactionManager {
         assert!(old_conn.is_some(), "Received unexpected connection ID, something has gone wrong")
     }
 
+    fn on_mtu_changed(&self, conn_id: ConnectionId, mtu: usize) {
+        self.callbacks.on_mtu_changed(conn_id, mtu)
+    }
+
+    fn on_link_dropped(&self, conn_id: ConnectionId, reason: DisconnectReason) {
+        self.callbacks.on_link_dropped(conn_id, reason)
+    }
+
     async fn read_characteristic(
         &self,
         conn_id: ConnectionId,
         handle: AttHandle,
+        offset: usize,
+        is_long: bool,
     ) -> Result<AttAttributeDataChild, AttErrorCode> {
         let (trans_id, rx) =
             self.pending_transactions.borrow_mut().start_new_transaction(conn_id)?;
 
-        self.callbacks.on_server_read_characteristic(conn_id, trans_id, handle, 0, false);
+        self.callbacks.on_server_read_characteristic(
+            conn_id,
+            trans_id,
+            handle,
+            offset as u32,
+            is_long,
+        );
 
         if let Ok(value) = rx.await {
             value
@@ -159,4 +176,89 @@ impl GattDatastore for CallbackTransactionManager {
             Err(AttErrorCode::UNLIKELY_ERROR)
         }
     }
+
+    fn write_no_response_characteristic(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    ) {
+        let mut pending = self.pending_transactions.borrow_mut();
+        if !pending.pending_transactions.contains_key(&conn_id) {
+            warn!("dropping write command since connection {conn_id:?} is down");
+            return;
+        }
+        // no response is expected, so the transaction is not tracked
+        let trans_id = TransactionId(pending.next_transaction_id);
+        pending.next_transaction_id += 1;
+        drop(pending);
+
+        self.callbacks
+            .on_server_write_characteristic(conn_id, trans_id, handle, 0, false, false, data);
+    }
+
+    fn write_no_response_descriptor(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    ) {
+        let mut pending = self.pending_transactions.borrow_mut();
+        if !pending.pending_transactions.contains_key(&conn_id) {
+            warn!("dropping descriptor write since connection {conn_id:?} is down");
+            return;
+        }
+        // no response is expected, so the transaction is not tracked
+        let trans_id = TransactionId(pending.next_transaction_id);
+        pending.next_transaction_id += 1;
+        drop(pending);
+
+        self.callbacks.on_server_write_descriptor(conn_id, trans_id, handle, 0, false, false, data);
+    }
+
+    async fn prepare_write_characteristic(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        let (trans_id, rx) =
+            self.pending_transactions.borrow_mut().start_new_transaction(conn_id)?;
+
+        self.callbacks.on_server_write_characteristic(
+            conn_id,
+            trans_id,
+            handle,
+            offset as u32,
+            true,
+            true,
+            data,
+        );
+
+        if let Ok(value) = rx.await {
+            value.map(|_| ())
+        } else {
+            warn!("sender side of {trans_id:?} dropped while handling request - most likely this response will not be sent over the air");
+            Err(AttErrorCode::UNLIKELY_ERROR)
+        }
+    }
+
+    async fn execute_write(
+        &self,
+        conn_id: ConnectionId,
+        decision: TransactionDecision,
+    ) -> Result<(), AttErrorCode> {
+        let (trans_id, rx) =
+            self.pending_transactions.borrow_mut().start_new_transaction(conn_id)?;
+
+        self.callbacks.on_server_execute_write(conn_id, trans_id, decision);
+
+        if let Ok(value) = rx.await {
+            value.map(|_| ())
+        } else {
+            warn!("sender side of {trans_id:?} dropped while handling request - most likely this response will not be sent over the air");
+            Err(AttErrorCode::UNLIKELY_ERROR)
+        }
+    }
 }
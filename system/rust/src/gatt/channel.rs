@@ -3,7 +3,20 @@
 
 use crate::packets::{AttBuilder, SerializeError};
 
-use super::ids::TransportIndex;
+use super::ids::{EnhancedChannelId, TransportIndex};
+
+/// Why the GATT server dropped a link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// A request was not answered within the ATT timeout (5.3 3F 3.3.3), e.g.
+    /// because the GattDatastore never replied
+    TransactionTimeout,
+    /// An indication was not confirmed within the ATT timeout (5.3 3F 3.3.3)
+    ConfirmationTimeout,
+    /// The peer sent a request while another was still outstanding on the same
+    /// bearer, violating 5.3 3F 3.3.2
+    ProtocolViolation,
+}
 
 /// An instance of this trait will be provided to the GattModule on
 /// initialization.
@@ -19,4 +32,34 @@ pub trait AttTransport {
         tcb_idx: TransportIndex,
         packet: AttBuilder,
     ) -> Result<(), SerializeError>;
+
+    /// Serializes and sends a packet on the given enhanced channel of the
+    /// transport. As with send_packet, the packet may be silently dropped if
+    /// the channel is closed.
+    fn send_packet_on_enhanced_channel(
+        &self,
+        tcb_idx: TransportIndex,
+        cid: EnhancedChannelId,
+        packet: AttBuilder,
+    ) -> Result<(), SerializeError>;
+
+    /// Ask the peer to open the given number of enhanced channels, each
+    /// advertising the given Rx MTU. The channels that are opened are reported
+    /// through GattModule::on_enhanced_channel_opened.
+    fn open_enhanced_channels(&self, tcb_idx: TransportIndex, count: usize, rx_mtu: usize);
+
+    /// Respond to the outstanding request of the peer to open enhanced
+    /// channels, accepting only those listed (the others are refused), and
+    /// advertising the given Rx MTU on them.
+    fn accept_enhanced_channels(
+        &self,
+        tcb_idx: TransportIndex,
+        accepted: &[EnhancedChannelId],
+        rx_mtu: usize,
+    );
+
+    /// Disconnect the link associated with the specified transport, since the
+    /// ATT bearer on it can no longer be used. The disconnection is reported
+    /// through GattModule::on_le_disconnect once it completes.
+    fn disconnect(&self, tcb_idx: TransportIndex, reason: DisconnectReason);
 }
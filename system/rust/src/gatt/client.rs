@@ -0,0 +1,793 @@
+//! This module is a simple GATT client, running over the same AttTransport as
+//! the GATT server. It performs discovery, reads, writes and subscriptions
+//! against the database of a remote server.
+//!
+//! This client is not wired into the stack yet: nothing outside of tests
+//! constructs a GattClient, there is no FFI surface for it, and the arbiter
+//! still forwards all client-bound packets to the legacy stack, which remains
+//! the only GATT client in production.
+
+pub mod att_client_bearer;
+
+use std::rc::Rc;
+
+use log::warn;
+use tokio::sync::{mpsc::UnboundedReceiver, Mutex};
+
+use crate::{
+    core::uuid::Uuid,
+    gatt::server::gatt_database::{
+        ClientConfiguration, CHARACTERISTIC_UUID, INCLUDE_DECLARATION_UUID,
+        PRIMARY_SERVICE_DECLARATION_UUID, SECONDARY_SERVICE_DECLARATION_UUID,
+    },
+    packets::{
+        AttAttributeDataChild, AttErrorCode, AttExchangeMtuRequestBuilder,
+        AttExchangeMtuResponseView, AttExecuteWriteRequestBuilder,
+        AttFindInformationLongResponseView, AttFindInformationRequestBuilder,
+        AttFindInformationResponseFormat, AttFindInformationResponseView,
+        AttFindInformationShortResponseView, AttPrepareWriteRequestBuilder,
+        AttPrepareWriteResponseView, AttReadBlobRequestBuilder, AttReadBlobResponseView,
+        AttReadByGroupTypeRequestBuilder, AttReadByGroupTypeResponseView,
+        AttReadByTypeRequestBuilder, AttReadByTypeResponseView, AttReadRequestBuilder,
+        AttReadResponseView, AttView, AttWriteCommandBuilder, AttWriteRequestBuilder,
+        GattCharacteristicDeclarationValueView, GattServiceDeclarationValueView, OwnedAttView,
+        Packet, UuidView,
+    },
+    utils::packet::build_att_data,
+};
+
+use self::att_client_bearer::{AttClientBearer, AttClientError, AttClientEvent};
+
+use super::{
+    channel::AttTransport,
+    ids::{AttHandle, TransportIndex},
+    server::att_server_bearer::DEFAULT_ATT_MTU,
+};
+
+/// The largest attribute handle, which bounds every discovery procedure
+const MAX_HANDLE: AttHandle = AttHandle(0xFFFF);
+
+/// A service discovered on the server, from 5.3 3G 4.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredService {
+    /// The handle of the service declaration
+    pub handle: AttHandle,
+    /// The last handle belonging to this service
+    pub end_handle: AttHandle,
+    /// The UUID of the service
+    pub type_: Uuid,
+    /// Whether this is a primary (rather than secondary) service
+    pub primary: bool,
+}
+
+/// A service included by another service, from 5.3 3G 4.5.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredIncludedService {
+    /// The handle of the include declaration
+    pub handle: AttHandle,
+    /// The handle of the declaration of the included service
+    pub service_handle: AttHandle,
+    /// The last handle belonging to the included service
+    pub end_handle: AttHandle,
+    /// The UUID of the included service
+    pub type_: Uuid,
+}
+
+/// The properties of a characteristic, from 5.3 3G 3.3.1.1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct CharacteristicProperties {
+    pub broadcast: bool,
+    pub read: bool,
+    pub write_without_response: bool,
+    pub write: bool,
+    pub notify: bool,
+    pub indicate: bool,
+    pub authenticated_signed_writes: bool,
+    pub extended_properties: bool,
+}
+
+/// A characteristic discovered on the server, from 5.3 3G 4.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredCharacteristic {
+    /// The handle of the characteristic declaration
+    pub declaration_handle: AttHandle,
+    /// The handle of the characteristic value
+    pub value_handle: AttHandle,
+    /// The last handle belonging to this characteristic (i.e. of its last
+    /// descriptor, if any)
+    pub end_handle: AttHandle,
+    /// The UUID of the characteristic
+    pub type_: Uuid,
+    /// The properties of the characteristic
+    pub properties: CharacteristicProperties,
+}
+
+/// A characteristic descriptor discovered on the server, from 5.3 3G 4.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredDescriptor {
+    /// The handle of the descriptor
+    pub handle: AttHandle,
+    /// The UUID of the descriptor
+    pub type_: Uuid,
+}
+
+/// A GATT client for a single connection. All procedures are sent over the
+/// unenhanced bearer, and are queued so only one transaction is outstanding
+/// at a time. Procedures spanning several transactions are also queued as a
+/// whole, so their requests are never interleaved with those of another.
+pub struct GattClient {
+    bearer: AttClientBearer,
+    // held for the duration of each multi-transaction procedure (and of MTU
+    // exchange, which changes the part length those rely on)
+    procedure_lock: Mutex<()>,
+}
+
+impl GattClient {
+    /// Constructor, sending packets over the given transport. Returns the RX
+    /// side of a channel containing the notifications and indications sent by
+    /// the server.
+    pub fn new(
+        tcb_idx: TransportIndex,
+        transport: Rc<dyn AttTransport>,
+    ) -> (Self, UnboundedReceiver<AttClientEvent>) {
+        let (bearer, event_rx) =
+            AttClientBearer::new(move |packet| transport.send_packet(tcb_idx, packet));
+        (Self { bearer, procedure_lock: Mutex::new(()) }, event_rx)
+    }
+
+    /// Handle an incoming packet sent by the server
+    pub fn handle_packet(&self, packet: AttView<'_>) {
+        self.bearer.handle_packet(packet)
+    }
+
+    /// Close the bearer (e.g. on disconnection), failing all pending
+    /// procedures
+    pub fn on_disconnect(&self) {
+        self.bearer.close()
+    }
+
+    /// The current ATT_MTU
+    pub fn mtu(&self) -> usize {
+        self.bearer.mtu()
+    }
+
+    /// Exchange MTU, from 5.3 3G 4.3.1. Returns the new ATT_MTU, which is the
+    /// smaller of the client_rx_mtu and the Rx MTU of the server.
+    pub async fn exchange_mtu(&self, client_rx_mtu: usize) -> Result<usize, AttClientError> {
+        let _guard = self.procedure_lock.lock().await;
+        let response = self
+            .bearer
+            .send_request(AttExchangeMtuRequestBuilder {
+                mtu: client_rx_mtu.try_into().map_err(|_| AttClientError::ValueTooLong)?,
+            })
+            .await?;
+        let server_rx_mtu = parse_response::<AttExchangeMtuResponseView>(&response)?.get_mtu();
+        let mtu = client_rx_mtu.min(server_rx_mtu.into()).max(DEFAULT_ATT_MTU);
+        self.bearer.set_mtu(mtu);
+        Ok(mtu)
+    }
+
+    /// Discover All Primary Services, from 5.3 3G 4.4.1
+    pub async fn discover_primary_services(
+        &self,
+    ) -> Result<Vec<DiscoveredService>, AttClientError> {
+        self.discover_services(PRIMARY_SERVICE_DECLARATION_UUID).await
+    }
+
+    /// Discover all secondary services, using the same procedure as for
+    /// primary services but with the secondary service group type
+    pub async fn discover_secondary_services(
+        &self,
+    ) -> Result<Vec<DiscoveredService>, AttClientError> {
+        self.discover_services(SECONDARY_SERVICE_DECLARATION_UUID).await
+    }
+
+    async fn discover_services(
+        &self,
+        group_type: Uuid,
+    ) -> Result<Vec<DiscoveredService>, AttClientError> {
+        let _guard = self.procedure_lock.lock().await;
+        let mut services = vec![];
+        let mut start = AttHandle(1);
+        loop {
+            let Some(response) = until_not_found(
+                self.bearer
+                    .send_request(AttReadByGroupTypeRequestBuilder {
+                        starting_handle: start.into(),
+                        ending_handle: MAX_HANDLE.into(),
+                        attribute_group_type: group_type.into(),
+                    })
+                    .await,
+            )?
+            else {
+                break;
+            };
+            let mut last_handle = None;
+            for element in
+                parse_response::<AttReadByGroupTypeResponseView>(&response)?.get_data_iter()
+            {
+                let value = GattServiceDeclarationValueView::try_parse(element.get_value())
+                    .map_err(|_| AttClientError::InvalidResponse)?;
+                let service = DiscoveredService {
+                    handle: element.get_handle().into(),
+                    end_handle: element.get_end_group_handle().into(),
+                    type_: parse_uuid(value.get_uuid())?,
+                    primary: group_type == PRIMARY_SERVICE_DECLARATION_UUID,
+                };
+                if service.handle < start || service.end_handle < service.handle {
+                    return Err(AttClientError::InvalidResponse);
+                }
+                last_handle = Some(service.end_handle);
+                services.push(service);
+            }
+            match next_start_handle(last_handle, MAX_HANDLE)? {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        Ok(services)
+    }
+
+    /// Find Included Services, from 5.3 3G 4.5.1. If the UUID of an included
+    /// service is not a 16-bit UUID, it is read from its service declaration.
+    pub async fn find_included_services(
+        &self,
+        service: &DiscoveredService,
+    ) -> Result<Vec<DiscoveredIncludedService>, AttClientError> {
+        let _guard = self.procedure_lock.lock().await;
+        let mut includes = vec![];
+        for (handle, value) in
+            self.read_by_type(INCLUDE_DECLARATION_UUID, service.handle, service.end_handle).await?
+        {
+            let (service_handle, end_handle) = match value[..] {
+                [a, b, c, d, ..] if value.len() == 4 || value.len() == 6 => {
+                    (AttHandle(u16::from_le_bytes([a, b])), AttHandle(u16::from_le_bytes([c, d])))
+                }
+                _ => return Err(AttClientError::InvalidResponse),
+            };
+            let type_ = match value[4..] {
+                [a, b] => Uuid::new(u16::from_le_bytes([a, b]).into()),
+                _ => {
+                    let declaration = self.read(service_handle).await?;
+                    parse_uuid(
+                        UuidView::try_parse_from_buffer(&declaration[..])
+                            .map_err(|_| AttClientError::InvalidResponse)?,
+                    )?
+                }
+            };
+            includes.push(DiscoveredIncludedService { handle, service_handle, end_handle, type_ });
+        }
+        Ok(includes)
+    }
+
+    /// Discover All Characteristics of a Service, from 5.3 3G 4.6.1
+    pub async fn discover_characteristics(
+        &self,
+        service: &DiscoveredService,
+    ) -> Result<Vec<DiscoveredCharacteristic>, AttClientError> {
+        let _guard = self.procedure_lock.lock().await;
+        let mut characteristics = vec![];
+        for (declaration_handle, value) in
+            self.read_by_type(CHARACTERISTIC_UUID, service.handle, service.end_handle).await?
+        {
+            let value = GattCharacteristicDeclarationValueView::try_parse_from_buffer(&value[..])
+                .map_err(|_| AttClientError::InvalidResponse)?;
+            let properties = value.get_properties();
+            characteristics.push(DiscoveredCharacteristic {
+                declaration_handle,
+                value_handle: value.get_handle().into(),
+                // filled in below, once the next declaration is known
+                end_handle: service.end_handle,
+                type_: parse_uuid(value.get_uuid())?,
+                properties: CharacteristicProperties {
+                    broadcast: properties.get_broadcast() != 0,
+                    read: properties.get_read() != 0,
+                    write_without_response: properties.get_write_without_response() != 0,
+                    write: properties.get_write() != 0,
+                    notify: properties.get_notify() != 0,
+                    indicate: properties.get_indicate() != 0,
+                    authenticated_signed_writes: properties.get_authenticated_signed_writes() != 0,
+                    extended_properties: properties.get_extended_properties() != 0,
+                },
+            });
+        }
+        let next_declarations =
+            characteristics.iter().skip(1).map(|next| next.declaration_handle).collect::<Vec<_>>();
+        for (characteristic, next) in characteristics.iter_mut().zip(next_declarations) {
+            characteristic.end_handle = AttHandle(next.0 - 1);
+        }
+        Ok(characteristics)
+    }
+
+    /// Discover All Characteristic Descriptors, from 5.3 3G 4.7.1
+    pub async fn discover_descriptors(
+        &self,
+        characteristic: &DiscoveredCharacteristic,
+    ) -> Result<Vec<DiscoveredDescriptor>, AttClientError> {
+        let _guard = self.procedure_lock.lock().await;
+        let mut descriptors = vec![];
+        let Some(mut start) =
+            next_start_handle(Some(characteristic.value_handle), characteristic.end_handle)?
+        else {
+            return Ok(descriptors);
+        };
+        loop {
+            let Some(response) = until_not_found(
+                self.bearer
+                    .send_request(AttFindInformationRequestBuilder {
+                        starting_handle: start.into(),
+                        ending_handle: characteristic.end_handle.into(),
+                    })
+                    .await,
+            )?
+            else {
+                break;
+            };
+            let response = parse_response::<AttFindInformationResponseView>(&response)?;
+            let found = match response.get_format() {
+                AttFindInformationResponseFormat::SHORT => {
+                    AttFindInformationShortResponseView::try_parse(response)
+                        .map_err(|_| AttClientError::InvalidResponse)?
+                        .get_data_iter()
+                        .map(|entry| DiscoveredDescriptor {
+                            handle: entry.get_handle().into(),
+                            type_: entry.get_uuid().into(),
+                        })
+                        .collect::<Vec<_>>()
+                }
+                AttFindInformationResponseFormat::LONG => {
+                    AttFindInformationLongResponseView::try_parse(response)
+                        .map_err(|_| AttClientError::InvalidResponse)?
+                        .get_data_iter()
+                        .map(|entry| DiscoveredDescriptor {
+                            handle: entry.get_handle().into(),
+                            type_: entry.get_uuid().into(),
+                        })
+                        .collect::<Vec<_>>()
+                }
+            };
+            if found.iter().any(|descriptor| descriptor.handle < start) {
+                return Err(AttClientError::InvalidResponse);
+            }
+            let last_handle = found.last().map(|descriptor| descriptor.handle);
+            descriptors.extend(found);
+            match next_start_handle(last_handle, characteristic.end_handle)? {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        Ok(descriptors)
+    }
+
+    /// Read Characteristic Value (or Descriptor), from 5.3 3G 4.8.1. The value
+    /// is truncated to ATT_MTU - 1 bytes; use read_long for longer values.
+    pub async fn read(&self, handle: AttHandle) -> Result<Vec<u8>, AttClientError> {
+        let response = self
+            .bearer
+            .send_request(AttReadRequestBuilder { attribute_handle: handle.into() })
+            .await?;
+        Ok(parse_response::<AttReadResponseView>(&response)?
+            .get_value()
+            .get_raw_payload()
+            .collect())
+    }
+
+    /// Read Long Characteristic Values (or Descriptors), from 5.3 3G 4.8.3
+    pub async fn read_long(&self, handle: AttHandle) -> Result<Vec<u8>, AttClientError> {
+        let _guard = self.procedure_lock.lock().await;
+        let mut value = self.read(handle).await?;
+        let mut last_part_len = value.len();
+        // a part shorter than the maximum means that we have reached the end
+        while last_part_len >= self.bearer.mtu() - 1 {
+            let offset = value.len().try_into().map_err(|_| AttClientError::InvalidResponse)?;
+            let response = match self
+                .bearer
+                .send_request(AttReadBlobRequestBuilder { attribute_handle: handle.into(), offset })
+                .await
+            {
+                Ok(response) => response,
+                // the value was exactly a multiple of the part length
+                Err(AttClientError::AttError {
+                    error_code: AttErrorCode::ATTRIBUTE_NOT_LONG | AttErrorCode::INVALID_OFFSET,
+                    ..
+                }) => break,
+                Err(err) => return Err(err),
+            };
+            let part = parse_response::<AttReadBlobResponseView>(&response)?
+                .get_value()
+                .get_raw_payload()
+                .collect::<Vec<_>>();
+            last_part_len = part.len();
+            if part.is_empty() {
+                break;
+            }
+            value.extend(part);
+        }
+        Ok(value)
+    }
+
+    /// Write Characteristic Value (or Descriptor), from 5.3 3G 4.9.3. The value
+    /// must fit in a single PDU; use write_long for longer values.
+    pub async fn write(&self, handle: AttHandle, value: &[u8]) -> Result<(), AttClientError> {
+        if value.len() > self.bearer.mtu() - 3 {
+            return Err(AttClientError::ValueTooLong);
+        }
+        self.bearer
+            .send_request(AttWriteRequestBuilder {
+                handle: handle.into(),
+                value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Write Without Response, from 5.3 3G 4.9.1. Succeeds once the command is
+    /// sent, since the server never acknowledges it.
+    pub fn write_without_response(
+        &self,
+        handle: AttHandle,
+        value: &[u8],
+    ) -> Result<(), AttClientError> {
+        if value.len() > self.bearer.mtu() - 3 {
+            return Err(AttClientError::ValueTooLong);
+        }
+        self.bearer.send_command(AttWriteCommandBuilder {
+            handle: handle.into(),
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        })
+    }
+
+    /// Write Long Characteristic Values (or Descriptors), from 5.3 3G 4.9.4.
+    /// The value is queued on the server using prepared writes, which are
+    /// executed only once every part has been echoed back correctly.
+    /// Otherwise, the queue is cancelled.
+    pub async fn write_long(&self, handle: AttHandle, value: &[u8]) -> Result<(), AttClientError> {
+        // the prepare queue on the server is shared by the whole connection
+        let _guard = self.procedure_lock.lock().await;
+        let res = self.prepare_write(handle, value).await;
+        let flags = if res.is_ok() { 1 } else { 0 };
+        let execute = self.bearer.send_request(AttExecuteWriteRequestBuilder { flags }).await;
+        match res {
+            Ok(()) => execute.map(|_| ()),
+            Err(err) => {
+                if let Err(cancel_err) = execute {
+                    warn!("failed to cancel prepared writes: {cancel_err:?}");
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn prepare_write(&self, handle: AttHandle, value: &[u8]) -> Result<(), AttClientError> {
+        let part_len = self.bearer.mtu() - 5;
+        for (i, part) in value.chunks(part_len).enumerate() {
+            let offset: u16 =
+                (i * part_len).try_into().map_err(|_| AttClientError::ValueTooLong)?;
+            let response = self
+                .bearer
+                .send_request(AttPrepareWriteRequestBuilder {
+                    handle: handle.into(),
+                    offset,
+                    value: build_att_data(AttAttributeDataChild::RawData(part.into())),
+                })
+                .await?;
+            let response = parse_response::<AttPrepareWriteResponseView>(&response)?;
+            // the server echoes each part, so we can detect corruption before executing
+            if AttHandle::from(response.get_handle()) != handle
+                || response.get_offset() != offset
+                || !response.get_value().get_raw_payload().eq(part.iter().copied())
+            {
+                warn!("prepared write of {handle:?} at offset {offset} was not echoed correctly");
+                return Err(AttClientError::InvalidResponse);
+            }
+        }
+        Ok(())
+    }
+
+    /// Configure which updates of a characteristic value are sent by the
+    /// server, by writing its Client Characteristic Configuration descriptor
+    /// (5.3 3G 4.12.3). Updates are delivered through the channel returned on
+    /// construction.
+    pub async fn subscribe(
+        &self,
+        cccd_handle: AttHandle,
+        configuration: ClientConfiguration,
+    ) -> Result<(), AttClientError> {
+        self.write(cccd_handle, &configuration.to_bits().to_le_bytes()).await
+    }
+
+    /// Read Using Characteristic UUID (5.3 3G 4.8.2) over the given range,
+    /// returning the handle and value of every match. This is the common part
+    /// of include and characteristic discovery.
+    async fn read_by_type(
+        &self,
+        type_: Uuid,
+        service_handle: AttHandle,
+        end_handle: AttHandle,
+    ) -> Result<Vec<(AttHandle, Vec<u8>)>, AttClientError> {
+        let mut out = vec![];
+        let Some(mut start) = next_start_handle(Some(service_handle), end_handle)? else {
+            return Ok(out);
+        };
+        loop {
+            let Some(response) = until_not_found(
+                self.bearer
+                    .send_request(AttReadByTypeRequestBuilder {
+                        starting_handle: start.into(),
+                        ending_handle: end_handle.into(),
+                        attribute_type: type_.into(),
+                    })
+                    .await,
+            )?
+            else {
+                break;
+            };
+            let mut last_handle = None;
+            for element in parse_response::<AttReadByTypeResponseView>(&response)?.get_data_iter() {
+                let handle: AttHandle = element.get_handle().into();
+                if handle < start || handle > end_handle {
+                    return Err(AttClientError::InvalidResponse);
+                }
+                last_handle = Some(handle);
+                out.push((handle, element.get_value().get_raw_payload().collect()));
+            }
+            match next_start_handle(last_handle, end_handle)? {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn parse_response<'a, T: Packet<'a, Parent = AttView<'a>>>(
+    response: &'a OwnedAttView,
+) -> Result<T, AttClientError> {
+    T::try_parse(response.view()).map_err(|_| AttClientError::InvalidResponse)
+}
+
+fn parse_uuid(uuid: UuidView<'_>) -> Result<Uuid, AttClientError> {
+    Uuid::try_from(uuid).map_err(|_| AttClientError::InvalidResponse)
+}
+
+/// Discovery procedures end when the server reports that no more attributes
+/// are in range, so this maps that error to None
+fn until_not_found(
+    response: Result<OwnedAttView, AttClientError>,
+) -> Result<Option<OwnedAttView>, AttClientError> {
+    match response {
+        Ok(response) => Ok(Some(response)),
+        Err(AttClientError::AttError { error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND, .. }) => {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The handle to continue a discovery procedure from, after the last handle
+/// found so far, or None if the range is exhausted. A response with no
+/// attributes would never make progress, so is rejected.
+fn next_start_handle(
+    last_handle: Option<AttHandle>,
+    end_handle: AttHandle,
+) -> Result<Option<AttHandle>, AttClientError> {
+    match last_handle {
+        Some(last_handle) if last_handle >= end_handle => Ok(None),
+        Some(last_handle) => Ok(Some(AttHandle(last_handle.0 + 1))),
+        None => Err(AttClientError::InvalidResponse),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{sync::mpsc::UnboundedReceiver, task::spawn_local};
+
+    use super::*;
+    use crate::{
+        gatt::{ids::TransportIndex, mocks::mock_transport::MockAttTransport},
+        packets::{
+            AttBuilder, AttChild, AttErrorResponseBuilder, AttExecuteWriteResponseBuilder,
+            AttOpcode, AttPrepareWriteResponseBuilder, AttReadByTypeDataElementBuilder,
+            AttReadByTypeRequestView, AttReadByTypeResponseBuilder, AttReadResponseBuilder,
+            Serializable, UuidBuilder,
+        },
+        utils::{packet::build_att_view_or_crash, task::block_on_locally},
+    };
+
+    const TCB_IDX: TransportIndex = TransportIndex(1);
+    const SERVICE: DiscoveredService = DiscoveredService {
+        handle: AttHandle(1),
+        end_handle: AttHandle(10),
+        type_: Uuid::new(0x0102),
+        primary: true,
+    };
+    const INCLUDED_SERVICE_HANDLE: AttHandle = AttHandle(20);
+    const HANDLE: AttHandle = AttHandle(3);
+
+    fn start_client() -> (Rc<GattClient>, UnboundedReceiver<(TransportIndex, AttBuilder)>) {
+        let (transport, rx) = MockAttTransport::new();
+        let (client, _event_rx) = GattClient::new(TCB_IDX, Rc::new(transport));
+        (Rc::new(client), rx)
+    }
+
+    fn read_by_type_response(handle: AttHandle, value: Vec<u8>) -> AttChild {
+        AttReadByTypeResponseBuilder {
+            data: [AttReadByTypeDataElementBuilder {
+                handle: handle.into(),
+                value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+            }]
+            .into(),
+        }
+        .into()
+    }
+
+    fn not_found(opcode_in_error: AttOpcode) -> AttChild {
+        AttErrorResponseBuilder {
+            opcode_in_error,
+            handle_in_error: AttHandle(0).into(),
+            error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_find_included_service_with_16_bit_uuid() {
+        block_on_locally(async {
+            // arrange
+            let (client, mut rx) = start_client();
+            let pending = spawn_local({
+                let client = client.clone();
+                async move { client.find_included_services(&SERVICE).await }
+            });
+
+            // act: respond with one include declaration, and then end the search
+            let (_, request) = rx.recv().await.unwrap();
+            let request = build_att_view_or_crash(request._child_);
+            let request = AttReadByTypeRequestView::try_parse(request.view()).unwrap();
+            assert_eq!(
+                Uuid::try_from(request.get_attribute_type()).unwrap(),
+                INCLUDE_DECLARATION_UUID
+            );
+            client.handle_packet(
+                build_att_view_or_crash(read_by_type_response(
+                    AttHandle(2),
+                    vec![20, 0, 25, 0, 0x0A, 0x18],
+                ))
+                .view(),
+            );
+            rx.recv().await.unwrap();
+            client.handle_packet(
+                build_att_view_or_crash(not_found(AttOpcode::READ_BY_TYPE_REQUEST)).view(),
+            );
+
+            // assert
+            assert_eq!(
+                pending.await.unwrap().unwrap(),
+                vec![DiscoveredIncludedService {
+                    handle: AttHandle(2),
+                    service_handle: INCLUDED_SERVICE_HANDLE,
+                    end_handle: AttHandle(25),
+                    type_: Uuid::new(0x180A),
+                }]
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_included_service_with_128_bit_uuid() {
+        block_on_locally(async {
+            // arrange
+            let (client, mut rx) = start_client();
+            let pending = spawn_local({
+                let client = client.clone();
+                async move { client.find_included_services(&SERVICE).await }
+            });
+            let uuid = Uuid::new(0x12345678);
+
+            // act: respond with an include declaration without a UUID...
+            rx.recv().await.unwrap();
+            client.handle_packet(
+                build_att_view_or_crash(read_by_type_response(AttHandle(2), vec![20, 0, 25, 0]))
+                    .view(),
+            );
+            rx.recv().await.unwrap();
+            client.handle_packet(
+                build_att_view_or_crash(not_found(AttOpcode::READ_BY_TYPE_REQUEST)).view(),
+            );
+            // ...so the client reads the included service declaration
+            let (_, request) = rx.recv().await.unwrap();
+            assert_eq!(request.opcode, AttOpcode::READ_REQUEST);
+            let declaration = UuidBuilder::from(uuid).to_vec().unwrap();
+            client.handle_packet(
+                build_att_view_or_crash(AttReadResponseBuilder {
+                    value: build_att_data(AttAttributeDataChild::RawData(declaration.into())),
+                })
+                .view(),
+            );
+
+            // assert
+            assert_eq!(pending.await.unwrap().unwrap()[0].type_, uuid);
+        });
+    }
+
+    #[test]
+    fn test_invalid_include_declaration() {
+        block_on_locally(async {
+            // arrange
+            let (client, mut rx) = start_client();
+            let pending = spawn_local({
+                let client = client.clone();
+                async move { client.find_included_services(&SERVICE).await }
+            });
+
+            // act: respond with a declaration of the wrong length
+            rx.recv().await.unwrap();
+            client.handle_packet(
+                build_att_view_or_crash(read_by_type_response(AttHandle(2), vec![20, 0, 25]))
+                    .view(),
+            );
+            rx.recv().await.unwrap();
+            client.handle_packet(
+                build_att_view_or_crash(not_found(AttOpcode::READ_BY_TYPE_REQUEST)).view(),
+            );
+
+            // assert
+            assert!(matches!(pending.await.unwrap(), Err(AttClientError::InvalidResponse)));
+        });
+    }
+
+    #[test]
+    fn test_write_long_cancelled_on_corrupted_echo() {
+        block_on_locally(async {
+            // arrange
+            let (client, mut rx) = start_client();
+            let pending = spawn_local({
+                let client = client.clone();
+                async move { client.write_long(HANDLE, &[1, 2, 3]).await }
+            });
+
+            // act: echo back a different value
+            let (_, request) = rx.recv().await.unwrap();
+            assert_eq!(request.opcode, AttOpcode::PREPARE_WRITE_REQUEST);
+            client.handle_packet(
+                build_att_view_or_crash(AttPrepareWriteResponseBuilder {
+                    handle: HANDLE.into(),
+                    offset: 0,
+                    value: build_att_data(AttAttributeDataChild::RawData([1, 2, 4].into())),
+                })
+                .view(),
+            );
+
+            // assert: the queue is cancelled, and the write fails
+            let (_, request) = rx.recv().await.unwrap();
+            let AttChild::AttExecuteWriteRequest(execute) = request._child_ else {
+                unreachable!("{request:?}")
+            };
+            assert_eq!(execute.flags, 0);
+            client.handle_packet(build_att_view_or_crash(AttExecuteWriteResponseBuilder {}).view());
+            assert!(matches!(pending.await.unwrap(), Err(AttClientError::InvalidResponse)));
+        });
+    }
+
+    #[test]
+    fn test_discovery_without_progress_rejected() {
+        block_on_locally(async {
+            // arrange
+            let (client, mut rx) = start_client();
+            let pending = spawn_local({
+                let client = client.clone();
+                async move { client.discover_characteristics(&SERVICE).await }
+            });
+
+            // act: respond with a handle outside the requested range
+            rx.recv().await.unwrap();
+            client.handle_packet(
+                build_att_view_or_crash(read_by_type_response(AttHandle(1), vec![0; 19])).view(),
+            );
+
+            // assert: the client does not loop forever
+            assert!(matches!(pending.await.unwrap(), Err(AttClientError::InvalidResponse)));
+        });
+    }
+}
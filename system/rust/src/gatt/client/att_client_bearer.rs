@@ -0,0 +1,520 @@
+//! This module handles the client side of an individual ATT bearer. It sends
+//! requests to the server one at a time, matches them with their responses,
+//! and forwards notifications and indications to the upper layer.
+
+use std::cell::Cell;
+
+use log::{trace, warn};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        oneshot, Mutex,
+    },
+    time::timeout,
+};
+
+use crate::{
+    gatt::{
+        ids::AttHandle,
+        server::att_server_bearer::{ATT_TIMEOUT, DEFAULT_ATT_MTU},
+    },
+    packets::{
+        AttBuilder, AttChild, AttErrorCode, AttErrorResponseView,
+        AttHandleValueConfirmationBuilder, AttHandleValueIndicationView,
+        AttHandleValueNotificationView, AttOpcode, AttView, OwnedAttView, Packet, SerializeError,
+    },
+    utils::packet::HACK_child_to_opcode,
+};
+
+/// Errors that can occur during a client operation
+#[derive(Debug)]
+pub enum AttClientError {
+    /// The server rejected the request with an ATT_ERROR_RSP
+    AttError {
+        /// The handle reported by the server as the cause of the error
+        handle: AttHandle,
+        /// The reason for the failure
+        error_code: AttErrorCode,
+    },
+    /// The server did not respond within the ATT timeout, so the bearer has
+    /// been closed
+    Timeout,
+    /// The bearer was closed before the operation could complete
+    BearerClosed,
+    /// The server sent a response that could not be parsed or was inconsistent
+    /// with the request
+    InvalidResponse,
+    /// The value does not fit in a single PDU at the current ATT_MTU
+    ValueTooLong,
+    /// The outgoing packet could not be serialized
+    SerializeError(SerializeError),
+}
+
+/// Server-initiated updates of a characteristic value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttClientEvent {
+    /// An ATT_HANDLE_VALUE_NTF was received
+    Notification {
+        /// The handle of the updated attribute
+        handle: AttHandle,
+        /// The (possibly truncated) new value
+        value: Vec<u8>,
+    },
+    /// An ATT_HANDLE_VALUE_IND was received, and has already been confirmed
+    Indication {
+        /// The handle of the updated attribute
+        handle: AttHandle,
+        /// The (possibly truncated) new value
+        value: Vec<u8>,
+    },
+}
+
+struct PendingRequest {
+    opcode: AttOpcode,
+    response_tx: oneshot::Sender<OwnedAttView>,
+}
+
+/// The client end of a single ATT bearer. Requests are queued, so at most one
+/// transaction is outstanding at a time, as per 5.3 3F 3.3.2
+pub struct AttClientBearer {
+    send_packet: Box<dyn Fn(AttBuilder) -> Result<(), SerializeError>>,
+    mtu: Cell<usize>,
+    // held for the duration of each transaction, so later requests wait their turn
+    transaction_lock: Mutex<()>,
+    pending_request: Cell<Option<PendingRequest>>,
+    event_tx: UnboundedSender<AttClientEvent>,
+    closed: Cell<bool>,
+}
+
+impl AttClientBearer {
+    /// Constructor, wrapping an ATT channel (for outgoing packets). Returns the
+    /// RX side of a channel containing notifications and indications from the
+    /// server.
+    pub fn new(
+        send_packet: impl Fn(AttBuilder) -> Result<(), SerializeError> + 'static,
+    ) -> (Self, UnboundedReceiver<AttClientEvent>) {
+        let (event_tx, event_rx) = unbounded_channel();
+        (
+            Self {
+                send_packet: Box::new(send_packet),
+                mtu: Cell::new(DEFAULT_ATT_MTU),
+                transaction_lock: Mutex::new(()),
+                pending_request: Cell::new(None),
+                event_tx,
+                closed: Cell::new(false),
+            },
+            event_rx,
+        )
+    }
+
+    /// The current ATT_MTU of this bearer
+    pub fn mtu(&self) -> usize {
+        self.mtu.get()
+    }
+
+    /// Update the ATT_MTU, after an MTU exchange
+    pub fn set_mtu(&self, mtu: usize) {
+        self.mtu.set(mtu);
+    }
+
+    /// Send a request and wait for the matching response. If the server
+    /// replies with an ATT_ERROR_RSP, it is converted into an AttError. If no
+    /// response arrives within the ATT timeout, the bearer is closed.
+    pub async fn send_request(
+        &self,
+        request: impl Into<AttChild>,
+    ) -> Result<OwnedAttView, AttClientError> {
+        let _guard = self.transaction_lock.lock().await;
+        if self.closed.get() {
+            return Err(AttClientError::BearerClosed);
+        }
+
+        let child = request.into();
+        let opcode = HACK_child_to_opcode(&child);
+        let (response_tx, response_rx) = oneshot::channel();
+        self.pending_request.replace(Some(PendingRequest { opcode, response_tx }));
+        if let Err(err) = (self.send_packet)(AttBuilder { opcode, _child_: child }) {
+            self.pending_request.take();
+            return Err(AttClientError::SerializeError(err));
+        }
+
+        trace!("waiting for response to {opcode:?}");
+        let response = match timeout(ATT_TIMEOUT, response_rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(AttClientError::BearerClosed),
+            Err(_) => {
+                warn!("no response to {opcode:?} was received in time, closing bearer");
+                self.close();
+                return Err(AttClientError::Timeout);
+            }
+        };
+
+        if response.view().get_opcode() == AttOpcode::ERROR_RESPONSE {
+            let error = AttErrorResponseView::try_parse(response.view())
+                .map_err(|_| AttClientError::InvalidResponse)?;
+            return Err(AttClientError::AttError {
+                handle: error.get_handle_in_error().into(),
+                error_code: error.get_error_code().into(),
+            });
+        }
+        Ok(response)
+    }
+
+    /// Send a command, which has no response, so bypasses the transaction
+    /// queue
+    pub fn send_command(&self, command: impl Into<AttChild>) -> Result<(), AttClientError> {
+        if self.closed.get() {
+            return Err(AttClientError::BearerClosed);
+        }
+        let child = command.into();
+        let opcode = HACK_child_to_opcode(&child);
+        (self.send_packet)(AttBuilder { opcode, _child_: child })
+            .map_err(AttClientError::SerializeError)
+    }
+
+    /// Handle an incoming packet from the server
+    pub fn handle_packet(&self, packet: AttView<'_>) {
+        if self.closed.get() {
+            warn!("dropping incoming ATT packet since the bearer is closed");
+            return;
+        }
+        match packet.get_opcode() {
+            AttOpcode::HANDLE_VALUE_NOTIFICATION => {
+                match AttHandleValueNotificationView::try_parse(packet) {
+                    Ok(notification) => self.send_event(AttClientEvent::Notification {
+                        handle: notification.get_handle().into(),
+                        value: notification.get_value().get_raw_payload().collect(),
+                    }),
+                    Err(err) => warn!("dropping malformed notification: {err:?}"),
+                }
+            }
+            AttOpcode::HANDLE_VALUE_INDICATION => {
+                match AttHandleValueIndicationView::try_parse(packet) {
+                    Ok(indication) => {
+                        // the value is queued for the upper layer, so we can confirm it immediately
+                        self.send_event(AttClientEvent::Indication {
+                            handle: indication.get_handle().into(),
+                            value: indication.get_value().get_raw_payload().collect(),
+                        });
+                        if let Err(err) = self.send_command(AttHandleValueConfirmationBuilder {}) {
+                            warn!("failed to confirm indication: {err:?}");
+                        }
+                    }
+                    Err(err) => warn!("dropping malformed indication: {err:?}"),
+                }
+            }
+            _ => self.handle_response(packet),
+        }
+    }
+
+    /// Tear down the bearer (e.g. on disconnection), failing any outstanding
+    /// request. No further ATT PDUs are sent or received.
+    pub fn close(&self) {
+        self.closed.set(true);
+        self.pending_request.take();
+    }
+
+    fn handle_response(&self, packet: AttView<'_>) {
+        let Some(pending) = self.pending_request.take() else {
+            warn!("received {:?} with no outstanding request, dropping", packet.get_opcode());
+            return;
+        };
+        if !is_response_to(packet, pending.opcode) {
+            warn!(
+                "received {:?}, which does not match the outstanding {:?}, dropping",
+                packet.get_opcode(),
+                pending.opcode
+            );
+            self.pending_request.replace(Some(pending));
+            return;
+        }
+        if pending.response_tx.send(packet.to_owned_packet()).is_err() {
+            warn!("response received, but nobody is waiting for it");
+        }
+    }
+
+    fn send_event(&self, event: AttClientEvent) {
+        if self.event_tx.send(event).is_err() {
+            trace!("nobody is listening for server-initiated updates, dropping");
+        }
+    }
+}
+
+fn is_response_to(packet: AttView<'_>, request: AttOpcode) -> bool {
+    let opcode = packet.get_opcode();
+    if opcode == AttOpcode::ERROR_RESPONSE {
+        return AttErrorResponseView::try_parse(packet)
+            .is_ok_and(|error| error.get_opcode_in_error() == request);
+    }
+    opcode
+        == match request {
+            AttOpcode::EXCHANGE_MTU_REQUEST => AttOpcode::EXCHANGE_MTU_RESPONSE,
+            AttOpcode::FIND_INFORMATION_REQUEST => AttOpcode::FIND_INFORMATION_RESPONSE,
+            AttOpcode::FIND_BY_TYPE_VALUE_REQUEST => AttOpcode::FIND_BY_TYPE_VALUE_RESPONSE,
+            AttOpcode::READ_BY_TYPE_REQUEST => AttOpcode::READ_BY_TYPE_RESPONSE,
+            AttOpcode::READ_REQUEST => AttOpcode::READ_RESPONSE,
+            AttOpcode::READ_BLOB_REQUEST => AttOpcode::READ_BLOB_RESPONSE,
+            AttOpcode::READ_MULTIPLE_REQUEST => AttOpcode::READ_MULTIPLE_RESPONSE,
+            AttOpcode::READ_BY_GROUP_TYPE_REQUEST => AttOpcode::READ_BY_GROUP_TYPE_RESPONSE,
+            AttOpcode::WRITE_REQUEST => AttOpcode::WRITE_RESPONSE,
+            AttOpcode::PREPARE_WRITE_REQUEST => AttOpcode::PREPARE_WRITE_RESPONSE,
+            AttOpcode::EXECUTE_WRITE_REQUEST => AttOpcode::EXECUTE_WRITE_RESPONSE,
+            AttOpcode::READ_MULTIPLE_VARIABLE_REQUEST => AttOpcode::READ_MULTIPLE_VARIABLE_RESPONSE,
+            _ => return false,
+        }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use tokio::{
+        sync::mpsc::error::TryRecvError,
+        task::{spawn_local, yield_now},
+    };
+
+    use super::*;
+    use crate::{
+        packets::{
+            AttAttributeDataChild, AttErrorResponseBuilder, AttHandleValueIndicationBuilder,
+            AttHandleValueNotificationBuilder, AttReadRequestBuilder, AttReadResponseBuilder,
+            AttWriteResponseBuilder,
+        },
+        utils::{
+            packet::{build_att_data, build_att_view_or_crash},
+            task::block_on_locally,
+        },
+    };
+
+    const HANDLE: AttHandle = AttHandle(3);
+
+    fn open_bearer(
+    ) -> (Rc<AttClientBearer>, UnboundedReceiver<AttBuilder>, UnboundedReceiver<AttClientEvent>)
+    {
+        let (tx, rx) = unbounded_channel();
+        let (bearer, event_rx) = AttClientBearer::new(move |packet| {
+            tx.send(packet).unwrap();
+            Ok(())
+        });
+        (Rc::new(bearer), rx, event_rx)
+    }
+
+    fn read_response(value: &[u8]) -> OwnedAttView {
+        build_att_view_or_crash(AttReadResponseBuilder {
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        })
+    }
+
+    fn send_read_request(
+        bearer: &Rc<AttClientBearer>,
+    ) -> tokio::task::JoinHandle<Result<OwnedAttView, AttClientError>> {
+        let bearer = bearer.clone();
+        spawn_local(async move {
+            bearer.send_request(AttReadRequestBuilder { attribute_handle: HANDLE.into() }).await
+        })
+    }
+
+    #[test]
+    fn test_request_and_response() {
+        block_on_locally(async {
+            let (bearer, mut rx, _event_rx) = open_bearer();
+
+            // act: send a request and respond to it
+            let pending = send_read_request(&bearer);
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::READ_REQUEST);
+            bearer.handle_packet(read_response(&[1, 2]).view());
+
+            // assert: the response is returned
+            let response = pending.await.unwrap().unwrap();
+            assert_eq!(response.view().get_opcode(), AttOpcode::READ_RESPONSE);
+        });
+    }
+
+    #[test]
+    fn test_error_response() {
+        block_on_locally(async {
+            let (bearer, mut rx, _event_rx) = open_bearer();
+
+            // act: send a request and reject it
+            let pending = send_read_request(&bearer);
+            rx.recv().await.unwrap();
+            bearer.handle_packet(
+                build_att_view_or_crash(AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::READ_REQUEST,
+                    handle_in_error: HANDLE.into(),
+                    error_code: AttErrorCode::READ_NOT_PERMITTED.into(),
+                })
+                .view(),
+            );
+
+            // assert: the error is returned
+            assert!(matches!(
+                pending.await.unwrap(),
+                Err(AttClientError::AttError {
+                    handle: HANDLE,
+                    error_code: AttErrorCode::READ_NOT_PERMITTED
+                })
+            ));
+        });
+    }
+
+    #[test]
+    fn test_requests_are_queued() {
+        block_on_locally(async {
+            let (bearer, mut rx, _event_rx) = open_bearer();
+
+            // act: send two requests at once
+            let first = send_read_request(&bearer);
+            let second = send_read_request(&bearer);
+            rx.recv().await.unwrap();
+            yield_now().await;
+
+            // assert: the second is only sent once the first completes
+            assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+            bearer.handle_packet(read_response(&[1]).view());
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::READ_REQUEST);
+            bearer.handle_packet(read_response(&[2]).view());
+            assert!(first.await.unwrap().is_ok());
+            assert!(second.await.unwrap().is_ok());
+        });
+    }
+
+    #[test]
+    fn test_mismatched_response_dropped() {
+        block_on_locally(async {
+            let (bearer, mut rx, _event_rx) = open_bearer();
+            let pending = send_read_request(&bearer);
+            rx.recv().await.unwrap();
+
+            // act: respond with the wrong PDU, and then with the right one
+            bearer.handle_packet(build_att_view_or_crash(AttWriteResponseBuilder {}).view());
+            bearer.handle_packet(read_response(&[1, 2]).view());
+
+            // assert: only the matching response completes the request
+            let response = pending.await.unwrap().unwrap();
+            assert_eq!(response.view().get_opcode(), AttOpcode::READ_RESPONSE);
+        });
+    }
+
+    #[test]
+    fn test_unsolicited_response_dropped() {
+        block_on_locally(async {
+            let (bearer, mut rx, _event_rx) = open_bearer();
+
+            // act: receive a response with no outstanding request
+            bearer.handle_packet(read_response(&[1, 2]).view());
+
+            // assert: the next request is not completed by the stale response
+            let pending = send_read_request(&bearer);
+            rx.recv().await.unwrap();
+            yield_now().await;
+            assert!(!pending.is_finished());
+            bearer.handle_packet(read_response(&[3]).view());
+            assert!(pending.await.unwrap().is_ok());
+        });
+    }
+
+    #[test]
+    fn test_timeout_closes_bearer() {
+        block_on_locally(async {
+            tokio::time::pause();
+            let (bearer, mut rx, _event_rx) = open_bearer();
+
+            // act: send a request and never respond
+            let res = send_read_request(&bearer).await.unwrap();
+            rx.recv().await.unwrap();
+
+            // assert: the request timed out, and no further requests are sent
+            assert!(matches!(res, Err(AttClientError::Timeout)));
+            assert!(matches!(
+                send_read_request(&bearer).await.unwrap(),
+                Err(AttClientError::BearerClosed)
+            ));
+            assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+        });
+    }
+
+    #[test]
+    fn test_close_fails_pending_request() {
+        block_on_locally(async {
+            let (bearer, mut rx, _event_rx) = open_bearer();
+            let pending = send_read_request(&bearer);
+            rx.recv().await.unwrap();
+
+            // act
+            bearer.close();
+
+            // assert
+            assert!(matches!(pending.await.unwrap(), Err(AttClientError::BearerClosed)));
+        });
+    }
+
+    #[test]
+    fn test_notification_forwarded() {
+        block_on_locally(async {
+            let (bearer, mut rx, mut event_rx) = open_bearer();
+
+            // act
+            bearer.handle_packet(
+                build_att_view_or_crash(AttHandleValueNotificationBuilder {
+                    handle: HANDLE.into(),
+                    value: build_att_data(AttAttributeDataChild::RawData([1, 2].into())),
+                })
+                .view(),
+            );
+
+            // assert: the value is forwarded, and nothing is sent back
+            assert_eq!(
+                event_rx.recv().await.unwrap(),
+                AttClientEvent::Notification { handle: HANDLE, value: vec![1, 2] }
+            );
+            assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+        });
+    }
+
+    #[test]
+    fn test_indication_forwarded_and_confirmed() {
+        block_on_locally(async {
+            let (bearer, mut rx, mut event_rx) = open_bearer();
+
+            // act
+            bearer.handle_packet(
+                build_att_view_or_crash(AttHandleValueIndicationBuilder {
+                    handle: HANDLE.into(),
+                    value: build_att_data(AttAttributeDataChild::RawData([1, 2].into())),
+                })
+                .view(),
+            );
+
+            // assert
+            assert_eq!(
+                event_rx.recv().await.unwrap(),
+                AttClientEvent::Indication { handle: HANDLE, value: vec![1, 2] }
+            );
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::HANDLE_VALUE_CONFIRMATION);
+        });
+    }
+
+    #[test]
+    fn test_indication_confirmed_during_transaction() {
+        block_on_locally(async {
+            let (bearer, mut rx, _event_rx) = open_bearer();
+            let pending = send_read_request(&bearer);
+            rx.recv().await.unwrap();
+
+            // act: receive an indication while a request is outstanding
+            bearer.handle_packet(
+                build_att_view_or_crash(AttHandleValueIndicationBuilder {
+                    handle: HANDLE.into(),
+                    value: build_att_data(AttAttributeDataChild::RawData([1].into())),
+                })
+                .view(),
+            );
+
+            // assert: it is confirmed without waiting for the transaction
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::HANDLE_VALUE_CONFIRMATION);
+            bearer.handle_packet(read_response(&[1]).view());
+            assert!(pending.await.unwrap().is_ok());
+        });
+    }
+}
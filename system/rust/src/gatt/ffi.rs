@@ -1,6 +1,8 @@
 //! FFI interfaces for the GATT module. Some structs are exported so that
 //! core::init can instantiate and pass them into the main loop.
 
+use std::{cell::RefCell, collections::HashMap};
+
 use anyhow::{bail, Result};
 use bt_common::init_flags::{
     always_use_private_gatt_for_debugging_is_enabled, rust_event_loop_is_enabled,
@@ -12,16 +14,29 @@ use log::{error, info, warn};
 use crate::{
     do_in_rust_thread,
     packets::{
-        AttAttributeDataChild, AttAttributeDataView, AttBuilder, AttErrorCode, Serializable,
-        SerializeError,
+        AttAttributeDataChild, AttAttributeDataView, AttBuilder, AttErrorCode, AttOpcode,
+        Serializable, SerializeError,
     },
 };
 
 use super::{
     arbiter::{self, with_arbiter},
-    channel::AttTransport,
-    ids::{AdvertiserId, AttHandle, ConnectionId, ServerId, TransactionId, TransportIndex},
-    server::gatt_database::{AttPermissions, GattCharacteristicWithHandle, GattServiceWithHandle},
+    callbacks::TransactionDecision,
+    channel::{AttTransport, DisconnectReason},
+    ids::{
+        AdvertiserId, AttHandle, ConnectionId, EnhancedChannelId, ServerId, TransactionId,
+        TransportIndex,
+    },
+    server::{
+        gatt_database::{
+            AttPermissions, AttSecurityRequirements, GattCharacteristicWithHandle,
+            GattDescriptorWithHandle, GattExtendedPropertiesWithHandle, GattIncludeWithHandle,
+            GattServiceWithHandle, CHARACTERISTIC_EXTENDED_PROPERTIES_UUID,
+            CHARACTERISTIC_USER_DESCRIPTION_UUID, CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+        },
+        sdp::{SdpPublisher, SdpServiceRecord},
+        signing::{PeerSigningKey, SigningKeyProvider},
+    },
     GattCallbacks,
 };
 
@@ -71,6 +86,58 @@ mod inner {
             is_prepare: bool,
             value: &[u8],
         );
+
+        /// This callback is invoked when writing a descriptor owned by Rust
+        /// (e.g. a CCCD) - the client must reply using SendResponse only if
+        /// need_response is set
+        #[cxx_name = "OnServerWriteDescriptor"]
+        fn on_server_write_descriptor(
+            self: &GattServerCallbacks,
+            conn_id: u16,
+            trans_id: u32,
+            attr_handle: u16,
+            offset: u32,
+            need_response: bool,
+            is_prepare: bool,
+            value: &[u8],
+        );
+
+        /// This callback is invoked when executing or cancelling the prepared
+        /// writes of a connection - the client must reply using SendResponse
+        #[cxx_name = "OnServerExecuteWrite"]
+        fn on_server_execute_write(
+            self: &GattServerCallbacks,
+            conn_id: u16,
+            trans_id: u32,
+            execute: bool,
+        );
+
+        /// This callback is invoked when the ATT_MTU of a connection changes
+        #[cxx_name = "OnMtuChanged"]
+        fn on_mtu_changed(self: &GattServerCallbacks, conn_id: u16, mtu: u16);
+
+        /// This callback is invoked when the server drops the link of a
+        /// connection, before it is disconnected. The status is the
+        /// tGATT_DISCONN_REASON to report with the disconnection.
+        #[cxx_name = "OnLinkDropped"]
+        fn on_link_dropped(self: &GattServerCallbacks, conn_id: u16, status: u8, reason: &str);
+
+        /// Publish an SDP record for the service of the given server spanning
+        /// the given handles
+        fn PublishSdpRecord(server_id: u8, uuid: Uuid, start_handle: u16, end_handle: u16);
+
+        /// Withdraw the SDP record (if any) of the service of the given server
+        /// at the given handle
+        fn WithdrawSdpRecord(server_id: u8, start_handle: u16);
+
+        /// Get the CSRK distributed by the peer on the given transport (in the
+        /// little-endian order used over the air), and the smallest SignCounter
+        /// still accepted from it. Returns false if the peer distributed none.
+        fn GetPeerSigningKey(tcb_idx: u8, csrk: &mut [u8], min_sign_counter: &mut u32) -> bool;
+
+        /// Record that a signed write with the given SignCounter was accepted
+        /// from the peer on the given transport
+        fn OnPeerSignCounterUsed(tcb_idx: u8, sign_counter: u32);
     }
 
     /// What action the arbiter should take in response to an incoming packet
@@ -102,6 +169,9 @@ mod inner {
         uuid: Uuid,
         record_type: GattRecordType,
         attribute_handle: u16,
+        /// For included services, the handle of the declaration of the
+        /// included service (attribute_handle is the include declaration)
+        start_handle: u16,
 
         properties: u8,
         extended_properties: u16,
@@ -118,11 +188,26 @@ mod inner {
         fn StoreCallbacksFromRust(
             on_le_connect: fn(tcb_idx: u8, advertiser: u8),
             on_le_disconnect: fn(tcb_idx: u8),
+            on_mtu_exchanged: fn(tcb_idx: u8, mtu: u16),
+            on_br_edr_connect: fn(tcb_idx: u8, mtu: u16),
+            on_br_edr_disconnect: fn(tcb_idx: u8),
+            on_security_changed: fn(
+                tcb_idx: u8,
+                encrypted: bool,
+                key_size: u8,
+                authenticated: bool,
+                bonded: bool,
+            ),
+            on_bonded_peer: fn(tcb_idx: u8, peer: [u8; 6]),
+            on_bond_removed: fn(peer: [u8; 6]),
             intercept_packet: fn(tcb_idx: u8, packet: Vec<u8>) -> InterceptAction,
         );
 
         /// Send an outgoing packet on the specified tcb_idx
         fn SendPacketToPeer(tcb_idx: u8, packet: Vec<u8>);
+
+        /// Disconnect the link of the specified tcb_idx
+        fn DisconnectPeer(tcb_idx: u8);
     }
 
     #[namespace = "bluetooth::gatt"]
@@ -140,6 +225,7 @@ mod inner {
         // arbitration
         fn associate_server_with_advertiser(server_id: u8, advertiser_id: u8);
         fn clear_advertiser(advertiser_id: u8);
+        fn expose_server_over_br_edr(server_id: u8);
     }
 }
 
@@ -181,6 +267,52 @@ impl GattCallbacks for GattCallbacksImpl {
             &value.get_raw_payload().collect::<Vec<_>>(),
         );
     }
+
+    fn on_server_write_descriptor(
+        &self,
+        conn_id: ConnectionId,
+        trans_id: TransactionId,
+        handle: AttHandle,
+        offset: u32,
+        need_response: bool,
+        is_prepare: bool,
+        value: AttAttributeDataView,
+    ) {
+        self.0.as_ref().unwrap().on_server_write_descriptor(
+            conn_id.0,
+            trans_id.0,
+            handle.0,
+            offset,
+            need_response,
+            is_prepare,
+            &value.get_raw_payload().collect::<Vec<_>>(),
+        );
+    }
+
+    fn on_server_execute_write(
+        &self,
+        conn_id: ConnectionId,
+        trans_id: TransactionId,
+        decision: TransactionDecision,
+    ) {
+        self.0.as_ref().unwrap().on_server_execute_write(
+            conn_id.0,
+            trans_id.0,
+            decision == TransactionDecision::Execute,
+        );
+    }
+
+    fn on_mtu_changed(&self, conn_id: ConnectionId, mtu: usize) {
+        self.0.as_ref().unwrap().on_mtu_changed(conn_id.0, mtu as u16);
+    }
+
+    fn on_link_dropped(&self, conn_id: ConnectionId, reason: DisconnectReason) {
+        self.0.as_ref().unwrap().on_link_dropped(
+            conn_id.0,
+            disconnect_status(reason),
+            &format!("{reason:?}"),
+        );
+    }
 }
 
 /// Implementation of AttTransport wrapping the corresponding C++ method
@@ -192,9 +324,84 @@ impl AttTransport for AttTransportImpl {
         tcb_idx: TransportIndex,
         packet: AttBuilder,
     ) -> Result<(), SerializeError> {
+        // recorded before sending, as the confirmation may arrive at any time
+        if packet.opcode == AttOpcode::HANDLE_VALUE_INDICATION {
+            with_arbiter(|arbiter| arbiter.on_indication_sent(tcb_idx));
+        }
         SendPacketToPeer(tcb_idx.0, packet.to_vec()?);
         Ok(())
     }
+
+    // TODO: enhanced channels are still owned by the legacy EATT module, so are
+    // not yet bridged to Rust
+
+    fn send_packet_on_enhanced_channel(
+        &self,
+        _: TransportIndex,
+        cid: EnhancedChannelId,
+        _: AttBuilder,
+    ) -> Result<(), SerializeError> {
+        error!("enhanced channels are not supported over FFI, dropping packet on {cid:?}");
+        Ok(())
+    }
+
+    fn open_enhanced_channels(&self, tcb_idx: TransportIndex, _: usize, _: usize) {
+        warn!("enhanced channels are not supported over FFI, not opening any on {tcb_idx:?}");
+    }
+
+    fn accept_enhanced_channels(&self, tcb_idx: TransportIndex, _: &[EnhancedChannelId], _: usize) {
+        warn!("enhanced channels are not supported over FFI, not accepting any on {tcb_idx:?}");
+    }
+
+    fn disconnect(&self, tcb_idx: TransportIndex, reason: DisconnectReason) {
+        info!("disconnecting {tcb_idx:?}: {reason:?}");
+        DisconnectPeer(tcb_idx.0);
+    }
+}
+
+/// Implementation of SigningKeyProvider wrapping the corresponding C++
+/// methods, which read the keys held by the security manager
+#[derive(Default)]
+pub struct SigningKeyProviderImpl {
+    // the security manager only learns of a used SignCounter once the main
+    // thread gets to it, so the latest one is also kept here, keyed by CSRK
+    used_sign_counters: RefCell<HashMap<[u8; 16], u32>>,
+}
+
+impl SigningKeyProvider for SigningKeyProviderImpl {
+    fn get_peer_signing_key(&self, conn_id: ConnectionId) -> Option<PeerSigningKey> {
+        let mut csrk = [0; 16];
+        let mut min_sign_counter = 0;
+        if !GetPeerSigningKey(conn_id.get_tcb_idx().0, &mut csrk, &mut min_sign_counter) {
+            return None;
+        }
+        if let Some(used) = self.used_sign_counters.borrow().get(&csrk) {
+            min_sign_counter = min_sign_counter.max(used.saturating_add(1));
+        }
+        Some(PeerSigningKey { csrk, min_sign_counter })
+    }
+
+    fn on_sign_counter_used(&self, conn_id: ConnectionId, sign_counter: u32) {
+        let tcb_idx = conn_id.get_tcb_idx().0;
+        let mut csrk = [0; 16];
+        if GetPeerSigningKey(tcb_idx, &mut csrk, &mut 0) {
+            self.used_sign_counters.borrow_mut().insert(csrk, sign_counter);
+        }
+        OnPeerSignCounterUsed(tcb_idx, sign_counter);
+    }
+}
+
+/// Implementation of SdpPublisher wrapping the corresponding C++ methods
+pub struct SdpPublisherImpl();
+
+impl SdpPublisher for SdpPublisherImpl {
+    fn publish(&self, server_id: ServerId, record: SdpServiceRecord) {
+        PublishSdpRecord(server_id.0, record.uuid, record.start_handle.0, record.end_handle.0);
+    }
+
+    fn withdraw(&self, server_id: ServerId, start_handle: AttHandle) {
+        WithdrawSdpRecord(server_id.0, start_handle.0);
+    }
 }
 
 fn open_server(server_id: u8) {
@@ -235,37 +442,196 @@ fn close_server(server_id: u8) {
     })
 }
 
+// GATT_PERM_* flags, from stack/include/gatt_api.h
+const GATT_PERM_READ: u16 = 1 << 0;
+const GATT_PERM_READ_ENCRYPTED: u16 = 1 << 1;
+const GATT_PERM_READ_ENC_MITM: u16 = 1 << 2;
+const GATT_PERM_WRITE: u16 = 1 << 4;
+const GATT_PERM_WRITE_ENCRYPTED: u16 = 1 << 5;
+const GATT_PERM_WRITE_ENC_MITM: u16 = 1 << 6;
+const GATT_PERM_WRITE_SIGNED: u16 = 1 << 7;
+const GATT_PERM_WRITE_SIGNED_MITM: u16 = 1 << 8;
+const GATT_PERM_READ_IF_ENCRYPTED_OR_DISCOVERABLE: u16 = 1 << 9;
+const GATT_ENCRYPT_KEY_SIZE_MASK: u16 = 0xF000;
+
+// as in gatt_db.cc, any of these flags allows the corresponding access
+const GATT_READ_ALLOWED: u16 = GATT_PERM_READ
+    | GATT_PERM_READ_ENCRYPTED
+    | GATT_PERM_READ_ENC_MITM
+    | GATT_PERM_READ_IF_ENCRYPTED_OR_DISCOVERABLE;
+const GATT_WRITE_ALLOWED: u16 = GATT_PERM_WRITE
+    | GATT_PERM_WRITE_ENCRYPTED
+    | GATT_PERM_WRITE_ENC_MITM
+    | GATT_PERM_WRITE_SIGNED
+    | GATT_PERM_WRITE_SIGNED_MITM;
+
+// tGATT_DISCONN_REASON values, from stack/include/gatt_api.h
+const GATT_CONN_TIMEOUT: u8 = 0x08;
+const GATT_CONN_TERMINATE_LOCAL_HOST: u8 = 0x16;
+
+/// The status reported to the upper layer when the server drops a link
+fn disconnect_status(reason: DisconnectReason) -> u8 {
+    match reason {
+        DisconnectReason::TransactionTimeout | DisconnectReason::ConfirmationTimeout => {
+            GATT_CONN_TIMEOUT
+        }
+        DisconnectReason::ProtocolViolation => GATT_CONN_TERMINATE_LOCAL_HOST,
+    }
+}
+
+/// Extract the security requirements of a single kind of access from the
+/// GATT_PERM_* flags of a record
+fn security_requirements(permissions: u16, encrypted: u16, mitm: u16) -> AttSecurityRequirements {
+    // as in the legacy stack, a non-zero nibble encodes the key size minus 6
+    let min_key_size = ((permissions & GATT_ENCRYPT_KEY_SIZE_MASK) >> 12) as u8;
+    AttSecurityRequirements {
+        encryption: permissions & encrypted != 0,
+        authentication: permissions & mitm != 0,
+        // there is no GATT_PERM_* flag for authorization: as in the legacy
+        // stack, the upper layer rejects unauthorized accesses itself
+        authorization: false,
+        min_key_size: if min_key_size == 0 { 0 } else { min_key_size + 6 },
+    }
+}
+
+/// Descriptors have no properties, so their accesses are only governed by the
+/// GATT_PERM_* flags of their record
+fn descriptor_permissions(permissions: u16) -> AttPermissions {
+    AttPermissions {
+        readable: permissions & GATT_READ_ALLOWED != 0,
+        writable: permissions & GATT_WRITE_ALLOWED != 0,
+        writable_without_response: false,
+        writable_signed: false,
+        read_security: security_requirements(
+            permissions,
+            GATT_PERM_READ_ENCRYPTED,
+            GATT_PERM_READ_ENC_MITM,
+        ),
+        write_security: security_requirements(
+            permissions,
+            GATT_PERM_WRITE_ENCRYPTED,
+            GATT_PERM_WRITE_ENC_MITM,
+        ),
+    }
+}
+
+fn extended_properties(handle: u16, extended_properties: u16) -> GattExtendedPropertiesWithHandle {
+    GattExtendedPropertiesWithHandle {
+        handle: AttHandle(handle),
+        reliable_write: extended_properties & 0x01 != 0,
+        writable_auxiliaries: extended_properties & 0x02 != 0,
+    }
+}
+
 fn records_to_service(service_records: &[GattRecord]) -> Result<GattServiceWithHandle> {
+    let mut includes = vec![];
     let mut characteristics = vec![];
+    let mut extended_property_bits = vec![];
     let mut service_handle_uuid = None;
 
     for record in service_records {
         match record.record_type {
-            GattRecordType::PrimaryService => {
+            GattRecordType::PrimaryService | GattRecordType::SecondaryService => {
                 if service_handle_uuid.is_some() {
-                    bail!("got service registration but with duplicate primary service! {service_records:?}".to_string());
+                    bail!(
+                        "got service registration but with duplicate service! {service_records:?}"
+                            .to_string()
+                    );
                 }
-                service_handle_uuid = Some((record.attribute_handle, record.uuid));
+                service_handle_uuid = Some((
+                    record.attribute_handle,
+                    record.uuid,
+                    record.record_type == GattRecordType::PrimaryService,
+                ));
             }
-            GattRecordType::Characteristic => characteristics.push(GattCharacteristicWithHandle {
+            GattRecordType::IncludedService => includes.push(GattIncludeWithHandle {
                 handle: AttHandle(record.attribute_handle),
-                type_: record.uuid,
-                permissions: AttPermissions {
-                    readable: record.properties & 0x02 != 0,
-                    writable: record.properties & 0x08 != 0,
-                },
+                service_handle: AttHandle(record.start_handle),
             }),
+            GattRecordType::Characteristic => {
+                extended_property_bits.push(record.extended_properties);
+                characteristics.push(GattCharacteristicWithHandle {
+                    handle: AttHandle(record.attribute_handle),
+                    type_: record.uuid,
+                    permissions: AttPermissions {
+                        readable: record.properties & 0x02 != 0,
+                        writable: record.properties & 0x08 != 0,
+                        writable_without_response: record.properties & 0x04 != 0,
+                        writable_signed: record.properties & 0x40 != 0,
+                        read_security: security_requirements(
+                            record.permissions,
+                            GATT_PERM_READ_ENCRYPTED,
+                            GATT_PERM_READ_ENC_MITM,
+                        ),
+                        write_security: security_requirements(
+                            record.permissions,
+                            GATT_PERM_WRITE_ENCRYPTED,
+                            GATT_PERM_WRITE_ENC_MITM,
+                        ),
+                    },
+                    notify: record.properties & 0x10 != 0,
+                    indicate: record.properties & 0x20 != 0,
+                    // the legacy stack adds this descriptor itself, right after
+                    // the characteristic value
+                    extended_properties: (record.properties & 0x80 != 0).then(|| {
+                        extended_properties(record.attribute_handle + 1, record.extended_properties)
+                    }),
+                    user_description: None,
+                    descriptors: vec![],
+                })
+            }
+            GattRecordType::Descriptor => {
+                let Some(characteristic) = characteristics.last_mut() else {
+                    bail!(
+                        "got descriptor at handle {} without a preceding characteristic",
+                        record.attribute_handle
+                    );
+                };
+                match record.uuid {
+                    CHARACTERISTIC_EXTENDED_PROPERTIES_UUID => {
+                        if characteristic.extended_properties.is_some() {
+                            bail!(
+                                "characteristic {:?} has multiple extended properties descriptors",
+                                characteristic.handle
+                            );
+                        }
+                        characteristic.extended_properties = Some(extended_properties(
+                            record.attribute_handle,
+                            extended_property_bits.last().copied().unwrap_or_default(),
+                        ));
+                    }
+                    // CCCDs are managed by the GattDatabase, while the value
+                    // of a user description is owned by the upper layer, so
+                    // it is listed as a descriptor to be forwarded there
+                    CLIENT_CHARACTERISTIC_CONFIGURATION_UUID
+                    | CHARACTERISTIC_USER_DESCRIPTION_UUID => {
+                        characteristic.descriptors.push(GattDescriptorWithHandle {
+                            handle: AttHandle(record.attribute_handle),
+                            type_: record.uuid,
+                            permissions: descriptor_permissions(record.permissions),
+                        })
+                    }
+                    // other descriptors are still left to the legacy stack
+                    _ => warn!("ignoring unsupported descriptor of type {:?}", record.uuid),
+                }
+            }
             _ => {
                 warn!("ignoring unsupported database entry of type {:?}", record.record_type)
             }
         }
     }
 
-    let Some((handle, uuid)) = service_handle_uuid else {
-        bail!("got service registration but with no primary service! {characteristics:?}".to_string())
+    let Some((handle, uuid, primary)) = service_handle_uuid else {
+        bail!("got service registration but with no service! {characteristics:?}".to_string())
     };
 
-    Ok(GattServiceWithHandle { handle: AttHandle(handle), type_: uuid, characteristics })
+    Ok(GattServiceWithHandle {
+        handle: AttHandle(handle),
+        type_: uuid,
+        primary,
+        includes,
+        characteristics,
+    })
 }
 
 fn add_service(server_id: u8, service_records: Vec<GattRecord>) {
@@ -330,12 +696,12 @@ fn send_response(_server_id: u8, conn_id: u16, trans_id: u32, status: u8, value:
         return;
     }
 
-    // TODO(aryarahul): fixup error codes to allow app-specific values (i.e. don't
-    // make it an enum in PDL)
+    // any non-zero status is an ATT error code, including those defined by the
+    // application or its profile, so it is sent to the peer unchanged
     let value = if status == 0 {
         Ok(AttAttributeDataChild::RawData(value.to_vec().into_boxed_slice()))
     } else {
-        Err(AttErrorCode::try_from(status).unwrap_or(AttErrorCode::UNLIKELY_ERROR))
+        Err(AttErrorCode(status))
     };
     do_in_rust_thread(move |modules| {
         match modules.gatt_callbacks.send_response(
@@ -367,6 +733,20 @@ fn clear_advertiser(advertiser_id: u8) {
     arbiter::with_arbiter(move |arbiter| arbiter.clear_advertiser(AdvertiserId(advertiser_id)))
 }
 
+fn expose_server_over_br_edr(server_id: u8) {
+    if !rust_event_loop_is_enabled() {
+        return;
+    }
+
+    let server_id = ServerId(server_id);
+    arbiter::with_arbiter(move |arbiter| arbiter.associate_server_with_br_edr(server_id));
+    do_in_rust_thread(move |modules| {
+        if let Err(err) = modules.gatt_module.expose_over_br_edr(server_id) {
+            error!("{err:?}")
+        }
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -380,11 +760,18 @@ mod test {
     const ANOTHER_CHARACTERISTIC_HANDLE: AttHandle = AttHandle(3);
     const ANOTHER_CHARACTERISTIC_UUID: Uuid = Uuid::new(0x9ABC);
 
+    const DESCRIPTOR_HANDLE: AttHandle = AttHandle(4);
+
+    const INCLUDE_HANDLE: AttHandle = AttHandle(2);
+    const INCLUDED_SERVICE_HANDLE: AttHandle = AttHandle(10);
+    const ANOTHER_SERVICE_UUID: Uuid = Uuid::new(0x4321);
+
     fn make_service_record(uuid: Uuid, handle: AttHandle) -> GattRecord {
         GattRecord {
             uuid,
             record_type: GattRecordType::PrimaryService,
             attribute_handle: handle.0,
+            start_handle: 0,
             properties: 0,
             extended_properties: 0,
             permissions: 0,
@@ -396,12 +783,35 @@ mod test {
             uuid,
             record_type: GattRecordType::Characteristic,
             attribute_handle: handle.0,
+            start_handle: 0,
             properties,
             extended_properties: 0,
             permissions: 0,
         }
     }
 
+    fn make_descriptor_record(uuid: Uuid, handle: AttHandle) -> GattRecord {
+        GattRecord {
+            uuid,
+            record_type: GattRecordType::Descriptor,
+            attribute_handle: handle.0,
+            start_handle: 0,
+            properties: 0,
+            extended_properties: 0,
+            permissions: 0,
+        }
+    }
+
+    #[test]
+    fn test_disconnect_status() {
+        assert_eq!(disconnect_status(DisconnectReason::TransactionTimeout), GATT_CONN_TIMEOUT);
+        assert_eq!(disconnect_status(DisconnectReason::ConfirmationTimeout), GATT_CONN_TIMEOUT);
+        assert_eq!(
+            disconnect_status(DisconnectReason::ProtocolViolation),
+            GATT_CONN_TERMINATE_LOCAL_HOST
+        );
+    }
+
     #[test]
     fn test_empty_records() {
         let res = records_to_service(&[]);
@@ -474,7 +884,12 @@ mod test {
 
         assert_eq!(
             service.characteristics[0].permissions,
-            AttPermissions { readable: true, writable: false }
+            AttPermissions {
+                readable: true,
+                writable: false,
+                writable_without_response: false,
+                ..Default::default()
+            }
         );
     }
 
@@ -488,7 +903,12 @@ mod test {
 
         assert_eq!(
             service.characteristics[0].permissions,
-            AttPermissions { readable: false, writable: true }
+            AttPermissions {
+                readable: false,
+                writable: true,
+                writable_without_response: false,
+                ..Default::default()
+            }
         );
     }
 
@@ -502,7 +922,229 @@ mod test {
 
         assert_eq!(
             service.characteristics[0].permissions,
-            AttPermissions { readable: true, writable: true }
+            AttPermissions {
+                readable: true,
+                writable: true,
+                writable_without_response: false,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_characteristic_signed_write_property() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0x40),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            service.characteristics[0].permissions,
+            AttPermissions { writable_signed: true, ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn test_characteristic_security_permissions() {
+        let mut record = make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0);
+        record.permissions = GATT_PERM_READ_ENCRYPTED | GATT_PERM_WRITE_ENC_MITM | 0xA000;
+
+        let service =
+            records_to_service(&[make_service_record(SERVICE_UUID, SERVICE_HANDLE), record])
+                .unwrap();
+
+        assert_eq!(
+            service.characteristics[0].permissions.read_security,
+            AttSecurityRequirements { encryption: true, min_key_size: 16, ..Default::default() }
         );
+        assert_eq!(
+            service.characteristics[0].permissions.write_security,
+            AttSecurityRequirements {
+                authentication: true,
+                min_key_size: 16,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_characteristic_notify_and_indicate_properties() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0x10 | 0x20),
+        ])
+        .unwrap();
+
+        assert!(service.characteristics[0].notify);
+        assert!(service.characteristics[0].indicate);
+    }
+
+    #[test]
+    fn test_cccd_descriptor() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0x10),
+            make_descriptor_record(CLIENT_CHARACTERISTIC_CONFIGURATION_UUID, DESCRIPTOR_HANDLE),
+        ])
+        .unwrap();
+
+        assert_eq!(service.characteristics[0].descriptors.len(), 1);
+        assert_eq!(service.characteristics[0].descriptors[0].handle, DESCRIPTOR_HANDLE);
+        assert_eq!(
+            service.characteristics[0].descriptors[0].type_,
+            CLIENT_CHARACTERISTIC_CONFIGURATION_UUID
+        );
+    }
+
+    #[test]
+    fn test_cccd_descriptor_permissions() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0x10),
+            GattRecord {
+                permissions: GATT_PERM_READ | GATT_PERM_WRITE_ENCRYPTED,
+                ..make_descriptor_record(
+                    CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                    DESCRIPTOR_HANDLE,
+                )
+            },
+        ])
+        .unwrap();
+
+        let permissions = service.characteristics[0].descriptors[0].permissions;
+        assert!(permissions.readable);
+        assert!(!permissions.read_security.encryption);
+        assert!(permissions.writable);
+        assert!(permissions.write_security.encryption);
+        assert!(!permissions.writable_without_response);
+    }
+
+    #[test]
+    fn test_read_only_cccd_descriptor() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0x10),
+            GattRecord {
+                permissions: GATT_PERM_READ,
+                ..make_descriptor_record(
+                    CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                    DESCRIPTOR_HANDLE,
+                )
+            },
+        ])
+        .unwrap();
+
+        let permissions = service.characteristics[0].descriptors[0].permissions;
+        assert!(permissions.readable);
+        assert!(!permissions.writable);
+    }
+
+    #[test]
+    fn test_other_descriptors_ignored() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0),
+            make_descriptor_record(Uuid::new(0x2904), DESCRIPTOR_HANDLE),
+        ])
+        .unwrap();
+
+        assert!(service.characteristics[0].descriptors.is_empty());
+    }
+
+    #[test]
+    fn test_included_service() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            GattRecord {
+                record_type: GattRecordType::IncludedService,
+                attribute_handle: INCLUDE_HANDLE.0,
+                start_handle: INCLUDED_SERVICE_HANDLE.0,
+                ..make_service_record(ANOTHER_SERVICE_UUID, INCLUDE_HANDLE)
+            },
+        ])
+        .unwrap();
+
+        assert_eq!(service.includes.len(), 1);
+        assert_eq!(service.includes[0].handle, INCLUDE_HANDLE);
+        assert_eq!(service.includes[0].service_handle, INCLUDED_SERVICE_HANDLE);
+    }
+
+    #[test]
+    fn test_characteristic_extended_properties() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            GattRecord {
+                extended_properties: 0x03,
+                ..make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0x80)
+            },
+        ])
+        .unwrap();
+
+        // the descriptor is allocated by the legacy stack after the value
+        let extended_properties = service.characteristics[0].extended_properties.as_ref().unwrap();
+        assert_eq!(extended_properties.handle, AttHandle(CHARACTERISTIC_HANDLE.0 + 1));
+        assert!(extended_properties.reliable_write);
+        assert!(extended_properties.writable_auxiliaries);
+        assert!(service.characteristics[0].descriptors.is_empty());
+    }
+
+    #[test]
+    fn test_extended_properties_descriptor() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            GattRecord {
+                extended_properties: 0x01,
+                ..make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0)
+            },
+            make_descriptor_record(CHARACTERISTIC_EXTENDED_PROPERTIES_UUID, DESCRIPTOR_HANDLE),
+        ])
+        .unwrap();
+
+        let extended_properties = service.characteristics[0].extended_properties.as_ref().unwrap();
+        assert_eq!(extended_properties.handle, DESCRIPTOR_HANDLE);
+        assert!(extended_properties.reliable_write);
+        assert!(!extended_properties.writable_auxiliaries);
+    }
+
+    #[test]
+    fn test_duplicate_extended_properties_descriptor() {
+        let res = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0x80),
+            make_descriptor_record(CHARACTERISTIC_EXTENDED_PROPERTIES_UUID, DESCRIPTOR_HANDLE),
+        ]);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_user_description_descriptor() {
+        let service = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_characteristic_record(CHARACTERISTIC_UUID, CHARACTERISTIC_HANDLE, 0),
+            GattRecord {
+                permissions: GATT_PERM_READ,
+                ..make_descriptor_record(CHARACTERISTIC_USER_DESCRIPTION_UUID, DESCRIPTOR_HANDLE)
+            },
+        ])
+        .unwrap();
+
+        // the description itself is owned by the upper layer
+        let descriptor = &service.characteristics[0].descriptors[0];
+        assert_eq!(descriptor.handle, DESCRIPTOR_HANDLE);
+        assert_eq!(descriptor.type_, CHARACTERISTIC_USER_DESCRIPTION_UUID);
+        assert!(descriptor.permissions.readable);
+        assert!(!descriptor.permissions.writable);
+    }
+
+    #[test]
+    fn test_cccd_without_characteristic() {
+        let res = records_to_service(&[
+            make_service_record(SERVICE_UUID, SERVICE_HANDLE),
+            make_descriptor_record(CLIENT_CHARACTERISTIC_CONFIGURATION_UUID, DESCRIPTOR_HANDLE),
+        ]);
+
+        assert!(res.is_err());
     }
 }
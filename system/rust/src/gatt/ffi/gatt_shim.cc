@@ -18,7 +18,11 @@
 #include <base/location.h>
 
 #include <cstdint>
+#include <limits>
+#include <map>
 #include <optional>
+#include <string>
+#include <utility>
 
 #include "include/hardware/bluetooth.h"
 #include "include/hardware/bt_common_types.h"
@@ -26,7 +30,12 @@
 #include "include/hardware/bt_gatt_server.h"
 #include "os/log.h"
 #include "rust/cxx.h"
+#include "stack/btm/btm_dev.h"
+#include "stack/gatt/gatt_int.h"
+#include "stack/include/btu.h"  // do_in_main_thread
 #include "stack/include/gatt_api.h"
+#include "stack/include/sdp_api.h"
+#include "stack/include/sdpdefs.h"
 #include "types/bluetooth/uuid.h"
 #include "types/raw_address.h"
 
@@ -45,6 +54,70 @@ std::optional<RawAddress> AddressOfConnection(uint16_t conn_id) {
   }
   return remote_bda;
 }
+
+// The SDP record handles of the services exposed over BR/EDR, keyed by server
+// and service handle. Only accessed from the main thread.
+std::map<std::pair<uint8_t, uint16_t>, uint32_t> sdp_records;
+
+void PublishSdpRecordOnMainThread(uint8_t server_id, bluetooth::Uuid uuid,
+                                  uint16_t start_handle, uint16_t end_handle) {
+  // the legacy stack already publishes the services it mirrors (except those
+  // owning their own records) when GATT over BR/EDR is enabled
+  if (gatt_cb.over_br_enabled ||
+      uuid == bluetooth::Uuid::From16Bit(UUID_SERVCLASS_GMCS_SERVER) ||
+      uuid == bluetooth::Uuid::From16Bit(UUID_SERVCLASS_GTBS_SERVER)) {
+    return;
+  }
+  auto sdp_handle = gatt_add_sdp_record(uuid, start_handle, end_handle);
+  if (sdp_handle == 0) {
+    LOG_WARN("Failed to publish SDP record for service %d of server %d",
+             start_handle, server_id);
+    return;
+  }
+  auto [it, inserted] =
+      sdp_records.try_emplace({server_id, start_handle}, sdp_handle);
+  if (!inserted) {
+    LOG_WARN("Replacing SDP record for service %d of server %d", start_handle,
+             server_id);
+    SDP_DeleteRecord(it->second);
+    it->second = sdp_handle;
+  }
+}
+
+void WithdrawSdpRecordOnMainThread(uint8_t server_id, uint16_t start_handle) {
+  auto it = sdp_records.find({server_id, start_handle});
+  if (it == sdp_records.end()) {
+    return;
+  }
+  SDP_DeleteRecord(it->second);
+  sdp_records.erase(it);
+}
+
+tBTM_SEC_DEV_REC* SigningDeviceOfTransport(uint8_t tcb_idx) {
+  tGATT_TCB* p_tcb = gatt_get_tcb_by_idx(tcb_idx);
+  if (p_tcb == nullptr) {
+    return nullptr;
+  }
+  tBTM_SEC_DEV_REC* p_rec = btm_find_dev(p_tcb->peer_bda);
+  if (p_rec == nullptr || !(p_rec->ble.key_type & BTM_LE_KEY_PCSRK)) {
+    return nullptr;
+  }
+  return p_rec;
+}
+
+void OnPeerSignCounterUsedOnMainThread(uint8_t tcb_idx, uint32_t sign_counter) {
+  tBTM_SEC_DEV_REC* p_rec = SigningDeviceOfTransport(tcb_idx);
+  if (p_rec == nullptr) {
+    LOG_WARN("Not updating sign counter since transport %d has no CSRK",
+             tcb_idx);
+    return;
+  }
+  // the counter holds the smallest value still accepted from the peer
+  if (sign_counter >= p_rec->ble.keys.counter &&
+      sign_counter < std::numeric_limits<uint32_t>::max()) {
+    p_rec->ble.keys.counter = sign_counter + 1;
+  }
+}
 }  // namespace
 
 namespace bluetooth {
@@ -91,5 +164,91 @@ void GattServerCallbacks::OnServerWriteCharacteristic(
                  base::Owned(buf), value.size()));
 }
 
+void GattServerCallbacks::OnServerWriteDescriptor(
+    uint16_t conn_id, uint32_t trans_id, uint16_t attr_handle, uint32_t offset,
+    bool need_response, bool is_prepare,
+    ::rust::Slice<const uint8_t> value) const {
+  auto addr = AddressOfConnection(conn_id);
+  if (!addr.has_value()) {
+    LOG_WARN("Dropping server write descriptor since connection %d not found",
+             conn_id);
+    return;
+  }
+
+  auto buf = new uint8_t[value.size()];
+  std::copy(value.begin(), value.end(), buf);
+
+  do_in_jni_thread(
+      FROM_HERE,
+      base::Bind(callbacks.request_write_descriptor_cb, conn_id, trans_id,
+                 addr.value(), attr_handle, offset, need_response, is_prepare,
+                 base::Owned(buf), value.size()));
+}
+
+void GattServerCallbacks::OnServerExecuteWrite(uint16_t conn_id,
+                                               uint32_t trans_id,
+                                               bool execute) const {
+  auto addr = AddressOfConnection(conn_id);
+  if (!addr.has_value()) {
+    LOG_WARN("Dropping server execute write since connection %d not found",
+             conn_id);
+    return;
+  }
+
+  do_in_jni_thread(FROM_HERE,
+                   base::Bind(callbacks.request_exec_write_cb, conn_id,
+                              trans_id, addr.value(), execute));
+}
+
+void GattServerCallbacks::OnMtuChanged(uint16_t conn_id, uint16_t mtu) const {
+  do_in_jni_thread(FROM_HERE,
+                   base::Bind(callbacks.mtu_changed_cb, conn_id, mtu));
+}
+
+void GattServerCallbacks::OnLinkDropped(uint16_t conn_id, uint8_t status,
+                                        ::rust::Str reason) const {
+  LOG_WARN("Rust GATT server dropped the link of connection %d: %s", conn_id,
+           std::string(reason).c_str());
+  // optional, so clients that don't set it only see the disconnection itself
+  if (callbacks.link_dropped_cb == nullptr) {
+    return;
+  }
+  do_in_jni_thread(FROM_HERE,
+                   base::Bind(callbacks.link_dropped_cb, conn_id, status));
+}
+
+void PublishSdpRecord(uint8_t server_id, Uuid uuid, uint16_t start_handle,
+                      uint16_t end_handle) {
+  do_in_main_thread(FROM_HERE,
+                    base::Bind(&PublishSdpRecordOnMainThread, server_id, uuid,
+                               start_handle, end_handle));
+}
+
+void WithdrawSdpRecord(uint8_t server_id, uint16_t start_handle) {
+  do_in_main_thread(FROM_HERE, base::Bind(&WithdrawSdpRecordOnMainThread,
+                                          server_id, start_handle));
+}
+
+bool GetPeerSigningKey(uint8_t tcb_idx, ::rust::Slice<uint8_t> csrk,
+                       uint32_t& min_sign_counter) {
+  tBTM_SEC_DEV_REC* p_rec = SigningDeviceOfTransport(tcb_idx);
+  if (p_rec == nullptr) {
+    return false;
+  }
+  const auto& pcsrk = p_rec->ble.keys.pcsrk;
+  if (csrk.size() != pcsrk.size()) {
+    LOG_ERROR("Unexpected CSRK buffer of size %zu", csrk.size());
+    return false;
+  }
+  std::copy(pcsrk.begin(), pcsrk.end(), csrk.begin());
+  min_sign_counter = p_rec->ble.keys.counter;
+  return true;
+}
+
+void OnPeerSignCounterUsed(uint8_t tcb_idx, uint32_t sign_counter) {
+  do_in_main_thread(FROM_HERE, base::Bind(&OnPeerSignCounterUsedOnMainThread,
+                                          tcb_idx, sign_counter));
+}
+
 }  // namespace gatt
 }  // namespace bluetooth
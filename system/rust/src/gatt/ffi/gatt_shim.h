@@ -21,6 +21,7 @@
 #include "include/hardware/bt_gatt_client.h"
 #include "include/hardware/bt_gatt_server.h"
 #include "rust/cxx.h"
+#include "types/bluetooth/uuid.h"
 
 namespace bluetooth {
 namespace gatt {
@@ -39,9 +40,32 @@ class GattServerCallbacks {
                                    bool need_response, bool is_prepare,
                                    ::rust::Slice<const uint8_t> value) const;
 
+  void OnServerWriteDescriptor(uint16_t conn_id, uint32_t trans_id,
+                               uint16_t attr_handle, uint32_t offset,
+                               bool need_response, bool is_prepare,
+                               ::rust::Slice<const uint8_t> value) const;
+
+  void OnServerExecuteWrite(uint16_t conn_id, uint32_t trans_id,
+                            bool execute) const;
+
+  void OnMtuChanged(uint16_t conn_id, uint16_t mtu) const;
+
+  void OnLinkDropped(uint16_t conn_id, uint8_t status,
+                     ::rust::Str reason) const;
+
  private:
   const btgatt_server_callbacks_t& callbacks;
 };
 
+void PublishSdpRecord(uint8_t server_id, Uuid uuid, uint16_t start_handle,
+                      uint16_t end_handle);
+
+void WithdrawSdpRecord(uint8_t server_id, uint16_t start_handle);
+
+bool GetPeerSigningKey(uint8_t tcb_idx, ::rust::Slice<uint8_t> csrk,
+                       uint32_t& min_sign_counter);
+
+void OnPeerSignCounterUsed(uint8_t tcb_idx, uint32_t sign_counter);
+
 }  // namespace gatt
 }  // namespace bluetooth
@@ -36,6 +36,20 @@ pub struct TransactionId(pub u32);
 #[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
 pub struct TransportIndex(pub u8);
 
+/// The local L2CAP CID of an enhanced credit-based channel carrying EATT
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
+pub struct EnhancedChannelId(pub u16);
+
+/// One of the (possibly many) ATT bearers of a connection, from 5.3 3F 3.2.11
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
+pub enum BearerId {
+    /// The unenhanced bearer, on the LE fixed channel or the BR/EDR ATT channel
+    /// (PSM 0x001F)
+    Unenhanced,
+    /// An enhanced bearer, on its own L2CAP channel
+    Enhanced(EnhancedChannelId),
+}
+
 /// An advertising set ID (zero-based)
 #[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
 pub struct AdvertiserId(pub u8);
@@ -43,3 +57,8 @@ pub struct AdvertiserId(pub u8);
 /// The handle of a given ATT attribute
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct AttHandle(pub u16);
+
+/// The identity address of a bonded peer, used to key state that persists
+/// across connections
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
+pub struct PeerIdentity(pub [u8; 6]);
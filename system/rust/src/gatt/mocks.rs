@@ -1,4 +1,6 @@
-//! Mocks for the GattDatastore + AttTransport traits, for use in test
+//! Mocks for the GattDatastore + AttTransport + SdpPublisher traits, for use in
+//! test
 pub mod mock_callbacks;
 pub mod mock_datastore;
+pub mod mock_sdp_publisher;
 pub mod mock_transport;
@@ -2,6 +2,8 @@
 
 use crate::{
     gatt::{
+        callbacks::TransactionDecision,
+        channel::DisconnectReason,
         ids::{AttHandle, ConnectionId, TransactionId},
         GattCallbacks,
     },
@@ -35,6 +37,22 @@ pub enum MockCallbackEvents {
         bool,
         OwnedAttAttributeDataView,
     ),
+    /// GattCallbacks#on_server_write_descriptor invoked
+    OnServerWriteDescriptor(
+        ConnectionId,
+        TransactionId,
+        AttHandle,
+        u32,
+        bool,
+        bool,
+        OwnedAttAttributeDataView,
+    ),
+    /// GattCallbacks#on_server_execute_write invoked
+    OnServerExecuteWrite(ConnectionId, TransactionId, TransactionDecision),
+    /// GattCallbacks#on_mtu_changed invoked
+    OnMtuChanged(ConnectionId, usize),
+    /// GattCallbacks#on_link_dropped invoked
+    OnLinkDropped(ConnectionId, DisconnectReason),
 }
 
 impl GattCallbacks for MockCallbacks {
@@ -75,4 +93,44 @@ impl GattCallbacks for MockCallbacks {
             ))
             .unwrap();
     }
+
+    fn on_server_write_descriptor(
+        &self,
+        conn_id: ConnectionId,
+        trans_id: TransactionId,
+        handle: AttHandle,
+        offset: u32,
+        need_response: bool,
+        is_prepare: bool,
+        value: AttAttributeDataView,
+    ) {
+        self.0
+            .send(MockCallbackEvents::OnServerWriteDescriptor(
+                conn_id,
+                trans_id,
+                handle,
+                offset,
+                need_response,
+                is_prepare,
+                value.to_owned_packet(),
+            ))
+            .unwrap();
+    }
+
+    fn on_server_execute_write(
+        &self,
+        conn_id: ConnectionId,
+        trans_id: TransactionId,
+        decision: TransactionDecision,
+    ) {
+        self.0.send(MockCallbackEvents::OnServerExecuteWrite(conn_id, trans_id, decision)).unwrap();
+    }
+
+    fn on_mtu_changed(&self, conn_id: ConnectionId, mtu: usize) {
+        self.0.send(MockCallbackEvents::OnMtuChanged(conn_id, mtu)).unwrap();
+    }
+
+    fn on_link_dropped(&self, conn_id: ConnectionId, reason: DisconnectReason) {
+        self.0.send(MockCallbackEvents::OnLinkDropped(conn_id, reason)).unwrap();
+    }
 }
@@ -2,7 +2,8 @@
 
 use crate::{
     gatt::{
-        callbacks::GattDatastore,
+        callbacks::{GattDatastore, TransactionDecision},
+        channel::DisconnectReason,
         ids::{AttHandle, ConnectionId},
     },
     packets::{
@@ -35,11 +36,18 @@ pub enum MockDatastoreEvents {
     AddConnection(ConnectionId),
     /// A connection was removed
     RemoveConnection(ConnectionId),
-    /// A characteristic was read on a given handle. The oneshot is used to
-    /// return the value read.
+    /// The ATT_MTU of a connection changed
+    MtuChanged(ConnectionId, usize),
+    /// The server dropped the link of a connection
+    LinkDropped(ConnectionId, DisconnectReason),
+    /// A characteristic was read on a given handle, from the given offset and
+    /// with the given is_long flag. The oneshot is used to return the value
+    /// read.
     ReadCharacteristic(
         ConnectionId,
         AttHandle,
+        usize,
+        bool,
         oneshot::Sender<Result<AttAttributeDataChild, AttErrorCode>>,
     ),
     /// A characteristic was written to on a given handle. The oneshot is used
@@ -50,6 +58,23 @@ pub enum MockDatastoreEvents {
         OwnedAttAttributeDataView,
         oneshot::Sender<Result<(), AttErrorCode>>,
     ),
+    /// A characteristic was written to on a given handle, without waiting for
+    /// the result
+    WriteNoResponseCharacteristic(ConnectionId, AttHandle, OwnedAttAttributeDataView),
+    /// A descriptor owned by the GattDatabase was written on a given handle
+    WriteNoResponseDescriptor(ConnectionId, AttHandle, OwnedAttAttributeDataView),
+    /// A write to a characteristic was prepared on a given handle. The oneshot
+    /// is used to return whether the write could be prepared.
+    PrepareWriteCharacteristic(
+        ConnectionId,
+        AttHandle,
+        usize,
+        OwnedAttAttributeDataView,
+        oneshot::Sender<Result<(), AttErrorCode>>,
+    ),
+    /// The prepared writes on a connection were executed or cancelled. The
+    /// oneshot is used to return whether this succeeded.
+    ExecuteWrite(ConnectionId, TransactionDecision, oneshot::Sender<Result<(), AttErrorCode>>),
 }
 
 #[async_trait(?Send)]
@@ -62,13 +87,25 @@ impl GattDatastore for MockDatastore {
         self.0.send(MockDatastoreEvents::RemoveConnection(conn_id)).unwrap();
     }
 
+    fn on_mtu_changed(&self, conn_id: ConnectionId, mtu: usize) {
+        self.0.send(MockDatastoreEvents::MtuChanged(conn_id, mtu)).unwrap();
+    }
+
+    fn on_link_dropped(&self, conn_id: ConnectionId, reason: DisconnectReason) {
+        self.0.send(MockDatastoreEvents::LinkDropped(conn_id, reason)).unwrap();
+    }
+
     async fn read_characteristic(
         &self,
         conn_id: ConnectionId,
         handle: AttHandle,
+        offset: usize,
+        is_long: bool,
     ) -> Result<AttAttributeDataChild, AttErrorCode> {
         let (tx, rx) = oneshot::channel();
-        self.0.send(MockDatastoreEvents::ReadCharacteristic(conn_id, handle, tx)).unwrap();
+        self.0
+            .send(MockDatastoreEvents::ReadCharacteristic(conn_id, handle, offset, is_long, tx))
+            .unwrap();
         let resp = rx.await.unwrap();
         info!("sending {resp:?} down from upper tester");
         resp
@@ -91,4 +128,64 @@ impl GattDatastore for MockDatastore {
             .unwrap();
         rx.await.unwrap()
     }
+
+    fn write_no_response_characteristic(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    ) {
+        self.0
+            .send(MockDatastoreEvents::WriteNoResponseCharacteristic(
+                conn_id,
+                handle,
+                data.to_owned_packet(),
+            ))
+            .unwrap();
+    }
+
+    fn write_no_response_descriptor(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    ) {
+        self.0
+            .send(MockDatastoreEvents::WriteNoResponseDescriptor(
+                conn_id,
+                handle,
+                data.to_owned_packet(),
+            ))
+            .unwrap();
+    }
+
+    async fn prepare_write_characteristic(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        let (tx, rx) = oneshot::channel();
+        self.0
+            .send(MockDatastoreEvents::PrepareWriteCharacteristic(
+                conn_id,
+                handle,
+                offset,
+                data.to_owned_packet(),
+                tx,
+            ))
+            .unwrap();
+        rx.await.unwrap()
+    }
+
+    async fn execute_write(
+        &self,
+        conn_id: ConnectionId,
+        decision: TransactionDecision,
+    ) -> Result<(), AttErrorCode> {
+        let (tx, rx) = oneshot::channel();
+        self.0.send(MockDatastoreEvents::ExecuteWrite(conn_id, decision, tx)).unwrap();
+        rx.await.unwrap()
+    }
 }
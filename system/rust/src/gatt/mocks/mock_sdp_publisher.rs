@@ -0,0 +1,37 @@
+//! Mocked implementation of SdpPublisher for use in test
+
+use crate::gatt::{
+    ids::{AttHandle, ServerId},
+    server::sdp::{SdpPublisher, SdpServiceRecord},
+};
+use tokio::sync::mpsc::{self, unbounded_channel, UnboundedReceiver};
+
+/// Routes calls to SdpPublisher into a channel of MockSdpEvents
+pub struct MockSdpPublisher(mpsc::UnboundedSender<MockSdpEvents>);
+
+impl MockSdpPublisher {
+    /// Constructor. Returns self and the RX side of the associated channel.
+    pub fn new() -> (Self, UnboundedReceiver<MockSdpEvents>) {
+        let (tx, rx) = unbounded_channel();
+        (Self(tx), rx)
+    }
+}
+
+/// Events representing calls to SdpPublisher
+#[derive(Debug, PartialEq, Eq)]
+pub enum MockSdpEvents {
+    /// SdpPublisher#publish invoked
+    Publish(ServerId, SdpServiceRecord),
+    /// SdpPublisher#withdraw invoked
+    Withdraw(ServerId, AttHandle),
+}
+
+impl SdpPublisher for MockSdpPublisher {
+    fn publish(&self, server_id: ServerId, record: SdpServiceRecord) {
+        self.0.send(MockSdpEvents::Publish(server_id, record)).unwrap();
+    }
+
+    fn withdraw(&self, server_id: ServerId, start_handle: AttHandle) {
+        self.0.send(MockSdpEvents::Withdraw(server_id, start_handle)).unwrap();
+    }
+}
@@ -1,20 +1,70 @@
 //! Mocked implementation of AttTransport for use in test
 
 use crate::{
-    gatt::{channel::AttTransport, ids::TransportIndex},
+    gatt::{
+        channel::{AttTransport, DisconnectReason},
+        ids::{EnhancedChannelId, TransportIndex},
+    },
     packets::{AttBuilder, Serializable, SerializeError},
 };
 use tokio::sync::mpsc::{self, unbounded_channel, UnboundedReceiver};
 
+type DisconnectSender = mpsc::UnboundedSender<(TransportIndex, DisconnectReason)>;
+type DisconnectReceiver = UnboundedReceiver<(TransportIndex, DisconnectReason)>;
+
 /// Routes calls to AttTransport into a channel containing AttBuilders
-pub struct MockAttTransport(mpsc::UnboundedSender<(TransportIndex, AttBuilder)>);
+pub struct MockAttTransport {
+    tx: mpsc::UnboundedSender<(TransportIndex, AttBuilder)>,
+    enhanced_tx: Option<mpsc::UnboundedSender<MockEnhancedChannelEvents>>,
+    disconnect_tx: Option<DisconnectSender>,
+}
+
+/// Events representing calls to the enhanced channel methods of AttTransport
+#[derive(Debug)]
+pub enum MockEnhancedChannelEvents {
+    /// A packet was sent on an enhanced channel
+    SendPacket(TransportIndex, EnhancedChannelId, AttBuilder),
+    /// The given number of channels were requested, with the given Rx MTU
+    OpenChannels(TransportIndex, usize, usize),
+    /// The given channels were accepted, with the given Rx MTU
+    AcceptChannels(TransportIndex, Vec<EnhancedChannelId>, usize),
+}
 
 impl MockAttTransport {
     /// Constructor. Returns Self and the RX side of a channel containing
     /// AttBuilders sent on TransportIndices
     pub fn new() -> (Self, UnboundedReceiver<(TransportIndex, AttBuilder)>) {
         let (tx, rx) = unbounded_channel();
-        (Self(tx), rx)
+        (Self { tx, enhanced_tx: None, disconnect_tx: None }, rx)
+    }
+
+    /// Constructor. As well as the channel returned by new(), returns the RX
+    /// side of a channel containing calls made on enhanced channels
+    pub fn new_with_enhanced_channels() -> (
+        Self,
+        UnboundedReceiver<(TransportIndex, AttBuilder)>,
+        UnboundedReceiver<MockEnhancedChannelEvents>,
+    ) {
+        let (tx, rx) = unbounded_channel();
+        let (enhanced_tx, enhanced_rx) = unbounded_channel();
+        (Self { tx, enhanced_tx: Some(enhanced_tx), disconnect_tx: None }, rx, enhanced_rx)
+    }
+
+    /// Constructor. As well as the channel returned by new(), returns the RX
+    /// side of a channel containing the links disconnected, and why
+    pub fn new_with_disconnects(
+    ) -> (Self, UnboundedReceiver<(TransportIndex, AttBuilder)>, DisconnectReceiver) {
+        let (tx, rx) = unbounded_channel();
+        let (disconnect_tx, disconnect_rx) = unbounded_channel();
+        (Self { tx, enhanced_tx: None, disconnect_tx: Some(disconnect_tx) }, rx, disconnect_rx)
+    }
+
+    fn send_enhanced_event(&self, event: MockEnhancedChannelEvents) {
+        self.enhanced_tx
+            .as_ref()
+            .expect("enhanced channels are not supported by this MockAttTransport")
+            .send(event)
+            .unwrap();
     }
 }
 
@@ -25,7 +75,43 @@ impl AttTransport for MockAttTransport {
         packet: AttBuilder,
     ) -> Result<(), SerializeError> {
         packet.to_vec()?; // trigger SerializeError if needed
-        self.0.send((tcb_idx, packet)).unwrap();
+        self.tx.send((tcb_idx, packet)).unwrap();
+        Ok(())
+    }
+
+    fn send_packet_on_enhanced_channel(
+        &self,
+        tcb_idx: TransportIndex,
+        cid: EnhancedChannelId,
+        packet: AttBuilder,
+    ) -> Result<(), SerializeError> {
+        packet.to_vec()?; // trigger SerializeError if needed
+        self.send_enhanced_event(MockEnhancedChannelEvents::SendPacket(tcb_idx, cid, packet));
         Ok(())
     }
+
+    fn open_enhanced_channels(&self, tcb_idx: TransportIndex, count: usize, rx_mtu: usize) {
+        self.send_enhanced_event(MockEnhancedChannelEvents::OpenChannels(tcb_idx, count, rx_mtu));
+    }
+
+    fn accept_enhanced_channels(
+        &self,
+        tcb_idx: TransportIndex,
+        accepted: &[EnhancedChannelId],
+        rx_mtu: usize,
+    ) {
+        self.send_enhanced_event(MockEnhancedChannelEvents::AcceptChannels(
+            tcb_idx,
+            accepted.to_vec(),
+            rx_mtu,
+        ));
+    }
+
+    fn disconnect(&self, tcb_idx: TransportIndex, reason: DisconnectReason) {
+        self.disconnect_tx
+            .as_ref()
+            .expect("disconnections are not expected by this MockAttTransport")
+            .send((tcb_idx, reason))
+            .unwrap();
+    }
 }
@@ -3,43 +3,118 @@
 
 mod att_database;
 pub mod att_server_bearer;
+pub mod cccd_store;
+mod command_handler;
 pub mod gatt_database;
+pub mod sdp;
+pub mod services;
+pub mod signing;
 mod transaction_handler;
 mod transactions;
 
 #[cfg(test)]
 mod test;
 
-use std::{collections::HashMap, rc::Rc};
+use std::{collections::HashMap, future::Future, rc::Rc};
 
 use crate::{
     gatt::{ids::ConnectionId, server::gatt_database::GattDatabase},
-    packets::AttView,
+    packets::{AttAttributeDataChild, AttView, GattServiceChangedBuilder},
 };
 
 use self::{
     super::ids::ServerId,
-    att_server_bearer::AttServerBearer,
-    gatt_database::{AttDatabaseImpl, GattServiceWithHandle},
+    att_server_bearer::{
+        AttServerBearer, IndicationError, DEFAULT_ATT_MTU, MAX_ATT_MTU, MIN_BR_EDR_ATT_MTU,
+        MIN_ENHANCED_ATT_MTU,
+    },
+    cccd_store::CccdStore,
+    gatt_database::{
+        AttDatabaseImpl, ClientConfiguration, ConnectionSecurity, GattServiceWithHandle,
+    },
+    sdp::{SdpPublisher, SdpServiceRecord},
+    services::{gatt::generic_attribute_service, LocalGattService},
+    signing::{SignedWriteVerifier, SigningKeyProvider},
 };
 
-use super::{callbacks::GattDatastore, channel::AttTransport, ids::AttHandle};
+use super::{
+    callbacks::GattDatastore,
+    channel::{AttTransport, DisconnectReason},
+    ids::{AttHandle, BearerId, EnhancedChannelId, PeerIdentity},
+};
 use anyhow::{anyhow, bail, Result};
-use log::info;
+use log::{info, warn};
+use tokio::task::spawn_local;
+
+/// The maximum number of enhanced bearers on a single connection, matching the
+/// legacy stack (L2CAP_CREDIT_BASED_MAX_CIDS)
+pub const MAX_ENHANCED_BEARERS: usize = 5;
+
+/// The handle of the Generic Attribute service registered in every server. The
+/// legacy stack keeps handles 1 to 19 for its own GATT service, so they are
+/// never allocated to the services of the upper layer.
+pub const GATT_SERVICE_HANDLE: AttHandle = AttHandle(0x0001);
+
+type ConnectionBearers = HashMap<BearerId, Rc<AttServerBearer<AttDatabaseImpl<dyn GattDatastore>>>>;
 
 #[allow(missing_docs)]
 pub struct GattModule {
-    connection_bearers:
-        HashMap<ConnectionId, Rc<AttServerBearer<AttDatabaseImpl<dyn GattDatastore>>>>,
+    connection_bearers: HashMap<ConnectionId, ConnectionBearers>,
     databases: HashMap<ServerId, Rc<GattDatabase<dyn GattDatastore>>>,
     datastore: Rc<dyn GattDatastore>,
     transport: Rc<dyn AttTransport>,
+    server_rx_mtu: usize,
+    cccd_store: Option<Rc<dyn CccdStore>>,
+    signing_key_provider: Option<Rc<dyn SigningKeyProvider>>,
+    sdp_publisher: Option<Rc<dyn SdpPublisher>>,
+    br_edr_server: Option<ServerId>,
 }
 
 impl GattModule {
     /// Constructor. Uses `datastore` to read/write characteristics.
     pub fn new(datastore: Rc<dyn GattDatastore>, transport: Rc<dyn AttTransport>) -> Self {
-        Self { connection_bearers: HashMap::new(), databases: HashMap::new(), datastore, transport }
+        Self {
+            connection_bearers: HashMap::new(),
+            databases: HashMap::new(),
+            datastore,
+            transport,
+            server_rx_mtu: MAX_ATT_MTU,
+            cccd_store: None,
+            signing_key_provider: None,
+            sdp_publisher: None,
+            br_edr_server: None,
+        }
+    }
+
+    /// Set the store used to persist the CCCD values of bonded peers. Only
+    /// affects servers opened after this call.
+    pub fn set_cccd_store(&mut self, cccd_store: Rc<dyn CccdStore>) {
+        self.cccd_store = Some(cccd_store);
+    }
+
+    /// Set the source of the peer signing keys used to verify signed writes.
+    /// Only affects connections established after this call. Until one is
+    /// set, signed writes are dropped; no provider backed by the security
+    /// manager exists yet.
+    pub fn set_signing_key_provider(&mut self, provider: Rc<dyn SigningKeyProvider>) {
+        self.signing_key_provider = Some(provider);
+    }
+
+    /// Set the publisher of the SDP records of the services exposed over
+    /// BR/EDR. Only affects services registered after this call.
+    pub fn set_sdp_publisher(&mut self, publisher: Rc<dyn SdpPublisher>) {
+        self.sdp_publisher = Some(publisher);
+    }
+
+    /// Set the Rx MTU advertised to clients during an MTU exchange. It also
+    /// caps the ATT_MTU when the exchange is answered by the legacy stack.
+    /// Only affects connections established after this call.
+    pub fn set_server_rx_mtu(&mut self, mtu: usize) -> Result<()> {
+        if !(DEFAULT_ATT_MTU..=MAX_ATT_MTU).contains(&mtu) {
+            bail!("server Rx MTU {mtu} must be between {DEFAULT_ATT_MTU} and {MAX_ATT_MTU}");
+        }
+        self.server_rx_mtu = mtu;
+        Ok(())
     }
 
     /// Handle LE link connect
@@ -52,43 +127,432 @@ impl GattModule {
                 conn_id.get_server_id(),
             );
         };
+        database.add_connection(conn_id);
+        self.datastore.add_connection(conn_id);
+        let transport = self.transport.clone();
+        let datastore = self.datastore.clone();
+        self.connection_bearers.insert(
+            conn_id,
+            HashMap::from([(
+                BearerId::Unenhanced,
+                AttServerBearer::new(
+                    database.get_att_database(conn_id),
+                    self.server_rx_mtu,
+                    self.signing_key_provider
+                        .clone()
+                        .map(|provider| SignedWriteVerifier::new(conn_id, provider)),
+                    move |packet| transport.send_packet(conn_id.get_tcb_idx(), packet),
+                    move |mtu| datastore.on_mtu_changed(conn_id, mtu),
+                    self.drop_link_on_close(conn_id),
+                ),
+            )]),
+        );
+        Ok(())
+    }
+
+    /// Handle an MTU exchange on the unenhanced bearer of an LE link that was
+    /// answered by the legacy stack, given the Rx MTU of the client
+    pub fn on_mtu_exchanged(&mut self, conn_id: ConnectionId, client_rx_mtu: usize) -> Result<()> {
+        self.get_bearer(conn_id, BearerId::Unenhanced)?.on_mtu_exchanged(client_rx_mtu);
+        Ok(())
+    }
+
+    /// Expose the given server over BR/EDR, so it accepts ATT bearers on classic
+    /// links and its primary services are published over SDP. Only one server
+    /// can be exposed at a time, since a classic link carries a single ATT
+    /// bearer bound to one server, so this displaces any previous one.
+    pub fn expose_over_br_edr(&mut self, server_id: ServerId) -> Result<()> {
+        if !self.databases.contains_key(&server_id) {
+            bail!("server {server_id:?} not opened");
+        }
+        match self.br_edr_server {
+            Some(old) if old == server_id => return Ok(()),
+            Some(old) => {
+                warn!("server {server_id:?} exposed over BR/EDR, displacing old server {old:?}");
+                self.withdraw_sdp_records(old);
+            }
+            None => {}
+        }
+        self.br_edr_server = Some(server_id);
+        if let Some(publisher) = &self.sdp_publisher {
+            for record in self.get_sdp_records(server_id) {
+                publisher.publish(server_id, record);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a BR/EDR ATT channel being opened, with the ATT_MTU configured
+    /// by L2CAP
+    pub fn on_br_edr_connect(&mut self, conn_id: ConnectionId, mtu: usize) -> Result<()> {
+        info!("connected over BR/EDR on conn_id {conn_id:?}");
+        if self.br_edr_server != Some(conn_id.get_server_id()) {
+            bail!("server {:?} is not exposed over BR/EDR", conn_id.get_server_id());
+        }
+        if mtu < MIN_BR_EDR_ATT_MTU {
+            bail!("ATT_MTU {mtu} of {conn_id:?} is below the minimum for BR/EDR");
+        }
+        let database = self
+            .databases
+            .get(&conn_id.get_server_id())
+            .ok_or_else(|| anyhow!("server for conn_id {conn_id:?} not opened"))?;
+        database.add_connection(conn_id);
         self.datastore.add_connection(conn_id);
+        let mtu = mtu.min(self.server_rx_mtu);
         let transport = self.transport.clone();
         self.connection_bearers.insert(
             conn_id,
-            AttServerBearer::new(database.get_att_database(conn_id), move |packet| {
-                transport.send_packet(conn_id.get_tcb_idx(), packet)
-            }),
+            HashMap::from([(
+                BearerId::Unenhanced,
+                AttServerBearer::new_br_edr(
+                    database.get_att_database(conn_id),
+                    mtu,
+                    move |packet| transport.send_packet(conn_id.get_tcb_idx(), packet),
+                    self.drop_link_on_close(conn_id),
+                ),
+            )]),
+        );
+        // there is no MTU exchange, so the upper layer learns of it here
+        self.datastore.on_mtu_changed(conn_id, mtu);
+        Ok(())
+    }
+
+    /// Ask the peer on the given connection to open enhanced bearers, up to
+    /// MAX_ENHANCED_BEARERS in total
+    pub fn open_enhanced_channels(&mut self, conn_id: ConnectionId, count: usize) -> Result<()> {
+        let count = count.min(self.get_enhanced_bearer_capacity(conn_id)?);
+        if count == 0 {
+            bail!("cannot open any more enhanced bearers on {conn_id:?}");
+        }
+        self.transport.open_enhanced_channels(conn_id.get_tcb_idx(), count, self.server_rx_mtu);
+        Ok(())
+    }
+
+    /// Handle a request from the peer to open enhanced channels. They are only
+    /// accepted once the link is encrypted (5.3 3G 5.4), if the peer can
+    /// receive packets of MIN_ENHANCED_ATT_MTU, and up to MAX_ENHANCED_BEARERS
+    /// in total, after which they are opened immediately.
+    pub fn on_enhanced_channels_requested(
+        &mut self,
+        conn_id: ConnectionId,
+        cids: &[EnhancedChannelId],
+        peer_rx_mtu: usize,
+    ) -> Result<()> {
+        let encrypted = self
+            .databases
+            .get(&conn_id.get_server_id())
+            .ok_or_else(|| anyhow!("server for conn_id {conn_id:?} not opened"))?
+            .get_connection_security(conn_id)
+            .encrypted;
+        let capacity = if !encrypted {
+            0
+        } else if peer_rx_mtu < MIN_ENHANCED_ATT_MTU {
+            warn!("peer Rx MTU {peer_rx_mtu} on {conn_id:?} is below the minimum for EATT");
+            0
+        } else {
+            self.get_enhanced_bearer_capacity(conn_id)?
+        };
+        let accepted = &cids[..cids.len().min(capacity)];
+        if accepted.len() < cids.len() {
+            warn!("refusing {} enhanced channels on {conn_id:?}", cids.len() - accepted.len());
+        }
+
+        self.transport.accept_enhanced_channels(
+            conn_id.get_tcb_idx(),
+            accepted,
+            self.server_rx_mtu,
+        );
+        // the channels are open now, so each needs a bearer even if another failed
+        for cid in accepted {
+            if let Err(err) = self.on_enhanced_channel_opened(conn_id, *cid, peer_rx_mtu) {
+                warn!("failed to open bearer on {cid:?} of {conn_id:?}: {err:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle an enhanced channel being opened, so it can carry a new bearer
+    pub fn on_enhanced_channel_opened(
+        &mut self,
+        conn_id: ConnectionId,
+        cid: EnhancedChannelId,
+        peer_rx_mtu: usize,
+    ) -> Result<()> {
+        info!("enhanced channel {cid:?} opened on {conn_id:?}");
+        // an enhanced bearer cannot outlive the link, so the whole link is dropped
+        let on_close = self.drop_link_on_close(conn_id);
+        let database = self
+            .databases
+            .get(&conn_id.get_server_id())
+            .ok_or_else(|| anyhow!("server for conn_id {conn_id:?} not opened"))?;
+        let bearers = self
+            .connection_bearers
+            .get_mut(&conn_id)
+            .ok_or_else(|| anyhow!("cannot open enhanced bearer on unregistered connection"))?;
+        if peer_rx_mtu < MIN_ENHANCED_ATT_MTU {
+            bail!("peer Rx MTU {peer_rx_mtu} of {cid:?} is below the minimum for EATT");
+        }
+
+        let transport = self.transport.clone();
+        let old = bearers.insert(
+            BearerId::Enhanced(cid),
+            AttServerBearer::new_enhanced(
+                database.get_att_database(conn_id),
+                peer_rx_mtu.min(self.server_rx_mtu),
+                move |packet| {
+                    transport.send_packet_on_enhanced_channel(conn_id.get_tcb_idx(), cid, packet)
+                },
+                on_close,
+            ),
         );
+        if old.is_some() {
+            warn!("enhanced channel {cid:?} on {conn_id:?} was reopened, displacing old bearer");
+        }
+        Ok(())
+    }
+
+    /// Handle an enhanced channel being reconfigured with a new peer Rx MTU
+    pub fn on_enhanced_channel_reconfigured(
+        &mut self,
+        conn_id: ConnectionId,
+        cid: EnhancedChannelId,
+        peer_rx_mtu: usize,
+    ) -> Result<()> {
+        self.get_bearer(conn_id, BearerId::Enhanced(cid))?
+            .on_enhanced_mtu_reconfigured(peer_rx_mtu.min(self.server_rx_mtu));
         Ok(())
     }
 
+    /// Handle an enhanced channel being closed. Any transaction outstanding on
+    /// its bearer is dropped.
+    pub fn on_enhanced_channel_closed(&mut self, conn_id: ConnectionId, cid: EnhancedChannelId) {
+        info!("enhanced channel {cid:?} closed on {conn_id:?}");
+        if let Some(bearers) = self.connection_bearers.get_mut(&conn_id) {
+            bearers.remove(&BearerId::Enhanced(cid));
+        }
+    }
+
+    /// The handler of a bearer of the given connection closing itself (e.g.
+    /// after an ATT timeout), which reports why to the datastore and
+    /// disconnects the link
+    fn drop_link_on_close(&self, conn_id: ConnectionId) -> impl Fn(DisconnectReason) + 'static {
+        let datastore = self.datastore.clone();
+        let transport = self.transport.clone();
+        move |reason| {
+            warn!("dropping the link of {conn_id:?}: {reason:?}");
+            datastore.on_link_dropped(conn_id, reason);
+            transport.disconnect(conn_id.get_tcb_idx(), reason);
+        }
+    }
+
+    /// The number of enhanced bearers that can still be opened on a connection
+    fn get_enhanced_bearer_capacity(&self, conn_id: ConnectionId) -> Result<usize> {
+        let bearers = self
+            .connection_bearers
+            .get(&conn_id)
+            .ok_or_else(|| anyhow!("unregistered connection {conn_id:?}"))?;
+        let enhanced_bearers =
+            bearers.keys().filter(|bearer| matches!(bearer, BearerId::Enhanced(_))).count();
+        Ok(MAX_ENHANCED_BEARERS.saturating_sub(enhanced_bearers))
+    }
+
+    fn get_bearer(
+        &self,
+        conn_id: ConnectionId,
+        bearer: BearerId,
+    ) -> Result<&Rc<AttServerBearer<AttDatabaseImpl<dyn GattDatastore>>>> {
+        self.connection_bearers
+            .get(&conn_id)
+            .and_then(|bearers| bearers.get(&bearer))
+            .ok_or_else(|| anyhow!("{bearer:?} of {conn_id:?} is not registered"))
+    }
+
     /// Handle an LE link disconnect
     pub fn on_le_disconnect(&mut self, conn_id: ConnectionId) {
+        self.on_disconnect(conn_id);
+    }
+
+    /// Handle a BR/EDR ATT channel being closed
+    pub fn on_br_edr_disconnect(&mut self, conn_id: ConnectionId) {
+        self.on_disconnect(conn_id);
+    }
+
+    fn on_disconnect(&mut self, conn_id: ConnectionId) {
         info!("disconnected conn_id {conn_id:?}");
         self.connection_bearers.remove(&conn_id);
+        if let Some(database) = self.databases.get(&conn_id.get_server_id()) {
+            database.remove_connection(conn_id);
+        }
         self.datastore.remove_connection(conn_id);
     }
 
-    /// Handle an incoming ATT packet
+    /// Handle the peer on a connection being identified as bonded, so its
+    /// CCCD values are restored and persisted
+    pub fn on_bonded_peer(&mut self, conn_id: ConnectionId, peer: PeerIdentity) -> Result<()> {
+        let database = self
+            .databases
+            .get(&conn_id.get_server_id())
+            .ok_or_else(|| anyhow!("server for conn_id {conn_id:?} not opened"))?;
+        // the stack reports the bond again whenever the link is re-encrypted
+        if database.get_bonded_peer(conn_id) == Some(peer) {
+            return Ok(());
+        }
+        database.on_bonded_peer(conn_id, peer)?;
+
+        // the database may have changed since the peer last connected, in
+        // which case its whole cache is stale (5.3 3G 7.1)
+        if database.is_change_unaware(conn_id) {
+            self.send_service_changed(conn_id, AttHandle(0x0001), AttHandle(0xFFFF));
+        }
+        Ok(())
+    }
+
+    /// Handle a change in the security of a connection (e.g. once the link is
+    /// encrypted, or the peer is authorized), which gates access to attributes
+    /// requiring it
+    pub fn on_security_changed(
+        &mut self,
+        conn_id: ConnectionId,
+        security: ConnectionSecurity,
+    ) -> Result<()> {
+        self.databases
+            .get(&conn_id.get_server_id())
+            .ok_or_else(|| anyhow!("server for conn_id {conn_id:?} not opened"))?
+            .set_connection_security(conn_id, security);
+        Ok(())
+    }
+
+    /// Handle a peer being unbonded, forgetting its persisted CCCD values
+    pub fn on_bond_removed(&mut self, peer: PeerIdentity) -> Result<()> {
+        for database in self.databases.values() {
+            database.on_bond_removed(peer);
+        }
+        match &self.cccd_store {
+            Some(cccd_store) => cccd_store.remove(peer),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the notifications/indications the client on the specified
+    /// connection has subscribed to, for the given characteristic
+    pub fn get_client_configuration(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+    ) -> Result<ClientConfiguration> {
+        Ok(self
+            .databases
+            .get(&conn_id.get_server_id())
+            .ok_or_else(|| anyhow!("server for conn_id {conn_id:?} not opened"))?
+            .get_client_configuration(conn_id, handle))
+    }
+
+    /// Handle an incoming ATT packet on the unenhanced bearer
     pub fn handle_packet(&mut self, conn_id: ConnectionId, packet: AttView<'_>) -> Result<()> {
-        self.connection_bearers
-            .get(&conn_id)
-            .ok_or_else(|| anyhow!("dropping ATT packet for unregistered connection"))?
+        self.handle_packet_on_bearer(conn_id, BearerId::Unenhanced, packet)
+    }
+
+    /// Handle an incoming ATT packet on the given bearer
+    pub fn handle_packet_on_bearer(
+        &mut self,
+        conn_id: ConnectionId,
+        bearer: BearerId,
+        packet: AttView<'_>,
+    ) -> Result<()> {
+        self.get_bearer(conn_id, bearer)
+            .map_err(|err| err.context("dropping ATT packet for unregistered bearer"))?
             .handle_packet(packet);
         Ok(())
     }
 
+    /// Send a notification of the given attribute value to the peer on the
+    /// unenhanced bearer of the specified connection
+    pub fn send_notification(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        value: AttAttributeDataChild,
+    ) -> Result<()> {
+        self.get_bearer(conn_id, BearerId::Unenhanced)
+            .map_err(|err| err.context("cannot send notification on unregistered connection"))?
+            .send_notification(handle, value)
+            .map_err(|err| anyhow!("failed to send notification: {err:?}"))
+    }
+
+    /// Send an indication of the given attribute value to the peer on the
+    /// unenhanced bearer of the specified connection. The returned future
+    /// resolves once the peer confirms the indication (or it fails).
+    pub fn send_indication(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        value: AttAttributeDataChild,
+    ) -> Result<impl Future<Output = Result<(), IndicationError>>> {
+        Ok(self
+            .get_bearer(conn_id, BearerId::Unenhanced)
+            .map_err(|err| err.context("cannot send indication on unregistered connection"))?
+            .send_indication(handle, value))
+    }
+
+    /// Send a notification of the given attribute value to every connection
+    /// of the given server whose client subscribed to notifications of it
+    pub fn notify_subscribers(
+        &self,
+        server_id: ServerId,
+        handle: AttHandle,
+        value: AttAttributeDataChild,
+    ) -> Result<()> {
+        let database = self
+            .databases
+            .get(&server_id)
+            .ok_or_else(|| anyhow!("server {server_id:?} not opened"))?;
+        for conn_id in self.connection_bearers.keys() {
+            if conn_id.get_server_id() == server_id
+                && database.get_client_configuration(*conn_id, handle).notify
+            {
+                self.send_notification(*conn_id, handle, value.clone())?;
+            }
+        }
+        Ok(())
+    }
+
     /// Register a new GATT service on a given server
     pub fn register_gatt_service(
         &mut self,
         server_id: ServerId,
         service: GattServiceWithHandle,
     ) -> Result<()> {
-        self.databases
+        self.register_local_gatt_service(
+            server_id,
+            LocalGattService { service, values: HashMap::new() },
+        )
+    }
+
+    /// Register a new GATT service on a given server, whose attributes are
+    /// (at least partly) implemented inside the stack
+    pub fn register_local_gatt_service(
+        &mut self,
+        server_id: ServerId,
+        service: LocalGattService,
+    ) -> Result<()> {
+        let service_handle = service.service.handle;
+        let database = self
+            .databases
             .get(&server_id)
-            .ok_or_else(|| anyhow!("server {server_id:?} not opened"))?
-            .add_service_with_handles(service)
+            .ok_or_else(|| anyhow!("server {server_id:?} not opened"))?;
+        database.add_local_service(service)?;
+        if let Some(end_handle) = database.get_service_end_handle(service_handle) {
+            self.on_service_changed(server_id, service_handle, end_handle);
+        }
+        if let (Some(publisher), Some(record)) = (
+            &self.sdp_publisher,
+            self.get_sdp_records(server_id)
+                .into_iter()
+                .find(|record| record.start_handle == service_handle),
+        ) {
+            publisher.publish(server_id, record);
+        }
+        Ok(())
     }
 
     /// Unregister an existing GATT service on a given server
@@ -97,16 +561,108 @@ impl GattModule {
         server_id: ServerId,
         service_handle: AttHandle,
     ) -> Result<()> {
-        self.databases
+        let database = self
+            .databases
             .get(&server_id)
-            .ok_or_else(|| anyhow!("server {server_id:?} not opened"))?
-            .remove_service_at_handle(service_handle)
+            .ok_or_else(|| anyhow!("server {server_id:?} not opened"))?;
+        let end_handle = database.get_service_end_handle(service_handle);
+        let published = self
+            .get_sdp_records(server_id)
+            .iter()
+            .any(|record| record.start_handle == service_handle);
+        database.remove_service_at_handle(service_handle)?;
+        if let Some(end_handle) = end_handle {
+            self.on_service_changed(server_id, service_handle, end_handle);
+        }
+        if let (Some(publisher), true) = (&self.sdp_publisher, published) {
+            publisher.withdraw(server_id, service_handle);
+        }
+        Ok(())
+    }
+
+    /// The SDP records of the primary services of the given server, if it is
+    /// exposed over BR/EDR
+    fn get_sdp_records(&self, server_id: ServerId) -> Vec<SdpServiceRecord> {
+        if self.br_edr_server != Some(server_id) {
+            return vec![];
+        }
+        let Some(database) = self.databases.get(&server_id) else {
+            return vec![];
+        };
+        database
+            .get_primary_services()
+            .into_iter()
+            .filter_map(|(start_handle, uuid)| {
+                Some(SdpServiceRecord {
+                    uuid,
+                    start_handle,
+                    end_handle: database.get_service_end_handle(start_handle)?,
+                })
+            })
+            .collect()
+    }
+
+    fn withdraw_sdp_records(&self, server_id: ServerId) {
+        if let Some(publisher) = &self.sdp_publisher {
+            for record in self.get_sdp_records(server_id) {
+                publisher.withdraw(server_id, record.start_handle);
+            }
+        }
     }
 
-    /// Open a GATT server
+    /// Notify the clients of the given server that the handles in the given
+    /// range were modified, so they must rediscover them
+    fn on_service_changed(&self, server_id: ServerId, start: AttHandle, end: AttHandle) {
+        let conn_ids = self
+            .connection_bearers
+            .keys()
+            .filter(|conn_id| conn_id.get_server_id() == server_id)
+            .copied()
+            .collect::<Vec<_>>();
+        for conn_id in conn_ids {
+            self.send_service_changed(conn_id, start, end);
+        }
+    }
+
+    /// Indicate a Service Changed to the client on the given connection, if it
+    /// subscribed to it. Once confirmed, the client becomes change-aware.
+    fn send_service_changed(&self, conn_id: ConnectionId, start: AttHandle, end: AttHandle) {
+        let Some(database) = self.databases.get(&conn_id.get_server_id()) else {
+            return;
+        };
+        let Ok(bearer) = self.get_bearer(conn_id, BearerId::Unenhanced) else {
+            return;
+        };
+        let Some(handle) = database.get_service_changed_handle() else {
+            return;
+        };
+        if !database.get_client_configuration(conn_id, handle).indicate {
+            return;
+        }
+
+        let indication = bearer.send_indication(
+            handle,
+            GattServiceChangedBuilder { start_handle: start.into(), end_handle: end.into() }.into(),
+        );
+        let database = database.clone();
+        spawn_local(async move {
+            match indication.await {
+                Ok(()) => database.on_service_changed_confirmed(conn_id),
+                Err(err) => warn!("failed to indicate service changed on {conn_id:?}: {err:?}"),
+            }
+        });
+    }
+
+    /// Open a GATT server, exposing the Generic Attribute service
     pub fn open_gatt_server(&mut self, server_id: ServerId) -> Result<()> {
-        let old =
-            self.databases.insert(server_id, GattDatabase::new(self.datastore.clone()).into());
+        let database = match &self.cccd_store {
+            Some(cccd_store) => {
+                GattDatabase::new_with_cccd_store(self.datastore.clone(), cccd_store.clone())
+            }
+            None => GattDatabase::new(self.datastore.clone()),
+        };
+        database.add_service_with_handles(generic_attribute_service(GATT_SERVICE_HANDLE))?;
+        let old = self.databases.insert(server_id, database.into());
         if old.is_some() {
             bail!("GATT server {server_id:?} already exists but was re-opened, clobbering old value...")
         }
@@ -115,6 +671,10 @@ impl GattModule {
 
     /// Close a GATT server
     pub fn close_gatt_server(&mut self, server_id: ServerId) -> Result<()> {
+        if self.br_edr_server == Some(server_id) {
+            self.withdraw_sdp_records(server_id);
+            self.br_edr_server = None;
+        }
         let old = self.databases.remove(&server_id);
         let Some(old) = old else {
             bail!("GATT server {server_id:?} did not exist")
@@ -1,18 +1,38 @@
+use std::rc::Rc;
+
 use async_trait::async_trait;
 
 use crate::{
     core::uuid::Uuid,
-    gatt::ids::AttHandle,
+    gatt::{callbacks::TransactionDecision, ids::AttHandle},
     packets::{
         AttAttributeDataChild, AttAttributeDataView, AttErrorCode, AttHandleBuilder, AttHandleView,
+        Serializable,
     },
 };
 
 // UUIDs from Bluetooth Assigned Numbers Sec 3.6
 pub const PRIMARY_SERVICE_DECLARATION_UUID: Uuid = Uuid::new(0x2800);
 pub const SECONDARY_SERVICE_DECLARATION_UUID: Uuid = Uuid::new(0x2801);
+pub const INCLUDE_DECLARATION_UUID: Uuid = Uuid::new(0x2802);
 pub const CHARACTERISTIC_UUID: Uuid = Uuid::new(0x2803);
 
+// UUIDs from Bluetooth Assigned Numbers Sec 3.7
+pub const CHARACTERISTIC_EXTENDED_PROPERTIES_UUID: Uuid = Uuid::new(0x2900);
+pub const CHARACTERISTIC_USER_DESCRIPTION_UUID: Uuid = Uuid::new(0x2901);
+pub const CLIENT_CHARACTERISTIC_CONFIGURATION_UUID: Uuid = Uuid::new(0x2902);
+pub const SERVER_CHARACTERISTIC_CONFIGURATION_UUID: Uuid = Uuid::new(0x2903);
+pub const CHARACTERISTIC_PRESENTATION_FORMAT_UUID: Uuid = Uuid::new(0x2904);
+pub const CHARACTERISTIC_AGGREGATE_FORMAT_UUID: Uuid = Uuid::new(0x2905);
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.4
+pub const GENERIC_ATTRIBUTE_SERVICE_UUID: Uuid = Uuid::new(0x1801);
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.8
+pub const SERVICE_CHANGED_UUID: Uuid = Uuid::new(0x2A05);
+pub const CLIENT_SUPPORTED_FEATURES_UUID: Uuid = Uuid::new(0x2B29);
+pub const DATABASE_HASH_UUID: Uuid = Uuid::new(0x2B2A);
+
 impl From<AttHandleView<'_>> for AttHandle {
     fn from(value: AttHandleView) -> Self {
         AttHandle(value.get_handle())
@@ -34,18 +54,97 @@ pub struct AttAttribute {
 
 /// The attribute properties supported by the current GATT server implementation
 /// Unimplemented properties will default to false.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub struct AttPermissions {
     /// Whether an attribute is readable
     pub readable: bool,
     /// Whether an attribute is writable
     /// (using ATT_WRITE_REQ, so a response is expected)
     pub writable: bool,
+    /// Whether an attribute is writable using ATT_WRITE_CMD or
+    /// ATT_SIGNED_WRITE_CMD, so no response is expected
+    pub writable_without_response: bool,
+    /// Whether an attribute is writable using ATT_SIGNED_WRITE_CMD (the
+    /// Authenticated Signed Writes property)
+    pub writable_signed: bool,
+    /// The security the connection needs before the attribute can be read
+    pub read_security: AttSecurityRequirements,
+    /// The security the connection needs before the attribute can be written
+    /// (by any means)
+    pub write_security: AttSecurityRequirements,
 }
 
 impl AttPermissions {
     /// An attribute that is readable, but not writable
-    pub const READONLY: Self = Self { readable: true, writable: false };
+    pub const READONLY: Self = Self {
+        readable: true,
+        writable: false,
+        writable_without_response: false,
+        writable_signed: false,
+        read_security: AttSecurityRequirements::NONE,
+        write_security: AttSecurityRequirements::NONE,
+    };
+}
+
+/// The security requirements of an attribute access, from 5.3 3C 10.3
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct AttSecurityRequirements {
+    /// Whether the link must be encrypted
+    pub encryption: bool,
+    /// Whether the link must be encrypted with an authenticated (MITM
+    /// protected) key. Implies encryption.
+    pub authentication: bool,
+    /// Whether the upper layer must have authorized the peer
+    pub authorization: bool,
+    /// The minimum size of the encryption key, in octets. Ignored unless
+    /// encryption is required.
+    pub min_key_size: u8,
+}
+
+impl AttSecurityRequirements {
+    /// No security is required
+    pub const NONE: Self =
+        Self { encryption: false, authentication: false, authorization: false, min_key_size: 0 };
+
+    /// Check whether a connection meets these requirements. If not, returns the
+    /// error telling the client what it needs to fix, in the same order as the
+    /// legacy stack (so the client pairs before it encrypts).
+    pub fn check(&self, security: ConnectionSecurity) -> Result<(), AttErrorCode> {
+        let encryption = self.encryption || self.authentication;
+        if encryption && !security.encrypted && !security.bonded {
+            return Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION);
+        }
+        if self.authentication && !security.authenticated {
+            return Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION);
+        }
+        if encryption && !security.encrypted {
+            return Err(AttErrorCode::INSUFFICIENT_ENCRYPTION);
+        }
+        if encryption && security.key_size < self.min_key_size {
+            return Err(AttErrorCode::INSUFFICIENT_ENCRYPTION_KEY_SIZE);
+        }
+        if self.authorization && !security.authorized {
+            return Err(AttErrorCode::INSUFFICIENT_AUTHORIZATION);
+        }
+        Ok(())
+    }
+}
+
+/// The security state of a connection, as needed to check the
+/// AttSecurityRequirements of its accesses
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ConnectionSecurity {
+    /// Whether the link is currently encrypted
+    pub encrypted: bool,
+    /// The size of the encryption key, in octets
+    pub key_size: u8,
+    /// Whether a key is shared with the peer (so it can encrypt without
+    /// pairing again)
+    pub bonded: bool,
+    /// Whether the shared key was generated with MITM protection
+    pub authenticated: bool,
+    /// Whether the upper layer has authorized the peer
+    pub authorized: bool,
 }
 
 #[async_trait(?Send)]
@@ -56,6 +155,17 @@ pub trait AttDatabase {
         handle: AttHandle,
     ) -> Result<AttAttributeDataChild, AttErrorCode>;
 
+    /// Read the part of an attribute value from the given offset, by handle
+    /// (for ATT_READ_BLOB_REQ). By default, the whole value is read and then
+    /// sliced.
+    async fn read_attribute_blob(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+    ) -> Result<AttAttributeDataChild, AttErrorCode> {
+        slice_attribute_value(self.read_attribute(handle).await?, offset)
+    }
+
     /// Write to an attribute by handle
     async fn write_attribute(
         &self,
@@ -63,11 +173,43 @@ pub trait AttDatabase {
         data: AttAttributeDataView<'_>,
     ) -> Result<(), AttErrorCode>;
 
+    /// Prepare a write of data at the given offset into an attribute, by
+    /// handle. It is only applied once execute_write() is invoked with
+    /// TransactionDecision::Execute, in the order the writes were prepared.
+    async fn prepare_write_attribute(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode>;
+
+    /// Apply or discard all prepared writes
+    async fn execute_write(&self, decision: TransactionDecision) -> Result<(), AttErrorCode>;
+
+    /// Write to an attribute by handle, without waiting for the result (e.g.
+    /// for ATT_WRITE_CMD). Failures are dropped, since the client expects no
+    /// response.
+    fn write_no_response_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>);
+
+    /// Write to an attribute by handle, for an ATT_SIGNED_WRITE_CMD whose
+    /// signature was already verified. As with write_no_response_attribute(),
+    /// failures are dropped.
+    fn write_signed_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>);
+
     /// List all the attributes in this database.
     ///
     /// Expected to return them in sorted order.
     fn list_attributes(&self) -> Vec<AttAttribute>;
 
+    /// Check whether a request from the client may be processed, or if it must
+    /// first be told that its attribute cache is out of sync (5.3 3G 2.5.2.1).
+    /// Each call counts as a received request.
+    fn check_cache_in_sync(&self) -> Result<(), AttErrorCode>;
+
+    /// Whether a command from the client may be processed, or must be dropped
+    /// since its attribute cache is out of sync. Does not modify any state.
+    fn is_cache_in_sync(&self) -> bool;
+
     /// Produce an implementation of StableAttDatabase
     fn snapshot(&self) -> SnapshottedAttDatabase<'_>
     where
@@ -77,6 +219,24 @@ pub trait AttDatabase {
     }
 }
 
+/// Take the part of an attribute value from the given offset, for an
+/// ATT_READ_BLOB_REQ.
+pub fn slice_attribute_value(
+    data: AttAttributeDataChild,
+    offset: usize,
+) -> Result<AttAttributeDataChild, AttErrorCode> {
+    // Values with a structured (rather than raw) representation are the
+    // fixed-length declarations, which always fit in a single ATT_READ_RSP
+    if offset > 0 && !matches!(data, AttAttributeDataChild::RawData(_)) {
+        return Err(AttErrorCode::ATTRIBUTE_NOT_LONG);
+    }
+    let value = data.to_vec().map_err(|_| AttErrorCode::UNLIKELY_ERROR)?;
+    // as per 5.3 3F 3.4.4.5, an offset equal to the length is valid, and
+    // gives an empty response
+    let value = value.get(offset..).ok_or(AttErrorCode::INVALID_OFFSET)?;
+    Ok(AttAttributeDataChild::RawData(value.into()))
+}
+
 /// Marker trait indicating that the backing attribute list of this
 /// database is guaranteed to remain unchanged across async points.
 ///
@@ -103,6 +263,14 @@ impl AttDatabase for SnapshottedAttDatabase<'_> {
         self.backing.read_attribute(handle).await
     }
 
+    async fn read_attribute_blob(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+    ) -> Result<AttAttributeDataChild, AttErrorCode> {
+        self.backing.read_attribute_blob(handle, offset).await
+    }
+
     async fn write_attribute(
         &self,
         handle: AttHandle,
@@ -111,9 +279,187 @@ impl AttDatabase for SnapshottedAttDatabase<'_> {
         self.backing.write_attribute(handle, data).await
     }
 
+    async fn prepare_write_attribute(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        self.backing.prepare_write_attribute(handle, offset, data).await
+    }
+
+    async fn execute_write(&self, decision: TransactionDecision) -> Result<(), AttErrorCode> {
+        self.backing.execute_write(decision).await
+    }
+
+    fn write_no_response_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        self.backing.write_no_response_attribute(handle, data)
+    }
+
+    fn write_signed_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        self.backing.write_signed_attribute(handle, data)
+    }
+
     fn list_attributes(&self) -> Vec<AttAttribute> {
         self.attributes.clone()
     }
+
+    fn check_cache_in_sync(&self) -> Result<(), AttErrorCode> {
+        self.backing.check_cache_in_sync()
+    }
+
+    fn is_cache_in_sync(&self) -> bool {
+        self.backing.is_cache_in_sync()
+    }
 }
 
 impl StableAttDatabase for SnapshottedAttDatabase<'_> {}
+
+/// Allows a single database to be shared between the transaction and command
+/// handlers of a bearer
+#[async_trait(?Send)]
+impl<T: AttDatabase + ?Sized> AttDatabase for Rc<T> {
+    async fn read_attribute(
+        &self,
+        handle: AttHandle,
+    ) -> Result<AttAttributeDataChild, AttErrorCode> {
+        self.as_ref().read_attribute(handle).await
+    }
+
+    async fn read_attribute_blob(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+    ) -> Result<AttAttributeDataChild, AttErrorCode> {
+        self.as_ref().read_attribute_blob(handle, offset).await
+    }
+
+    async fn write_attribute(
+        &self,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        self.as_ref().write_attribute(handle, data).await
+    }
+
+    async fn prepare_write_attribute(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        self.as_ref().prepare_write_attribute(handle, offset, data).await
+    }
+
+    async fn execute_write(&self, decision: TransactionDecision) -> Result<(), AttErrorCode> {
+        self.as_ref().execute_write(decision).await
+    }
+
+    fn write_no_response_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        self.as_ref().write_no_response_attribute(handle, data)
+    }
+
+    fn write_signed_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        self.as_ref().write_signed_attribute(handle, data)
+    }
+
+    fn list_attributes(&self) -> Vec<AttAttribute> {
+        self.as_ref().list_attributes()
+    }
+
+    fn check_cache_in_sync(&self) -> Result<(), AttErrorCode> {
+        self.as_ref().check_cache_in_sync()
+    }
+
+    fn is_cache_in_sync(&self) -> bool {
+        self.as_ref().is_cache_in_sync()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UNENCRYPTED: ConnectionSecurity = ConnectionSecurity {
+        encrypted: false,
+        key_size: 0,
+        bonded: false,
+        authenticated: false,
+        authorized: false,
+    };
+
+    const ENCRYPTED: ConnectionSecurity =
+        ConnectionSecurity { encrypted: true, key_size: 16, bonded: true, ..UNENCRYPTED };
+
+    #[test]
+    fn test_no_requirements() {
+        assert_eq!(AttSecurityRequirements::NONE.check(UNENCRYPTED), Ok(()));
+    }
+
+    #[test]
+    fn test_encryption_without_bond() {
+        let requirements =
+            AttSecurityRequirements { encryption: true, ..AttSecurityRequirements::NONE };
+
+        assert_eq!(requirements.check(UNENCRYPTED), Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION));
+    }
+
+    #[test]
+    fn test_encryption_with_bond() {
+        let requirements =
+            AttSecurityRequirements { encryption: true, ..AttSecurityRequirements::NONE };
+
+        assert_eq!(
+            requirements.check(ConnectionSecurity { bonded: true, ..UNENCRYPTED }),
+            Err(AttErrorCode::INSUFFICIENT_ENCRYPTION)
+        );
+        assert_eq!(requirements.check(ENCRYPTED), Ok(()));
+    }
+
+    #[test]
+    fn test_authentication() {
+        let requirements =
+            AttSecurityRequirements { authentication: true, ..AttSecurityRequirements::NONE };
+
+        assert_eq!(requirements.check(ENCRYPTED), Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION));
+        assert_eq!(
+            requirements.check(ConnectionSecurity {
+                bonded: true,
+                authenticated: true,
+                ..UNENCRYPTED
+            }),
+            Err(AttErrorCode::INSUFFICIENT_ENCRYPTION)
+        );
+        assert_eq!(
+            requirements.check(ConnectionSecurity { authenticated: true, ..ENCRYPTED }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_key_size() {
+        let requirements = AttSecurityRequirements {
+            encryption: true,
+            min_key_size: 16,
+            ..AttSecurityRequirements::NONE
+        };
+
+        assert_eq!(
+            requirements.check(ConnectionSecurity { key_size: 7, ..ENCRYPTED }),
+            Err(AttErrorCode::INSUFFICIENT_ENCRYPTION_KEY_SIZE)
+        );
+        assert_eq!(requirements.check(ENCRYPTED), Ok(()));
+    }
+
+    #[test]
+    fn test_authorization() {
+        let requirements =
+            AttSecurityRequirements { authorization: true, ..AttSecurityRequirements::NONE };
+
+        assert_eq!(requirements.check(UNENCRYPTED), Err(AttErrorCode::INSUFFICIENT_AUTHORIZATION));
+        assert_eq!(
+            requirements.check(ConnectionSecurity { authorized: true, ..UNENCRYPTED }),
+            Ok(())
+        );
+    }
+}
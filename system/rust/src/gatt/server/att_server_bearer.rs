@@ -1,62 +1,366 @@
-//! This module handles an individual connection on the ATT fixed channel.
-//! It handles ATT transactions and unacknowledged operations, backed by an
-//! AttDatabase (that may in turn be backed by an upper-layer protocol)
+//! This module handles an individual ATT bearer of a connection, either on the
+//! LE fixed channel, on an enhanced (EATT) channel, or on a BR/EDR channel. It
+//! handles ATT transactions
+//! and unacknowledged operations, backed by an AttDatabase (that may in turn be
+//! backed by an upper-layer protocol)
 
 use std::{
     cell::Cell,
+    future::Future,
     rc::{Rc, Weak},
+    time::Duration,
 };
 
 use log::{error, trace, warn};
-use tokio::task::spawn_local;
+use tokio::{
+    sync::{oneshot, Mutex},
+    task::spawn_local,
+    time::timeout,
+};
 
 use crate::{
-    gatt::ids::AttHandle,
+    gatt::{channel::DisconnectReason, ids::AttHandle},
     packets::{
-        AttBuilder, AttChild, AttErrorCode, AttErrorResponseBuilder, AttView, Packet,
-        SerializeError,
+        AttAttributeDataChild, AttBuilder, AttChild, AttErrorCode, AttErrorResponseBuilder,
+        AttExchangeMtuRequestView, AttExchangeMtuResponseBuilder, AttHandleValueIndicationBuilder,
+        AttHandleValueNotificationBuilder, AttOpcode, AttView, Packet, SerializeError,
+    },
+    utils::{
+        owned_handle::OwnedHandle,
+        packet::{build_att_data, HACK_child_to_opcode},
     },
-    utils::{owned_handle::OwnedHandle, packet::HACK_child_to_opcode},
 };
 
-use super::{att_database::AttDatabase, transaction_handler::AttTransactionHandler};
+use super::{
+    att_database::AttDatabase, command_handler::AttCommandHandler, signing::SignedWriteVerifier,
+    transaction_handler::AttTransactionHandler,
+    transactions::helpers::truncate_att_data::truncate_att_data,
+};
 
 enum AttTransaction<T: AttDatabase> {
-    Idle(AttTransactionHandler<T>),
+    Idle(AttTransactionHandler<Rc<T>>),
     Pending(Option<OwnedHandle<()>>),
 }
 
-const DEFAULT_ATT_MTU: usize = 23;
+/// The minimum (and initial) ATT_MTU on LE, from 5.3 3F 3.2.8
+pub const DEFAULT_ATT_MTU: usize = 23;
+
+/// The largest Rx MTU we will advertise, matching the legacy stack
+/// (GATT_MAX_MTU_SIZE), since attribute values are at most 512 bytes long
+pub const MAX_ATT_MTU: usize = 517;
+
+/// The minimum ATT_MTU on an enhanced bearer, from 5.3 3F 3.2.8
+pub const MIN_ENHANCED_ATT_MTU: usize = 64;
+
+/// The minimum ATT_MTU on BR/EDR, from 5.3 3F 3.2.8
+pub const MIN_BR_EDR_ATT_MTU: usize = 48;
+
+/// The ATT transaction timeout, from 5.3 3F 3.3.3
+pub const ATT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Errors that can occur while sending a notification
+#[derive(Debug)]
+pub enum NotificationError {
+    /// The bearer was closed (e.g. after an ATT timeout), so nothing can be sent
+    BearerClosed,
+    /// The outgoing packet could not be serialized
+    SerializeError(SerializeError),
+}
+
+/// Errors that can occur while sending an indication
+#[derive(Debug)]
+pub enum IndicationError {
+    /// The bearer was closed or dropped before the indication was confirmed
+    BearerClosed,
+    /// The peer did not confirm the indication within the ATT timeout, so the
+    /// bearer has been closed
+    ConfirmationTimeout,
+    /// The outgoing packet could not be serialized
+    SerializeError(SerializeError),
+}
 
-/// This represents a single ATT bearer (currently, always the unenhanced fixed
-/// channel on LE) The AttTransaction ensures that only one transaction can take
-/// place at a time
+/// The kind of channel carrying a bearer, which determines how its ATT_MTU is
+/// set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BearerKind {
+    /// The LE fixed channel, whose ATT_MTU is set by an MTU exchange
+    Unenhanced,
+    /// An enhanced channel, whose ATT_MTU is set (and reconfigured) by L2CAP
+    Enhanced,
+    /// A BR/EDR channel, whose ATT_MTU is set by L2CAP configuration
+    BrEdr,
+}
+
+/// This represents a single ATT bearer, either the unenhanced fixed channel on
+/// LE, an enhanced channel, or a BR/EDR channel. Each bearer has its own
+/// ATT_MTU, and the
+/// AttTransaction ensures that only one transaction can take place at a time on
+/// it
 pub struct AttServerBearer<T: AttDatabase> {
     curr_operation: Cell<AttTransaction<T>>,
+    // commands are not transactions, so they bypass curr_operation
+    command_handler: AttCommandHandler<Rc<T>>,
     send_packet: Box<dyn Fn(AttBuilder) -> Result<(), SerializeError>>,
+    on_mtu_changed: Box<dyn Fn(usize)>,
+    on_close: Box<dyn Fn(DisconnectReason)>,
     mtu: Cell<usize>,
+    server_rx_mtu: usize,
+    kind: BearerKind,
+    // held while an indication is outstanding, so only one is sent at a time
+    indication_lock: Rc<Mutex<()>>,
+    pending_confirmation: Cell<Option<oneshot::Sender<()>>>,
+    closed: Cell<bool>,
 }
 
 impl<T: AttDatabase + 'static> AttServerBearer<T> {
     /// Constructor, wrapping an ATT channel (for outgoing packets) and an
-    /// AttDatabase
+    /// AttDatabase. The server_rx_mtu is the value sent to the peer during an
+    /// MTU exchange, and on_mtu_changed is invoked whenever the exchange
+    /// changes the ATT_MTU of this bearer. Signed writes are verified using
+    /// the signed_write_verifier, or dropped if it is absent. on_close is
+    /// invoked (at most once) if the bearer closes itself after an ATT timeout
+    /// or a protocol violation, so that the link can be disconnected.
     pub fn new(
         db: T,
+        server_rx_mtu: usize,
+        signed_write_verifier: Option<SignedWriteVerifier>,
         send_packet: impl Fn(AttBuilder) -> Result<(), SerializeError> + 'static,
+        on_mtu_changed: impl Fn(usize) + 'static,
+        on_close: impl Fn(DisconnectReason) + 'static,
     ) -> Rc<Self> {
+        let db = Rc::new(db);
         Self {
-            curr_operation: AttTransaction::Idle(AttTransactionHandler::new(db)).into(),
+            curr_operation: AttTransaction::Idle(AttTransactionHandler::new(db.clone())).into(),
+            command_handler: AttCommandHandler::new(db, signed_write_verifier),
             send_packet: Box::new(send_packet),
+            on_mtu_changed: Box::new(on_mtu_changed),
+            on_close: Box::new(on_close),
             mtu: Cell::new(DEFAULT_ATT_MTU),
+            server_rx_mtu,
+            kind: BearerKind::Unenhanced,
+            indication_lock: Rc::new(Mutex::new(())),
+            pending_confirmation: Cell::new(None),
+            closed: Cell::new(false),
         }
         .into()
     }
 
+    /// Constructor for an enhanced bearer, whose ATT_MTU is the smaller of the
+    /// L2CAP MTUs of the two devices (5.3 3F 3.2.8). Signed writes are never
+    /// accepted, since enhanced bearers are always encrypted (5.3 3F 3.4.5.4).
+    /// on_close is invoked as for an unenhanced bearer.
+    pub fn new_enhanced(
+        db: T,
+        mtu: usize,
+        send_packet: impl Fn(AttBuilder) -> Result<(), SerializeError> + 'static,
+        on_close: impl Fn(DisconnectReason) + 'static,
+    ) -> Rc<Self> {
+        Self::new_with_l2cap_mtu(db, BearerKind::Enhanced, mtu, send_packet, on_close)
+    }
+
+    /// Constructor for a bearer on a BR/EDR channel, whose ATT_MTU is set by
+    /// L2CAP configuration, so the MTU exchange is not supported (5.3 3G
+    /// 4.3.1). Signed writes are never accepted, since they are only defined on
+    /// LE (5.3 3G 4.9.2). on_close is invoked as for an unenhanced bearer.
+    pub fn new_br_edr(
+        db: T,
+        mtu: usize,
+        send_packet: impl Fn(AttBuilder) -> Result<(), SerializeError> + 'static,
+        on_close: impl Fn(DisconnectReason) + 'static,
+    ) -> Rc<Self> {
+        Self::new_with_l2cap_mtu(db, BearerKind::BrEdr, mtu, send_packet, on_close)
+    }
+
+    fn new_with_l2cap_mtu(
+        db: T,
+        kind: BearerKind,
+        mtu: usize,
+        send_packet: impl Fn(AttBuilder) -> Result<(), SerializeError> + 'static,
+        on_close: impl Fn(DisconnectReason) + 'static,
+    ) -> Rc<Self> {
+        let db = Rc::new(db);
+        Self {
+            curr_operation: AttTransaction::Idle(AttTransactionHandler::new(db.clone())).into(),
+            command_handler: AttCommandHandler::new(db, None),
+            send_packet: Box::new(send_packet),
+            on_mtu_changed: Box::new(|_| {}),
+            on_close: Box::new(on_close),
+            mtu: Cell::new(mtu),
+            server_rx_mtu: mtu,
+            kind,
+            indication_lock: Rc::new(Mutex::new(())),
+            pending_confirmation: Cell::new(None),
+            closed: Cell::new(false),
+        }
+        .into()
+    }
+
+    /// The current ATT_MTU of this bearer
+    pub fn mtu(&self) -> usize {
+        self.mtu.get()
+    }
+
+    /// Update the ATT_MTU of an enhanced bearer, after its L2CAP channel is
+    /// reconfigured. As per 5.3 3A 4.27, the MTU can only increase.
+    pub fn on_enhanced_mtu_reconfigured(&self, mtu: usize) {
+        if self.kind != BearerKind::Enhanced {
+            warn!("the ATT_MTU of a {:?} bearer cannot be reconfigured, ignoring", self.kind);
+            return;
+        }
+        if mtu < self.mtu.get() {
+            warn!("ATT_MTU cannot decrease from {} to {mtu}, ignoring", self.mtu.get());
+            return;
+        }
+        self.mtu.set(mtu);
+    }
+
+    /// Update the ATT_MTU of an unenhanced bearer after an MTU exchange, given
+    /// the Rx MTU of the client. This is also used when the exchange was
+    /// answered by the legacy stack, as it keeps its own copy of the ATT_MTU.
+    pub fn on_mtu_exchanged(&self, client_rx_mtu: usize) {
+        if self.kind != BearerKind::Unenhanced {
+            warn!("the ATT_MTU of a {:?} bearer cannot be exchanged, ignoring", self.kind);
+            return;
+        }
+
+        // as per 5.3 3F 3.4.2.2, if either Rx MTU is (incorrectly) below the default,
+        // the ATT_MTU is left at the default
+        let new_mtu = if client_rx_mtu < DEFAULT_ATT_MTU {
+            warn!("client Rx MTU {client_rx_mtu} is below the minimum, ignoring");
+            DEFAULT_ATT_MTU
+        } else {
+            client_rx_mtu.min(self.server_rx_mtu)
+        };
+
+        if self.mtu.replace(new_mtu) != new_mtu {
+            trace!("ATT_MTU updated to {new_mtu}");
+            (self.on_mtu_changed)(new_mtu);
+        }
+    }
+
     /// Handle an incoming packet, and send outgoing packets as appropriate
     /// using the owned ATT channel.
     pub fn handle_packet(self: &Rc<Self>, packet: AttView<'_>) {
+        if self.closed.get() {
+            warn!("dropping incoming ATT packet since the bearer is closed");
+            return;
+        }
+        match packet.get_opcode() {
+            // confirmations are not requests, so they don't use the transaction slot
+            AttOpcode::HANDLE_VALUE_CONFIRMATION => self.handle_confirmation(),
+            AttOpcode::WRITE_COMMAND | AttOpcode::SIGNED_WRITE_COMMAND => {
+                self.command_handler.process_packet(packet)
+            }
+            _ => self.handle_request(packet),
+        }
+    }
+
+    /// Send an ATT_HANDLE_VALUE_NTF to the peer. The value is truncated to
+    /// ATT_MTU - 3 bytes, as per 5.3 3F 3.4.7.1
+    pub fn send_notification(
+        &self,
+        handle: AttHandle,
+        value: AttAttributeDataChild,
+    ) -> Result<(), NotificationError> {
+        if self.closed.get() {
+            return Err(NotificationError::BearerClosed);
+        }
+        self.send_att_packet(AttHandleValueNotificationBuilder {
+            handle: handle.into(),
+            value: build_att_data(truncate_att_data(value, self.mtu.get() - 3)),
+        })
+        .map_err(NotificationError::SerializeError)
+    }
+
+    /// Send an ATT_HANDLE_VALUE_IND to the peer. The value is truncated to
+    /// ATT_MTU - 3 bytes, as per 5.3 3F 3.4.7.2
+    ///
+    /// Only one indication may be outstanding at a time, so the returned future
+    /// first waits for any earlier indications to complete. It then resolves
+    /// once the peer confirms this indication. If no confirmation arrives
+    /// within the ATT timeout, the bearer is closed.
+    pub fn send_indication(
+        self: &Rc<Self>,
+        handle: AttHandle,
+        value: AttAttributeDataChild,
+    ) -> impl Future<Output = Result<(), IndicationError>> {
+        // we hold only a weak reference across await points, so dropping the bearer
+        // (e.g. on disconnection) fails the pending indication
+        let this = Rc::downgrade(self);
+        let indication_lock = self.indication_lock.clone();
+        async move {
+            let _guard = indication_lock.lock().await;
+            let confirmation = this
+                .upgrade()
+                .ok_or(IndicationError::BearerClosed)?
+                .start_indication(handle, value)?;
+            match timeout(ATT_TIMEOUT, confirmation).await {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(_)) => Err(IndicationError::BearerClosed),
+                Err(_) => {
+                    warn!("indication on {handle:?} was not confirmed in time, closing bearer");
+                    if let Some(this) = this.upgrade() {
+                        this.close(DisconnectReason::ConfirmationTimeout);
+                    }
+                    Err(IndicationError::ConfirmationTimeout)
+                }
+            }
+        }
+    }
+
+    fn start_indication(
+        &self,
+        handle: AttHandle,
+        value: AttAttributeDataChild,
+    ) -> Result<oneshot::Receiver<()>, IndicationError> {
+        if self.closed.get() {
+            return Err(IndicationError::BearerClosed);
+        }
+        self.send_att_packet(AttHandleValueIndicationBuilder {
+            handle: handle.into(),
+            value: build_att_data(truncate_att_data(value, self.mtu.get() - 3)),
+        })
+        .map_err(IndicationError::SerializeError)?;
+        let (tx, rx) = oneshot::channel();
+        self.pending_confirmation.replace(Some(tx));
+        Ok(rx)
+    }
+
+    fn handle_confirmation(&self) {
+        match self.pending_confirmation.take() {
+            Some(tx) => {
+                if tx.send(()).is_err() {
+                    warn!("indication was confirmed, but nobody is waiting for it");
+                }
+            }
+            None => warn!("received a confirmation with no outstanding indication, dropping"),
+        }
+    }
+
+    /// Tear down the bearer after an ATT timeout or a protocol violation. No
+    /// further ATT PDUs are sent or received, as per 5.3 3F 3.3.3, and the owner
+    /// is asked to disconnect the link.
+    fn close(&self, reason: DisconnectReason) {
+        if self.closed.replace(true) {
+            return;
+        }
+        warn!("closing ATT bearer: {reason:?}");
+        // drops the handler of any in-flight transaction, aborting it
+        self.curr_operation.replace(AttTransaction::Pending(None));
+        self.pending_confirmation.take();
+        (self.on_close)(reason);
+    }
+
+    fn handle_request(self: &Rc<Self>, packet: AttView<'_>) {
         let curr_operation = self.curr_operation.replace(AttTransaction::Pending(None));
         self.clone().curr_operation.replace(match curr_operation {
+            AttTransaction::Idle(request_handler)
+                if packet.get_opcode() == AttOpcode::EXCHANGE_MTU_REQUEST =>
+            {
+                // the MTU exchange touches only the bearer, so it completes synchronously
+                self.handle_mtu_exchange(packet);
+                AttTransaction::Idle(request_handler)
+            }
             AttTransaction::Idle(mut request_handler) => {
                 // even if the MTU is updated afterwards, 5.3 3F 3.4.2.2 states that the request-time MTU should be used
                 let mtu = self.mtu.get();
@@ -64,19 +368,28 @@ impl<T: AttDatabase + 'static> AttServerBearer<T> {
                 let packet = packet.to_owned_packet();
                 let task = spawn_local(async move {
                     trace!("starting ATT transaction");
-                    let reply = request_handler.process_packet(packet.view(), mtu).await;
+                    let reply =
+                        timeout(ATT_TIMEOUT, request_handler.process_packet(packet.view(), mtu))
+                            .await;
+                    let Ok(reply) = reply else {
+                        warn!("ATT transaction was not completed in time");
+                        if let Some(this) = Weak::upgrade(&this) {
+                            this.close(DisconnectReason::TransactionTimeout);
+                        }
+                        return;
+                    };
                     match Weak::upgrade(&this) {
                         None => {
                             warn!("callback returned after disconnect");
                         }
                         Some(this) => {
                             trace!("sending reply packet");
-                            if let Err(err) = this.send_response(reply) {
+                            if let Err(err) = this.send_att_packet(reply) {
                                 error!("serializer failure {err:?}, dropping packet and sending failed reply");
-                                this.send_response(AttErrorResponseBuilder {
+                                this.send_att_packet(AttErrorResponseBuilder {
                                     opcode_in_error: packet.view().get_opcode(),
                                     handle_in_error: AttHandle(0).into(),
-                                    error_code: AttErrorCode::UNLIKELY_ERROR,
+                                    error_code: AttErrorCode::UNLIKELY_ERROR.into(),
                                 }).expect("packet should never fail to serialize");
                             }
                             // ready for next transaction
@@ -87,14 +400,49 @@ impl<T: AttDatabase + 'static> AttServerBearer<T> {
                 AttTransaction::Pending(Some(task.into()))
             }
             AttTransaction::Pending(_) => {
-                warn!("multiple ATT operations cannot simultaneously take place, dropping one");
-                // TODO(aryarahul) - disconnect connection here;
-                curr_operation
+                // as per 5.3 3F 3.3.2, a client may only have one request outstanding
+                warn!("multiple ATT operations cannot simultaneously take place, disconnecting");
+                // the outstanding transaction is aborted once its handle is dropped
+                self.close(DisconnectReason::ProtocolViolation);
+                AttTransaction::Pending(None)
             }
         });
     }
 
-    fn send_response(&self, packet: impl Into<AttChild>) -> Result<(), SerializeError> {
+    fn handle_mtu_exchange(&self, packet: AttView<'_>) {
+        let client_rx_mtu = match AttExchangeMtuRequestView::try_parse(packet) {
+            // as per 5.3 3F 3.4.2.1, the exchange is only used on the unenhanced LE bearer
+            Ok(_) if self.kind != BearerKind::Unenhanced => {
+                warn!("rejecting MTU exchange on a {:?} bearer", self.kind);
+                self.send_att_packet(AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::EXCHANGE_MTU_REQUEST,
+                    handle_in_error: AttHandle(0).into(),
+                    error_code: AttErrorCode::REQUEST_NOT_SUPPORTED.into(),
+                })
+                .expect("packet should never fail to serialize");
+                return;
+            }
+            Ok(request) => request.get_mtu() as usize,
+            Err(err) => {
+                warn!("failed to parse MTU exchange request {err:?}, rejecting");
+                self.send_att_packet(AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::EXCHANGE_MTU_REQUEST,
+                    handle_in_error: AttHandle(0).into(),
+                    error_code: AttErrorCode::INVALID_PDU.into(),
+                })
+                .expect("packet should never fail to serialize");
+                return;
+            }
+        };
+
+        self.send_att_packet(AttExchangeMtuResponseBuilder { mtu: self.server_rx_mtu as u16 })
+            .expect("packet should never fail to serialize");
+
+        // the new MTU only applies once the response has been sent
+        self.on_mtu_exchanged(client_rx_mtu);
+    }
+
+    fn send_att_packet(&self, packet: impl Into<AttChild>) -> Result<(), SerializeError> {
         let child = packet.into();
         let packet = AttBuilder { opcode: HACK_child_to_opcode(&child), _child_: child };
         (self.send_packet)(packet)
@@ -116,18 +464,17 @@ mod test {
             server::{
                 att_database::{AttAttribute, AttPermissions},
                 gatt_database::{
-                    GattCharacteristicWithHandle, GattDatabase, GattServiceWithHandle,
+                    AttDatabaseImpl, GattCharacteristicWithHandle, GattDatabase,
+                    GattServiceWithHandle,
                 },
                 test::test_att_db::TestAttDatabase,
             },
         },
         packets::{
-            AttAttributeDataChild, AttOpcode, AttReadRequestBuilder, AttReadResponseBuilder,
-        },
-        utils::{
-            packet::{build_att_data, build_att_view_or_crash},
-            task::block_on_locally,
+            AttExchangeMtuRequestBuilder, AttHandleValueConfirmationBuilder, AttReadRequestBuilder,
+            AttReadResponseBuilder, AttWriteCommandBuilder,
         },
+        utils::{packet::build_att_view_or_crash, task::block_on_locally},
     };
 
     const VALID_HANDLE: AttHandle = AttHandle(3);
@@ -136,21 +483,48 @@ mod test {
 
     const CONN_ID: ConnectionId = ConnectionId(1);
 
+    const SERVER_RX_MTU: usize = 100;
+
     fn open_connection() -> (Rc<AttServerBearer<TestAttDatabase>>, UnboundedReceiver<AttBuilder>) {
+        let (conn, rx, _) = open_connection_reporting_close();
+        (conn, rx)
+    }
+
+    fn open_connection_reporting_close() -> (
+        Rc<AttServerBearer<TestAttDatabase>>,
+        UnboundedReceiver<AttBuilder>,
+        UnboundedReceiver<DisconnectReason>,
+    ) {
         let db = TestAttDatabase::new(vec![(
             AttAttribute {
                 handle: VALID_HANDLE,
                 type_: Uuid::new(0x1234),
-                permissions: AttPermissions { readable: true, writable: false },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![5, 6],
         )]);
         let (tx, rx) = unbounded_channel();
-        let conn = AttServerBearer::new(db, move |packet| {
-            tx.send(packet).unwrap();
-            Ok(())
-        });
-        (conn, rx)
+        let (close_tx, close_rx) = unbounded_channel();
+        let conn = AttServerBearer::new(
+            db,
+            SERVER_RX_MTU,
+            None,
+            move |packet| {
+                tx.send(packet).unwrap();
+                Ok(())
+            },
+            |_| {},
+            move |reason| {
+                // the receiver is dropped by tests that don't expect the bearer to close
+                let _ = close_tx.send(reason);
+            },
+        );
+        (conn, rx, close_rx)
     }
 
     #[test]
@@ -192,73 +566,709 @@ mod test {
         });
     }
 
+    fn exchange_mtu(conn: &Rc<AttServerBearer<TestAttDatabase>>, client_rx_mtu: u16) {
+        conn.handle_packet(
+            build_att_view_or_crash(AttExchangeMtuRequestBuilder { mtu: client_rx_mtu }).view(),
+        );
+    }
+
     #[test]
-    fn test_concurrent_transaction_failure() {
-        // arrange: AttServerBearer linked to a backing datastore and packet queue, with
-        // two characteristics in the database
+    fn test_mtu_exchange_response() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+
+            exchange_mtu(&conn, 50);
+
+            assert_eq!(
+                rx.recv().await.unwrap(),
+                AttBuilder {
+                    opcode: AttOpcode::EXCHANGE_MTU_RESPONSE,
+                    _child_: AttExchangeMtuResponseBuilder { mtu: SERVER_RX_MTU as u16 }.into(),
+                }
+            );
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        });
+    }
+
+    #[test]
+    fn test_mtu_exchange_uses_smaller_client_mtu() {
+        block_on_locally(async {
+            let (conn, _rx) = open_connection();
+
+            exchange_mtu(&conn, 50);
+
+            assert_eq!(conn.mtu(), 50);
+        });
+    }
+
+    #[test]
+    fn test_mtu_exchange_uses_smaller_server_mtu() {
+        block_on_locally(async {
+            let (conn, _rx) = open_connection();
+
+            exchange_mtu(&conn, 200);
+
+            assert_eq!(conn.mtu(), SERVER_RX_MTU);
+        });
+    }
+
+    #[test]
+    fn test_mtu_exchange_below_minimum_ignored() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+
+            exchange_mtu(&conn, 10);
+
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::EXCHANGE_MTU_RESPONSE);
+            assert_eq!(conn.mtu(), DEFAULT_ATT_MTU);
+        });
+    }
+
+    #[test]
+    fn test_mtu_change_callback() {
+        block_on_locally(async {
+            let (tx, _rx) = unbounded_channel();
+            let (mtu_tx, mut mtu_rx) = unbounded_channel();
+            let conn = AttServerBearer::new(
+                TestAttDatabase::new(vec![]),
+                SERVER_RX_MTU,
+                None,
+                move |packet| {
+                    tx.send(packet).unwrap();
+                    Ok(())
+                },
+                move |mtu| mtu_tx.send(mtu).unwrap(),
+                |_| {},
+            );
+
+            exchange_mtu(&conn, 50);
+            // the same MTU again should not trigger a second callback
+            exchange_mtu(&conn, 50);
+
+            assert_eq!(mtu_rx.recv().await.unwrap(), 50);
+            assert_eq!(mtu_rx.try_recv(), Err(TryRecvError::Empty));
+        });
+    }
+
+    #[test]
+    fn test_mtu_exchanged_elsewhere() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+
+            conn.on_mtu_exchanged(50);
+
+            // the response was already sent by whoever handled the exchange
+            assert_eq!(conn.mtu(), 50);
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        });
+    }
+
+    #[test]
+    fn test_read_truncated_to_exchanged_mtu() {
+        block_on_locally(async {
+            let db = TestAttDatabase::new(vec![(
+                AttAttribute {
+                    handle: VALID_HANDLE,
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions::READONLY,
+                },
+                vec![1; 60],
+            )]);
+            let (tx, mut rx) = unbounded_channel();
+            let conn = AttServerBearer::new(
+                db,
+                SERVER_RX_MTU,
+                None,
+                move |packet| {
+                    tx.send(packet).unwrap();
+                    Ok(())
+                },
+                |_| {},
+                |_| {},
+            );
+            exchange_mtu(&conn, 50);
+            rx.recv().await.unwrap(); // drop the MTU exchange response
+
+            conn.handle_packet(
+                build_att_view_or_crash(AttReadRequestBuilder {
+                    attribute_handle: VALID_HANDLE.into(),
+                })
+                .view(),
+            );
+
+            // the value is truncated to ATT_MTU - 1, rather than the default of 22 bytes
+            assert_eq!(
+                rx.recv().await.unwrap(),
+                AttBuilder {
+                    opcode: AttOpcode::READ_RESPONSE,
+                    _child_: AttReadResponseBuilder {
+                        value: build_att_data(AttAttributeDataChild::RawData([1; 49].into()))
+                    }
+                    .into(),
+                }
+            );
+        });
+    }
+
+    fn open_enhanced_bearer(
+        mtu: usize,
+    ) -> (Rc<AttServerBearer<TestAttDatabase>>, UnboundedReceiver<AttBuilder>) {
+        open_bearer_with_l2cap_mtu(BearerKind::Enhanced, mtu)
+    }
+
+    fn open_br_edr_bearer(
+        mtu: usize,
+    ) -> (Rc<AttServerBearer<TestAttDatabase>>, UnboundedReceiver<AttBuilder>) {
+        open_bearer_with_l2cap_mtu(BearerKind::BrEdr, mtu)
+    }
+
+    fn open_bearer_with_l2cap_mtu(
+        kind: BearerKind,
+        mtu: usize,
+    ) -> (Rc<AttServerBearer<TestAttDatabase>>, UnboundedReceiver<AttBuilder>) {
+        let db = TestAttDatabase::new(vec![(
+            AttAttribute {
+                handle: VALID_HANDLE,
+                type_: Uuid::new(0x1234),
+                permissions: AttPermissions::READONLY,
+            },
+            vec![1; 100],
+        )]);
+        let (tx, rx) = unbounded_channel();
+        let conn = AttServerBearer::new_with_l2cap_mtu(
+            db,
+            kind,
+            mtu,
+            move |packet| {
+                tx.send(packet).unwrap();
+                Ok(())
+            },
+            |_| {},
+        );
+        (conn, rx)
+    }
+
+    #[test]
+    fn test_enhanced_bearer_initial_mtu() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_enhanced_bearer(70);
+
+            conn.send_notification(VALID_HANDLE, AttAttributeDataChild::RawData([1; 100].into()))
+                .unwrap();
+
+            let AttChild::AttHandleValueNotification(notification) =
+                rx.recv().await.unwrap()._child_
+            else {
+                unreachable!()
+            };
+            assert_eq!(conn.mtu(), 70);
+            assert_eq!(
+                notification.value,
+                build_att_data(AttAttributeDataChild::RawData([1; 67].into()))
+            );
+        });
+    }
+
+    #[test]
+    fn test_enhanced_bearer_rejects_mtu_exchange() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_enhanced_bearer(70);
+
+            exchange_mtu(&conn, 200);
+
+            assert_eq!(
+                rx.recv().await.unwrap(),
+                AttBuilder {
+                    opcode: AttOpcode::ERROR_RESPONSE,
+                    _child_: AttErrorResponseBuilder {
+                        opcode_in_error: AttOpcode::EXCHANGE_MTU_REQUEST,
+                        handle_in_error: AttHandle(0).into(),
+                        error_code: AttErrorCode::REQUEST_NOT_SUPPORTED.into(),
+                    }
+                    .into(),
+                }
+            );
+            assert_eq!(conn.mtu(), 70);
+        });
+    }
+
+    #[test]
+    fn test_enhanced_mtu_reconfiguration() {
+        block_on_locally(async {
+            let (conn, _rx) = open_enhanced_bearer(70);
+
+            conn.on_enhanced_mtu_reconfigured(80);
+            // the MTU of a channel cannot decrease
+            conn.on_enhanced_mtu_reconfigured(75);
+
+            assert_eq!(conn.mtu(), 80);
+        });
+    }
+
+    #[test]
+    fn test_unenhanced_mtu_cannot_be_reconfigured() {
+        block_on_locally(async {
+            let (conn, _rx) = open_connection();
+
+            conn.on_enhanced_mtu_reconfigured(80);
+
+            assert_eq!(conn.mtu(), DEFAULT_ATT_MTU);
+        });
+    }
+
+    #[test]
+    fn test_br_edr_bearer_rejects_mtu_exchange() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_br_edr_bearer(MIN_BR_EDR_ATT_MTU);
+
+            exchange_mtu(&conn, 200);
+
+            assert_eq!(
+                rx.recv().await.unwrap(),
+                AttBuilder {
+                    opcode: AttOpcode::ERROR_RESPONSE,
+                    _child_: AttErrorResponseBuilder {
+                        opcode_in_error: AttOpcode::EXCHANGE_MTU_REQUEST,
+                        handle_in_error: AttHandle(0).into(),
+                        error_code: AttErrorCode::REQUEST_NOT_SUPPORTED.into(),
+                    }
+                    .into(),
+                }
+            );
+            assert_eq!(conn.mtu(), MIN_BR_EDR_ATT_MTU);
+        });
+    }
+
+    #[test]
+    fn test_br_edr_mtu_cannot_be_reconfigured() {
+        block_on_locally(async {
+            let (conn, _rx) = open_br_edr_bearer(MIN_BR_EDR_ATT_MTU);
+
+            conn.on_enhanced_mtu_reconfigured(80);
+
+            assert_eq!(conn.mtu(), MIN_BR_EDR_ATT_MTU);
+        });
+    }
+
+    #[test]
+    fn test_br_edr_mtu_cannot_be_exchanged_elsewhere() {
+        block_on_locally(async {
+            let (conn, _rx) = open_br_edr_bearer(MIN_BR_EDR_ATT_MTU);
+
+            conn.on_mtu_exchanged(200);
+
+            assert_eq!(conn.mtu(), MIN_BR_EDR_ATT_MTU);
+        });
+    }
+
+    fn confirm_indication(conn: &Rc<AttServerBearer<TestAttDatabase>>) {
+        conn.handle_packet(build_att_view_or_crash(AttHandleValueConfirmationBuilder {}).view());
+    }
+
+    #[test]
+    fn test_notification() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+            let data = AttAttributeDataChild::RawData([1, 2].into());
+
+            conn.send_notification(VALID_HANDLE, data.clone()).unwrap();
+
+            assert_eq!(
+                rx.recv().await.unwrap(),
+                AttBuilder {
+                    opcode: AttOpcode::HANDLE_VALUE_NOTIFICATION,
+                    _child_: AttHandleValueNotificationBuilder {
+                        handle: VALID_HANDLE.into(),
+                        value: build_att_data(data),
+                    }
+                    .into(),
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_notification_truncated_to_mtu() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+
+            conn.send_notification(VALID_HANDLE, AttAttributeDataChild::RawData([1; 30].into()))
+                .unwrap();
+
+            let AttChild::AttHandleValueNotification(notification) =
+                rx.recv().await.unwrap()._child_
+            else {
+                unreachable!()
+            };
+            assert_eq!(
+                notification.value,
+                build_att_data(AttAttributeDataChild::RawData([1; DEFAULT_ATT_MTU - 3].into()))
+            );
+        });
+    }
+
+    #[test]
+    fn test_indication_confirmed() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+            let data = AttAttributeDataChild::RawData([1, 2].into());
+
+            let pending_indication = spawn_local(conn.send_indication(VALID_HANDLE, data.clone()));
+            assert_eq!(
+                rx.recv().await.unwrap(),
+                AttBuilder {
+                    opcode: AttOpcode::HANDLE_VALUE_INDICATION,
+                    _child_: AttHandleValueIndicationBuilder {
+                        handle: VALID_HANDLE.into(),
+                        value: build_att_data(data),
+                    }
+                    .into(),
+                }
+            );
+            confirm_indication(&conn);
+
+            assert!(matches!(pending_indication.await.unwrap(), Ok(())));
+        });
+    }
+
+    #[test]
+    fn test_only_one_outstanding_indication() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+            let data = AttAttributeDataChild::RawData([1, 2].into());
+
+            // act: send two indications without confirming the first
+            let pending_indication_1 =
+                spawn_local(conn.send_indication(VALID_HANDLE, data.clone()));
+            let pending_indication_2 =
+                spawn_local(conn.send_indication(ANOTHER_VALID_HANDLE, data.clone()));
+
+            // assert: only the first is sent until it is confirmed
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::HANDLE_VALUE_INDICATION);
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+            confirm_indication(&conn);
+            assert!(matches!(pending_indication_1.await.unwrap(), Ok(())));
+
+            let AttChild::AttHandleValueIndication(indication) = rx.recv().await.unwrap()._child_
+            else {
+                unreachable!()
+            };
+            assert_eq!(indication.handle, ANOTHER_VALID_HANDLE.into());
+            confirm_indication(&conn);
+            assert!(matches!(pending_indication_2.await.unwrap(), Ok(())));
+        });
+    }
+
+    #[test]
+    fn test_indication_timeout_closes_bearer() {
+        block_on_locally(async {
+            tokio::time::pause();
+            let (conn, mut rx, mut close_rx) = open_connection_reporting_close();
+
+            // act: send an indication and never confirm it
+            let res = conn
+                .send_indication(VALID_HANDLE, AttAttributeDataChild::RawData([1, 2].into()))
+                .await;
+            rx.recv().await.unwrap(); // drop the indication
+
+            // assert: the indication timed out, and the bearer closed itself and no longer
+            // responds
+            assert!(matches!(res, Err(IndicationError::ConfirmationTimeout)));
+            assert_eq!(close_rx.recv().await, Some(DisconnectReason::ConfirmationTimeout));
+            conn.handle_packet(
+                build_att_view_or_crash(AttReadRequestBuilder {
+                    attribute_handle: VALID_HANDLE.into(),
+                })
+                .view(),
+            );
+            assert!(matches!(
+                conn.send_notification(VALID_HANDLE, AttAttributeDataChild::RawData([1].into())),
+                Err(NotificationError::BearerClosed)
+            ));
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        });
+    }
+
+    #[test]
+    fn test_indication_fails_when_bearer_dropped() {
+        block_on_locally(async {
+            let (conn, _rx) = open_connection();
+
+            let pending_indication = spawn_local(
+                conn.send_indication(VALID_HANDLE, AttAttributeDataChild::RawData([1, 2].into())),
+            );
+            // let the indication be sent before dropping the connection
+            tokio::task::yield_now().await;
+            drop(conn);
+
+            assert!(matches!(
+                pending_indication.await.unwrap(),
+                Err(IndicationError::BearerClosed)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_unexpected_confirmation_ignored() {
+        block_on_locally(async {
+            let (conn, mut rx) = open_connection();
+
+            confirm_indication(&conn);
+
+            // the bearer should still accept requests afterwards
+            conn.handle_packet(
+                build_att_view_or_crash(AttReadRequestBuilder {
+                    attribute_handle: VALID_HANDLE.into(),
+                })
+                .view(),
+            );
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::READ_RESPONSE);
+        });
+    }
+
+    type DatastoreBearer = Rc<AttServerBearer<AttDatabaseImpl<MockDatastore>>>;
+
+    fn open_datastore_connection() -> (
+        DatastoreBearer,
+        UnboundedReceiver<AttBuilder>,
+        UnboundedReceiver<MockDatastoreEvents>,
+        UnboundedReceiver<DisconnectReason>,
+    ) {
         let (datastore, mut data_rx) = MockDatastore::new();
         let datastore = Rc::new(datastore);
         datastore.add_connection(CONN_ID);
-        data_rx.blocking_recv().unwrap(); // ignore AddConnection() event
+        data_rx.try_recv().unwrap(); // ignore AddConnection() event
         let db = Rc::new(GattDatabase::new(datastore));
         db.add_service_with_handles(GattServiceWithHandle {
             handle: AttHandle(1),
             type_: Uuid::new(1),
+            primary: true,
+            includes: vec![],
             characteristics: vec![
                 GattCharacteristicWithHandle {
                     handle: VALID_HANDLE,
                     type_: Uuid::new(2),
                     permissions: AttPermissions::READONLY,
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 },
                 GattCharacteristicWithHandle {
                     handle: ANOTHER_VALID_HANDLE,
                     type_: Uuid::new(2),
                     permissions: AttPermissions::READONLY,
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 },
             ],
         })
         .unwrap();
-        let (tx, mut rx) = unbounded_channel();
-        let send_response = move |packet| {
-            tx.send(packet).unwrap();
-            Ok(())
-        };
-        let conn = AttServerBearer::new(db.get_att_database(CONN_ID), send_response);
-        let data = AttAttributeDataChild::RawData([1, 2].into());
+        let (tx, rx) = unbounded_channel();
+        let (close_tx, close_rx) = unbounded_channel();
+        let conn = AttServerBearer::new(
+            db.get_att_database(CONN_ID),
+            SERVER_RX_MTU,
+            None,
+            move |packet| {
+                tx.send(packet).unwrap();
+                Ok(())
+            },
+            |_| {},
+            move |reason| close_tx.send(reason).unwrap(),
+        );
+        (conn, rx, data_rx, close_rx)
+    }
 
-        // act: send two read requests before replying to either read
-        // first request
+    #[test]
+    fn test_concurrent_transaction_failure() {
         block_on_locally(async {
+            // arrange: AttServerBearer linked to a backing datastore and packet queue, with
+            // two characteristics in the database
+            let (conn, mut rx, mut data_rx, mut close_rx) = open_datastore_connection();
+
+            // act: send two read requests before replying to either read
+            // first request
             let req1 = build_att_view_or_crash(AttReadRequestBuilder {
                 attribute_handle: VALID_HANDLE.into(),
             });
             conn.handle_packet(req1.view());
+            let MockDatastoreEvents::ReadCharacteristic(
+                CONN_ID,
+                VALID_HANDLE,
+                0,
+                false,
+                data_resp,
+            ) = data_rx.recv().await.unwrap() else {
+                    unreachable!();
+            };
             // second request
             let req2 = build_att_view_or_crash(AttReadRequestBuilder {
                 attribute_handle: ANOTHER_VALID_HANDLE.into(),
             });
             conn.handle_packet(req2.view());
-            // handle first reply
-            let MockDatastoreEvents::ReadCharacteristic(CONN_ID, VALID_HANDLE, data_resp) =
-                data_rx.recv().await.unwrap() else {
+
+            // assert: the bearer closed itself due to the protocol violation
+            assert_eq!(close_rx.recv().await, Some(DisconnectReason::ProtocolViolation));
+            // assert: the first transaction was aborted, so even if the datastore replies, no
+            // reply is sent to the peer
+            let _ = data_resp.send(Ok(AttAttributeDataChild::RawData([1, 2].into())));
+            tokio::task::yield_now().await;
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+            // assert: the second request was never forwarded
+            assert_eq!(data_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+            // assert: the bearer no longer accepts requests
+            conn.handle_packet(req1.view());
+            assert_eq!(data_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+            assert_eq!(close_rx.try_recv(), Err(TryRecvError::Empty));
+        });
+    }
+
+    #[test]
+    fn test_transaction_timeout_closes_bearer() {
+        block_on_locally(async {
+            tokio::time::pause();
+            let (conn, mut rx, mut data_rx, mut close_rx) = open_datastore_connection();
+
+            // act: start a read, and never reply to it from the datastore
+            conn.handle_packet(
+                build_att_view_or_crash(AttReadRequestBuilder {
+                    attribute_handle: VALID_HANDLE.into(),
+                })
+                .view(),
+            );
+            let MockDatastoreEvents::ReadCharacteristic(
+                CONN_ID,
+                VALID_HANDLE,
+                0,
+                false,
+                _data_resp,
+            ) = data_rx.recv().await.unwrap() else {
                     unreachable!();
             };
-            data_resp.send(Ok(data.clone())).unwrap();
-            trace!("reply sent from upper tester");
 
-            // assert: that the first reply was made
-            let resp = rx.recv().await.unwrap();
-            assert_eq!(
-                resp,
-                AttBuilder {
-                    opcode: AttOpcode::READ_RESPONSE,
-                    _child_: AttReadResponseBuilder { value: build_att_data(data) }.into(),
-                }
+            // assert: the bearer closed itself once the ATT timeout expired, without replying
+            assert_eq!(close_rx.recv().await, Some(DisconnectReason::TransactionTimeout));
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+            assert!(matches!(
+                conn.send_notification(VALID_HANDLE, AttAttributeDataChild::RawData([1].into())),
+                Err(NotificationError::BearerClosed)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_transaction_completed_before_timeout() {
+        block_on_locally(async {
+            tokio::time::pause();
+            let (conn, mut rx, mut data_rx, mut close_rx) = open_datastore_connection();
+
+            // act: start a read, and only reply just before the ATT timeout
+            conn.handle_packet(
+                build_att_view_or_crash(AttReadRequestBuilder {
+                    attribute_handle: VALID_HANDLE.into(),
+                })
+                .view(),
+            );
+            let MockDatastoreEvents::ReadCharacteristic(
+                CONN_ID,
+                VALID_HANDLE,
+                0,
+                false,
+                data_resp,
+            ) = data_rx.recv().await.unwrap() else {
+                    unreachable!();
+            };
+            tokio::time::sleep(ATT_TIMEOUT - Duration::from_millis(1)).await;
+            data_resp.send(Ok(AttAttributeDataChild::RawData([1, 2].into()))).unwrap();
+
+            // assert: the reply was sent, and the bearer stays open
+            assert_eq!(rx.recv().await.unwrap().opcode, AttOpcode::READ_RESPONSE);
+            tokio::time::sleep(ATT_TIMEOUT).await;
+            assert_eq!(close_rx.try_recv(), Err(TryRecvError::Empty));
+        });
+    }
+
+    #[test]
+    fn test_write_command_during_transaction() {
+        // arrange: AttServerBearer linked to a backing datastore, with a
+        // characteristic writable without response
+        let (datastore, mut data_rx) = MockDatastore::new();
+        let datastore = Rc::new(datastore);
+        datastore.add_connection(CONN_ID);
+        data_rx.blocking_recv().unwrap(); // ignore AddConnection() event
+        let db = Rc::new(GattDatabase::new(datastore));
+        db.add_service_with_handles(GattServiceWithHandle {
+            handle: AttHandle(1),
+            type_: Uuid::new(1),
+            primary: true,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: VALID_HANDLE,
+                type_: Uuid::new(2),
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: true,
+                    ..Default::default()
+                },
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
+            }],
+        })
+        .unwrap();
+        let (tx, mut rx) = unbounded_channel();
+        let conn = AttServerBearer::new(
+            db.get_att_database(CONN_ID),
+            SERVER_RX_MTU,
+            None,
+            move |packet| {
+                tx.send(packet).unwrap();
+                Ok(())
+            },
+            |_| {},
+            |_| {},
+        );
+
+        block_on_locally(async {
+            // act: start a read, then send a write command before it completes
+            conn.handle_packet(
+                build_att_view_or_crash(AttReadRequestBuilder {
+                    attribute_handle: VALID_HANDLE.into(),
+                })
+                .view(),
             );
-            // assert no other replies were made
+            let MockDatastoreEvents::ReadCharacteristic(
+                CONN_ID,
+                VALID_HANDLE,
+                0,
+                false,
+                _data_resp,
+            ) = data_rx.recv().await.unwrap() else {
+                    unreachable!();
+            };
+            conn.handle_packet(
+                build_att_view_or_crash(AttWriteCommandBuilder {
+                    handle: VALID_HANDLE.into(),
+                    value: build_att_data(AttAttributeDataChild::RawData([1, 2].into())),
+                })
+                .view(),
+            );
+
+            // assert: the write is forwarded immediately, and not replied to
+            let MockDatastoreEvents::WriteNoResponseCharacteristic(CONN_ID, VALID_HANDLE, data) =
+                data_rx.recv().await.unwrap() else {
+                    unreachable!();
+            };
+            assert_eq!(data.view().get_raw_payload().collect::<Vec<_>>(), vec![1, 2]);
             assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
-            // assert no callbacks are pending
-            assert_eq!(data_rx.try_recv().unwrap_err(), TryRecvError::Empty);
         });
     }
 }
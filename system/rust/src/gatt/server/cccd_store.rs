@@ -0,0 +1,212 @@
+//! This module defines the persistence of Client Characteristic Configuration
+//! Descriptor values for bonded peers, so subscriptions survive reconnection.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::gatt::ids::{AttHandle, PeerIdentity};
+
+use super::gatt_database::ClientConfiguration;
+
+/// A store for the CCCD values of bonded peers, keyed by peer identity and
+/// characteristic value handle.
+pub trait CccdStore {
+    /// Load all the values stored for a peer
+    fn load(&self, peer: PeerIdentity) -> Result<BTreeMap<AttHandle, ClientConfiguration>>;
+
+    /// Store the value for a given characteristic. Storing the default
+    /// (unsubscribed) value may remove the entry.
+    fn store(
+        &self,
+        peer: PeerIdentity,
+        handle: AttHandle,
+        configuration: ClientConfiguration,
+    ) -> Result<()>;
+
+    /// Remove all values stored for a peer (e.g. once it is unbonded)
+    fn remove(&self, peer: PeerIdentity) -> Result<()>;
+}
+
+/// A CccdStore keeping one file per peer in a local directory. Each line of a
+/// file holds a characteristic value handle and its CCCD value.
+pub struct FileCccdStore {
+    directory: PathBuf,
+}
+
+impl FileCccdStore {
+    /// Constructor. The directory is created on first write, if needed.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+
+    fn path_of(&self, peer: PeerIdentity) -> PathBuf {
+        let name = peer.0.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+        self.directory.join(name)
+    }
+
+    fn write(&self, path: &Path, values: &BTreeMap<AttHandle, ClientConfiguration>) -> Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let contents = values
+            .iter()
+            .map(|(handle, configuration)| format!("{} {}\n", handle.0, configuration.to_bits()))
+            .collect::<String>();
+        fs::write(path, contents).with_context(|| format!("failed to write {path:?}"))
+    }
+}
+
+impl CccdStore for FileCccdStore {
+    fn load(&self, peer: PeerIdentity) -> Result<BTreeMap<AttHandle, ClientConfiguration>> {
+        let path = self.path_of(peer);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(err).with_context(|| format!("failed to read {path:?}")),
+        };
+
+        contents
+            .lines()
+            .map(|line| {
+                let (handle, bits) = line
+                    .split_once(' ')
+                    .ok_or_else(|| anyhow!("malformed line {line:?} in {path:?}"))?;
+                let configuration = ClientConfiguration::from_bits(bits.parse()?)
+                    .ok_or_else(|| anyhow!("invalid CCCD value {bits} in {path:?}"))?;
+                Ok((AttHandle(handle.parse()?), configuration))
+            })
+            .collect()
+    }
+
+    fn store(
+        &self,
+        peer: PeerIdentity,
+        handle: AttHandle,
+        configuration: ClientConfiguration,
+    ) -> Result<()> {
+        let mut values = self.load(peer)?;
+        if configuration == ClientConfiguration::default() {
+            values.remove(&handle);
+        } else {
+            values.insert(handle, configuration);
+        }
+        self.write(&self.path_of(peer), &values)
+    }
+
+    fn remove(&self, peer: PeerIdentity) -> Result<()> {
+        match fs::remove_file(self.path_of(peer)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PEER: PeerIdentity = PeerIdentity([1, 2, 3, 4, 5, 6]);
+    const ANOTHER_PEER: PeerIdentity = PeerIdentity([6, 5, 4, 3, 2, 1]);
+    const HANDLE: AttHandle = AttHandle(3);
+    const ANOTHER_HANDLE: AttHandle = AttHandle(6);
+
+    const NOTIFY: ClientConfiguration = ClientConfiguration { notify: true, indicate: false };
+    const INDICATE: ClientConfiguration = ClientConfiguration { notify: false, indicate: true };
+
+    fn make_store(name: &str) -> FileCccdStore {
+        let directory =
+            std::env::temp_dir().join(format!("cccd_store_test_{}_{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        FileCccdStore::new(directory)
+    }
+
+    #[test]
+    fn test_load_unknown_peer() {
+        let store = make_store("unknown_peer");
+
+        let values = store.load(PEER).unwrap();
+
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        // arrange
+        let store = make_store("store_and_load");
+
+        // act
+        store.store(PEER, HANDLE, NOTIFY).unwrap();
+        store.store(PEER, ANOTHER_HANDLE, INDICATE).unwrap();
+        store.store(ANOTHER_PEER, HANDLE, INDICATE).unwrap();
+
+        // assert
+        assert_eq!(
+            store.load(PEER).unwrap(),
+            BTreeMap::from([(HANDLE, NOTIFY), (ANOTHER_HANDLE, INDICATE)])
+        );
+        assert_eq!(store.load(ANOTHER_PEER).unwrap(), BTreeMap::from([(HANDLE, INDICATE)]));
+    }
+
+    #[test]
+    fn test_store_persists_across_instances() {
+        let store = make_store("persists");
+        store.store(PEER, HANDLE, NOTIFY).unwrap();
+
+        let reopened = FileCccdStore::new(store.directory.clone());
+
+        assert_eq!(reopened.load(PEER).unwrap(), BTreeMap::from([(HANDLE, NOTIFY)]));
+    }
+
+    #[test]
+    fn test_store_default_removes_entry() {
+        // arrange
+        let store = make_store("store_default");
+        store.store(PEER, HANDLE, NOTIFY).unwrap();
+
+        // act
+        store.store(PEER, HANDLE, ClientConfiguration::default()).unwrap();
+
+        // assert
+        assert!(store.load(PEER).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remove_peer() {
+        // arrange
+        let store = make_store("remove_peer");
+        store.store(PEER, HANDLE, NOTIFY).unwrap();
+        store.store(ANOTHER_PEER, HANDLE, NOTIFY).unwrap();
+
+        // act
+        store.remove(PEER).unwrap();
+
+        // assert: only the removed peer is forgotten
+        assert!(store.load(PEER).unwrap().is_empty());
+        assert_eq!(store.load(ANOTHER_PEER).unwrap(), BTreeMap::from([(HANDLE, NOTIFY)]));
+    }
+
+    #[test]
+    fn test_remove_unknown_peer() {
+        let store = make_store("remove_unknown");
+
+        assert!(store.remove(PEER).is_ok());
+    }
+
+    #[test]
+    fn test_load_corrupted_file() {
+        // arrange
+        let store = make_store("corrupted");
+        fs::create_dir_all(&store.directory).unwrap();
+        fs::write(store.path_of(PEER), "3 8\n").unwrap();
+
+        // act
+        let values = store.load(PEER);
+
+        // assert: reserved bits are rejected
+        assert!(values.is_err());
+    }
+}
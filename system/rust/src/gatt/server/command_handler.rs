@@ -0,0 +1,296 @@
+use log::warn;
+
+use crate::packets::{
+    AttOpcode, AttSignedWriteCommandView, AttView, AttWriteCommandView, Packet, ParseError,
+};
+
+use super::{att_database::AttDatabase, signing::SignedWriteVerifier};
+
+/// The length of the MAC at the end of an ATT_SIGNED_WRITE_CMD
+const SIGNATURE_MAC_LENGTH: usize = 8;
+
+/// This struct handles all commands, i.e. PDUs that expect no response. Unlike
+/// requests, commands may be processed at any time, even while a transaction
+/// is outstanding (5.3 3F 3.3).
+pub struct AttCommandHandler<Db: AttDatabase> {
+    db: Db,
+    signed_write_verifier: Option<SignedWriteVerifier>,
+}
+
+impl<Db: AttDatabase> AttCommandHandler<Db> {
+    /// Constructor. Signed writes are dropped if no verifier is provided.
+    pub fn new(db: Db, signed_write_verifier: Option<SignedWriteVerifier>) -> Self {
+        Self { db, signed_write_verifier }
+    }
+
+    /// Process an incoming command. As per 5.3 3F 3.4.5.3 and 3.4.5.4, invalid
+    /// commands are silently dropped.
+    pub fn process_packet(&self, packet: AttView<'_>) {
+        // as per 5.3 3G 2.5.2.1, commands from a change-unaware client are
+        // dropped, since they may refer to stale handles
+        if !self.db.is_cache_in_sync() {
+            warn!("dropping ATT command {:?} from out-of-sync client", packet.get_opcode());
+            return;
+        }
+        if let Err(err) = self.try_parse_and_process_packet(packet) {
+            warn!("failed to parse ATT command {:?}: {err:?}", packet.get_opcode());
+        }
+    }
+
+    fn try_parse_and_process_packet(&self, packet: AttView<'_>) -> Result<(), ParseError> {
+        match packet.get_opcode() {
+            AttOpcode::WRITE_COMMAND => {
+                let command = AttWriteCommandView::try_parse(packet)?;
+                self.db
+                    .write_no_response_attribute(command.get_handle().into(), command.get_value());
+            }
+            AttOpcode::SIGNED_WRITE_COMMAND => {
+                let command = AttSignedWriteCommandView::try_parse(packet)?;
+                self.process_signed_write(packet, command);
+            }
+            opcode => warn!("dropping unsupported ATT command {opcode:?}"),
+        }
+        Ok(())
+    }
+
+    fn process_signed_write(&self, packet: AttView<'_>, command: AttSignedWriteCommandView<'_>) {
+        let Some(verifier) = &self.signed_write_verifier else {
+            warn!("signed writes are not supported on this bearer, dropping");
+            return;
+        };
+
+        // the signature covers the whole PDU, except for the MAC itself
+        let payload = packet.get_raw_payload().collect::<Vec<_>>();
+        let signed_data = std::iter::once(u8::from(packet.get_opcode()))
+            .chain(payload[..payload.len() - SIGNATURE_MAC_LENGTH].iter().copied())
+            .collect::<Vec<_>>();
+        let mut mac = [0; SIGNATURE_MAC_LENGTH];
+        for (dst, src) in mac.iter_mut().zip(command.get_mac_iter()) {
+            *dst = src;
+        }
+
+        if let Err(err) = verifier.verify(&signed_data, command.get_sign_counter(), mac) {
+            warn!("dropping signed write with invalid signature: {err:?}");
+            return;
+        }
+
+        self.db.write_signed_attribute(command.get_handle().into(), command.get_value());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    use crate::{
+        core::uuid::Uuid,
+        gatt::{
+            ids::{AttHandle, ConnectionId},
+            server::{
+                att_database::{AttAttribute, AttPermissions},
+                signing::{compute_mac, PeerSigningKey, SigningKeyProvider},
+                test::test_att_db::TestAttDatabase,
+            },
+        },
+        packets::{
+            AttAttributeDataChild, AttSignedWriteCommandBuilder, AttWriteCommandBuilder,
+            Serializable,
+        },
+        utils::packet::{build_att_data, build_att_view_or_crash},
+    };
+
+    const CONN_ID: ConnectionId = ConnectionId(1);
+    const WRITABLE_HANDLE: AttHandle = AttHandle(3);
+    const NOT_WRITABLE_HANDLE: AttHandle = AttHandle(4);
+    const UNSIGNED_HANDLE: AttHandle = AttHandle(5);
+    const CSRK: [u8; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+
+    struct FakeSigningKeyProvider {
+        min_sign_counter: Cell<u32>,
+    }
+
+    impl SigningKeyProvider for FakeSigningKeyProvider {
+        fn get_peer_signing_key(&self, _: ConnectionId) -> Option<PeerSigningKey> {
+            Some(PeerSigningKey { csrk: CSRK, min_sign_counter: self.min_sign_counter.get() })
+        }
+
+        fn on_sign_counter_used(&self, _: ConnectionId, sign_counter: u32) {
+            self.min_sign_counter.set(sign_counter + 1);
+        }
+    }
+
+    fn make_db() -> TestAttDatabase {
+        TestAttDatabase::new(vec![
+            (
+                AttAttribute {
+                    handle: WRITABLE_HANDLE,
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: true,
+                        writable_signed: true,
+                        ..Default::default()
+                    },
+                },
+                vec![9],
+            ),
+            (
+                AttAttribute {
+                    handle: NOT_WRITABLE_HANDLE,
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                },
+                vec![9],
+            ),
+            (
+                AttAttribute {
+                    handle: UNSIGNED_HANDLE,
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: true,
+                        writable_signed: false,
+                        ..Default::default()
+                    },
+                },
+                vec![9],
+            ),
+        ])
+    }
+
+    fn make_handler(with_signing_key: bool) -> AttCommandHandler<Rc<TestAttDatabase>> {
+        let verifier = with_signing_key.then(|| {
+            SignedWriteVerifier::new(
+                CONN_ID,
+                Rc::new(FakeSigningKeyProvider { min_sign_counter: Cell::new(0) }),
+            )
+        });
+        AttCommandHandler::new(Rc::new(make_db()), verifier)
+    }
+
+    fn read(handle: AttHandle, handler: &AttCommandHandler<Rc<TestAttDatabase>>) -> Vec<u8> {
+        tokio_test::block_on(handler.db.read_attribute(handle)).unwrap().to_vec().unwrap()
+    }
+
+    fn build_signed_write(
+        handle: AttHandle,
+        value: &[u8],
+        sign_counter: u32,
+    ) -> AttSignedWriteCommandBuilder {
+        let mut signed_data = vec![u8::from(AttOpcode::SIGNED_WRITE_COMMAND)];
+        signed_data.extend(handle.0.to_le_bytes());
+        signed_data.extend(value);
+        signed_data.extend(sign_counter.to_le_bytes());
+        AttSignedWriteCommandBuilder {
+            handle: handle.into(),
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+            sign_counter,
+            mac: compute_mac(&CSRK, &signed_data).into(),
+        }
+    }
+
+    fn build_write(handle: AttHandle, value: &[u8]) -> AttWriteCommandBuilder {
+        AttWriteCommandBuilder {
+            handle: handle.into(),
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        }
+    }
+
+    #[test]
+    fn test_write_command() {
+        // arrange
+        let handler = make_handler(false);
+        let packet = build_att_view_or_crash(build_write(WRITABLE_HANDLE, &[1, 2]));
+
+        // act
+        handler.process_packet(packet.view());
+
+        // assert
+        assert_eq!(read(WRITABLE_HANDLE, &handler), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_write_command_not_writable_without_response() {
+        let handler = make_handler(false);
+        let packet = build_att_view_or_crash(build_write(NOT_WRITABLE_HANDLE, &[1, 2]));
+
+        handler.process_packet(packet.view());
+
+        assert_eq!(read(NOT_WRITABLE_HANDLE, &handler), vec![9]);
+    }
+
+    #[test]
+    fn test_signed_write_command() {
+        // arrange
+        let handler = make_handler(true);
+        let packet = build_att_view_or_crash(build_signed_write(WRITABLE_HANDLE, &[1, 2], 5));
+
+        // act
+        handler.process_packet(packet.view());
+
+        // assert
+        assert_eq!(read(WRITABLE_HANDLE, &handler), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_signed_write_command_not_permitted() {
+        // arrange: the attribute accepts write commands, but not signed ones
+        let handler = make_handler(true);
+        let packet = build_att_view_or_crash(build_signed_write(UNSIGNED_HANDLE, &[1, 2], 5));
+
+        // act
+        handler.process_packet(packet.view());
+
+        // assert
+        assert_eq!(read(UNSIGNED_HANDLE, &handler), vec![9]);
+    }
+
+    #[test]
+    fn test_signed_write_command_replayed() {
+        // arrange: accept a signed write once, then overwrite its value
+        let handler = make_handler(true);
+        let packet = build_att_view_or_crash(build_signed_write(WRITABLE_HANDLE, &[1, 2], 5));
+        handler.process_packet(packet.view());
+        handler.process_packet(build_att_view_or_crash(build_write(WRITABLE_HANDLE, &[3])).view());
+
+        // act: replay the signed write
+        handler.process_packet(packet.view());
+
+        // assert
+        assert_eq!(read(WRITABLE_HANDLE, &handler), vec![3]);
+    }
+
+    #[test]
+    fn test_signed_write_command_invalid_mac() {
+        // arrange: tamper with the value after signing
+        let handler = make_handler(true);
+        let mut builder = build_signed_write(WRITABLE_HANDLE, &[1, 2], 5);
+        builder.value = build_att_data(AttAttributeDataChild::RawData([1, 3].into()));
+        let packet = build_att_view_or_crash(builder);
+
+        // act
+        handler.process_packet(packet.view());
+
+        // assert
+        assert_eq!(read(WRITABLE_HANDLE, &handler), vec![9]);
+    }
+
+    #[test]
+    fn test_signed_write_command_without_signing_key() {
+        let handler = make_handler(false);
+        let packet = build_att_view_or_crash(build_signed_write(WRITABLE_HANDLE, &[1, 2], 5));
+
+        handler.process_packet(packet.view());
+
+        assert_eq!(read(WRITABLE_HANDLE, &handler), vec![9]);
+    }
+}
@@ -2,44 +2,78 @@
 //! by converting a registry of services into a list of attributes, and proxying
 //! ATT read/write requests into characteristic reads/writes
 
-use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
 use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
+use log::{error, warn};
 
 use crate::{
     core::uuid::Uuid,
     gatt::{
-        callbacks::GattDatastore,
-        ids::{AttHandle, ConnectionId},
+        callbacks::{GattDatastore, TransactionDecision},
+        ids::{AttHandle, ConnectionId, PeerIdentity},
     },
     packets::{
         AttAttributeDataChild, AttAttributeDataView, AttCharacteristicPropertiesBuilder,
         AttErrorCode, GattCharacteristicDeclarationValueBuilder,
-        GattServiceDeclarationValueBuilder, UuidBuilder,
+        GattClientCharacteristicConfigurationBuilder, GattServiceDeclarationValueBuilder,
+        OwnedAttAttributeDataView, OwnedPacket, Serializable, Uuid16Builder, UuidBuilder,
     },
+    utils::crypto::aes_cmac,
 };
 
-use super::att_database::{
-    AttAttribute, AttDatabase, CHARACTERISTIC_UUID, PRIMARY_SERVICE_DECLARATION_UUID,
+use super::{
+    att_database::{
+        slice_attribute_value, AttAttribute, AttDatabase, CHARACTERISTIC_AGGREGATE_FORMAT_UUID,
+        CHARACTERISTIC_PRESENTATION_FORMAT_UUID, CLIENT_SUPPORTED_FEATURES_UUID,
+        DATABASE_HASH_UUID, SERVER_CHARACTERISTIC_CONFIGURATION_UUID, SERVICE_CHANGED_UUID,
+    },
+    cccd_store::CccdStore,
+    services::{LocalGattService, LocalValue},
 };
 
-pub use super::att_database::AttPermissions;
+pub use super::att_database::{
+    AttPermissions, AttSecurityRequirements, ConnectionSecurity,
+    CHARACTERISTIC_EXTENDED_PROPERTIES_UUID, CHARACTERISTIC_USER_DESCRIPTION_UUID,
+    CHARACTERISTIC_UUID, CLIENT_CHARACTERISTIC_CONFIGURATION_UUID, INCLUDE_DECLARATION_UUID,
+    PRIMARY_SERVICE_DECLARATION_UUID, SECONDARY_SERVICE_DECLARATION_UUID,
+};
 
-/// A GattService (currently, only primary services are supported) has an
-/// identifying UUID and a list of contained characteristics, as well as a
-/// handle (indicating the attribute where the service declaration will live)
+/// A GattService has an identifying UUID, a list of included services, and a
+/// list of contained characteristics, as well as a handle (indicating the
+/// attribute where the service declaration will live)
 #[derive(Debug, Clone)]
 pub struct GattServiceWithHandle {
     /// The handle of the service declaration
     pub handle: AttHandle,
     /// The type of the service
     pub type_: Uuid,
+    /// Whether this is a primary service, or a secondary service (that is only
+    /// meant to be included by other services)
+    pub primary: bool,
+    /// The include declarations of the service (that must have handles after
+    /// the service declaration, and before the first characteristic)
+    pub includes: Vec<GattIncludeWithHandle>,
     /// A list of contained characteristics (that must have handles between the
     /// service declaration handle, and that of the next service)
     pub characteristics: Vec<GattCharacteristicWithHandle>,
 }
 
+/// An include declaration, referencing another service of the same database
+#[derive(Debug, Clone)]
+pub struct GattIncludeWithHandle {
+    /// The handle of the include declaration
+    pub handle: AttHandle,
+    /// The handle of the declaration of the included service, which must
+    /// already be registered
+    pub service_handle: AttHandle,
+}
+
 /// A GattCharacteristic consists of a handle (where the value attribute lives),
 /// a UUID identifying its type, and permissions indicating what operations can
 /// be performed
@@ -52,6 +86,127 @@ pub struct GattCharacteristicWithHandle {
     pub type_: Uuid,
     /// The permissions (read/write) indicate what operations can be performed.
     pub permissions: AttPermissions,
+    /// Whether the characteristic value can be notified to clients
+    pub notify: bool,
+    /// Whether the characteristic value can be indicated to clients
+    pub indicate: bool,
+    /// The Characteristic Extended Properties descriptor, if any. Its presence
+    /// is also reflected in the properties of the characteristic declaration.
+    pub extended_properties: Option<GattExtendedPropertiesWithHandle>,
+    /// The Characteristic User Description descriptor, if any, owned by the
+    /// GattDatabase. A user description can instead be listed among the
+    /// descriptors below, to be forwarded to the GattDatastore.
+    pub user_description: Option<GattUserDescriptionWithHandle>,
+    /// The other descriptors of this characteristic (that must have handles
+    /// after the characteristic value handle). If the characteristic can be
+    /// notified or indicated, exactly one of them should be a CCCD.
+    pub descriptors: Vec<GattDescriptorWithHandle>,
+}
+
+/// A Characteristic Extended Properties descriptor (5.3 3G 3.3.3.1), whose
+/// value is owned by the GattDatabase
+#[derive(Debug, Clone)]
+pub struct GattExtendedPropertiesWithHandle {
+    /// The handle of the descriptor
+    pub handle: AttHandle,
+    /// Whether the characteristic value can be written with Reliable Writes
+    pub reliable_write: bool,
+    /// Whether clients can write the Characteristic User Description
+    pub writable_auxiliaries: bool,
+}
+
+impl GattExtendedPropertiesWithHandle {
+    /// Encode the 16-bit descriptor value
+    fn to_bits(&self) -> u16 {
+        u16::from(self.reliable_write) | u16::from(self.writable_auxiliaries) << 1
+    }
+}
+
+/// A Characteristic User Description descriptor (5.3 3G 3.3.3.2), whose value
+/// is owned by the GattDatabase. It can be written by clients only if the
+/// writable auxiliaries extended property is set.
+#[derive(Debug, Clone)]
+pub struct GattUserDescriptionWithHandle {
+    /// The handle of the descriptor
+    pub handle: AttHandle,
+    /// The initial description of the characteristic
+    pub description: String,
+}
+
+/// A GattDescriptor consists of a handle, a UUID identifying its type, and
+/// permissions indicating what operations can be performed. Client
+/// Characteristic Configuration Descriptors are handled by the GattDatabase
+/// itself (ignoring these permissions), all others are forwarded to the
+/// GattDatastore.
+#[derive(Debug, Clone)]
+pub struct GattDescriptorWithHandle {
+    /// The handle of the descriptor
+    pub handle: AttHandle,
+    /// The UUID representing the type of the descriptor
+    pub type_: Uuid,
+    /// The permissions (read/write) indicate what operations can be performed.
+    pub permissions: AttPermissions,
+}
+
+/// The value of a Client Characteristic Configuration Descriptor, indicating
+/// which updates of a characteristic value a client has subscribed to
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ClientConfiguration {
+    /// Whether the client has enabled notifications
+    pub notify: bool,
+    /// Whether the client has enabled indications
+    pub indicate: bool,
+}
+
+impl ClientConfiguration {
+    /// Decode the 16-bit descriptor value. Fails if any reserved bit is set.
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        if bits & !0b11 != 0 {
+            return None;
+        }
+        Some(Self { notify: bits & 0b01 != 0, indicate: bits & 0b10 != 0 })
+    }
+
+    /// Encode the 16-bit descriptor value
+    pub fn to_bits(self) -> u16 {
+        u16::from(self.notify) | u16::from(self.indicate) << 1
+    }
+
+    /// Whether every subscription in `self` is also enabled in `other`
+    fn is_subset_of(self, other: Self) -> bool {
+        (!self.notify || other.notify) && (!self.indicate || other.indicate)
+    }
+}
+
+/// The features a client enables in its Client Supported Features
+/// characteristic (5.3 3G 7.2). Only those supported by this server are kept.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ClientSupportedFeatures {
+    /// Whether the client uses robust caching, so it should be told when its
+    /// attribute cache is out of sync
+    pub robust_caching: bool,
+}
+
+impl ClientSupportedFeatures {
+    /// Decode the first octet of the characteristic value, ignoring
+    /// unsupported features
+    pub fn from_bits(bits: u8) -> Self {
+        Self { robust_caching: bits & 0b1 != 0 }
+    }
+
+    /// Encode the first octet of the characteristic value
+    pub fn to_bits(self) -> u8 {
+        u8::from(self.robust_caching)
+    }
+}
+
+impl From<ClientConfiguration> for GattClientCharacteristicConfigurationBuilder {
+    fn from(value: ClientConfiguration) -> Self {
+        GattClientCharacteristicConfigurationBuilder {
+            notification: value.notify.into(),
+            indication: value.indicate.into(),
+        }
+    }
 }
 
 /// The GattDatabase implements AttDatabase, and converts attribute reads/writes
@@ -60,6 +215,36 @@ pub struct GattCharacteristicWithHandle {
 pub struct GattDatabase<T: ?Sized> {
     datastore: Rc<T>,
     schema: RefCell<GattDatabaseSchema>,
+    cccd_store: Option<Rc<dyn CccdStore>>,
+    client_configurations: RefCell<HashMap<ConnectionId, ConnectionClientConfigurations>>,
+    connection_security: RefCell<HashMap<ConnectionId, ConnectionSecurity>>,
+    /// The caching state of bonded peers, kept across their connections
+    bonded_caching_states: RefCell<HashMap<PeerIdentity, ClientCachingState>>,
+}
+
+/// The CCCD and caching state of a single connection
+#[derive(Default)]
+struct ConnectionClientConfigurations {
+    /// Set if the peer is bonded, so configurations should be persisted
+    bonded_peer: Option<PeerIdentity>,
+    caching: ClientCachingState,
+    /// Set once a change-unaware client has been told its cache is out of
+    /// sync, so the next request makes it change-aware
+    out_of_sync_reported: bool,
+    /// Keyed by characteristic value handle
+    configurations: BTreeMap<AttHandle, ClientConfiguration>,
+    /// Values from prepared writes, along with the handle of their CCCD,
+    /// applied once the writes are executed
+    prepared_configurations: BTreeMap<AttHandle, (AttHandle, ClientConfiguration)>,
+}
+
+/// The state of a client's attribute cache, from 5.3 3G 2.5.2.1
+#[derive(Debug, Copy, Clone, Default)]
+struct ClientCachingState {
+    supported_features: ClientSupportedFeatures,
+    /// Set if the database changed since the client last synchronized its
+    /// attribute cache
+    change_unaware: bool,
 }
 
 #[derive(Default)]
@@ -68,9 +253,161 @@ struct GattDatabaseSchema {
     attributes: BTreeMap<AttHandle, AttAttributeWithBackingValue>,
 }
 
+impl GattDatabaseSchema {
+    /// The subscriptions supported by the CCCD of the characteristic with the
+    /// given value handle, if it has one
+    fn supported_client_configuration(
+        &self,
+        value_handle: AttHandle,
+    ) -> Option<ClientConfiguration> {
+        self.client_configuration_descriptor(value_handle).map(|(_, supported)| supported)
+    }
+
+    /// The handle of the CCCD of the characteristic with the given value
+    /// handle, along with the configurations it supports
+    fn client_configuration_descriptor(
+        &self,
+        value_handle: AttHandle,
+    ) -> Option<(AttHandle, ClientConfiguration)> {
+        self.attributes.iter().find_map(|(handle, attr)| match attr.value {
+            AttAttributeBackingValue::ClientConfiguration { value_handle: value, supported }
+                if value == value_handle =>
+            {
+                Some((*handle, supported))
+            }
+            _ => None,
+        })
+    }
+
+    /// The handle of the last attribute of the service declared at the given
+    /// handle
+    fn service_end_handle(&self, service_handle: AttHandle) -> AttHandle {
+        self.attributes
+            .range(service_handle..)
+            .take_while(|(handle, attr)| {
+                **handle == service_handle || !is_service_declaration(attr.attribute.type_)
+            })
+            .last()
+            .map(|(handle, _)| *handle)
+            .unwrap_or(service_handle)
+    }
+
+    /// The handle of the Service Changed characteristic value, if the Generic
+    /// Attribute service is registered
+    fn service_changed_handle(&self) -> Option<AttHandle> {
+        self.attributes
+            .values()
+            .find(|attr| attr.attribute.type_ == SERVICE_CHANGED_UUID)
+            .map(|attr| attr.attribute.handle)
+    }
+
+    /// Compute the Database Hash, from 5.3 3G 7.3.1. This covers the handle,
+    /// type, and (for declarations) value of the attributes defining the
+    /// structure of the database. The result is in the little-endian order of
+    /// the characteristic value.
+    fn database_hash(&self) -> [u8; 16] {
+        let mut message = vec![];
+        for attr in self.attributes.values() {
+            let include_value = match attr.attribute.type_ {
+                PRIMARY_SERVICE_DECLARATION_UUID
+                | SECONDARY_SERVICE_DECLARATION_UUID
+                | INCLUDE_DECLARATION_UUID
+                | CHARACTERISTIC_UUID
+                | CHARACTERISTIC_EXTENDED_PROPERTIES_UUID => true,
+                CHARACTERISTIC_USER_DESCRIPTION_UUID
+                | CLIENT_CHARACTERISTIC_CONFIGURATION_UUID
+                | SERVER_CHARACTERISTIC_CONFIGURATION_UUID
+                | CHARACTERISTIC_PRESENTATION_FORMAT_UUID
+                | CHARACTERISTIC_AGGREGATE_FORMAT_UUID => false,
+                _ => continue,
+            };
+            // all the hashed attribute types are 16-bit UUIDs
+            let Ok(type_) = Uuid16Builder::try_from(attr.attribute.type_) else {
+                continue;
+            };
+            message.extend(attr.attribute.handle.0.to_le_bytes());
+            message.extend(type_.data.to_le_bytes());
+            if include_value {
+                if let AttAttributeBackingValue::Static(value) = &attr.value {
+                    match value.to_vec() {
+                        Ok(value) => message.extend(value),
+                        Err(err) => error!("failed to serialize {:?}: {err:?}", attr.attribute),
+                    }
+                }
+            }
+        }
+        // AES-CMAC is defined most-significant-octet-first, while the message
+        // above is little-endian, as on the air
+        let mut hash = aes_cmac(&[0; 16], &message);
+        hash.reverse();
+        hash
+    }
+}
+
+fn is_service_declaration(type_: Uuid) -> bool {
+    matches!(type_, PRIMARY_SERVICE_DECLARATION_UUID | SECONDARY_SERVICE_DECLARATION_UUID)
+}
+
+/// Check that the attributes of a service are in the order required by 5.3 3G
+/// 3: the service declaration, then its include declarations, then each
+/// characteristic declaration followed by its value and descriptors. Returns
+/// the handle of the last attribute of the service.
+fn validate_service_layout(service: &GattServiceWithHandle) -> Result<AttHandle> {
+    if service.handle == AttHandle(0) {
+        bail!("service {:?} cannot be declared at handle 0", service.type_);
+    }
+    let mut last_handle = service.handle;
+    for include in &service.includes {
+        if include.handle <= last_handle {
+            bail!("include {include:?} must come after the preceding attributes");
+        }
+        last_handle = include.handle;
+    }
+    for characteristic in &service.characteristics {
+        if characteristic.handle.0.saturating_sub(1) <= last_handle.0 {
+            bail!(
+                "characteristic {:?} must come after the preceding attributes",
+                characteristic.handle
+            );
+        }
+        last_handle = characteristic.handle;
+        let mut descriptor_handles = characteristic
+            .descriptors
+            .iter()
+            .map(|descriptor| descriptor.handle)
+            .chain(characteristic.extended_properties.as_ref().map(|descriptor| descriptor.handle))
+            .chain(characteristic.user_description.as_ref().map(|descriptor| descriptor.handle))
+            .collect::<Vec<_>>();
+        descriptor_handles.sort();
+        for handle in descriptor_handles {
+            if handle <= last_handle {
+                bail!(
+                    "descriptor at {handle:?} must come after its characteristic value, and not overlap other descriptors"
+                );
+            }
+            last_handle = handle;
+        }
+    }
+    Ok(last_handle)
+}
+
 enum AttAttributeBackingValue {
     Static(AttAttributeDataChild),
     Dynamic,
+    /// The Database Hash characteristic value, owned by the GattDatabase
+    DatabaseHash,
+    /// The Client Supported Features characteristic value of each client,
+    /// owned by the GattDatabase
+    ClientSupportedFeatures,
+    /// A CCCD, owned by the GattDatabase, configuring the characteristic with
+    /// the given value handle, and which subscriptions it supports
+    ClientConfiguration {
+        value_handle: AttHandle,
+        supported: ClientConfiguration,
+    },
+    /// A value implemented inside the stack, so never forwarded to the
+    /// GattDatastore
+    Local(LocalValue),
 }
 
 struct AttAttributeWithBackingValue {
@@ -78,18 +415,57 @@ struct AttAttributeWithBackingValue {
     value: AttAttributeBackingValue,
 }
 
-// TODO(aryarahul) - send srvc_chg indication when the schema is modified
 impl<T: GattDatastore + ?Sized> GattDatabase<T> {
     /// Constructor, wrapping a GattDatastore
     pub fn new(datastore: Rc<T>) -> Self {
-        Self { datastore, schema: Default::default() }
+        Self {
+            datastore,
+            schema: Default::default(),
+            cccd_store: None,
+            client_configurations: Default::default(),
+            connection_security: Default::default(),
+            bonded_caching_states: Default::default(),
+        }
+    }
+
+    /// Constructor, wrapping a GattDatastore and persisting the CCCDs of
+    /// bonded peers into the supplied CccdStore
+    pub fn new_with_cccd_store(datastore: Rc<T>, cccd_store: Rc<dyn CccdStore>) -> Self {
+        Self { cccd_store: Some(cccd_store), ..Self::new(datastore) }
     }
 
     /// Add a service with pre-allocated handles (for co-existence with C++)
     /// Assumes that the characteristic DECLARATION handles are one less than
     /// the characteristic handles.
-    /// Returns failure if handles overlap with ones already allocated
+    /// Returns failure if handles overlap with ones already allocated, or are
+    /// not laid out in the order required by 5.3 3G 3
     pub fn add_service_with_handles(&self, service: GattServiceWithHandle) -> Result<()> {
+        self.add_local_service(LocalGattService { service, values: HashMap::new() })
+    }
+
+    /// Add a service as in add_service_with_handles(), but backing the
+    /// attributes with local values where supplied, rather than by the
+    /// GattDatastore. Returns failure if a local value does not belong to a
+    /// characteristic value or a (non-CCCD) descriptor of the service.
+    pub fn add_local_service(&self, service: LocalGattService) -> Result<()> {
+        let LocalGattService { service, mut values } = service;
+        let end_handle = validate_service_layout(&service)?;
+
+        let mut static_data = self.schema.borrow_mut();
+
+        // validate against the existing services
+        for other in &static_data.services {
+            let other_end_handle = static_data.service_end_handle(other.handle);
+            if other.handle <= end_handle && service.handle <= other_end_handle {
+                bail!(
+                    "service {:?} overlaps with the service at handles {:?} to {:?}",
+                    service.handle,
+                    other.handle,
+                    other_end_handle
+                );
+            }
+        }
+
         let mut attributes = BTreeMap::new();
         let mut attribute_cnt = 0;
 
@@ -98,14 +474,21 @@ impl<T: GattDatastore + ?Sized> GattDatabase<T> {
             attributes.insert(attribute.handle, AttAttributeWithBackingValue { attribute, value })
         };
 
-        let mut characteristics = vec![];
-
         // service definition
         add_attribute(
             AttAttribute {
                 handle: service.handle,
-                type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                permissions: AttPermissions { readable: true, writable: false },
+                type_: if service.primary {
+                    PRIMARY_SERVICE_DECLARATION_UUID
+                } else {
+                    SECONDARY_SERVICE_DECLARATION_UUID
+                },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             AttAttributeBackingValue::Static(
                 GattServiceDeclarationValueBuilder { uuid: UuidBuilder::from(service.type_) }
@@ -113,14 +496,36 @@ impl<T: GattDatastore + ?Sized> GattDatabase<T> {
             ),
         );
 
-        // characteristics
-        for characteristic in service.characteristics {
-            characteristics.push(GattCharacteristicWithHandle {
-                handle: characteristic.handle,
-                type_: characteristic.type_,
-                permissions: characteristic.permissions,
-            });
+        // includes
+        for include in &service.includes {
+            let Some(included) = static_data
+                .services
+                .iter()
+                .find(|included| included.handle == include.service_handle)
+            else {
+                bail!("included service {:?} is not registered", include.service_handle);
+            };
+            // the UUID is only present if it is a 16-bit UUID, from 5.3 3G 3.2
+            let mut value =
+                [include.service_handle, static_data.service_end_handle(included.handle)]
+                    .into_iter()
+                    .flat_map(|handle| handle.0.to_le_bytes())
+                    .collect::<Vec<_>>();
+            if let Ok(uuid) = Uuid16Builder::try_from(included.type_) {
+                value.extend(uuid.data.to_le_bytes());
+            }
+            add_attribute(
+                AttAttribute {
+                    handle: include.handle,
+                    type_: INCLUDE_DECLARATION_UUID,
+                    permissions: AttPermissions::READONLY,
+                },
+                AttAttributeBackingValue::Static(AttAttributeDataChild::RawData(value.into())),
+            );
+        }
 
+        // characteristics
+        for characteristic in &service.characteristics {
             // declaration
             // Recall that we assume the declaration handle is one less than the value
             // handle
@@ -130,19 +535,33 @@ impl<T: GattDatastore + ?Sized> GattDatabase<T> {
                 AttAttribute {
                     handle: declaration_handle,
                     type_: CHARACTERISTIC_UUID,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 AttAttributeBackingValue::Static(
                     GattCharacteristicDeclarationValueBuilder {
                         properties: AttCharacteristicPropertiesBuilder {
                             broadcast: 0,
                             read: characteristic.permissions.readable.into(),
-                            write_without_response: 0,
+                            write_without_response: characteristic
+                                .permissions
+                                .writable_without_response
+                                .into(),
                             write: characteristic.permissions.writable.into(),
-                            notify: 0,
-                            indicate: 0,
-                            authenticated_signed_writes: 0,
-                            extended_properties: 0,
+                            notify: characteristic.notify.into(),
+                            indicate: characteristic.indicate.into(),
+                            authenticated_signed_writes: characteristic
+                                .permissions
+                                .writable_signed
+                                .into(),
+                            extended_properties: characteristic
+                                .extended_properties
+                                .is_some()
+                                .into(),
                         },
                         handle: characteristic.handle.into(),
                         uuid: characteristic.type_.into(),
@@ -158,13 +577,154 @@ impl<T: GattDatastore + ?Sized> GattDatabase<T> {
                     type_: characteristic.type_,
                     permissions: characteristic.permissions,
                 },
-                AttAttributeBackingValue::Dynamic,
+                match (values.remove(&characteristic.handle), characteristic.type_) {
+                    (Some(value), _) => AttAttributeBackingValue::Local(value),
+                    (None, DATABASE_HASH_UUID) => AttAttributeBackingValue::DatabaseHash,
+                    (None, CLIENT_SUPPORTED_FEATURES_UUID) => {
+                        AttAttributeBackingValue::ClientSupportedFeatures
+                    }
+                    (None, _) => AttAttributeBackingValue::Dynamic,
+                },
             );
+
+            // descriptors
+            let writable_auxiliaries = match &characteristic.extended_properties {
+                Some(extended_properties) => {
+                    add_attribute(
+                        AttAttribute {
+                            handle: extended_properties.handle,
+                            type_: CHARACTERISTIC_EXTENDED_PROPERTIES_UUID,
+                            permissions: AttPermissions::READONLY,
+                        },
+                        AttAttributeBackingValue::Static(AttAttributeDataChild::RawData(
+                            extended_properties.to_bits().to_le_bytes().into(),
+                        )),
+                    );
+                    extended_properties.writable_auxiliaries
+                }
+                None => false,
+            };
+            match &characteristic.user_description {
+                Some(user_description) => {
+                    let value = match values.remove(&user_description.handle) {
+                        Some(value) => value,
+                        None if writable_auxiliaries => {
+                            let description =
+                                Rc::new(RefCell::new(user_description.description.clone()));
+                            LocalValue::read_write(
+                                {
+                                    let description = description.clone();
+                                    move || description.borrow().as_bytes().to_vec()
+                                },
+                                move |value| {
+                                    *description.borrow_mut() =
+                                        String::from_utf8(value.to_vec())
+                                            .map_err(|_| AttErrorCode::VALUE_NOT_ALLOWED)?;
+                                    Ok(())
+                                },
+                            )
+                        }
+                        None => {
+                            LocalValue::Static(user_description.description.as_bytes().to_vec())
+                        }
+                    };
+                    add_attribute(
+                        AttAttribute {
+                            handle: user_description.handle,
+                            type_: CHARACTERISTIC_USER_DESCRIPTION_UUID,
+                            permissions: AttPermissions {
+                                readable: true,
+                                writable: writable_auxiliaries,
+                                ..Default::default()
+                            },
+                        },
+                        AttAttributeBackingValue::Local(value),
+                    );
+                }
+                None if writable_auxiliaries
+                    && !characteristic.descriptors.iter().any(|descriptor| {
+                        descriptor.type_ == CHARACTERISTIC_USER_DESCRIPTION_UUID
+                    }) =>
+                {
+                    bail!(
+                        "characteristic {:?} has writable auxiliaries but no user description",
+                        characteristic.handle
+                    );
+                }
+                None => {}
+            }
+
+            let supported = ClientConfiguration {
+                notify: characteristic.notify,
+                indicate: characteristic.indicate,
+            };
+            let mut has_cccd = false;
+            for descriptor in &characteristic.descriptors {
+                match descriptor.type_ {
+                    // the declaration must reflect the extended properties
+                    CHARACTERISTIC_EXTENDED_PROPERTIES_UUID => {
+                        bail!(
+                            "descriptor {descriptor:?} must be declared through its dedicated field"
+                        );
+                    }
+                    CHARACTERISTIC_USER_DESCRIPTION_UUID
+                        if characteristic.user_description.is_some() =>
+                    {
+                        bail!(
+                            "characteristic {:?} has multiple user descriptions",
+                            characteristic.handle
+                        );
+                    }
+                    CLIENT_CHARACTERISTIC_CONFIGURATION_UUID => {
+                        if has_cccd {
+                            bail!("characteristic {:?} has multiple CCCDs", characteristic.handle);
+                        }
+                        has_cccd = true;
+                        add_attribute(
+                            AttAttribute {
+                                handle: descriptor.handle,
+                                type_: descriptor.type_,
+                                // CCCD values are configured through requests
+                                // only, as their writes must be validated
+                                permissions: AttPermissions {
+                                    writable_without_response: false,
+                                    ..descriptor.permissions
+                                },
+                            },
+                            AttAttributeBackingValue::ClientConfiguration {
+                                value_handle: characteristic.handle,
+                                supported,
+                            },
+                        );
+                    }
+                    _ => {
+                        add_attribute(
+                            AttAttribute {
+                                handle: descriptor.handle,
+                                type_: descriptor.type_,
+                                permissions: descriptor.permissions,
+                            },
+                            match values.remove(&descriptor.handle) {
+                                Some(value) => AttAttributeBackingValue::Local(value),
+                                None => AttAttributeBackingValue::Dynamic,
+                            },
+                        );
+                    }
+                }
+            }
+            if (supported.notify || supported.indicate) && !has_cccd {
+                warn!(
+                    "characteristic {:?} supports notifications/indications but has no CCCD",
+                    characteristic.handle
+                );
+            }
         }
 
-        // validate attributes for overlap
-        let mut static_data = self.schema.borrow_mut();
+        if let Some(handle) = values.keys().min() {
+            bail!("local value at {handle:?} is not a characteristic value or descriptor");
+        }
 
+        // validate attributes for overlap
         for handle in attributes.keys() {
             if static_data.attributes.contains_key(handle) {
                 bail!("duplicate handle detected");
@@ -175,17 +735,28 @@ impl<T: GattDatastore + ?Sized> GattDatabase<T> {
         }
 
         // if we made it here, we successfully loaded the new service
-        let service =
-            GattServiceWithHandle { handle: service.handle, type_: service.type_, characteristics };
         static_data.services.push(service);
         static_data.attributes.extend(attributes.into_iter());
+        drop(static_data);
+
+        self.on_schema_changed();
         Ok(())
     }
 
-    /// Remove a previously-added service by service handle
+    /// Remove a previously-added service by service handle. Returns failure if
+    /// the service is included by another service.
     pub fn remove_service_at_handle(&self, service_handle: AttHandle) -> Result<()> {
         let mut static_data = self.schema.borrow_mut();
 
+        if let Some(including) = static_data.services.iter().find(|service| {
+            service.includes.iter().any(|include| include.service_handle == service_handle)
+        }) {
+            bail!(
+                "service at handle {service_handle:?} is included by the service at {:?}, cannot remove",
+                including.handle
+            );
+        }
+
         // remove old service
         static_data
             .services
@@ -202,7 +773,7 @@ impl<T: GattDatastore + ?Sized> GattDatabase<T> {
             .values()
             .find(|attribute| {
                 attribute.attribute.handle > service_handle
-                    && attribute.attribute.type_ == PRIMARY_SERVICE_DECLARATION_UUID
+                    && is_service_declaration(attribute.attribute.type_)
             })
             .map(|service| service.attribute.handle);
 
@@ -212,120 +783,743 @@ impl<T: GattDatastore + ?Sized> GattDatabase<T> {
                 && next_service_handle.map(|x| *curr_handle < x).unwrap_or(true))
         });
 
+        // forget subscriptions to removed characteristics
+        for state in self.client_configurations.borrow_mut().values_mut() {
+            state
+                .configurations
+                .retain(|handle, _| static_data.supported_client_configuration(*handle).is_some());
+        }
+        drop(static_data);
+
+        self.on_schema_changed();
         Ok(())
     }
 
+    /// The handle of the last attribute of the service at the given handle,
+    /// e.g. to indicate the range affected by a change to that service
+    pub fn get_service_end_handle(&self, service_handle: AttHandle) -> Option<AttHandle> {
+        let schema = self.schema.borrow();
+        if !schema.services.iter().any(|service| service.handle == service_handle) {
+            return None;
+        }
+        Some(schema.service_end_handle(service_handle))
+    }
+
+    /// The handle and type of each primary service, e.g. to advertise them
+    /// over SDP
+    pub fn get_primary_services(&self) -> Vec<(AttHandle, Uuid)> {
+        self.schema
+            .borrow()
+            .services
+            .iter()
+            .filter(|service| service.primary)
+            .map(|service| (service.handle, service.type_))
+            .collect()
+    }
+
+    /// The handle of the Service Changed characteristic value, if the Generic
+    /// Attribute service is registered
+    pub fn get_service_changed_handle(&self) -> Option<AttHandle> {
+        self.schema.borrow().service_changed_handle()
+    }
+
+    /// After the schema changes, every client (connected or bonded) must
+    /// resynchronize its attribute cache, so becomes change-unaware
+    fn on_schema_changed(&self) {
+        for state in self.client_configurations.borrow_mut().values_mut() {
+            state.caching.change_unaware = true;
+            state.out_of_sync_reported = false;
+        }
+        for caching in self.bonded_caching_states.borrow_mut().values_mut() {
+            caching.change_unaware = true;
+        }
+    }
+
     /// Clear all services
     pub fn clear_all_services(&self) {
         *self.schema.borrow_mut() = Default::default();
+        for state in self.client_configurations.borrow_mut().values_mut() {
+            state.configurations.clear();
+        }
+        self.on_schema_changed();
     }
 
-    /// Generate an impl AttDatabase from a backing GattDatabase, associated
-    /// with a given connection.
-    pub fn get_att_database(self: &Rc<Self>, conn_id: ConnectionId) -> AttDatabaseImpl<T> {
-        AttDatabaseImpl { gatt_db: self.clone(), conn_id }
-    }
-}
+    /// Indicate that the peer on a connection is bonded with the given
+    /// identity. Restores any CCCD values previously stored for this peer
+    /// (reporting them to the datastore as if the client wrote them), and
+    /// persists subsequent changes.
+    pub fn on_bonded_peer(&self, conn_id: ConnectionId, peer: PeerIdentity) -> Result<()> {
+        let stored = match &self.cccd_store {
+            Some(cccd_store) => cccd_store.load(peer)?,
+            None => Default::default(),
+        };
 
-/// An implementation of AttDatabase wrapping an underlying GattDatabase
-pub struct AttDatabaseImpl<T: ?Sized> {
-    gatt_db: Rc<GattDatabase<T>>,
-    conn_id: ConnectionId,
-}
+        let schema = self.schema.borrow();
+        let mut client_configurations = self.client_configurations.borrow_mut();
+        let state = client_configurations.entry(conn_id).or_default();
+        state.bonded_peer = Some(peer);
+
+        // features cannot be disabled, and any change since the last connection
+        // must be caught up on
+        if let Some(caching) = self.bonded_caching_states.borrow().get(&peer) {
+            state.caching.supported_features.robust_caching |=
+                caching.supported_features.robust_caching;
+            state.caching.change_unaware |= caching.change_unaware;
+        }
 
-#[async_trait(?Send)]
-impl<T> AttDatabase for AttDatabaseImpl<T>
-where
-    T: GattDatastore + ?Sized,
-{
-    async fn read_attribute(
-        &self,
-        handle: AttHandle,
-    ) -> Result<AttAttributeDataChild, AttErrorCode> {
-        {
-            let services = self.gatt_db.schema.borrow();
-            let Some(attr) = services.attributes.get(&handle) else {
-                return Err(AttErrorCode::INVALID_HANDLE);
-            };
-            if !attr.attribute.permissions.readable {
-                return Err(AttErrorCode::READ_NOT_PERMITTED);
+        // persist anything configured before the peer was known to be bonded
+        if let Some(cccd_store) = &self.cccd_store {
+            for (handle, configuration) in &state.configurations {
+                cccd_store.store(peer, *handle, *configuration)?;
             }
-            match &attr.value {
-                AttAttributeBackingValue::Static(val) => return Ok(val.clone()),
-                AttAttributeBackingValue::Dynamic => { /* fallthrough */ }
-            };
         }
 
-        self.gatt_db.datastore.read_characteristic(self.conn_id, handle).await
-    }
-
-    async fn write_attribute(
-        &self,
-        handle: AttHandle,
-        data: AttAttributeDataView<'_>,
-    ) -> Result<(), AttErrorCode> {
-        {
-            // block needed to drop the RefCell before the async point
-            let services = self.gatt_db.schema.borrow();
-            let Some(attr) = services.attributes.get(&handle) else {
-                return Err(AttErrorCode::INVALID_HANDLE);
-            };
-            if !attr.attribute.permissions.writable {
-                return Err(AttErrorCode::WRITE_NOT_PERMITTED);
+        // restore stored values for characteristics that still support them
+        let mut restored = vec![];
+        for (value_handle, configuration) in stored {
+            if state.configurations.contains_key(&value_handle) {
+                continue;
+            }
+            match schema.client_configuration_descriptor(value_handle) {
+                Some((handle, supported)) if configuration.is_subset_of(supported) => {
+                    state.configurations.insert(value_handle, configuration);
+                    restored.push((handle, configuration));
+                }
+                // the stored value may belong to a different server
+                _ => {}
             }
         }
+        drop(client_configurations);
+        drop(schema);
 
-        self.gatt_db.datastore.write_characteristic(self.conn_id, handle, data).await
+        for (handle, configuration) in restored {
+            self.report_client_configuration(conn_id, handle, configuration);
+        }
+
+        Ok(())
     }
 
-    fn list_attributes(&self) -> Vec<AttAttribute> {
-        self.gatt_db.schema.borrow().attributes.values().map(|attr| attr.attribute).collect()
+    /// The bonded peer on the specified connection, if it is known to be bonded
+    pub fn get_bonded_peer(&self, conn_id: ConnectionId) -> Option<PeerIdentity> {
+        self.client_configurations.borrow().get(&conn_id).and_then(|state| state.bonded_peer)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use tokio::{join, task::spawn_local};
+    /// Indicate that a previously bonded peer is no longer bonded. Any of its
+    /// connections stop persisting CCCD values.
+    pub fn on_bond_removed(&self, peer: PeerIdentity) {
+        for state in self.client_configurations.borrow_mut().values_mut() {
+            if state.bonded_peer == Some(peer) {
+                state.bonded_peer = None;
+            }
+        }
+        self.bonded_caching_states.borrow_mut().remove(&peer);
+    }
 
-    use crate::{
-        gatt::mocks::mock_datastore::{MockDatastore, MockDatastoreEvents},
-        packets::Packet,
-        utils::{
-            packet::{build_att_data, build_view_or_crash},
-            task::block_on_locally,
-        },
-    };
+    /// Update the security state of a connection, against which the security
+    /// requirements of each access are checked. Connections are initially
+    /// unencrypted and unauthorized.
+    pub fn set_connection_security(&self, conn_id: ConnectionId, security: ConnectionSecurity) {
+        self.connection_security.borrow_mut().insert(conn_id, security);
+    }
 
-    use super::*;
+    /// Start tracking the state of a new connection, whose client is initially
+    /// change-aware (5.3 3G 2.5.2.1)
+    pub fn add_connection(&self, conn_id: ConnectionId) {
+        self.client_configurations.borrow_mut().insert(conn_id, Default::default());
+    }
 
-    const SERVICE_HANDLE: AttHandle = AttHandle(1);
-    const SERVICE_TYPE: Uuid = Uuid::new(0x1234);
+    /// Drop the CCCD and security state of a closed connection. Values of
+    /// bonded peers remain in the CccdStore, and their caching state is kept
+    /// until their next connection.
+    pub fn remove_connection(&self, conn_id: ConnectionId) {
+        if let Some(state) = self.client_configurations.borrow_mut().remove(&conn_id) {
+            if let Some(peer) = state.bonded_peer {
+                self.bonded_caching_states.borrow_mut().insert(peer, state.caching);
+            }
+        }
+        self.connection_security.borrow_mut().remove(&conn_id);
+    }
 
-    const CHARACTERISTIC_DECLARATION_HANDLE: AttHandle = AttHandle(2);
-    const CHARACTERISTIC_VALUE_HANDLE: AttHandle = AttHandle(3);
-    const CHARACTERISTIC_TYPE: Uuid = Uuid::new(0x5678);
+    /// Whether the client on the given connection has not yet synchronized its
+    /// attribute cache since the database last changed
+    pub fn is_change_unaware(&self, conn_id: ConnectionId) -> bool {
+        self.client_configurations
+            .borrow()
+            .get(&conn_id)
+            .map(|state| state.caching.change_unaware)
+            .unwrap_or(false)
+    }
 
-    const CONN_ID: ConnectionId = ConnectionId(1);
+    /// Indicate that the client on the given connection confirmed a Service
+    /// Changed indication, so is now change-aware
+    pub fn on_service_changed_confirmed(&self, conn_id: ConnectionId) {
+        self.mark_change_aware(conn_id);
+    }
 
-    #[test]
-    fn test_read_empty_db() {
-        let (gatt_datastore, _) = MockDatastore::new();
-        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
-        let att_db = gatt_db.get_att_database(CONN_ID);
+    /// Get the features enabled by the client on the given connection
+    pub fn get_client_supported_features(&self, conn_id: ConnectionId) -> ClientSupportedFeatures {
+        self.client_configurations
+            .borrow()
+            .get(&conn_id)
+            .map(|state| state.caching.supported_features)
+            .unwrap_or_default()
+    }
 
-        let resp = tokio_test::block_on(att_db.read_attribute(AttHandle(1)));
+    fn mark_change_aware(&self, conn_id: ConnectionId) {
+        if let Some(state) = self.client_configurations.borrow_mut().get_mut(&conn_id) {
+            state.caching.change_unaware = false;
+            state.out_of_sync_reported = false;
+        }
+    }
 
-        assert_eq!(resp, Err(AttErrorCode::INVALID_HANDLE))
+    fn write_client_supported_features(
+        &self,
+        conn_id: ConnectionId,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        let Some(bits) = data.get_raw_payload().next() else {
+            return Err(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH);
+        };
+        let features = ClientSupportedFeatures::from_bits(bits);
+        let mut client_configurations = self.client_configurations.borrow_mut();
+        let state = client_configurations.entry(conn_id).or_default();
+        // as per 5.3 3G 7.2, a client may not disable a feature it enabled
+        if state.caching.supported_features.robust_caching && !features.robust_caching {
+            return Err(AttErrorCode::VALUE_NOT_ALLOWED);
+        }
+        state.caching.supported_features = features;
+        Ok(())
     }
 
-    #[test]
-    fn test_single_service() {
-        let (gatt_datastore, _) = MockDatastore::new();
-        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
-        gatt_db
+    /// Robust caching (5.3 3G 2.5.2.1): a change-unaware client using it gets
+    /// DATABASE_OUT_OF_SYNC for its first request, and becomes change-aware
+    /// on the next one
+    fn check_cache_in_sync(&self, conn_id: ConnectionId) -> Result<(), AttErrorCode> {
+        let mut client_configurations = self.client_configurations.borrow_mut();
+        let Some(state) = client_configurations.get_mut(&conn_id) else {
+            return Ok(());
+        };
+        if !state.caching.supported_features.robust_caching || !state.caching.change_unaware {
+            return Ok(());
+        }
+        if state.out_of_sync_reported {
+            state.caching.change_unaware = false;
+            state.out_of_sync_reported = false;
+            Ok(())
+        } else {
+            state.out_of_sync_reported = true;
+            Err(AttErrorCode::DATABASE_OUT_OF_SYNC)
+        }
+    }
+
+    fn is_cache_in_sync(&self, conn_id: ConnectionId) -> bool {
+        self.client_configurations
+            .borrow()
+            .get(&conn_id)
+            .map(|state| {
+                !state.caching.supported_features.robust_caching || !state.caching.change_unaware
+            })
+            .unwrap_or(true)
+    }
+
+    /// Get the security of the given connection, as last reported through
+    /// set_connection_security
+    pub fn get_connection_security(&self, conn_id: ConnectionId) -> ConnectionSecurity {
+        self.connection_security.borrow().get(&conn_id).copied().unwrap_or_default()
+    }
+
+    /// Get the subscriptions of the client on the given connection to the
+    /// characteristic with the given value handle
+    pub fn get_client_configuration(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+    ) -> ClientConfiguration {
+        self.client_configurations
+            .borrow()
+            .get(&conn_id)
+            .and_then(|state| state.configurations.get(&handle))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn write_client_configuration(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        value_handle: AttHandle,
+        supported: ClientConfiguration,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        let value = data.get_raw_payload().collect::<Vec<_>>();
+        let configuration = parse_client_configuration(supported, &value)?;
+        self.set_client_configuration(conn_id, handle, value_handle, configuration);
+        Ok(())
+    }
+
+    fn prepare_write_client_configuration(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        value_handle: AttHandle,
+        supported: ClientConfiguration,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        let mut client_configurations = self.client_configurations.borrow_mut();
+        let state = client_configurations.entry(conn_id).or_default();
+
+        // the write applies on top of any write already prepared for this CCCD
+        let current = match state.prepared_configurations.get(&value_handle) {
+            Some((_, configuration)) => *configuration,
+            None => state.configurations.get(&value_handle).copied().unwrap_or_default(),
+        };
+        let mut value = current.to_bits().to_le_bytes().to_vec();
+        if offset > value.len() {
+            return Err(AttErrorCode::INVALID_OFFSET);
+        }
+        let data = data.get_raw_payload().collect::<Vec<_>>();
+        let end = value.len().min(offset + data.len());
+        value.splice(offset..end, data);
+
+        let configuration = parse_client_configuration(supported, &value)?;
+        state.prepared_configurations.insert(value_handle, (handle, configuration));
+        Ok(())
+    }
+
+    fn execute_write_client_configurations(
+        &self,
+        conn_id: ConnectionId,
+        decision: TransactionDecision,
+    ) {
+        let prepared = self
+            .client_configurations
+            .borrow_mut()
+            .get_mut(&conn_id)
+            .map(|state| std::mem::take(&mut state.prepared_configurations))
+            .unwrap_or_default();
+        if decision == TransactionDecision::Execute {
+            for (value_handle, (handle, configuration)) in prepared {
+                self.set_client_configuration(conn_id, handle, value_handle, configuration);
+            }
+        }
+    }
+
+    /// Apply a CCCD value written by the client, then report it to the
+    /// datastore so the upper layer can track its subscribers
+    fn set_client_configuration(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        value_handle: AttHandle,
+        configuration: ClientConfiguration,
+    ) {
+        let mut client_configurations = self.client_configurations.borrow_mut();
+        let state = client_configurations.entry(conn_id).or_default();
+        if configuration == ClientConfiguration::default() {
+            state.configurations.remove(&value_handle);
+        } else {
+            state.configurations.insert(value_handle, configuration);
+        }
+
+        if let (Some(cccd_store), Some(peer)) = (&self.cccd_store, state.bonded_peer) {
+            if let Err(err) = cccd_store.store(peer, value_handle, configuration) {
+                error!("failed to persist CCCD value for {peer:?}: {err:?}");
+            }
+        }
+        drop(client_configurations);
+
+        self.report_client_configuration(conn_id, handle, configuration);
+    }
+
+    /// Report the value of the CCCD at the given handle to the datastore, as a
+    /// write without response
+    fn report_client_configuration(
+        &self,
+        conn_id: ConnectionId,
+        handle: AttHandle,
+        configuration: ClientConfiguration,
+    ) {
+        let value = configuration.to_bits().to_le_bytes();
+        match OwnedAttAttributeDataView::try_parse(value.into()) {
+            Ok(data) => self.datastore.write_no_response_descriptor(conn_id, handle, data.view()),
+            Err(err) => error!("failed to encode CCCD value {configuration:?}: {err:?}"),
+        }
+    }
+
+    /// Generate an impl AttDatabase from a backing GattDatabase, associated
+    /// with a given connection.
+    pub fn get_att_database(self: &Rc<Self>, conn_id: ConnectionId) -> AttDatabaseImpl<T> {
+        AttDatabaseImpl { gatt_db: self.clone(), conn_id }
+    }
+}
+
+/// Decode a CCCD value, rejecting it if the characteristic does not support
+/// the requested subscriptions
+fn parse_client_configuration(
+    supported: ClientConfiguration,
+    value: &[u8],
+) -> Result<ClientConfiguration, AttErrorCode> {
+    let Ok(bits) = <[u8; 2]>::try_from(value) else {
+        return Err(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH);
+    };
+    ClientConfiguration::from_bits(u16::from_le_bytes(bits))
+        .filter(|configuration| configuration.is_subset_of(supported))
+        .ok_or(AttErrorCode::CCCD_IMPROPERLY_CONFIGURED)
+}
+
+/// An implementation of AttDatabase wrapping an underlying GattDatabase
+pub struct AttDatabaseImpl<T: ?Sized> {
+    gatt_db: Rc<GattDatabase<T>>,
+    conn_id: ConnectionId,
+}
+
+impl<T> AttDatabaseImpl<T>
+where
+    T: GattDatastore + ?Sized,
+{
+    /// Read the value of an attribute held by the GattDatabase, once the
+    /// connection is permitted to read it. Returns None for values held by the
+    /// datastore.
+    fn read_local_attribute(
+        &self,
+        handle: AttHandle,
+    ) -> Result<Option<AttAttributeDataChild>, AttErrorCode> {
+        let services = self.gatt_db.schema.borrow();
+        let Some(attr) = services.attributes.get(&handle) else {
+            return Err(AttErrorCode::INVALID_HANDLE);
+        };
+        if !attr.attribute.permissions.readable {
+            return Err(AttErrorCode::READ_NOT_PERMITTED);
+        }
+        attr.attribute
+            .permissions
+            .read_security
+            .check(self.gatt_db.get_connection_security(self.conn_id))?;
+        match &attr.value {
+            AttAttributeBackingValue::Static(val) => Ok(Some(val.clone())),
+            AttAttributeBackingValue::ClientConfiguration { value_handle, .. } => Ok(Some(
+                GattClientCharacteristicConfigurationBuilder::from(
+                    self.gatt_db.get_client_configuration(self.conn_id, *value_handle),
+                )
+                .into(),
+            )),
+            AttAttributeBackingValue::DatabaseHash => {
+                let hash = services.database_hash();
+                drop(services);
+                // reading the hash is how a client resynchronizes its cache
+                self.gatt_db.mark_change_aware(self.conn_id);
+                Ok(Some(AttAttributeDataChild::RawData(hash.into())))
+            }
+            AttAttributeBackingValue::ClientSupportedFeatures => {
+                Ok(Some(AttAttributeDataChild::RawData(
+                    [self.gatt_db.get_client_supported_features(self.conn_id).to_bits()].into(),
+                )))
+            }
+            AttAttributeBackingValue::Local(value) => {
+                // the value may be produced by arbitrary code, so must not hold the schema
+                let value = value.clone();
+                drop(services);
+                Ok(Some(AttAttributeDataChild::RawData(value.read().into())))
+            }
+            AttAttributeBackingValue::Dynamic => Ok(None),
+        }
+    }
+
+    /// Handle an ATT_WRITE_CMD or a verified ATT_SIGNED_WRITE_CMD, which are
+    /// permitted by distinct characteristic properties
+    fn write_command(&self, handle: AttHandle, data: AttAttributeDataView<'_>, signed: bool) {
+        {
+            let services = self.gatt_db.schema.borrow();
+            let Some(attr) = services.attributes.get(&handle) else {
+                warn!("cannot find handle {handle:?}, dropping write command");
+                return;
+            };
+            if signed && !attr.attribute.permissions.writable_signed {
+                warn!("{handle:?} does not accept signed writes, dropping write command");
+                return;
+            }
+            if !signed && !attr.attribute.permissions.writable_without_response {
+                warn!("{handle:?} is not writable without response, dropping write command");
+                return;
+            }
+            if let Err(err) = attr
+                .attribute
+                .permissions
+                .write_security
+                .check(self.gatt_db.get_connection_security(self.conn_id))
+            {
+                warn!(
+                    "insufficient security to write {handle:?} ({err:?}), dropping write command"
+                );
+                return;
+            }
+            if let AttAttributeBackingValue::Local(value) = &attr.value {
+                let value = value.clone();
+                drop(services);
+                if let Err(err) = value.write(&data.get_raw_payload().collect::<Vec<_>>()) {
+                    warn!("failed to write {handle:?} ({err:?}), dropping write command");
+                }
+                return;
+            }
+            if !matches!(attr.value, AttAttributeBackingValue::Dynamic) {
+                warn!("{handle:?} is owned by the GattDatabase, dropping write command");
+                return;
+            }
+        }
+
+        self.gatt_db.datastore.write_no_response_characteristic(self.conn_id, handle, data);
+    }
+}
+
+#[async_trait(?Send)]
+impl<T> AttDatabase for AttDatabaseImpl<T>
+where
+    T: GattDatastore + ?Sized,
+{
+    async fn read_attribute(
+        &self,
+        handle: AttHandle,
+    ) -> Result<AttAttributeDataChild, AttErrorCode> {
+        match self.read_local_attribute(handle)? {
+            Some(value) => Ok(value),
+            None => {
+                self.gatt_db.datastore.read_characteristic(self.conn_id, handle, 0, false).await
+            }
+        }
+    }
+
+    async fn read_attribute_blob(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+    ) -> Result<AttAttributeDataChild, AttErrorCode> {
+        // values held by the datastore are read from the offset, so that long
+        // values are not fetched again in full for each blob
+        match self.read_local_attribute(handle)? {
+            Some(value) => slice_attribute_value(value, offset),
+            None => {
+                self.gatt_db.datastore.read_characteristic(self.conn_id, handle, offset, true).await
+            }
+        }
+    }
+
+    async fn write_attribute(
+        &self,
+        handle: AttHandle,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        {
+            // block needed to drop the RefCell before the async point
+            let services = self.gatt_db.schema.borrow();
+            let Some(attr) = services.attributes.get(&handle) else {
+                return Err(AttErrorCode::INVALID_HANDLE);
+            };
+            if !attr.attribute.permissions.writable {
+                return Err(AttErrorCode::WRITE_NOT_PERMITTED);
+            }
+            attr.attribute
+                .permissions
+                .write_security
+                .check(self.gatt_db.get_connection_security(self.conn_id))?;
+            if let AttAttributeBackingValue::ClientConfiguration { value_handle, supported } =
+                attr.value
+            {
+                return self.gatt_db.write_client_configuration(
+                    self.conn_id,
+                    handle,
+                    value_handle,
+                    supported,
+                    data,
+                );
+            }
+            if let AttAttributeBackingValue::ClientSupportedFeatures = attr.value {
+                return self.gatt_db.write_client_supported_features(self.conn_id, data);
+            }
+            if let AttAttributeBackingValue::Local(value) = &attr.value {
+                let value = value.clone();
+                drop(services);
+                return value.write(&data.get_raw_payload().collect::<Vec<_>>());
+            }
+        }
+
+        self.gatt_db.datastore.write_characteristic(self.conn_id, handle, data).await
+    }
+
+    async fn prepare_write_attribute(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        {
+            // block needed to drop the RefCell before the async point
+            let services = self.gatt_db.schema.borrow();
+            let Some(attr) = services.attributes.get(&handle) else {
+                return Err(AttErrorCode::INVALID_HANDLE);
+            };
+            if !attr.attribute.permissions.writable {
+                return Err(AttErrorCode::WRITE_NOT_PERMITTED);
+            }
+            attr.attribute
+                .permissions
+                .write_security
+                .check(self.gatt_db.get_connection_security(self.conn_id))?;
+            if let AttAttributeBackingValue::ClientConfiguration { value_handle, supported } =
+                attr.value
+            {
+                return self.gatt_db.prepare_write_client_configuration(
+                    self.conn_id,
+                    handle,
+                    value_handle,
+                    supported,
+                    offset,
+                    data,
+                );
+            }
+            if let AttAttributeBackingValue::ClientSupportedFeatures = attr.value {
+                // features must be enabled atomically, so cannot be queued
+                return Err(AttErrorCode::REQUEST_NOT_SUPPORTED);
+            }
+            if let AttAttributeBackingValue::Local(_) = attr.value {
+                // local values are written synchronously, so have no write queue
+                return Err(AttErrorCode::REQUEST_NOT_SUPPORTED);
+            }
+        }
+
+        self.gatt_db
+            .datastore
+            .prepare_write_characteristic(self.conn_id, handle, offset, data)
+            .await
+    }
+
+    fn write_no_response_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        self.write_command(handle, data, false)
+    }
+
+    fn write_signed_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        self.write_command(handle, data, true)
+    }
+
+    async fn execute_write(&self, decision: TransactionDecision) -> Result<(), AttErrorCode> {
+        let result = self.gatt_db.datastore.execute_write(self.conn_id, decision).await;
+        // prepared CCCD values are only applied if the upper layer accepted its
+        // own prepared writes too
+        self.gatt_db.execute_write_client_configurations(
+            self.conn_id,
+            if result.is_ok() { decision } else { TransactionDecision::Cancel },
+        );
+        result
+    }
+
+    fn list_attributes(&self) -> Vec<AttAttribute> {
+        self.gatt_db.schema.borrow().attributes.values().map(|attr| attr.attribute).collect()
+    }
+
+    fn check_cache_in_sync(&self) -> Result<(), AttErrorCode> {
+        self.gatt_db.check_cache_in_sync(self.conn_id)
+    }
+
+    fn is_cache_in_sync(&self) -> bool {
+        self.gatt_db.is_cache_in_sync(self.conn_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{join, sync::mpsc::UnboundedReceiver, task::spawn_local};
+
+    use crate::{
+        gatt::{
+            mocks::mock_datastore::{MockDatastore, MockDatastoreEvents},
+            server::{cccd_store::FileCccdStore, services::gatt::generic_attribute_service},
+        },
+        packets::{GattCharacteristicDeclarationValueView, Packet},
+        utils::{
+            packet::{build_att_data, build_view_or_crash},
+            task::block_on_locally,
+        },
+    };
+
+    use super::*;
+
+    const SERVICE_HANDLE: AttHandle = AttHandle(1);
+    const SERVICE_TYPE: Uuid = Uuid::new(0x1234);
+
+    const CHARACTERISTIC_DECLARATION_HANDLE: AttHandle = AttHandle(2);
+    const CHARACTERISTIC_VALUE_HANDLE: AttHandle = AttHandle(3);
+    const CHARACTERISTIC_TYPE: Uuid = Uuid::new(0x5678);
+
+    const CCCD_HANDLE: AttHandle = AttHandle(4);
+
+    const CONN_ID: ConnectionId = ConnectionId(1);
+    const ANOTHER_CONN_ID: ConnectionId = ConnectionId(2);
+    const PEER: PeerIdentity = PeerIdentity([1, 2, 3, 4, 5, 6]);
+
+    const NOTIFY: ClientConfiguration = ClientConfiguration { notify: true, indicate: false };
+
+    fn make_service_with_cccd(notify: bool, indicate: bool) -> GattServiceWithHandle {
+        GattServiceWithHandle {
+            handle: SERVICE_HANDLE,
+            type_: SERVICE_TYPE,
+            primary: true,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: CHARACTERISTIC_VALUE_HANDLE,
+                type_: CHARACTERISTIC_TYPE,
+                permissions: AttPermissions::READONLY,
+                notify,
+                indicate,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![GattDescriptorWithHandle {
+                    handle: CCCD_HANDLE,
+                    type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        ..Default::default()
+                    },
+                }],
+            }],
+        }
+    }
+
+    fn write_cccd(att_db: &impl AttDatabase, value: &[u8]) -> Result<(), AttErrorCode> {
+        let data = build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(
+            value.to_vec().into_boxed_slice(),
+        )));
+        tokio_test::block_on(att_db.write_attribute(CCCD_HANDLE, data.view()))
+    }
+
+    fn make_cccd_store(name: &str) -> Rc<FileCccdStore> {
+        let directory =
+            std::env::temp_dir().join(format!("gatt_database_test_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        Rc::new(FileCccdStore::new(directory))
+    }
+
+    #[test]
+    fn test_read_empty_db() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        let resp = tokio_test::block_on(att_db.read_attribute(AttHandle(1)));
+
+        assert_eq!(resp, Err(AttErrorCode::INVALID_HANDLE))
+    }
+
+    #[test]
+    fn test_single_service() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![],
             })
             .unwrap();
@@ -339,7 +1533,12 @@ mod test {
             vec![AttAttribute {
                 handle: SERVICE_HANDLE,
                 type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                permissions: AttPermissions { readable: true, writable: false }
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                }
             }]
         );
         assert_eq!(
@@ -360,10 +1559,22 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: AttHandle(1),
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: AttHandle(3),
                     type_: CHARACTERISTIC_TYPE,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -371,10 +1582,22 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: AttHandle(4),
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: AttHandle(6),
                     type_: CHARACTERISTIC_TYPE,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -382,10 +1605,22 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: AttHandle(7),
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: AttHandle(9),
                     type_: CHARACTERISTIC_TYPE,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -405,7 +1640,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(1),
                 type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                permissions: AttPermissions { readable: true, writable: false }
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                }
             }
         );
         assert_eq!(
@@ -413,7 +1653,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(7),
                 type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                permissions: AttPermissions { readable: true, writable: false }
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                }
             }
         );
     }
@@ -426,10 +1671,22 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: CHARACTERISTIC_VALUE_HANDLE,
                     type_: CHARACTERISTIC_TYPE,
-                    permissions: AttPermissions { readable: true, writable: true },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -446,7 +1703,12 @@ mod test {
             AttAttribute {
                 handle: CHARACTERISTIC_DECLARATION_HANDLE,
                 type_: CHARACTERISTIC_UUID,
-                permissions: AttPermissions { readable: true, writable: false }
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                }
             }
         );
         assert_eq!(
@@ -454,7 +1716,12 @@ mod test {
             AttAttribute {
                 handle: CHARACTERISTIC_VALUE_HANDLE,
                 type_: CHARACTERISTIC_TYPE,
-                permissions: AttPermissions { readable: true, writable: true }
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: true,
+                    writable_without_response: false,
+                    ..Default::default()
+                }
             }
         );
 
@@ -488,10 +1755,17 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: CHARACTERISTIC_VALUE_HANDLE,
                     type_: CHARACTERISTIC_TYPE,
                     permissions: AttPermissions::READONLY,
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -505,6 +1779,8 @@ mod test {
                     let MockDatastoreEvents::ReadCharacteristic(
                     CONN_ID,
                     CHARACTERISTIC_VALUE_HANDLE,
+                    0,
+                    false,
                     reply,
                 ) = data_evts.recv().await.unwrap() else {
                     unreachable!()
@@ -528,10 +1804,22 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: CHARACTERISTIC_VALUE_HANDLE,
                     type_: CHARACTERISTIC_TYPE,
-                    permissions: AttPermissions { readable: false, writable: false },
+                    permissions: AttPermissions {
+                        readable: false,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -551,10 +1839,22 @@ mod test {
         let result = gatt_db.add_service_with_handles(GattServiceWithHandle {
             handle: SERVICE_HANDLE,
             type_: SERVICE_TYPE,
+            primary: true,
+            includes: vec![],
             characteristics: vec![GattCharacteristicWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: CHARACTERISTIC_TYPE,
-                permissions: AttPermissions { readable: false, writable: true },
+                permissions: AttPermissions {
+                    readable: false,
+                    writable: true,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
             }],
         });
 
@@ -570,6 +1870,8 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![],
             })
             .unwrap();
@@ -577,6 +1879,8 @@ mod test {
         let result = gatt_db.add_service_with_handles(GattServiceWithHandle {
             handle: SERVICE_HANDLE,
             type_: SERVICE_TYPE,
+            primary: true,
+            includes: vec![],
             characteristics: vec![],
         });
 
@@ -592,6 +1896,8 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![],
             })
             .unwrap();
@@ -615,10 +1921,22 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: CHARACTERISTIC_VALUE_HANDLE,
                     type_: CHARACTERISTIC_TYPE,
-                    permissions: AttPermissions { readable: false, writable: true },
+                    permissions: AttPermissions {
+                        readable: false,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -664,10 +1982,22 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: CHARACTERISTIC_VALUE_HANDLE,
                     type_: CHARACTERISTIC_TYPE,
-                    permissions: AttPermissions { readable: false, writable: true },
+                    permissions: AttPermissions {
+                        readable: false,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -701,10 +2031,17 @@ mod test {
             .add_service_with_handles(GattServiceWithHandle {
                 handle: SERVICE_HANDLE,
                 type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
                 characteristics: vec![GattCharacteristicWithHandle {
                     handle: CHARACTERISTIC_VALUE_HANDLE,
                     type_: CHARACTERISTIC_TYPE,
                     permissions: AttPermissions::READONLY,
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
                 }],
             })
             .unwrap();
@@ -719,4 +2056,1532 @@ mod test {
 
         assert_eq!(characteristic_value, Err(AttErrorCode::WRITE_NOT_PERMITTED));
     }
+
+    #[test]
+    fn test_characteristic_declaration_notify_indicate() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, true)).unwrap();
+
+        let characteristic_decl = tokio_test::block_on(
+            gatt_db.get_att_database(CONN_ID).read_attribute(CHARACTERISTIC_DECLARATION_HANDLE),
+        );
+
+        let Ok(AttAttributeDataChild::GattCharacteristicDeclarationValue(decl)) =
+            characteristic_decl
+        else {
+            unreachable!()
+        };
+        assert_eq!(decl.properties.notify, 1);
+        assert_eq!(decl.properties.indicate, 1);
+    }
+
+    #[test]
+    fn test_cccd_attribute() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        let attrs = att_db.list_attributes();
+        let value = tokio_test::block_on(att_db.read_attribute(CCCD_HANDLE));
+
+        // assert: the CCCD takes the permissions of its descriptor, and is initially unset
+        assert_eq!(
+            attrs[3],
+            AttAttribute {
+                handle: CCCD_HANDLE,
+                type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: true,
+                    writable_without_response: false,
+                    ..Default::default()
+                }
+            }
+        );
+        assert_eq!(
+            value,
+            Ok(GattClientCharacteristicConfigurationBuilder { notification: 0, indication: 0 }
+                .into())
+        );
+    }
+
+    #[test]
+    fn test_cccd_write_is_per_connection() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        // act
+        let res = write_cccd(&gatt_db.get_att_database(CONN_ID), &[0x01, 0x00]);
+
+        // assert: only the writing connection is subscribed, and the datastore is told
+        assert_eq!(res, Ok(()));
+        assert_eq!(gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE), NOTIFY);
+        assert_eq!(
+            gatt_db.get_client_configuration(ANOTHER_CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            ClientConfiguration::default()
+        );
+        assert_eq!(
+            tokio_test::block_on(gatt_db.get_att_database(CONN_ID).read_attribute(CCCD_HANDLE)),
+            Ok(GattClientCharacteristicConfigurationBuilder { notification: 1, indication: 0 }
+                .into())
+        );
+        let Ok(MockDatastoreEvents::WriteNoResponseDescriptor(CONN_ID, CCCD_HANDLE, data)) =
+            data_evts.try_recv()
+        else {
+            unreachable!()
+        };
+        assert_eq!(data.view().get_raw_payload().collect::<Vec<_>>(), vec![0x01, 0x00]);
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_cccd_write_not_permitted() {
+        // arrange: a CCCD whose descriptor is read-only
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_service_with_cccd(true, false);
+        service.characteristics[0].descriptors[0].permissions = AttPermissions::READONLY;
+        gatt_db.add_service_with_handles(service).unwrap();
+
+        // act
+        let res = write_cccd(&gatt_db.get_att_database(CONN_ID), &[0x01, 0x00]);
+
+        // assert
+        assert_eq!(res, Err(AttErrorCode::WRITE_NOT_PERMITTED));
+        assert_eq!(
+            gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            ClientConfiguration::default()
+        );
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_cccd_write_rejects_unsupported_values() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // indications are not supported
+        assert_eq!(
+            write_cccd(&att_db, &[0x02, 0x00]),
+            Err(AttErrorCode::CCCD_IMPROPERLY_CONFIGURED)
+        );
+        // reserved bits are set
+        assert_eq!(
+            write_cccd(&att_db, &[0x05, 0x00]),
+            Err(AttErrorCode::CCCD_IMPROPERLY_CONFIGURED)
+        );
+        // wrong length
+        assert_eq!(write_cccd(&att_db, &[0x01]), Err(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH));
+        assert_eq!(
+            gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            ClientConfiguration::default()
+        );
+    }
+
+    #[test]
+    fn test_cccd_cleared_on_disconnect_if_not_bonded() {
+        // arrange
+        let (gatt_datastore, _data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        write_cccd(&gatt_db.get_att_database(CONN_ID), &[0x01, 0x00]).unwrap();
+
+        // act
+        gatt_db.remove_connection(CONN_ID);
+
+        // assert
+        assert_eq!(
+            gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            ClientConfiguration::default()
+        );
+    }
+
+    #[test]
+    fn test_cccd_written_before_bonding_is_persisted() {
+        // arrange: write before the peer is known to be bonded
+        let (gatt_datastore, _data_evts) = MockDatastore::new();
+        let cccd_store = make_cccd_store("written_before_bonding");
+        let gatt_db =
+            Rc::new(GattDatabase::new_with_cccd_store(gatt_datastore.into(), cccd_store.clone()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        write_cccd(&gatt_db.get_att_database(CONN_ID), &[0x01, 0x00]).unwrap();
+
+        // act
+        gatt_db.on_bonded_peer(CONN_ID, PEER).unwrap();
+
+        // assert
+        assert_eq!(
+            cccd_store.load(PEER).unwrap(),
+            BTreeMap::from([(CHARACTERISTIC_VALUE_HANDLE, NOTIFY)])
+        );
+    }
+
+    #[test]
+    fn test_cccd_restored_for_bonded_peer() {
+        // arrange: a bonded peer subscribes on one connection, then disconnects
+        let (gatt_datastore, _data_evts) = MockDatastore::new();
+        let cccd_store = make_cccd_store("restored");
+        let gatt_db =
+            Rc::new(GattDatabase::new_with_cccd_store(gatt_datastore.into(), cccd_store.clone()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        gatt_db.on_bonded_peer(CONN_ID, PEER).unwrap();
+        write_cccd(&gatt_db.get_att_database(CONN_ID), &[0x01, 0x00]).unwrap();
+        gatt_db.remove_connection(CONN_ID);
+
+        // act: it reconnects on another connection
+        gatt_db.on_bonded_peer(ANOTHER_CONN_ID, PEER).unwrap();
+
+        // assert
+        assert_eq!(
+            gatt_db.get_client_configuration(ANOTHER_CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            NOTIFY
+        );
+    }
+
+    #[test]
+    fn test_restored_cccd_reported_to_datastore() {
+        // arrange: a stored value for a bonded peer
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let cccd_store = make_cccd_store("restored_reported");
+        cccd_store.store(PEER, CHARACTERISTIC_VALUE_HANDLE, NOTIFY).unwrap();
+        let gatt_db = Rc::new(GattDatabase::new_with_cccd_store(gatt_datastore.into(), cccd_store));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        // act
+        gatt_db.on_bonded_peer(CONN_ID, PEER).unwrap();
+
+        // assert: the upper layer learns of the subscription as if it were written
+        let Ok(MockDatastoreEvents::WriteNoResponseDescriptor(CONN_ID, CCCD_HANDLE, data)) =
+            data_evts.try_recv()
+        else {
+            unreachable!()
+        };
+        assert_eq!(data.view().get_raw_payload().collect::<Vec<_>>(), vec![0x01, 0x00]);
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_bonded_peer_is_per_connection() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_connection(CONN_ID);
+        let before_bonding = gatt_db.get_bonded_peer(CONN_ID);
+
+        gatt_db.on_bonded_peer(CONN_ID, PEER).unwrap();
+
+        assert_eq!(before_bonding, None);
+        assert_eq!(gatt_db.get_bonded_peer(CONN_ID), Some(PEER));
+        assert_eq!(gatt_db.get_bonded_peer(ANOTHER_CONN_ID), None);
+    }
+
+    #[test]
+    fn test_stale_stored_cccd_not_restored() {
+        // arrange: a stored value that the characteristic no longer supports
+        let (gatt_datastore, _) = MockDatastore::new();
+        let cccd_store = make_cccd_store("stale");
+        cccd_store
+            .store(
+                PEER,
+                CHARACTERISTIC_VALUE_HANDLE,
+                ClientConfiguration { notify: false, indicate: true },
+            )
+            .unwrap();
+        let gatt_db = Rc::new(GattDatabase::new_with_cccd_store(gatt_datastore.into(), cccd_store));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        // act
+        gatt_db.on_bonded_peer(CONN_ID, PEER).unwrap();
+
+        // assert
+        assert_eq!(
+            gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            ClientConfiguration::default()
+        );
+    }
+
+    #[test]
+    fn test_cccd_cleared_on_service_removal() {
+        // arrange
+        let (gatt_datastore, _data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        write_cccd(&gatt_db.get_att_database(CONN_ID), &[0x01, 0x00]).unwrap();
+
+        // act
+        gatt_db.remove_service_at_handle(SERVICE_HANDLE).unwrap();
+
+        // assert
+        assert_eq!(
+            gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            ClientConfiguration::default()
+        );
+    }
+
+    #[test]
+    fn test_multiple_cccds() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_service_with_cccd(true, false);
+        service.characteristics[0].descriptors.push(GattDescriptorWithHandle {
+            handle: AttHandle(5),
+            type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+            permissions: AttPermissions::READONLY,
+        });
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_descriptor_before_characteristic_value() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_service_with_cccd(true, false);
+        service.characteristics[0].descriptors[0].handle = AttHandle(0);
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_other_descriptors_forwarded_to_datastore() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_service_with_cccd(false, false);
+        service.characteristics[0].descriptors[0].type_ = Uuid::new(0x2901);
+        gatt_db.add_service_with_handles(service).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let data = AttAttributeDataChild::RawData(Box::new([1, 2]));
+
+        // act
+        let value = tokio_test::block_on(async {
+            join!(
+                async {
+                    let MockDatastoreEvents::ReadCharacteristic(
+                        CONN_ID,
+                        CCCD_HANDLE,
+                        0,
+                        false,
+                        reply,
+                    ) = data_evts.recv().await.unwrap()
+                    else {
+                        unreachable!()
+                    };
+                    reply.send(Ok(data.clone())).unwrap();
+                },
+                att_db.read_attribute(CCCD_HANDLE)
+            )
+            .1
+        });
+
+        // assert
+        assert_eq!(value, Ok(data));
+    }
+
+    fn execute_write_replying(
+        att_db: &impl AttDatabase,
+        data_evts: &mut UnboundedReceiver<MockDatastoreEvents>,
+        decision: TransactionDecision,
+        reply_result: Result<(), AttErrorCode>,
+    ) -> Result<(), AttErrorCode> {
+        tokio_test::block_on(async {
+            join!(
+                async {
+                    let MockDatastoreEvents::ExecuteWrite(CONN_ID, received_decision, reply) =
+                        data_evts.recv().await.unwrap()
+                    else {
+                        unreachable!()
+                    };
+                    assert_eq!(received_decision, decision);
+                    reply.send(reply_result).unwrap();
+                },
+                att_db.execute_write(decision)
+            )
+            .1
+        })
+    }
+
+    #[test]
+    fn test_prepared_write_forwarded_to_datastore() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_service_with_cccd(false, false);
+        service.characteristics[0].permissions = AttPermissions {
+            readable: false,
+            writable: true,
+            writable_without_response: false,
+            ..Default::default()
+        };
+        gatt_db.add_service_with_handles(service).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let data =
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(Box::new([1, 2]))));
+
+        // act
+        let res = tokio_test::block_on(async {
+            join!(
+                async {
+                    let MockDatastoreEvents::PrepareWriteCharacteristic(
+                        CONN_ID,
+                        CHARACTERISTIC_VALUE_HANDLE,
+                        2,
+                        prepared,
+                        reply,
+                    ) = data_evts.recv().await.unwrap()
+                    else {
+                        unreachable!()
+                    };
+                    assert_eq!(prepared.view().get_raw_payload().collect::<Vec<_>>(), vec![1, 2]);
+                    reply.send(Ok(())).unwrap();
+                },
+                att_db.prepare_write_attribute(CHARACTERISTIC_VALUE_HANDLE, 2, data.view())
+            )
+            .1
+        });
+        let executed =
+            execute_write_replying(&att_db, &mut data_evts, TransactionDecision::Execute, Ok(()));
+
+        // assert
+        assert_eq!(res, Ok(()));
+        assert_eq!(executed, Ok(()));
+    }
+
+    #[test]
+    fn test_prepared_write_unwriteable_characteristic() {
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(false, false)).unwrap();
+        let data =
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(Box::new([1, 2]))));
+
+        let res = tokio_test::block_on(gatt_db.get_att_database(CONN_ID).prepare_write_attribute(
+            CHARACTERISTIC_VALUE_HANDLE,
+            0,
+            data.view(),
+        ));
+
+        assert_eq!(res, Err(AttErrorCode::WRITE_NOT_PERMITTED));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_cccd_prepared_write_applied_on_execute() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let data = build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(Box::new([
+            0x01, 0x00,
+        ]))));
+
+        // act: the value is only applied once executed
+        let res = tokio_test::block_on(att_db.prepare_write_attribute(CCCD_HANDLE, 0, data.view()));
+        let before_execution =
+            gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE);
+        execute_write_replying(&att_db, &mut data_evts, TransactionDecision::Execute, Ok(()))
+            .unwrap();
+
+        // assert
+        assert_eq!(res, Ok(()));
+        assert_eq!(before_execution, ClientConfiguration::default());
+        assert_eq!(gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE), NOTIFY);
+        assert!(matches!(
+            data_evts.try_recv(),
+            Ok(MockDatastoreEvents::WriteNoResponseDescriptor(CONN_ID, CCCD_HANDLE, _))
+        ));
+    }
+
+    #[test]
+    fn test_cccd_prepared_write_discarded() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let data = build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(Box::new([
+            0x01, 0x00,
+        ]))));
+
+        // act: cancel once, then have the upper layer fail the execution
+        tokio_test::block_on(att_db.prepare_write_attribute(CCCD_HANDLE, 0, data.view())).unwrap();
+        execute_write_replying(&att_db, &mut data_evts, TransactionDecision::Cancel, Ok(()))
+            .unwrap();
+        tokio_test::block_on(att_db.prepare_write_attribute(CCCD_HANDLE, 0, data.view())).unwrap();
+        let res = execute_write_replying(
+            &att_db,
+            &mut data_evts,
+            TransactionDecision::Execute,
+            Err(AttErrorCode::UNLIKELY_ERROR),
+        );
+
+        // assert: neither value was applied
+        assert_eq!(res, Err(AttErrorCode::UNLIKELY_ERROR));
+        assert_eq!(
+            gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE),
+            ClientConfiguration::default()
+        );
+    }
+
+    #[test]
+    fn test_cccd_prepared_write_rejects_unsupported_values() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        let data = build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(Box::new([
+            0x02, 0x00,
+        ]))));
+
+        let res = tokio_test::block_on(gatt_db.get_att_database(CONN_ID).prepare_write_attribute(
+            CCCD_HANDLE,
+            0,
+            data.view(),
+        ));
+
+        assert_eq!(res, Err(AttErrorCode::CCCD_IMPROPERLY_CONFIGURED));
+    }
+
+    #[test]
+    fn test_cccd_prepared_write_at_offset() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let data =
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(Box::new([0x01]))));
+
+        // act: only the first octet is written, the second keeps its value
+        let res = tokio_test::block_on(att_db.prepare_write_attribute(CCCD_HANDLE, 0, data.view()));
+        let past_end =
+            tokio_test::block_on(att_db.prepare_write_attribute(CCCD_HANDLE, 3, data.view()));
+        execute_write_replying(&att_db, &mut data_evts, TransactionDecision::Execute, Ok(()))
+            .unwrap();
+
+        // assert
+        assert_eq!(res, Ok(()));
+        assert_eq!(past_end, Err(AttErrorCode::INVALID_OFFSET));
+        assert_eq!(gatt_db.get_client_configuration(CONN_ID, CHARACTERISTIC_VALUE_HANDLE), NOTIFY);
+    }
+
+    fn make_service_with_security(
+        read_security: AttSecurityRequirements,
+        write_security: AttSecurityRequirements,
+    ) -> GattServiceWithHandle {
+        GattServiceWithHandle {
+            handle: SERVICE_HANDLE,
+            type_: SERVICE_TYPE,
+            primary: true,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: CHARACTERISTIC_VALUE_HANDLE,
+                type_: CHARACTERISTIC_TYPE,
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: true,
+                    writable_without_response: true,
+                    writable_signed: false,
+                    read_security,
+                    write_security,
+                },
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
+            }],
+        }
+    }
+
+    const ENCRYPTION_REQUIRED: AttSecurityRequirements =
+        AttSecurityRequirements { encryption: true, ..AttSecurityRequirements::NONE };
+
+    const ENCRYPTED: ConnectionSecurity = ConnectionSecurity {
+        encrypted: true,
+        key_size: 16,
+        bonded: true,
+        authenticated: false,
+        authorized: false,
+    };
+
+    #[test]
+    fn test_read_insufficient_security() {
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_service_with_handles(make_service_with_security(
+                ENCRYPTION_REQUIRED,
+                AttSecurityRequirements::NONE,
+            ))
+            .unwrap();
+
+        let res = tokio_test::block_on(
+            gatt_db.get_att_database(CONN_ID).read_attribute(CHARACTERISTIC_VALUE_HANDLE),
+        );
+
+        assert_eq!(res, Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_read_after_encryption() {
+        // arrange: encrypt the link
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_service_with_handles(make_service_with_security(
+                ENCRYPTION_REQUIRED,
+                AttSecurityRequirements::NONE,
+            ))
+            .unwrap();
+        gatt_db.set_connection_security(CONN_ID, ENCRYPTED);
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let data = AttAttributeDataChild::RawData(Box::new([1, 2]));
+
+        // act: read from the database, and supply a value from the backing datastore
+        let res = tokio_test::block_on(async {
+            join!(
+                async {
+                    let MockDatastoreEvents::ReadCharacteristic(
+                    CONN_ID,
+                    CHARACTERISTIC_VALUE_HANDLE,
+                    0,
+                    false,
+                    reply,
+                ) = data_evts.recv().await.unwrap() else {
+                    unreachable!()
+                };
+                    reply.send(Ok(data.clone())).unwrap();
+                },
+                att_db.read_attribute(CHARACTERISTIC_VALUE_HANDLE)
+            )
+            .1
+        });
+
+        // assert: the read was allowed
+        assert_eq!(res, Ok(data));
+    }
+
+    #[test]
+    fn test_write_insufficient_security() {
+        // arrange: encrypt the link, but without MITM protection
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_service_with_handles(make_service_with_security(
+                AttSecurityRequirements::NONE,
+                AttSecurityRequirements { authentication: true, ..AttSecurityRequirements::NONE },
+            ))
+            .unwrap();
+        gatt_db.set_connection_security(CONN_ID, ENCRYPTED);
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let data =
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(Box::new([1, 2]))));
+
+        // act
+        let res =
+            tokio_test::block_on(att_db.write_attribute(CHARACTERISTIC_VALUE_HANDLE, data.view()));
+        let prepare_res = tokio_test::block_on(att_db.prepare_write_attribute(
+            CHARACTERISTIC_VALUE_HANDLE,
+            0,
+            data.view(),
+        ));
+        att_db.write_no_response_attribute(CHARACTERISTIC_VALUE_HANDLE, data.view());
+
+        // assert: nothing was forwarded to the datastore
+        assert_eq!(res, Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION));
+        assert_eq!(prepare_res, Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_connection_security_is_per_connection() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_service_with_handles(make_service_with_security(
+                ENCRYPTION_REQUIRED,
+                AttSecurityRequirements::NONE,
+            ))
+            .unwrap();
+
+        gatt_db.set_connection_security(ANOTHER_CONN_ID, ENCRYPTED);
+        let res = tokio_test::block_on(
+            gatt_db.get_att_database(CONN_ID).read_attribute(CHARACTERISTIC_VALUE_HANDLE),
+        );
+
+        assert_eq!(res, Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION));
+    }
+
+    #[test]
+    fn test_connection_security_cleared_on_disconnect() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_service_with_handles(make_service_with_security(
+                ENCRYPTION_REQUIRED,
+                AttSecurityRequirements::NONE,
+            ))
+            .unwrap();
+
+        gatt_db.set_connection_security(CONN_ID, ENCRYPTED);
+        gatt_db.remove_connection(CONN_ID);
+        let res = tokio_test::block_on(
+            gatt_db.get_att_database(CONN_ID).read_attribute(CHARACTERISTIC_VALUE_HANDLE),
+        );
+
+        assert_eq!(res, Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION));
+    }
+
+    const GATT_SERVICE_HANDLE: AttHandle = AttHandle(10);
+    const SERVICE_CHANGED_HANDLE: AttHandle = AttHandle(12);
+    const CLIENT_SUPPORTED_FEATURES_HANDLE: AttHandle = AttHandle(15);
+    const DATABASE_HASH_HANDLE: AttHandle = AttHandle(17);
+
+    fn make_db_with_gatt_service() -> Rc<GattDatabase<MockDatastore>> {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db.add_service_with_handles(generic_attribute_service(GATT_SERVICE_HANDLE)).unwrap();
+        gatt_db.add_connection(CONN_ID);
+        gatt_db
+    }
+
+    fn read(att_db: &impl AttDatabase, handle: AttHandle) -> Result<Vec<u8>, AttErrorCode> {
+        tokio_test::block_on(att_db.read_attribute(handle)).map(|value| value.to_vec().unwrap())
+    }
+
+    fn write(
+        att_db: &impl AttDatabase,
+        handle: AttHandle,
+        value: &[u8],
+    ) -> Result<(), AttErrorCode> {
+        let data = build_view_or_crash(build_att_data(AttAttributeDataChild::RawData(
+            value.to_vec().into_boxed_slice(),
+        )));
+        tokio_test::block_on(att_db.write_attribute(handle, data.view()))
+    }
+
+    #[test]
+    fn test_database_hash() {
+        // arrange: the message is each declaration's handle, type, and value,
+        // then each CCCD's handle and type
+        let gatt_db = make_db_with_gatt_service();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let mut message = vec![];
+        for (handle, type_) in
+            [(10, 0x2800), (11, 0x2803), (13, 0x2902), (14, 0x2803), (16, 0x2803)]
+        {
+            message.extend(u16::to_le_bytes(handle));
+            message.extend(u16::to_le_bytes(type_));
+            if type_ != 0x2902 {
+                message.extend(read(&att_db, AttHandle(handle)).unwrap());
+            }
+        }
+        let mut expected = aes_cmac(&[0; 16], &message);
+        expected.reverse();
+
+        // act
+        let hash = read(&att_db, DATABASE_HASH_HANDLE);
+
+        // assert
+        assert_eq!(hash, Ok(expected.to_vec()));
+    }
+
+    #[test]
+    fn test_database_hash_changes_with_schema() {
+        // arrange
+        let gatt_db = make_db_with_gatt_service();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let initial_hash = read(&att_db, DATABASE_HASH_HANDLE).unwrap();
+
+        // act
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+        let hash = read(&att_db, DATABASE_HASH_HANDLE).unwrap();
+        gatt_db.remove_service_at_handle(SERVICE_HANDLE).unwrap();
+        let final_hash = read(&att_db, DATABASE_HASH_HANDLE).unwrap();
+
+        // assert
+        assert_ne!(hash, initial_hash);
+        assert_eq!(final_hash, initial_hash);
+    }
+
+    #[test]
+    fn test_client_supported_features_are_per_connection() {
+        let gatt_db = make_db_with_gatt_service();
+
+        write(&gatt_db.get_att_database(CONN_ID), CLIENT_SUPPORTED_FEATURES_HANDLE, &[0x07])
+            .unwrap();
+
+        assert_eq!(
+            read(&gatt_db.get_att_database(CONN_ID), CLIENT_SUPPORTED_FEATURES_HANDLE),
+            Ok(vec![0x01])
+        );
+        assert_eq!(
+            read(&gatt_db.get_att_database(ANOTHER_CONN_ID), CLIENT_SUPPORTED_FEATURES_HANDLE),
+            Ok(vec![0x00])
+        );
+    }
+
+    #[test]
+    fn test_client_supported_features_cannot_be_disabled() {
+        // arrange
+        let gatt_db = make_db_with_gatt_service();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        write(&att_db, CLIENT_SUPPORTED_FEATURES_HANDLE, &[0x01]).unwrap();
+
+        // act
+        let res = write(&att_db, CLIENT_SUPPORTED_FEATURES_HANDLE, &[0x00]);
+
+        // assert
+        assert_eq!(res, Err(AttErrorCode::VALUE_NOT_ALLOWED));
+        assert_eq!(read(&att_db, CLIENT_SUPPORTED_FEATURES_HANDLE), Ok(vec![0x01]));
+    }
+
+    #[test]
+    fn test_client_supported_features_empty_write() {
+        let gatt_db = make_db_with_gatt_service();
+
+        let res = write(&gatt_db.get_att_database(CONN_ID), CLIENT_SUPPORTED_FEATURES_HANDLE, &[]);
+
+        assert_eq!(res, Err(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH));
+    }
+
+    #[test]
+    fn test_client_becomes_change_unaware_on_schema_change() {
+        let gatt_db = make_db_with_gatt_service();
+        assert!(!gatt_db.is_change_unaware(CONN_ID));
+
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        assert!(gatt_db.is_change_unaware(CONN_ID));
+    }
+
+    #[test]
+    fn test_clients_change_unaware_after_clearing_services() {
+        let gatt_db = make_db_with_gatt_service();
+
+        gatt_db.clear_all_services();
+
+        assert!(gatt_db.is_change_unaware(CONN_ID));
+    }
+
+    #[test]
+    fn test_robust_caching_out_of_sync() {
+        // arrange: a robust caching client, whose cache is then invalidated
+        let gatt_db = make_db_with_gatt_service();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        write(&att_db, CLIENT_SUPPORTED_FEATURES_HANDLE, &[0x01]).unwrap();
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        // act
+        let is_cache_in_sync = att_db.is_cache_in_sync();
+        let first_request = att_db.check_cache_in_sync();
+        let second_request = att_db.check_cache_in_sync();
+
+        // assert: the client is told once, then becomes change-aware
+        assert!(!is_cache_in_sync);
+        assert_eq!(first_request, Err(AttErrorCode::DATABASE_OUT_OF_SYNC));
+        assert_eq!(second_request, Ok(()));
+        assert!(!gatt_db.is_change_unaware(CONN_ID));
+        assert!(att_db.is_cache_in_sync());
+    }
+
+    #[test]
+    fn test_change_unaware_client_without_robust_caching_is_in_sync() {
+        let gatt_db = make_db_with_gatt_service();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        assert_eq!(att_db.check_cache_in_sync(), Ok(()));
+        assert!(att_db.is_cache_in_sync());
+    }
+
+    #[test]
+    fn test_reading_database_hash_makes_client_change_aware() {
+        // arrange
+        let gatt_db = make_db_with_gatt_service();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        write(&att_db, CLIENT_SUPPORTED_FEATURES_HANDLE, &[0x01]).unwrap();
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        // act
+        read(&att_db, DATABASE_HASH_HANDLE).unwrap();
+
+        // assert
+        assert!(!gatt_db.is_change_unaware(CONN_ID));
+        assert_eq!(att_db.check_cache_in_sync(), Ok(()));
+    }
+
+    #[test]
+    fn test_service_changed_confirmation_makes_client_change_aware() {
+        let gatt_db = make_db_with_gatt_service();
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        gatt_db.on_service_changed_confirmed(CONN_ID);
+
+        assert!(!gatt_db.is_change_unaware(CONN_ID));
+    }
+
+    #[test]
+    fn test_bonded_peer_caching_state_restored() {
+        // arrange: a bonded robust caching client disconnects, then the
+        // database changes
+        let gatt_db = make_db_with_gatt_service();
+        gatt_db.on_bonded_peer(CONN_ID, PEER).unwrap();
+        write(&gatt_db.get_att_database(CONN_ID), CLIENT_SUPPORTED_FEATURES_HANDLE, &[0x01])
+            .unwrap();
+        gatt_db.remove_connection(CONN_ID);
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        // act: it reconnects
+        gatt_db.add_connection(ANOTHER_CONN_ID);
+        gatt_db.on_bonded_peer(ANOTHER_CONN_ID, PEER).unwrap();
+
+        // assert
+        assert!(gatt_db.is_change_unaware(ANOTHER_CONN_ID));
+        assert_eq!(
+            gatt_db.get_client_supported_features(ANOTHER_CONN_ID),
+            ClientSupportedFeatures { robust_caching: true }
+        );
+    }
+
+    #[test]
+    fn test_unbonded_peer_caching_state_forgotten() {
+        // arrange
+        let gatt_db = make_db_with_gatt_service();
+        gatt_db.on_bonded_peer(CONN_ID, PEER).unwrap();
+        gatt_db.remove_connection(CONN_ID);
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        // act
+        gatt_db.on_bond_removed(PEER);
+        gatt_db.add_connection(ANOTHER_CONN_ID);
+        gatt_db.on_bonded_peer(ANOTHER_CONN_ID, PEER).unwrap();
+
+        // assert
+        assert!(!gatt_db.is_change_unaware(ANOTHER_CONN_ID));
+    }
+
+    #[test]
+    fn test_service_end_handle() {
+        let gatt_db = make_db_with_gatt_service();
+        gatt_db.add_service_with_handles(make_service_with_cccd(true, false)).unwrap();
+
+        assert_eq!(gatt_db.get_service_end_handle(SERVICE_HANDLE), Some(CCCD_HANDLE));
+        assert_eq!(gatt_db.get_service_end_handle(GATT_SERVICE_HANDLE), Some(DATABASE_HASH_HANDLE));
+        assert_eq!(gatt_db.get_service_end_handle(CHARACTERISTIC_VALUE_HANDLE), None);
+        assert_eq!(gatt_db.get_service_changed_handle(), Some(SERVICE_CHANGED_HANDLE));
+    }
+
+    fn make_local_service(value: LocalValue, permissions: AttPermissions) -> LocalGattService {
+        LocalGattService {
+            service: GattServiceWithHandle {
+                handle: SERVICE_HANDLE,
+                type_: SERVICE_TYPE,
+                primary: true,
+                includes: vec![],
+                characteristics: vec![GattCharacteristicWithHandle {
+                    handle: CHARACTERISTIC_VALUE_HANDLE,
+                    type_: CHARACTERISTIC_TYPE,
+                    permissions,
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
+                }],
+            },
+            values: HashMap::from([(CHARACTERISTIC_VALUE_HANDLE, value)]),
+        }
+    }
+
+    #[test]
+    fn test_local_static_value() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_local_service(make_local_service(
+                LocalValue::Static(vec![1, 2]),
+                AttPermissions { readable: true, writable: true, ..Default::default() },
+            ))
+            .unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // act
+        let value = read(&att_db, CHARACTERISTIC_VALUE_HANDLE);
+        let write_result = write(&att_db, CHARACTERISTIC_VALUE_HANDLE, &[3]);
+
+        // assert: the value is read, but not written, without involving the datastore
+        assert_eq!(value, Ok(vec![1, 2]));
+        assert_eq!(write_result, Err(AttErrorCode::WRITE_NOT_PERMITTED));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_local_dynamic_value() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let stored = Rc::new(RefCell::new(vec![1]));
+        gatt_db
+            .add_local_service(make_local_service(
+                LocalValue::read_write(
+                    {
+                        let stored = stored.clone();
+                        move || stored.borrow().clone()
+                    },
+                    {
+                        let stored = stored.clone();
+                        move |value| {
+                            *stored.borrow_mut() = value.to_vec();
+                            Ok(())
+                        }
+                    },
+                ),
+                AttPermissions {
+                    readable: true,
+                    writable: true,
+                    writable_without_response: true,
+                    ..Default::default()
+                },
+            ))
+            .unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // act + assert
+        assert_eq!(read(&att_db, CHARACTERISTIC_VALUE_HANDLE), Ok(vec![1]));
+        write(&att_db, CHARACTERISTIC_VALUE_HANDLE, &[2, 3]).unwrap();
+        assert_eq!(read(&att_db, CHARACTERISTIC_VALUE_HANDLE), Ok(vec![2, 3]));
+        att_db.write_no_response_attribute(
+            CHARACTERISTIC_VALUE_HANDLE,
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData([4].into()))).view(),
+        );
+        assert_eq!(*stored.borrow(), vec![4]);
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_signed_write_requires_property() {
+        // arrange: only the second database accepts signed writes
+        let make_db = |writable_signed| {
+            let (gatt_datastore, _) = MockDatastore::new();
+            let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+            let stored = Rc::new(RefCell::new(vec![1]));
+            gatt_db
+                .add_local_service(make_local_service(
+                    LocalValue::read_write(Vec::new, {
+                        let stored = stored.clone();
+                        move |value| {
+                            *stored.borrow_mut() = value.to_vec();
+                            Ok(())
+                        }
+                    }),
+                    AttPermissions {
+                        writable_without_response: true,
+                        writable_signed,
+                        ..Default::default()
+                    },
+                ))
+                .unwrap();
+            (gatt_db, stored)
+        };
+        let (unsigned_db, unsigned_stored) = make_db(false);
+        let (signed_db, signed_stored) = make_db(true);
+        let data = build_view_or_crash(build_att_data(AttAttributeDataChild::RawData([2].into())));
+
+        // act
+        unsigned_db
+            .get_att_database(CONN_ID)
+            .write_signed_attribute(CHARACTERISTIC_VALUE_HANDLE, data.view());
+        signed_db
+            .get_att_database(CONN_ID)
+            .write_signed_attribute(CHARACTERISTIC_VALUE_HANDLE, data.view());
+
+        // assert
+        assert_eq!(*unsigned_stored.borrow(), vec![1]);
+        assert_eq!(*signed_stored.borrow(), vec![2]);
+    }
+
+    #[test]
+    fn test_local_value_write_error() {
+        // arrange
+        let (gatt_datastore, _data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_local_service(make_local_service(
+                LocalValue::read_write(Vec::new, |_| Err(AttErrorCode::VALUE_NOT_ALLOWED)),
+                AttPermissions { readable: true, writable: true, ..Default::default() },
+            ))
+            .unwrap();
+
+        // act
+        let res = write(&gatt_db.get_att_database(CONN_ID), CHARACTERISTIC_VALUE_HANDLE, &[1]);
+
+        // assert
+        assert_eq!(res, Err(AttErrorCode::VALUE_NOT_ALLOWED));
+    }
+
+    #[test]
+    fn test_local_value_permissions_enforced() {
+        // arrange
+        let (gatt_datastore, _data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_local_service(make_local_service(
+                LocalValue::Static(vec![1]),
+                AttPermissions::default(),
+            ))
+            .unwrap();
+
+        // act
+        let res = read(&gatt_db.get_att_database(CONN_ID), CHARACTERISTIC_VALUE_HANDLE);
+
+        // assert
+        assert_eq!(res, Err(AttErrorCode::READ_NOT_PERMITTED));
+    }
+
+    #[test]
+    fn test_local_value_prepared_write_not_supported() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_local_service(make_local_service(
+                LocalValue::read_write(Vec::new, |_| Ok(())),
+                AttPermissions { readable: true, writable: true, ..Default::default() },
+            ))
+            .unwrap();
+        let data = build_view_or_crash(build_att_data(AttAttributeDataChild::RawData([1].into())));
+
+        // act
+        let res = tokio_test::block_on(gatt_db.get_att_database(CONN_ID).prepare_write_attribute(
+            CHARACTERISTIC_VALUE_HANDLE,
+            0,
+            data.view(),
+        ));
+
+        // assert
+        assert_eq!(res, Err(AttErrorCode::REQUEST_NOT_SUPPORTED));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_local_value_must_belong_to_service() {
+        // arrange
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_local_service(LocalValue::Static(vec![1]), AttPermissions::READONLY);
+        // the declaration of a characteristic cannot be overridden
+        service.values.insert(CHARACTERISTIC_DECLARATION_HANDLE, LocalValue::Static(vec![2]));
+
+        // act
+        let res = gatt_db.add_local_service(service);
+
+        // assert
+        assert!(res.is_err());
+        assert!(gatt_db.get_att_database(CONN_ID).list_attributes().is_empty());
+    }
+
+    #[test]
+    fn test_local_descriptor_value() {
+        // arrange
+        let (gatt_datastore, mut data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let descriptor_handle = AttHandle(CHARACTERISTIC_VALUE_HANDLE.0 + 1);
+        let mut service = make_local_service(LocalValue::Static(vec![1]), AttPermissions::READONLY);
+        service.service.characteristics[0].descriptors.push(GattDescriptorWithHandle {
+            handle: descriptor_handle,
+            type_: Uuid::new(0x2901),
+            permissions: AttPermissions::READONLY,
+        });
+        service.values.insert(descriptor_handle, LocalValue::Static(b"desc".to_vec()));
+        gatt_db.add_local_service(service).unwrap();
+
+        // act
+        let value = read(&gatt_db.get_att_database(CONN_ID), descriptor_handle);
+
+        // assert
+        assert_eq!(value, Ok(b"desc".to_vec()));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    const SECONDARY_SERVICE_HANDLE: AttHandle = AttHandle(20);
+    const SECONDARY_SERVICE_TYPE: Uuid = Uuid::new(0x180F);
+    const LONG_SECONDARY_SERVICE_HANDLE: AttHandle = AttHandle(30);
+    const LONG_SECONDARY_SERVICE_TYPE: Uuid = Uuid::new(0x12345678);
+    const INCLUDING_SERVICE_HANDLE: AttHandle = AttHandle(40);
+    const INCLUDE_HANDLE: AttHandle = AttHandle(41);
+    const LONG_INCLUDE_HANDLE: AttHandle = AttHandle(42);
+    const EXTENDED_VALUE_HANDLE: AttHandle = AttHandle(44);
+    const EXTENDED_PROPERTIES_HANDLE: AttHandle = AttHandle(45);
+    const USER_DESCRIPTION_HANDLE: AttHandle = AttHandle(46);
+
+    fn make_secondary_service(handle: AttHandle, type_: Uuid) -> GattServiceWithHandle {
+        GattServiceWithHandle {
+            handle,
+            type_,
+            primary: false,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: AttHandle(handle.0 + 2),
+                type_: CHARACTERISTIC_TYPE,
+                permissions: AttPermissions::READONLY,
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
+            }],
+        }
+    }
+
+    fn make_including_service(writable_auxiliaries: bool) -> GattServiceWithHandle {
+        GattServiceWithHandle {
+            handle: INCLUDING_SERVICE_HANDLE,
+            type_: SERVICE_TYPE,
+            primary: true,
+            includes: vec![
+                GattIncludeWithHandle {
+                    handle: INCLUDE_HANDLE,
+                    service_handle: SECONDARY_SERVICE_HANDLE,
+                },
+                GattIncludeWithHandle {
+                    handle: LONG_INCLUDE_HANDLE,
+                    service_handle: LONG_SECONDARY_SERVICE_HANDLE,
+                },
+            ],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: EXTENDED_VALUE_HANDLE,
+                type_: CHARACTERISTIC_TYPE,
+                permissions: AttPermissions::READONLY,
+                notify: false,
+                indicate: false,
+                extended_properties: Some(GattExtendedPropertiesWithHandle {
+                    handle: EXTENDED_PROPERTIES_HANDLE,
+                    reliable_write: true,
+                    writable_auxiliaries,
+                }),
+                user_description: Some(GattUserDescriptionWithHandle {
+                    handle: USER_DESCRIPTION_HANDLE,
+                    description: "level".into(),
+                }),
+                descriptors: vec![],
+            }],
+        }
+    }
+
+    fn make_db_with_included_services(
+    ) -> (Rc<GattDatabase<MockDatastore>>, UnboundedReceiver<MockDatastoreEvents>) {
+        let (gatt_datastore, data_evts) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_service_with_handles(make_secondary_service(
+                SECONDARY_SERVICE_HANDLE,
+                SECONDARY_SERVICE_TYPE,
+            ))
+            .unwrap();
+        gatt_db
+            .add_service_with_handles(make_secondary_service(
+                LONG_SECONDARY_SERVICE_HANDLE,
+                LONG_SECONDARY_SERVICE_TYPE,
+            ))
+            .unwrap();
+        (gatt_db, data_evts)
+    }
+
+    #[test]
+    fn test_secondary_service_declaration() {
+        // arrange
+        let (gatt_db, _) = make_db_with_included_services();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // act
+        let attrs = att_db.list_attributes();
+        let value = read(&att_db, SECONDARY_SERVICE_HANDLE);
+
+        // assert
+        assert_eq!(attrs[0].handle, SECONDARY_SERVICE_HANDLE);
+        assert_eq!(attrs[0].type_, SECONDARY_SERVICE_DECLARATION_UUID);
+        assert_eq!(value, Ok(UuidBuilder::from(SECONDARY_SERVICE_TYPE).to_vec().unwrap()));
+    }
+
+    #[test]
+    fn test_include_declarations() {
+        // arrange
+        let (gatt_db, _) = make_db_with_included_services();
+        gatt_db.add_service_with_handles(make_including_service(false)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // act
+        let include = read(&att_db, INCLUDE_HANDLE);
+        let long_include = read(&att_db, LONG_INCLUDE_HANDLE);
+
+        // assert: each covers the whole included service, but only 16-bit
+        // UUIDs are present
+        assert_eq!(
+            att_db
+                .list_attributes()
+                .iter()
+                .find(|attr| attr.handle == INCLUDE_HANDLE)
+                .unwrap()
+                .type_,
+            INCLUDE_DECLARATION_UUID
+        );
+        assert_eq!(include, Ok(vec![20, 0, 22, 0, 0x0F, 0x18]));
+        assert_eq!(long_include, Ok(vec![30, 0, 32, 0]));
+    }
+
+    #[test]
+    fn test_include_of_unregistered_service() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+
+        let result = gatt_db.add_service_with_handles(make_including_service(false));
+
+        assert!(result.is_err());
+        assert!(gatt_db.get_att_database(CONN_ID).list_attributes().is_empty());
+    }
+
+    #[test]
+    fn test_included_service_cannot_be_removed() {
+        // arrange
+        let (gatt_db, _) = make_db_with_included_services();
+        gatt_db.add_service_with_handles(make_including_service(false)).unwrap();
+
+        // act
+        let result = gatt_db.remove_service_at_handle(SECONDARY_SERVICE_HANDLE);
+        gatt_db.remove_service_at_handle(INCLUDING_SERVICE_HANDLE).unwrap();
+        let result_after_removal = gatt_db.remove_service_at_handle(SECONDARY_SERVICE_HANDLE);
+
+        // assert
+        assert!(result.is_err());
+        assert!(result_after_removal.is_ok());
+    }
+
+    #[test]
+    fn test_extended_properties() {
+        // arrange
+        let (gatt_db, _) = make_db_with_included_services();
+        gatt_db.add_service_with_handles(make_including_service(true)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // act
+        let declaration = read(&att_db, AttHandle(EXTENDED_VALUE_HANDLE.0 - 1)).unwrap();
+        let extended_properties = read(&att_db, EXTENDED_PROPERTIES_HANDLE);
+
+        // assert
+        let declaration =
+            GattCharacteristicDeclarationValueView::try_parse_from_buffer(&declaration[..])
+                .unwrap();
+        assert_eq!(declaration.get_properties().get_extended_properties(), 1);
+        assert_eq!(extended_properties, Ok(vec![0b11, 0]));
+    }
+
+    #[test]
+    fn test_extended_properties_must_use_dedicated_field() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_service_with_cccd(false, false);
+        service.characteristics[0].descriptors[0].type_ = CHARACTERISTIC_EXTENDED_PROPERTIES_UUID;
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_user_description_read_only() {
+        // arrange
+        let (gatt_db, mut data_evts) = make_db_with_included_services();
+        gatt_db.add_service_with_handles(make_including_service(false)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // act
+        let value = read(&att_db, USER_DESCRIPTION_HANDLE);
+        let write_result = write(&att_db, USER_DESCRIPTION_HANDLE, b"other");
+
+        // assert: the value is owned by the GattDatabase
+        assert_eq!(value, Ok(b"level".to_vec()));
+        assert_eq!(write_result, Err(AttErrorCode::WRITE_NOT_PERMITTED));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_user_description_writable_auxiliaries() {
+        // arrange
+        let (gatt_db, mut data_evts) = make_db_with_included_services();
+        gatt_db.add_service_with_handles(make_including_service(true)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+
+        // act
+        write(&att_db, USER_DESCRIPTION_HANDLE, b"other").unwrap();
+        let invalid_write_result = write(&att_db, USER_DESCRIPTION_HANDLE, &[0xFF]);
+
+        // assert: the new value is visible to every client
+        assert_eq!(
+            read(&gatt_db.get_att_database(ANOTHER_CONN_ID), USER_DESCRIPTION_HANDLE),
+            Ok(b"other".to_vec())
+        );
+        assert_eq!(invalid_write_result, Err(AttErrorCode::VALUE_NOT_ALLOWED));
+        assert!(data_evts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_writable_auxiliaries_without_user_description() {
+        let (gatt_db, _) = make_db_with_included_services();
+        let mut service = make_including_service(true);
+        service.characteristics[0].user_description = None;
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_multiple_user_descriptions() {
+        let (gatt_db, _) = make_db_with_included_services();
+        let mut service = make_including_service(false);
+        service.characteristics[0].descriptors.push(GattDescriptorWithHandle {
+            handle: AttHandle(USER_DESCRIPTION_HANDLE.0 + 1),
+            type_: CHARACTERISTIC_USER_DESCRIPTION_UUID,
+            permissions: AttPermissions::READONLY,
+        });
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_include_after_characteristic() {
+        let (gatt_db, _) = make_db_with_included_services();
+        let mut service = make_including_service(false);
+        service.includes[1].handle = AttHandle(USER_DESCRIPTION_HANDLE.0 + 1);
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_characteristics_out_of_order() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_secondary_service(SECONDARY_SERVICE_HANDLE, SECONDARY_SERVICE_TYPE);
+        let mut other_characteristic = service.characteristics[0].clone();
+        other_characteristic.handle = AttHandle(SECONDARY_SERVICE_HANDLE.0 + 4);
+        service.characteristics.insert(0, other_characteristic);
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_descriptor_overlaps_next_characteristic() {
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        let mut service = make_service_with_cccd(true, false);
+        let mut other_characteristic = service.characteristics[0].clone();
+        // the declaration of this characteristic is at the CCCD handle
+        other_characteristic.handle = AttHandle(CCCD_HANDLE.0 + 1);
+        other_characteristic.descriptors.clear();
+        service.characteristics.push(other_characteristic);
+
+        let result = gatt_db.add_service_with_handles(service);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_service_overlapping_existing_service() {
+        // arrange: a service spanning handles 20 to 22
+        let (gatt_datastore, _) = MockDatastore::new();
+        let gatt_db = Rc::new(GattDatabase::new(gatt_datastore.into()));
+        gatt_db
+            .add_service_with_handles(make_secondary_service(
+                SECONDARY_SERVICE_HANDLE,
+                SECONDARY_SERVICE_TYPE,
+            ))
+            .unwrap();
+
+        // act: add a service at an unused handle inside it
+        let result = gatt_db.add_service_with_handles(GattServiceWithHandle {
+            handle: AttHandle(SECONDARY_SERVICE_HANDLE.0 + 1),
+            type_: SERVICE_TYPE,
+            primary: true,
+            includes: vec![],
+            characteristics: vec![],
+        });
+
+        // assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_secondary_service_end_handle() {
+        let (gatt_db, _) = make_db_with_included_services();
+        gatt_db.add_service_with_handles(make_including_service(false)).unwrap();
+
+        assert_eq!(gatt_db.get_service_end_handle(SECONDARY_SERVICE_HANDLE), Some(AttHandle(22)));
+        assert_eq!(
+            gatt_db.get_service_end_handle(INCLUDING_SERVICE_HANDLE),
+            Some(USER_DESCRIPTION_HANDLE)
+        );
+    }
+
+    #[test]
+    fn test_database_hash_covers_includes_and_extended_properties() {
+        // arrange: the message is each declaration's handle, type, and value,
+        // the extended properties' handle, type, and value, and the user
+        // description's handle and type
+        let (gatt_db, _) = make_db_with_included_services();
+        gatt_db.add_service_with_handles(make_including_service(false)).unwrap();
+        gatt_db.add_service_with_handles(generic_attribute_service(GATT_SERVICE_HANDLE)).unwrap();
+        let att_db = gatt_db.get_att_database(CONN_ID);
+        let mut message = vec![];
+        for (handle, type_) in [
+            (10, 0x2800),
+            (11, 0x2803),
+            (13, 0x2902),
+            (14, 0x2803),
+            (16, 0x2803),
+            (20, 0x2801),
+            (21, 0x2803),
+            (30, 0x2801),
+            (31, 0x2803),
+            (40, 0x2800),
+            (41, 0x2802),
+            (42, 0x2802),
+            (43, 0x2803),
+            (45, 0x2900),
+            (46, 0x2901),
+        ] {
+            message.extend(u16::to_le_bytes(handle));
+            message.extend(u16::to_le_bytes(type_));
+            if !matches!(type_, 0x2901 | 0x2902) {
+                message.extend(read(&att_db, AttHandle(handle)).unwrap());
+            }
+        }
+        let mut expected = aes_cmac(&[0; 16], &message);
+        expected.reverse();
+
+        // act
+        let hash = read(&att_db, DATABASE_HASH_HANDLE);
+
+        // assert
+        assert_eq!(hash, Ok(expected.to_vec()));
+    }
 }
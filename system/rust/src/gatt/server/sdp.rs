@@ -0,0 +1,30 @@
+//! This module defines the publication of SDP records for the primary services
+//! of a GATT server exposed over BR/EDR, so that classic clients can discover
+//! them (5.3 3G 7)
+
+use crate::{
+    core::uuid::Uuid,
+    gatt::ids::{AttHandle, ServerId},
+};
+
+/// The SDP record of a primary service, locating its attributes on the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SdpServiceRecord {
+    /// The type of the service
+    pub uuid: Uuid,
+    /// The handle of the service declaration
+    pub start_handle: AttHandle,
+    /// The handle of the last attribute of the service
+    pub end_handle: AttHandle,
+}
+
+/// A publisher of SDP records. Records are keyed by server and service
+/// handle, so they can be withdrawn once the service is removed.
+pub trait SdpPublisher {
+    /// Publish the record of a service of the given server
+    fn publish(&self, server_id: ServerId, record: SdpServiceRecord);
+
+    /// Withdraw the record of the service at the given handle, if one was
+    /// published
+    fn withdraw(&self, server_id: ServerId, start_handle: AttHandle);
+}
@@ -0,0 +1,76 @@
+//! This module contains the services implemented by the GATT server itself,
+//! rather than by an upper layer
+
+pub mod battery;
+pub mod device_information;
+pub mod gap;
+pub mod gatt;
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{gatt::ids::AttHandle, packets::AttErrorCode};
+
+use super::gatt_database::GattServiceWithHandle;
+
+/// Applies a value written by a client to a LocalValue
+pub type LocalWriteHandler = Rc<dyn Fn(&[u8]) -> Result<(), AttErrorCode>>;
+
+/// The value of an attribute implemented inside the stack. Reads and writes of
+/// such attributes are answered synchronously, and are never forwarded to the
+/// GattDatastore.
+#[derive(Clone)]
+pub enum LocalValue {
+    /// A constant value, which cannot be written
+    Static(Vec<u8>),
+    /// A value produced on every read. If a write handler is present, it is
+    /// invoked on every write, otherwise writes are rejected.
+    Dynamic {
+        /// Produces the current value
+        read: Rc<dyn Fn() -> Vec<u8>>,
+        /// Applies a new value written by a client
+        write: Option<LocalWriteHandler>,
+    },
+}
+
+impl LocalValue {
+    /// A value produced by the given closure on every read
+    pub fn read_only(read: impl Fn() -> Vec<u8> + 'static) -> Self {
+        Self::Dynamic { read: Rc::new(read), write: None }
+    }
+
+    /// A value produced by the read closure, and updated by the write closure
+    pub fn read_write(
+        read: impl Fn() -> Vec<u8> + 'static,
+        write: impl Fn(&[u8]) -> Result<(), AttErrorCode> + 'static,
+    ) -> Self {
+        Self::Dynamic { read: Rc::new(read), write: Some(Rc::new(write)) }
+    }
+
+    /// The current value
+    pub fn read(&self) -> Vec<u8> {
+        match self {
+            Self::Static(value) => value.clone(),
+            Self::Dynamic { read, .. } => read(),
+        }
+    }
+
+    /// Write a new value, if supported
+    pub fn write(&self, value: &[u8]) -> Result<(), AttErrorCode> {
+        match self {
+            Self::Dynamic { write: Some(write), .. } => write(value),
+            _ => Err(AttErrorCode::WRITE_NOT_PERMITTED),
+        }
+    }
+}
+
+/// A service whose attribute values are (at least partly) implemented inside
+/// the stack
+#[derive(Clone)]
+pub struct LocalGattService {
+    /// The layout of the service
+    pub service: GattServiceWithHandle,
+    /// The values of characteristics and descriptors (other than CCCDs) of the
+    /// service, keyed by handle. Attributes without a value here are still
+    /// forwarded to the GattDatastore.
+    pub values: HashMap<AttHandle, LocalValue>,
+}
@@ -0,0 +1,102 @@
+//! The Battery service (BAS v1.0), exposing the charge level of the battery
+//! of the device.
+//!
+//! Its characteristic value is local, so is never forwarded to the
+//! GattDatastore. Changes to the level are not detected by the service itself:
+//! the owner of the level should notify subscribers through the GattModule.
+
+use std::collections::HashMap;
+
+use crate::{
+    core::uuid::Uuid,
+    gatt::{
+        ids::AttHandle,
+        server::{
+            att_database::{AttPermissions, CLIENT_CHARACTERISTIC_CONFIGURATION_UUID},
+            gatt_database::{
+                GattCharacteristicWithHandle, GattDescriptorWithHandle, GattServiceWithHandle,
+            },
+        },
+    },
+};
+
+use super::{LocalGattService, LocalValue};
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.4
+pub const BATTERY_SERVICE_UUID: Uuid = Uuid::new(0x180F);
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.8
+pub const BATTERY_LEVEL_UUID: Uuid = Uuid::new(0x2A19);
+
+/// The handle of the Battery Level characteristic value, in the Battery
+/// service declared at the given handle
+pub fn battery_level_handle(service_handle: AttHandle) -> AttHandle {
+    AttHandle(service_handle.0 + 2)
+}
+
+/// Build the Battery service, with its declaration at the given handle,
+/// followed by the Battery Level characteristic and its CCCD. The level (a
+/// percentage, capped at 100) is queried on every read.
+pub fn battery_service(handle: AttHandle, level: impl Fn() -> u8 + 'static) -> LocalGattService {
+    let value_handle = battery_level_handle(handle);
+    LocalGattService {
+        service: GattServiceWithHandle {
+            handle,
+            type_: BATTERY_SERVICE_UUID,
+            primary: true,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: value_handle,
+                type_: BATTERY_LEVEL_UUID,
+                permissions: AttPermissions::READONLY,
+                notify: true,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![GattDescriptorWithHandle {
+                    handle: AttHandle(value_handle.0 + 1),
+                    type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        ..Default::default()
+                    },
+                }],
+            }],
+        },
+        values: HashMap::from([(
+            value_handle,
+            LocalValue::read_only(move || vec![level().min(100)]),
+        )]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    const SERVICE_HANDLE: AttHandle = AttHandle(40);
+
+    #[test]
+    fn test_level_is_read_on_demand() {
+        let level = Rc::new(Cell::new(50));
+        let service = battery_service(SERVICE_HANDLE, {
+            let level = level.clone();
+            move || level.get()
+        });
+        let value = &service.values[&battery_level_handle(SERVICE_HANDLE)];
+
+        assert_eq!(value.read(), vec![50]);
+        level.set(49);
+        assert_eq!(value.read(), vec![49]);
+    }
+
+    #[test]
+    fn test_level_is_capped() {
+        let service = battery_service(SERVICE_HANDLE, || 150);
+
+        assert_eq!(service.values[&battery_level_handle(SERVICE_HANDLE)].read(), vec![100]);
+    }
+}
@@ -0,0 +1,183 @@
+//! The Device Information service (DIS v1.1), exposing static information
+//! about the manufacturer and version of the device.
+//!
+//! Its characteristic values are all local, so are never forwarded to the
+//! GattDatastore.
+
+use std::collections::HashMap;
+
+use crate::{
+    core::uuid::Uuid,
+    gatt::{
+        ids::AttHandle,
+        server::{
+            att_database::AttPermissions,
+            gatt_database::{GattCharacteristicWithHandle, GattServiceWithHandle},
+        },
+    },
+};
+
+use super::{LocalGattService, LocalValue};
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.4
+pub const DEVICE_INFORMATION_SERVICE_UUID: Uuid = Uuid::new(0x180A);
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.8
+pub const MODEL_NUMBER_STRING_UUID: Uuid = Uuid::new(0x2A24);
+pub const SERIAL_NUMBER_STRING_UUID: Uuid = Uuid::new(0x2A25);
+pub const FIRMWARE_REVISION_STRING_UUID: Uuid = Uuid::new(0x2A26);
+pub const HARDWARE_REVISION_STRING_UUID: Uuid = Uuid::new(0x2A27);
+pub const SOFTWARE_REVISION_STRING_UUID: Uuid = Uuid::new(0x2A28);
+pub const MANUFACTURER_NAME_STRING_UUID: Uuid = Uuid::new(0x2A29);
+pub const PNP_ID_UUID: Uuid = Uuid::new(0x2A50);
+
+/// The value of the PnP ID characteristic, from DIS v1.1 3.9
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PnpId {
+    /// Whether the vendor ID was assigned by the Bluetooth SIG (0x01) or by
+    /// the USB Implementer's Forum (0x02)
+    pub vendor_id_source: u8,
+    /// The ID of the vendor, from the source above
+    pub vendor_id: u16,
+    /// A vendor-defined product ID
+    pub product_id: u16,
+    /// A vendor-defined product version
+    pub product_version: u16,
+}
+
+impl PnpId {
+    fn to_bytes(self) -> Vec<u8> {
+        [self.vendor_id_source]
+            .into_iter()
+            .chain(self.vendor_id.to_le_bytes())
+            .chain(self.product_id.to_le_bytes())
+            .chain(self.product_version.to_le_bytes())
+            .collect()
+    }
+}
+
+/// The information exposed by the Device Information service. Only the fields
+/// that are present are exposed as characteristics.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct DeviceInformation {
+    pub manufacturer_name: Option<String>,
+    pub model_number: Option<String>,
+    pub serial_number: Option<String>,
+    pub hardware_revision: Option<String>,
+    pub firmware_revision: Option<String>,
+    pub software_revision: Option<String>,
+    pub pnp_id: Option<PnpId>,
+}
+
+/// Build the Device Information service, with its declaration at the given
+/// handle, followed by one read-only characteristic for each field of the
+/// DeviceInformation that is present (in the order of the struct), each taking
+/// two handles.
+pub fn device_information_service(
+    handle: AttHandle,
+    information: &DeviceInformation,
+) -> LocalGattService {
+    let strings = [
+        (MANUFACTURER_NAME_STRING_UUID, &information.manufacturer_name),
+        (MODEL_NUMBER_STRING_UUID, &information.model_number),
+        (SERIAL_NUMBER_STRING_UUID, &information.serial_number),
+        (HARDWARE_REVISION_STRING_UUID, &information.hardware_revision),
+        (FIRMWARE_REVISION_STRING_UUID, &information.firmware_revision),
+        (SOFTWARE_REVISION_STRING_UUID, &information.software_revision),
+    ];
+    let fields = strings
+        .into_iter()
+        .filter_map(|(type_, value)| Some((type_, value.as_ref()?.as_bytes().to_vec())))
+        .chain(information.pnp_id.map(|pnp_id| (PNP_ID_UUID, pnp_id.to_bytes())));
+
+    let mut characteristics = vec![];
+    let mut values = HashMap::new();
+    for ((type_, value), value_handle) in fields.zip((handle.0 + 2..).step_by(2).map(AttHandle)) {
+        characteristics.push(GattCharacteristicWithHandle {
+            handle: value_handle,
+            type_,
+            permissions: AttPermissions::READONLY,
+            notify: false,
+            indicate: false,
+            extended_properties: None,
+            user_description: None,
+            descriptors: vec![],
+        });
+        values.insert(value_handle, LocalValue::Static(value));
+    }
+    LocalGattService {
+        service: GattServiceWithHandle {
+            handle,
+            type_: DEVICE_INFORMATION_SERVICE_UUID,
+            primary: true,
+            includes: vec![],
+            characteristics,
+        },
+        values,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SERVICE_HANDLE: AttHandle = AttHandle(30);
+
+    #[test]
+    fn test_empty_service() {
+        let service = device_information_service(SERVICE_HANDLE, &DeviceInformation::default());
+
+        assert!(service.service.characteristics.is_empty());
+        assert!(service.values.is_empty());
+    }
+
+    #[test]
+    fn test_only_present_fields_are_exposed() {
+        let service = device_information_service(
+            SERVICE_HANDLE,
+            &DeviceInformation {
+                manufacturer_name: Some("Google".into()),
+                software_revision: Some("1.0".into()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            service
+                .service
+                .characteristics
+                .iter()
+                .map(|characteristic| (characteristic.handle, characteristic.type_))
+                .collect::<Vec<_>>(),
+            vec![
+                (AttHandle(32), MANUFACTURER_NAME_STRING_UUID),
+                (AttHandle(34), SOFTWARE_REVISION_STRING_UUID)
+            ]
+        );
+        assert_eq!(service.values[&AttHandle(32)].read(), b"Google".to_vec());
+        assert_eq!(service.values[&AttHandle(34)].read(), b"1.0".to_vec());
+    }
+
+    #[test]
+    fn test_pnp_id() {
+        let service = device_information_service(
+            SERVICE_HANDLE,
+            &DeviceInformation {
+                pnp_id: Some(PnpId {
+                    vendor_id_source: 0x01,
+                    vendor_id: 0x00E0,
+                    product_id: 0x1234,
+                    product_version: 0x0100,
+                }),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(service.service.characteristics[0].type_, PNP_ID_UUID);
+        assert_eq!(
+            service.values[&AttHandle(32)].read(),
+            vec![0x01, 0xE0, 0x00, 0x34, 0x12, 0x00, 0x01]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! The GAP service (5.3 3C 12), exposing the name and appearance of the
+//! device, and its preferred connection parameters as a peripheral.
+//!
+//! Its characteristic values are all local, so are never forwarded to the
+//! GattDatastore.
+
+use std::collections::HashMap;
+
+use crate::{
+    core::uuid::Uuid,
+    gatt::{
+        ids::AttHandle,
+        server::{
+            att_database::AttPermissions,
+            gatt_database::{GattCharacteristicWithHandle, GattServiceWithHandle},
+        },
+    },
+};
+
+use super::{LocalGattService, LocalValue};
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.4
+pub const GENERIC_ACCESS_SERVICE_UUID: Uuid = Uuid::new(0x1800);
+
+// UUIDs from Bluetooth Assigned Numbers Sec 3.8
+pub const DEVICE_NAME_UUID: Uuid = Uuid::new(0x2A00);
+pub const APPEARANCE_UUID: Uuid = Uuid::new(0x2A01);
+pub const PERIPHERAL_PREFERRED_CONNECTION_PARAMETERS_UUID: Uuid = Uuid::new(0x2A04);
+
+/// The longest Device Name, from 5.3 3C 12.1
+pub const MAX_DEVICE_NAME_LENGTH: usize = 248;
+
+/// The value of the Peripheral Preferred Connection Parameters
+/// characteristic, from 5.3 3C 12.3. All fields are in the units of the
+/// corresponding HCI parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeripheralPreferredConnectionParameters {
+    /// In units of 1.25ms
+    pub min_connection_interval: u16,
+    /// In units of 1.25ms
+    pub max_connection_interval: u16,
+    /// In connection events
+    pub peripheral_latency: u16,
+    /// In units of 10ms
+    pub supervision_timeout: u16,
+}
+
+impl PeripheralPreferredConnectionParameters {
+    fn to_bytes(self) -> Vec<u8> {
+        [
+            self.min_connection_interval,
+            self.max_connection_interval,
+            self.peripheral_latency,
+            self.supervision_timeout,
+        ]
+        .into_iter()
+        .flat_map(u16::to_le_bytes)
+        .collect()
+    }
+}
+
+/// Build the GAP service, with its declaration at the given handle, followed
+/// by the Device Name, Appearance, and (if supplied) Peripheral Preferred
+/// Connection Parameters characteristics. The device name is queried on every
+/// read, so it can change without re-registering the service.
+pub fn generic_access_service(
+    handle: AttHandle,
+    device_name: impl Fn() -> String + 'static,
+    appearance: u16,
+    preferred_connection_parameters: Option<PeripheralPreferredConnectionParameters>,
+) -> LocalGattService {
+    let AttHandle(start) = handle;
+    let mut characteristics = vec![
+        GattCharacteristicWithHandle {
+            handle: AttHandle(start + 2),
+            type_: DEVICE_NAME_UUID,
+            permissions: AttPermissions::READONLY,
+            notify: false,
+            indicate: false,
+            extended_properties: None,
+            user_description: None,
+            descriptors: vec![],
+        },
+        GattCharacteristicWithHandle {
+            handle: AttHandle(start + 4),
+            type_: APPEARANCE_UUID,
+            permissions: AttPermissions::READONLY,
+            notify: false,
+            indicate: false,
+            extended_properties: None,
+            user_description: None,
+            descriptors: vec![],
+        },
+    ];
+    let mut values = HashMap::from([
+        (
+            AttHandle(start + 2),
+            LocalValue::read_only(move || {
+                let mut name = device_name();
+                // don't split a multi-byte character
+                let mut len = name.len().min(MAX_DEVICE_NAME_LENGTH);
+                while !name.is_char_boundary(len) {
+                    len -= 1;
+                }
+                name.truncate(len);
+                name.into_bytes()
+            }),
+        ),
+        (AttHandle(start + 4), LocalValue::Static(appearance.to_le_bytes().to_vec())),
+    ]);
+    if let Some(parameters) = preferred_connection_parameters {
+        characteristics.push(GattCharacteristicWithHandle {
+            handle: AttHandle(start + 6),
+            type_: PERIPHERAL_PREFERRED_CONNECTION_PARAMETERS_UUID,
+            permissions: AttPermissions::READONLY,
+            notify: false,
+            indicate: false,
+            extended_properties: None,
+            user_description: None,
+            descriptors: vec![],
+        });
+        values.insert(AttHandle(start + 6), LocalValue::Static(parameters.to_bytes()));
+    }
+    LocalGattService {
+        service: GattServiceWithHandle {
+            handle,
+            type_: GENERIC_ACCESS_SERVICE_UUID,
+            primary: true,
+            includes: vec![],
+            characteristics,
+        },
+        values,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SERVICE_HANDLE: AttHandle = AttHandle(20);
+
+    #[test]
+    fn test_layout() {
+        let service = generic_access_service(SERVICE_HANDLE, || "name".into(), 0x0340, None);
+
+        assert_eq!(service.service.handle, SERVICE_HANDLE);
+        assert_eq!(
+            service.service.characteristics.iter().map(|c| c.handle).collect::<Vec<_>>(),
+            vec![AttHandle(22), AttHandle(24)]
+        );
+        assert_eq!(service.values[&AttHandle(22)].read(), b"name".to_vec());
+        assert_eq!(service.values[&AttHandle(24)].read(), vec![0x40, 0x03]);
+    }
+
+    #[test]
+    fn test_device_name_is_truncated() {
+        let service = generic_access_service(SERVICE_HANDLE, || "a".repeat(300), 0, None);
+
+        assert_eq!(service.values[&AttHandle(22)].read().len(), MAX_DEVICE_NAME_LENGTH);
+    }
+
+    #[test]
+    fn test_device_name_is_truncated_at_character_boundary() {
+        // after the first byte, each character is two bytes long, so the limit
+        // falls inside one
+        let service =
+            generic_access_service(SERVICE_HANDLE, || format!("a{}", "é".repeat(200)), 0, None);
+
+        let name = service.values[&AttHandle(22)].read();
+        assert_eq!(name.len(), MAX_DEVICE_NAME_LENGTH - 1);
+        assert!(String::from_utf8(name).is_ok());
+    }
+
+    #[test]
+    fn test_preferred_connection_parameters() {
+        let service = generic_access_service(
+            SERVICE_HANDLE,
+            String::new,
+            0,
+            Some(PeripheralPreferredConnectionParameters {
+                min_connection_interval: 0x0006,
+                max_connection_interval: 0x0C80,
+                peripheral_latency: 0x0001,
+                supervision_timeout: 0x0C80,
+            }),
+        );
+
+        assert_eq!(
+            service.service.characteristics[2].type_,
+            PERIPHERAL_PREFERRED_CONNECTION_PARAMETERS_UUID
+        );
+        assert_eq!(
+            service.values[&AttHandle(26)].read(),
+            vec![0x06, 0x00, 0x80, 0x0C, 0x01, 0x00, 0x80, 0x0C]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! The Generic Attribute service (5.3 3G 7), exposing the state of the GATT
+//! database to clients so they can keep their attribute caches in sync.
+//!
+//! Its characteristic values are all owned by the GattDatabase, so they are
+//! never forwarded to the GattDatastore.
+
+use crate::gatt::{
+    ids::AttHandle,
+    server::{
+        att_database::{
+            AttPermissions, CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+            CLIENT_SUPPORTED_FEATURES_UUID, DATABASE_HASH_UUID, GENERIC_ATTRIBUTE_SERVICE_UUID,
+            SERVICE_CHANGED_UUID,
+        },
+        gatt_database::{
+            GattCharacteristicWithHandle, GattDescriptorWithHandle, GattServiceWithHandle,
+        },
+    },
+};
+
+/// Build the Generic Attribute service, with its declaration at the given
+/// handle, followed by the Service Changed, Client Supported Features, and
+/// Database Hash characteristics.
+pub fn generic_attribute_service(handle: AttHandle) -> GattServiceWithHandle {
+    let AttHandle(start) = handle;
+    GattServiceWithHandle {
+        handle,
+        type_: GENERIC_ATTRIBUTE_SERVICE_UUID,
+        primary: true,
+        includes: vec![],
+        characteristics: vec![
+            GattCharacteristicWithHandle {
+                handle: AttHandle(start + 2),
+                type_: SERVICE_CHANGED_UUID,
+                // as per 5.3 3G 7.1, the value can only be indicated
+                permissions: AttPermissions::default(),
+                notify: false,
+                indicate: true,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![GattDescriptorWithHandle {
+                    handle: AttHandle(start + 3),
+                    type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        ..Default::default()
+                    },
+                }],
+            },
+            GattCharacteristicWithHandle {
+                handle: AttHandle(start + 5),
+                type_: CLIENT_SUPPORTED_FEATURES_UUID,
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: true,
+                    ..Default::default()
+                },
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
+            },
+            GattCharacteristicWithHandle {
+                handle: AttHandle(start + 7),
+                type_: DATABASE_HASH_UUID,
+                permissions: AttPermissions::READONLY,
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
+            },
+        ],
+    }
+}
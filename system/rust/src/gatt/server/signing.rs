@@ -0,0 +1,183 @@
+//! This module verifies the authentication signature of ATT_SIGNED_WRITE_CMD
+//! PDUs, using the Connection Signature Resolving Key (CSRK) distributed by
+//! the peer during pairing (5.3 3H 2.4.5).
+
+use std::rc::Rc;
+
+use crate::{gatt::ids::ConnectionId, utils::crypto::aes_cmac};
+
+/// The signing material distributed by a bonded peer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PeerSigningKey {
+    /// The peer's CSRK, in the little-endian order used over the air
+    pub csrk: [u8; 16],
+    /// The smallest SignCounter that will be accepted from the peer, so
+    /// earlier signed writes cannot be replayed
+    pub min_sign_counter: u32,
+}
+
+/// A source of peer signing keys, typically backed by the security manager.
+pub trait SigningKeyProvider {
+    /// Get the signing key distributed by the peer on this connection, if any
+    fn get_peer_signing_key(&self, conn_id: ConnectionId) -> Option<PeerSigningKey>;
+
+    /// Invoked once a signed write with the given SignCounter is accepted, so
+    /// that later writes must use a larger one
+    fn on_sign_counter_used(&self, conn_id: ConnectionId, sign_counter: u32);
+}
+
+/// The reason a signed write was rejected
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The peer has not distributed a CSRK
+    NoSigningKey,
+    /// The SignCounter was already used
+    ReplayedSignCounter,
+    /// The MAC does not match the signed data
+    InvalidMac,
+}
+
+/// Verifies the signed writes received on a single connection
+pub struct SignedWriteVerifier {
+    conn_id: ConnectionId,
+    provider: Rc<dyn SigningKeyProvider>,
+}
+
+impl SignedWriteVerifier {
+    /// Constructor, using the keys of the peer on the given connection
+    pub fn new(conn_id: ConnectionId, provider: Rc<dyn SigningKeyProvider>) -> Self {
+        Self { conn_id, provider }
+    }
+
+    /// Verify the MAC of a signed write. The signed data is the PDU up to and
+    /// including the SignCounter, i.e. everything but the MAC itself.
+    pub fn verify(
+        &self,
+        signed_data: &[u8],
+        sign_counter: u32,
+        mac: [u8; 8],
+    ) -> Result<(), SignatureError> {
+        let key =
+            self.provider.get_peer_signing_key(self.conn_id).ok_or(SignatureError::NoSigningKey)?;
+        if sign_counter < key.min_sign_counter {
+            return Err(SignatureError::ReplayedSignCounter);
+        }
+        if compute_mac(&key.csrk, signed_data) != mac {
+            return Err(SignatureError::InvalidMac);
+        }
+        self.provider.on_sign_counter_used(self.conn_id, sign_counter);
+        Ok(())
+    }
+}
+
+/// The MAC is the 64 most significant bits of the AES-CMAC of the signed data.
+/// Since AES-CMAC is defined MSO-first, while the key, data, and MAC are all
+/// sent LSO-first, each of them is reversed.
+pub fn compute_mac(csrk: &[u8; 16], signed_data: &[u8]) -> [u8; 8] {
+    let mut key = *csrk;
+    key.reverse();
+    let message = signed_data.iter().rev().copied().collect::<Vec<_>>();
+    let cmac = aes_cmac(&key, &message);
+    let mut mac = [0; 8];
+    mac.copy_from_slice(&cmac[..8]);
+    mac.reverse();
+    mac
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+
+    const CONN_ID: ConnectionId = ConnectionId(1);
+    const CSRK: [u8; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    const SIGNED_DATA: [u8; 9] = [0xD2, 0x03, 0x00, 0xAA, 0xBB, 0x05, 0x00, 0x00, 0x00];
+
+    struct FakeSigningKeyProvider {
+        key: Option<PeerSigningKey>,
+        last_used: Cell<Option<u32>>,
+    }
+
+    impl SigningKeyProvider for FakeSigningKeyProvider {
+        fn get_peer_signing_key(&self, conn_id: ConnectionId) -> Option<PeerSigningKey> {
+            assert_eq!(conn_id, CONN_ID);
+            self.key
+        }
+
+        fn on_sign_counter_used(&self, _: ConnectionId, sign_counter: u32) {
+            self.last_used.set(Some(sign_counter));
+        }
+    }
+
+    fn make_verifier(
+        key: Option<PeerSigningKey>,
+    ) -> (SignedWriteVerifier, Rc<FakeSigningKeyProvider>) {
+        let provider = Rc::new(FakeSigningKeyProvider { key, last_used: Cell::new(None) });
+        (SignedWriteVerifier::new(CONN_ID, provider.clone()), provider)
+    }
+
+    #[test]
+    fn test_mac_matches_reversed_aes_cmac() {
+        // arrange: the same computation, written out MSO-first
+        let mut key = CSRK;
+        key.reverse();
+        let mut message = SIGNED_DATA;
+        message.reverse();
+        let cmac = aes_cmac(&key, &message);
+
+        // act
+        let mac = compute_mac(&CSRK, &SIGNED_DATA);
+
+        // assert: the on-air MAC is the top half of the CMAC, LSO-first
+        assert_eq!(mac, [cmac[7], cmac[6], cmac[5], cmac[4], cmac[3], cmac[2], cmac[1], cmac[0]]);
+    }
+
+    #[test]
+    fn test_valid_signature() {
+        let (verifier, provider) =
+            make_verifier(Some(PeerSigningKey { csrk: CSRK, min_sign_counter: 5 }));
+
+        let result = verifier.verify(&SIGNED_DATA, 5, compute_mac(&CSRK, &SIGNED_DATA));
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(provider.last_used.get(), Some(5));
+    }
+
+    #[test]
+    fn test_no_signing_key() {
+        let (verifier, _) = make_verifier(None);
+
+        let result = verifier.verify(&SIGNED_DATA, 5, compute_mac(&CSRK, &SIGNED_DATA));
+
+        assert_eq!(result, Err(SignatureError::NoSigningKey));
+    }
+
+    #[test]
+    fn test_replayed_sign_counter() {
+        let (verifier, provider) =
+            make_verifier(Some(PeerSigningKey { csrk: CSRK, min_sign_counter: 6 }));
+
+        let result = verifier.verify(&SIGNED_DATA, 5, compute_mac(&CSRK, &SIGNED_DATA));
+
+        assert_eq!(result, Err(SignatureError::ReplayedSignCounter));
+        assert_eq!(provider.last_used.get(), None);
+    }
+
+    #[test]
+    fn test_invalid_mac() {
+        // arrange: tamper with the signed value
+        let (verifier, provider) =
+            make_verifier(Some(PeerSigningKey { csrk: CSRK, min_sign_counter: 0 }));
+        let mac = compute_mac(&CSRK, &SIGNED_DATA);
+        let mut tampered = SIGNED_DATA;
+        tampered[3] ^= 1;
+
+        // act
+        let result = verifier.verify(&tampered, 5, mac);
+
+        // assert
+        assert_eq!(result, Err(SignatureError::InvalidMac));
+        assert_eq!(provider.last_used.get(), None);
+    }
+}
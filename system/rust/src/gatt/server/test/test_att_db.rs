@@ -1,5 +1,6 @@
 use crate::{
     gatt::{
+        callbacks::TransactionDecision,
         ids::AttHandle,
         server::{
             att_database::{AttAttribute, AttDatabase, StableAttDatabase},
@@ -11,10 +12,14 @@ use crate::{
 
 use async_trait::async_trait;
 use log::info;
-use std::{cell::Cell, collections::BTreeMap};
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+};
 
 pub struct TestAttDatabase {
     attributes: BTreeMap<AttHandle, (AttAttribute, Cell<Vec<u8>>)>,
+    prepared_writes: RefCell<Vec<(AttHandle, usize, Vec<u8>)>>,
 }
 
 impl TestAttDatabase {
@@ -24,6 +29,7 @@ impl TestAttDatabase {
                 .into_iter()
                 .map(|(att, data)| (att.handle, (att, Cell::new(data))))
                 .collect(),
+            prepared_writes: RefCell::new(vec![]),
         }
     }
 }
@@ -63,9 +69,91 @@ impl AttDatabase for TestAttDatabase {
             None => Err(AttErrorCode::INVALID_HANDLE),
         }
     }
+    async fn prepare_write_attribute(
+        &self,
+        handle: AttHandle,
+        offset: usize,
+        data: AttAttributeDataView<'_>,
+    ) -> Result<(), AttErrorCode> {
+        match self.attributes.get(&handle) {
+            Some((AttAttribute { permissions: AttPermissions { writable: false, .. }, .. }, _)) => {
+                Err(AttErrorCode::WRITE_NOT_PERMITTED)
+            }
+            Some((_, data_cell)) => {
+                // the offset must fall within the value as modified by the
+                // writes already prepared
+                let mut value = data_cell.take();
+                data_cell.set(value.clone());
+                let mut prepared_writes = self.prepared_writes.borrow_mut();
+                for (_, prepared_offset, prepared_data) in prepared_writes
+                    .iter()
+                    .filter(|(prepared_handle, ..)| *prepared_handle == handle)
+                {
+                    apply_write(&mut value, *prepared_offset, prepared_data);
+                }
+                if offset > value.len() {
+                    return Err(AttErrorCode::INVALID_OFFSET);
+                }
+                prepared_writes.push((handle, offset, data.get_raw_payload().collect()));
+                Ok(())
+            }
+            None => Err(AttErrorCode::INVALID_HANDLE),
+        }
+    }
+    async fn execute_write(&self, decision: TransactionDecision) -> Result<(), AttErrorCode> {
+        let prepared_writes = self.prepared_writes.take();
+        if decision == TransactionDecision::Execute {
+            for (handle, offset, data) in prepared_writes {
+                let data_cell = &self.attributes[&handle].1;
+                let mut value = data_cell.take();
+                apply_write(&mut value, offset, &data);
+                data_cell.set(value);
+            }
+        }
+        Ok(())
+    }
+    fn write_no_response_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        match self.attributes.get(&handle) {
+            Some((
+                AttAttribute {
+                    permissions: AttPermissions { writable_without_response: true, .. },
+                    ..
+                },
+                data_cell,
+            )) => {
+                data_cell.replace(data.get_raw_payload().collect());
+            }
+            _ => info!("dropping write command to {handle:?}"),
+        }
+    }
+    fn write_signed_attribute(&self, handle: AttHandle, data: AttAttributeDataView<'_>) {
+        match self.attributes.get(&handle) {
+            Some((
+                AttAttribute { permissions: AttPermissions { writable_signed: true, .. }, .. },
+                data_cell,
+            )) => {
+                data_cell.replace(data.get_raw_payload().collect());
+            }
+            _ => info!("dropping signed write command to {handle:?}"),
+        }
+    }
     fn list_attributes(&self) -> Vec<AttAttribute> {
         self.attributes.values().map(|(att, _)| *att).collect()
     }
+
+    fn check_cache_in_sync(&self) -> Result<(), AttErrorCode> {
+        Ok(())
+    }
+
+    fn is_cache_in_sync(&self) -> bool {
+        true
+    }
+}
+
+/// Overwrite the value from the given offset, extending it if needed
+fn apply_write(value: &mut Vec<u8>, offset: usize, data: &[u8]) {
+    let end = value.len().min(offset + data.len());
+    value.splice(offset..end, data.iter().copied());
 }
 
 // We guarantee that the contents of a TestAttDatabase will remain stable
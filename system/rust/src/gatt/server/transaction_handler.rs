@@ -1,22 +1,31 @@
 use log::warn;
 
 use crate::{
+    core::uuid::Uuid,
     gatt::ids::AttHandle,
     packets::{
-        AttChild, AttErrorCode, AttErrorResponseBuilder, AttFindByTypeValueRequestView,
-        AttFindInformationRequestView, AttOpcode, AttReadByGroupTypeRequestView,
-        AttReadByTypeRequestView, AttReadRequestView, AttView, AttWriteRequestView, Packet,
-        ParseError,
+        AttChild, AttErrorCode, AttErrorResponseBuilder, AttExecuteWriteRequestView,
+        AttFindByTypeValueRequestView, AttFindInformationRequestView, AttOpcode,
+        AttPrepareWriteRequestView, AttReadBlobRequestView, AttReadByGroupTypeRequestView,
+        AttReadByTypeRequestView, AttReadMultipleRequestView, AttReadMultipleVariableRequestView,
+        AttReadRequestView, AttView, AttWriteRequestView, Packet, ParseError,
     },
 };
 
 use super::{
-    att_database::AttDatabase,
+    att_database::{AttDatabase, DATABASE_HASH_UUID},
     transactions::{
+        execute_write_request::handle_execute_write_request,
         find_by_type_value::handle_find_by_type_value_request,
         find_information_request::handle_find_information_request,
+        prepare_write_request::{handle_prepare_write_request, PreparedWriteQueue},
+        read_blob_request::handle_read_blob_request,
         read_by_group_type_request::handle_read_by_group_type_request,
-        read_by_type_request::handle_read_by_type_request, read_request::handle_read_request,
+        read_by_type_request::handle_read_by_type_request,
+        read_multiple_request::{
+            handle_read_multiple_request, handle_read_multiple_variable_request,
+        },
+        read_request::handle_read_request,
         write_request::handle_write_request,
     },
 };
@@ -25,17 +34,29 @@ use super::{
 /// bearer per database, to ensure serialization.
 pub struct AttTransactionHandler<Db: AttDatabase> {
     db: Db,
+    prepared_writes: PreparedWriteQueue,
 }
 
 impl<Db: AttDatabase> AttTransactionHandler<Db> {
     pub fn new(db: Db) -> Self {
-        Self { db }
+        Self { db, prepared_writes: PreparedWriteQueue::default() }
     }
 
     // Runs a task to process an incoming packet. Takes an exclusive reference to
     // ensure that only one request is outstanding at a time (notifications +
     // commands should take a different path)
     pub async fn process_packet(&mut self, packet: AttView<'_>, mtu: usize) -> AttChild {
+        if !reads_database_hash(packet) {
+            if let Err(error_code) = self.db.check_cache_in_sync() {
+                return AttErrorResponseBuilder {
+                    opcode_in_error: packet.get_opcode(),
+                    handle_in_error: AttHandle(0).into(),
+                    error_code: error_code.into(),
+                }
+                .into();
+            }
+        }
+
         match self.try_parse_and_process_packet(packet, mtu).await {
             Ok(result) => result,
             Err(_) => {
@@ -43,7 +64,7 @@ impl<Db: AttDatabase> AttTransactionHandler<Db> {
                 AttErrorResponseBuilder {
                     opcode_in_error: packet.get_opcode(),
                     handle_in_error: AttHandle(0).into(),
-                    error_code: AttErrorCode::REQUEST_NOT_SUPPORTED,
+                    error_code: AttErrorCode::REQUEST_NOT_SUPPORTED.into(),
                 }
                 .into()
             }
@@ -60,6 +81,24 @@ impl<Db: AttDatabase> AttTransactionHandler<Db> {
             AttOpcode::READ_REQUEST => {
                 Ok(handle_read_request(AttReadRequestView::try_parse(packet)?, mtu, &self.db).await)
             }
+            AttOpcode::READ_BLOB_REQUEST => Ok(handle_read_blob_request(
+                AttReadBlobRequestView::try_parse(packet)?,
+                mtu,
+                &self.db,
+            )
+            .await),
+            AttOpcode::READ_MULTIPLE_REQUEST => Ok(handle_read_multiple_request(
+                AttReadMultipleRequestView::try_parse(packet)?,
+                mtu,
+                &self.db,
+            )
+            .await),
+            AttOpcode::READ_MULTIPLE_VARIABLE_REQUEST => Ok(handle_read_multiple_variable_request(
+                AttReadMultipleVariableRequestView::try_parse(packet)?,
+                mtu,
+                &self.db,
+            )
+            .await),
             AttOpcode::READ_BY_GROUP_TYPE_REQUEST => {
                 handle_read_by_group_type_request(
                     AttReadByGroupTypeRequestView::try_parse(packet)?,
@@ -90,6 +129,17 @@ impl<Db: AttDatabase> AttTransactionHandler<Db> {
             AttOpcode::WRITE_REQUEST => {
                 Ok(handle_write_request(AttWriteRequestView::try_parse(packet)?, &self.db).await)
             }
+            AttOpcode::PREPARE_WRITE_REQUEST => Ok(handle_prepare_write_request(
+                AttPrepareWriteRequestView::try_parse(packet)?,
+                &mut self.prepared_writes,
+                &snapshotted_db,
+            )),
+            AttOpcode::EXECUTE_WRITE_REQUEST => Ok(handle_execute_write_request(
+                AttExecuteWriteRequestView::try_parse(packet)?,
+                &mut self.prepared_writes,
+                &self.db,
+            )
+            .await),
             _ => {
                 warn!("Dropping unsupported opcode {:?}", packet.get_opcode());
                 Err(ParseError::InvalidEnumValue)
@@ -98,6 +148,18 @@ impl<Db: AttDatabase> AttTransactionHandler<Db> {
     }
 }
 
+/// A client with an out-of-sync cache may still read the Database Hash by type
+/// (5.3 3G 2.5.2.1), since this is how it resynchronizes
+fn reads_database_hash(packet: AttView<'_>) -> bool {
+    if packet.get_opcode() != AttOpcode::READ_BY_TYPE_REQUEST {
+        return false;
+    }
+    let Ok(request) = AttReadByTypeRequestView::try_parse(packet) else {
+        return false;
+    };
+    Uuid::try_from(request.get_attribute_type()).is_ok_and(|type_| type_ == DATABASE_HASH_UUID)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -123,11 +185,16 @@ mod test {
             AttAttribute {
                 handle: AttHandle(3),
                 type_: Uuid::new(0x1234),
-                permissions: AttPermissions { readable: true, writable: false },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![1, 2, 3],
         )]);
-        let mut handler = AttTransactionHandler { db };
+        let mut handler = AttTransactionHandler::new(db);
         let att_view = build_att_view_or_crash(AttReadRequestBuilder {
             attribute_handle: AttHandle(3).into(),
         });
@@ -151,11 +218,16 @@ mod test {
             AttAttribute {
                 handle: AttHandle(3),
                 type_: Uuid::new(0x1234),
-                permissions: AttPermissions { readable: true, writable: false },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![1, 2, 3],
         )]);
-        let mut handler = AttTransactionHandler { db };
+        let mut handler = AttTransactionHandler::new(db);
         let att_view = build_att_view_or_crash(AttWriteResponseBuilder {});
 
         // act
@@ -167,7 +239,7 @@ mod test {
             AttChild::AttErrorResponse(AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::WRITE_RESPONSE,
                 handle_in_error: AttHandle(0).into(),
-                error_code: AttErrorCode::REQUEST_NOT_SUPPORTED
+                error_code: AttErrorCode::REQUEST_NOT_SUPPORTED.into()
             })
         );
     }
@@ -0,0 +1,316 @@
+use crate::{
+    gatt::{callbacks::TransactionDecision, ids::AttHandle, server::att_database::AttDatabase},
+    packets::{
+        AttChild, AttErrorCode, AttErrorResponseBuilder, AttExecuteWriteRequestView,
+        AttExecuteWriteResponseBuilder, AttOpcode, OwnedAttAttributeDataView, OwnedPacket,
+    },
+};
+
+use super::prepare_write_request::{PreparedWrite, PreparedWriteQueue};
+
+/// As per 5.3 3F 3.2.9, attribute values are at most 512 octets long
+const MAX_ATTRIBUTE_VALUE_LENGTH: usize = 512;
+
+const FLAGS_CANCEL: u8 = 0x00;
+const FLAGS_EXECUTE: u8 = 0x01;
+
+pub async fn handle_execute_write_request<T: AttDatabase>(
+    request: AttExecuteWriteRequestView<'_>,
+    queue: &mut PreparedWriteQueue,
+    db: &T,
+) -> AttChild {
+    let result = match request.get_flags() {
+        FLAGS_CANCEL => {
+            queue.take();
+            Ok(())
+        }
+        FLAGS_EXECUTE => execute_writes(queue.take(), db).await,
+        _ => Err((AttHandle(0), AttErrorCode::INVALID_PDU)),
+    };
+
+    match result {
+        Ok(()) => AttExecuteWriteResponseBuilder {}.into(),
+        Err((handle, error_code)) => AttErrorResponseBuilder {
+            opcode_in_error: AttOpcode::EXECUTE_WRITE_REQUEST,
+            handle_in_error: handle.into(),
+            error_code: error_code.into(),
+        }
+        .into(),
+    }
+}
+
+/// Hand each prepared write over to the database, in the order they were
+/// prepared, then have them all applied atomically. Offsets are relative to
+/// the value of the attribute as modified by the previous writes, so are
+/// checked by the database.
+async fn execute_writes<T: AttDatabase>(
+    writes: Vec<PreparedWrite>,
+    db: &T,
+) -> Result<(), (AttHandle, AttErrorCode)> {
+    if writes.is_empty() {
+        return Ok(());
+    }
+
+    // reject the whole queue before handing anything over
+    for write in &writes {
+        if write.offset + write.value.len() > MAX_ATTRIBUTE_VALUE_LENGTH {
+            return Err((write.handle, AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH));
+        }
+    }
+
+    for PreparedWrite { handle, offset, value } in writes {
+        let Ok(data) = OwnedAttAttributeDataView::try_parse(value.into_boxed_slice()) else {
+            return Err((handle, AttErrorCode::UNLIKELY_ERROR));
+        };
+        if let Err(error_code) = db.prepare_write_attribute(handle, offset, data.view()).await {
+            // the writes already handed over must be discarded, so the
+            // execution remains atomic
+            let _ = db.execute_write(TransactionDecision::Cancel).await;
+            return Err((handle, error_code));
+        }
+    }
+
+    db.execute_write(TransactionDecision::Execute)
+        .await
+        .map_err(|error_code| (AttHandle(0), error_code))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        core::uuid::Uuid,
+        gatt::server::{
+            att_database::{AttAttribute, AttPermissions},
+            test::test_att_db::TestAttDatabase,
+            transactions::prepare_write_request::{
+                handle_prepare_write_request, MAX_PREPARED_WRITES,
+            },
+        },
+        packets::{
+            AttAttributeDataChild, AttExecuteWriteRequestBuilder, AttPrepareWriteRequestBuilder,
+            Serializable,
+        },
+        utils::packet::{build_att_data, build_view_or_crash},
+    };
+
+    fn make_db() -> TestAttDatabase {
+        TestAttDatabase::new(vec![
+            (
+                AttAttribute {
+                    handle: AttHandle(3),
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                },
+                vec![9],
+            ),
+            (
+                AttAttribute {
+                    handle: AttHandle(5),
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                },
+                vec![9],
+            ),
+        ])
+    }
+
+    fn prepare_write(
+        handle: u16,
+        offset: u16,
+        value: &[u8],
+        queue: &mut PreparedWriteQueue,
+        db: &TestAttDatabase,
+    ) {
+        let att_view = build_view_or_crash(AttPrepareWriteRequestBuilder {
+            handle: AttHandle(handle).into(),
+            offset,
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        });
+        let response = handle_prepare_write_request(att_view.view(), queue, db);
+        assert!(matches!(response, AttChild::AttPrepareWriteResponse(_)));
+    }
+
+    fn do_execute_write_request(
+        flags: u8,
+        queue: &mut PreparedWriteQueue,
+        db: &TestAttDatabase,
+    ) -> AttChild {
+        let att_view = build_view_or_crash(AttExecuteWriteRequestBuilder { flags });
+        tokio_test::block_on(handle_execute_write_request(att_view.view(), queue, db))
+    }
+
+    fn read(handle: u16, db: &TestAttDatabase) -> Vec<u8> {
+        tokio_test::block_on(db.read_attribute(AttHandle(handle))).unwrap().to_vec().unwrap()
+    }
+
+    #[test]
+    fn test_execute_assembles_long_values() {
+        // arrange
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        prepare_write(3, 0, &[1, 2, 3], &mut queue, &db);
+        prepare_write(5, 0, &[7], &mut queue, &db);
+        prepare_write(3, 3, &[4, 5], &mut queue, &db);
+
+        // act
+        let response = do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+
+        // assert
+        assert_eq!(response, AttExecuteWriteResponseBuilder {}.into());
+        assert_eq!(read(3, &db), vec![1, 2, 3, 4, 5]);
+        assert_eq!(read(5, &db), vec![7]);
+        assert!(queue.take().is_empty());
+    }
+
+    #[test]
+    fn test_execute_relative_to_current_value() {
+        // arrange: modify the existing value, rather than replacing it
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        prepare_write(3, 1, &[1, 2], &mut queue, &db);
+
+        // act
+        do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+
+        // assert
+        assert_eq!(read(3, &db), vec![9, 1, 2]);
+    }
+
+    #[test]
+    fn test_execute_partial_overwrite() {
+        // arrange: a write at offset 0 only replaces the bytes it covers
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        prepare_write(3, 1, &[1, 2], &mut queue, &db);
+        prepare_write(3, 0, &[7], &mut queue, &db);
+
+        // act
+        do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+
+        // assert
+        assert_eq!(read(3, &db), vec![7, 1, 2]);
+    }
+
+    #[test]
+    fn test_execute_overlapping_writes() {
+        // arrange: overwrite the middle of an already prepared value
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        prepare_write(3, 0, &[1, 2, 3, 4], &mut queue, &db);
+        prepare_write(3, 1, &[8], &mut queue, &db);
+
+        // act
+        do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+
+        // assert
+        assert_eq!(read(3, &db), vec![1, 8, 3, 4]);
+    }
+
+    #[test]
+    fn test_cancel_discards_queue() {
+        // arrange
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        prepare_write(3, 0, &[1, 2, 3], &mut queue, &db);
+
+        // act
+        let response = do_execute_write_request(FLAGS_CANCEL, &mut queue, &db);
+
+        // assert: nothing was written, and a later execution is a no-op
+        assert_eq!(response, AttExecuteWriteResponseBuilder {}.into());
+        assert_eq!(read(3, &db), vec![9]);
+        do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+        assert_eq!(read(3, &db), vec![9]);
+    }
+
+    #[test]
+    fn test_execute_empty_queue() {
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+
+        let response = do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+
+        assert_eq!(response, AttExecuteWriteResponseBuilder {}.into());
+    }
+
+    #[test]
+    fn test_execute_invalid_offset() {
+        // arrange: leave a gap in the value
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        prepare_write(5, 0, &[7], &mut queue, &db);
+        prepare_write(3, 0, &[1, 2], &mut queue, &db);
+        prepare_write(3, 3, &[4], &mut queue, &db);
+
+        // act
+        let response = do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+
+        // assert: no value is written
+        assert_eq!(
+            response,
+            AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::EXECUTE_WRITE_REQUEST,
+                handle_in_error: AttHandle(3).into(),
+                error_code: AttErrorCode::INVALID_OFFSET.into(),
+            }
+            .into()
+        );
+        assert_eq!(read(3, &db), vec![9]);
+        assert_eq!(read(5, &db), vec![9]);
+    }
+
+    #[test]
+    fn test_execute_value_too_long() {
+        // arrange
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        for i in 0..(MAX_ATTRIBUTE_VALUE_LENGTH / 64 + 1).min(MAX_PREPARED_WRITES) {
+            prepare_write(3, (i * 64) as u16, &[0; 64], &mut queue, &db);
+        }
+
+        // act
+        let response = do_execute_write_request(FLAGS_EXECUTE, &mut queue, &db);
+
+        // assert
+        assert_eq!(
+            response,
+            AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::EXECUTE_WRITE_REQUEST,
+                handle_in_error: AttHandle(3).into(),
+                error_code: AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH.into(),
+            }
+            .into()
+        );
+        assert_eq!(read(3, &db), vec![9]);
+    }
+
+    #[test]
+    fn test_execute_invalid_flags() {
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+
+        let response = do_execute_write_request(2, &mut queue, &db);
+
+        assert_eq!(
+            response,
+            AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::EXECUTE_WRITE_REQUEST,
+                handle_in_error: AttHandle(0).into(),
+                error_code: AttErrorCode::INVALID_PDU.into(),
+            }
+            .into()
+        );
+    }
+}
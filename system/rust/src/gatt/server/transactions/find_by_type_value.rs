@@ -30,7 +30,7 @@ pub async fn handle_find_by_type_value_request(
         return AttErrorResponseBuilder {
             opcode_in_error: AttOpcode::FIND_BY_TYPE_VALUE_REQUEST,
             handle_in_error: AttHandle::from(request.get_starting_handle()).into(),
-            error_code: AttErrorCode::INVALID_HANDLE,
+            error_code: AttErrorCode::INVALID_HANDLE.into(),
         }
         .into();
     };
@@ -66,7 +66,7 @@ pub async fn handle_find_by_type_value_request(
         AttErrorResponseBuilder {
             opcode_in_error: AttOpcode::FIND_BY_TYPE_VALUE_REQUEST,
             handle_in_error: AttHandle::from(request.get_starting_handle()).into(),
-            error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+            error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
         }
         .into()
     } else {
@@ -245,7 +245,7 @@ mod test {
             AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::FIND_BY_TYPE_VALUE_REQUEST,
                 handle_in_error: AttHandle(3).into(),
-                error_code: AttErrorCode::INVALID_HANDLE,
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
             }
         );
     }
@@ -281,7 +281,7 @@ mod test {
             AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::FIND_BY_TYPE_VALUE_REQUEST,
                 handle_in_error: AttHandle(4).into(),
-                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
             }
         );
     }
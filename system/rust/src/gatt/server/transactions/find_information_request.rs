@@ -27,7 +27,7 @@ pub fn handle_find_information_request<T: AttDatabase>(
         return AttErrorResponseBuilder {
             opcode_in_error: AttOpcode::FIND_INFORMATION_REQUEST,
             handle_in_error: AttHandle::from(request.get_starting_handle()).into(),
-            error_code: AttErrorCode::INVALID_HANDLE,
+            error_code: AttErrorCode::INVALID_HANDLE.into(),
         }
         .into()
     };
@@ -48,7 +48,7 @@ pub fn handle_find_information_request<T: AttDatabase>(
         AttErrorResponseBuilder {
             opcode_in_error: AttOpcode::FIND_INFORMATION_REQUEST,
             handle_in_error: AttHandle::from(request.get_starting_handle()).into(),
-            error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+            error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
         }
         .into()
     }
@@ -123,7 +123,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(3),
                     type_: Uuid::new(0x01020304),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -131,7 +136,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(4),
                     type_: Uuid::new(0x01020305),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -139,7 +149,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(5),
                     type_: Uuid::new(0x01020306),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -186,7 +201,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(3),
                     type_: Uuid::new(0x0102),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -194,7 +214,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(4),
                     type_: Uuid::new(0x0103),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -202,7 +227,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(5),
                     type_: Uuid::new(0x01020306),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -262,7 +292,7 @@ mod test {
             AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::FIND_INFORMATION_REQUEST,
                 handle_in_error: AttHandle(3).into(),
-                error_code: AttErrorCode::INVALID_HANDLE,
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
             }
         );
     }
@@ -275,7 +305,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(3),
                     type_: Uuid::new(0x0102),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -283,7 +318,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(4),
                     type_: Uuid::new(0x0103),
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -323,7 +363,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(3),
                 type_: Uuid::new(0x0102),
-                permissions: AttPermissions { readable: true, writable: false },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![4, 5],
         )]);
@@ -344,7 +389,7 @@ mod test {
             AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::FIND_INFORMATION_REQUEST,
                 handle_in_error: AttHandle(4).into(),
-                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
             }
         );
     }
@@ -276,7 +276,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(3),
                 type_: UUID,
-                permissions: AttPermissions { readable: false, writable: false },
+                permissions: AttPermissions {
+                    readable: false,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![4, 5, 6],
         )]);
@@ -310,7 +315,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(4),
                     type_: UUID,
-                    permissions: AttPermissions { readable: false, writable: false },
+                    permissions: AttPermissions {
+                        readable: false,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![5, 6, 7],
             ),
@@ -361,7 +371,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(4),
                     type_: ANOTHER_UUID,
-                    permissions: AttPermissions { readable: false, writable: false },
+                    permissions: AttPermissions {
+                        readable: false,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![5, 6, 7],
             ),
@@ -0,0 +1,211 @@
+use crate::{
+    gatt::{ids::AttHandle, server::att_database::StableAttDatabase},
+    packets::{
+        AttAttributeDataChild, AttChild, AttErrorCode, AttErrorResponseBuilder, AttOpcode,
+        AttPrepareWriteRequestView, AttPrepareWriteResponseBuilder,
+    },
+    utils::packet::build_att_data,
+};
+
+/// The maximum number of prepared writes queued by a single client, before we
+/// start rejecting them with PREPARE_QUEUE_FULL
+pub const MAX_PREPARED_WRITES: usize = 64;
+
+/// A single queued ATT_PREPARE_WRITE_REQ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedWrite {
+    pub handle: AttHandle,
+    pub offset: usize,
+    pub value: Vec<u8>,
+}
+
+/// The queue of prepared writes of a bearer, awaiting an ATT_EXECUTE_WRITE_REQ
+#[derive(Debug, Default)]
+pub struct PreparedWriteQueue {
+    writes: Vec<PreparedWrite>,
+}
+
+impl PreparedWriteQueue {
+    /// Remove all queued writes, in the order they were prepared
+    pub fn take(&mut self) -> Vec<PreparedWrite> {
+        std::mem::take(&mut self.writes)
+    }
+}
+
+pub fn handle_prepare_write_request<T: StableAttDatabase>(
+    request: AttPrepareWriteRequestView<'_>,
+    queue: &mut PreparedWriteQueue,
+    db: &T,
+) -> AttChild {
+    let handle = request.get_handle().into();
+    let offset = request.get_offset();
+    let value = request.get_value().get_raw_payload().collect::<Vec<_>>();
+
+    // as per 5.3 3F 3.4.6.1, the permissions are checked when the write is
+    // prepared, while offsets and lengths are only checked on execution
+    let error_code = match db.find_attribute(handle) {
+        None => Some(AttErrorCode::INVALID_HANDLE),
+        Some(attr) if !attr.permissions.writable => Some(AttErrorCode::WRITE_NOT_PERMITTED),
+        Some(_) if queue.writes.len() >= MAX_PREPARED_WRITES => {
+            Some(AttErrorCode::PREPARE_QUEUE_FULL)
+        }
+        Some(_) => None,
+    };
+    if let Some(error_code) = error_code {
+        return AttErrorResponseBuilder {
+            opcode_in_error: AttOpcode::PREPARE_WRITE_REQUEST,
+            handle_in_error: handle.into(),
+            error_code: error_code.into(),
+        }
+        .into();
+    }
+
+    queue.writes.push(PreparedWrite { handle, offset: offset.into(), value: value.clone() });
+
+    AttPrepareWriteResponseBuilder {
+        handle: handle.into(),
+        offset,
+        value: build_att_data(AttAttributeDataChild::RawData(value.into_boxed_slice())),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        core::uuid::Uuid,
+        gatt::server::{
+            att_database::{AttAttribute, AttPermissions},
+            test::test_att_db::TestAttDatabase,
+        },
+        packets::AttPrepareWriteRequestBuilder,
+        utils::packet::build_view_or_crash,
+    };
+
+    fn make_db() -> TestAttDatabase {
+        TestAttDatabase::new(vec![
+            (
+                AttAttribute {
+                    handle: AttHandle(3),
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                },
+                vec![],
+            ),
+            (
+                AttAttribute {
+                    handle: AttHandle(4),
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions::READONLY,
+                },
+                vec![],
+            ),
+        ])
+    }
+
+    fn do_prepare_write_request(
+        handle: u16,
+        offset: u16,
+        value: &[u8],
+        queue: &mut PreparedWriteQueue,
+        db: &TestAttDatabase,
+    ) -> AttChild {
+        let att_view = build_view_or_crash(AttPrepareWriteRequestBuilder {
+            handle: AttHandle(handle).into(),
+            offset,
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        });
+        handle_prepare_write_request(att_view.view(), queue, db)
+    }
+
+    #[test]
+    fn test_prepare_write_is_echoed_and_queued() {
+        // arrange
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+
+        // act
+        let response = do_prepare_write_request(3, 2, &[1, 2], &mut queue, &db);
+
+        // assert
+        assert_eq!(
+            response,
+            AttChild::AttPrepareWriteResponse(AttPrepareWriteResponseBuilder {
+                handle: AttHandle(3).into(),
+                offset: 2,
+                value: build_att_data(AttAttributeDataChild::RawData([1, 2].into())),
+            })
+        );
+        assert_eq!(
+            queue.take(),
+            vec![PreparedWrite { handle: AttHandle(3), offset: 2, value: vec![1, 2] }]
+        );
+    }
+
+    #[test]
+    fn test_prepare_write_missing_handle() {
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+
+        let response = do_prepare_write_request(5, 0, &[1], &mut queue, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::PREPARE_WRITE_REQUEST,
+                handle_in_error: AttHandle(5).into(),
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
+            })
+        );
+        assert!(queue.take().is_empty());
+    }
+
+    #[test]
+    fn test_prepare_write_not_writable() {
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+
+        let response = do_prepare_write_request(4, 0, &[1], &mut queue, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::PREPARE_WRITE_REQUEST,
+                handle_in_error: AttHandle(4).into(),
+                error_code: AttErrorCode::WRITE_NOT_PERMITTED.into(),
+            })
+        );
+        assert!(queue.take().is_empty());
+    }
+
+    #[test]
+    fn test_prepare_write_queue_full() {
+        // arrange: fill up the queue
+        let db = make_db();
+        let mut queue = PreparedWriteQueue::default();
+        for _ in 0..MAX_PREPARED_WRITES {
+            do_prepare_write_request(3, 0, &[1], &mut queue, &db);
+        }
+
+        // act
+        let response = do_prepare_write_request(3, 0, &[1], &mut queue, &db);
+
+        // assert
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::PREPARE_WRITE_REQUEST,
+                handle_in_error: AttHandle(3).into(),
+                error_code: AttErrorCode::PREPARE_QUEUE_FULL.into(),
+            })
+        );
+        assert_eq!(queue.take().len(), MAX_PREPARED_WRITES);
+    }
+}
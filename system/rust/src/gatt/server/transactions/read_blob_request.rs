@@ -0,0 +1,161 @@
+use crate::{
+    gatt::server::att_database::AttDatabase,
+    packets::{
+        AttAttributeDataChild, AttChild, AttErrorCode, AttErrorResponseBuilder, AttOpcode,
+        AttReadBlobRequestView, AttReadBlobResponseBuilder, Serializable,
+    },
+    utils::packet::build_att_data,
+};
+
+pub async fn handle_read_blob_request<T: AttDatabase>(
+    request: AttReadBlobRequestView<'_>,
+    mtu: usize,
+    db: &T,
+) -> AttChild {
+    let handle = request.get_attribute_handle().into();
+    let offset = request.get_offset() as usize;
+
+    let value = match db
+        .read_attribute_blob(handle, offset)
+        .await
+        .and_then(|data| data.to_vec().map_err(|_| AttErrorCode::UNLIKELY_ERROR))
+    {
+        Ok(value) => value,
+        Err(error_code) => {
+            return AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::READ_BLOB_REQUEST,
+                handle_in_error: handle.into(),
+                error_code: error_code.into(),
+            }
+            .into()
+        }
+    };
+
+    // as per 5.3 3F 3.4.4.6 ATT_READ_BLOB_RSP, we truncate to MTU - 1
+    let value = &value[..value.len().min(mtu - 1)];
+    AttReadBlobResponseBuilder {
+        value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        core::uuid::Uuid,
+        gatt::{
+            ids::AttHandle,
+            server::{
+                att_database::{AttAttribute, AttPermissions},
+                test::test_att_db::TestAttDatabase,
+            },
+        },
+        packets::AttReadBlobRequestBuilder,
+        utils::packet::build_view_or_crash,
+    };
+
+    fn make_db_with_handle_and_value(handle: u16, value: Vec<u8>) -> TestAttDatabase {
+        TestAttDatabase::new(vec![(
+            AttAttribute {
+                handle: AttHandle(handle),
+                type_: Uuid::new(0x1234),
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
+            },
+            value,
+        )])
+    }
+
+    fn do_read_blob_request(
+        handle: u16,
+        offset: u16,
+        mtu: usize,
+        db: &TestAttDatabase,
+    ) -> AttChild {
+        let att_view = build_view_or_crash(AttReadBlobRequestBuilder {
+            attribute_handle: AttHandle(handle).into(),
+            offset,
+        });
+        tokio_test::block_on(handle_read_blob_request(att_view.view(), mtu, db))
+    }
+
+    #[test]
+    fn test_read_blob_with_offset() {
+        let db = make_db_with_handle_and_value(3, vec![1, 2, 3, 4, 5]);
+
+        let response = do_read_blob_request(3, 2, 31, &db);
+
+        response.to_vec().unwrap(); // check it serializes
+        assert_eq!(
+            response,
+            AttChild::AttReadBlobResponse(AttReadBlobResponseBuilder {
+                value: build_att_data(AttAttributeDataChild::RawData([3, 4, 5].into()))
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_blob_truncated_to_mtu() {
+        let db = make_db_with_handle_and_value(3, (0..40).collect());
+
+        // act: read the second chunk of a long value
+        let response = do_read_blob_request(3, 22, 23, &db);
+
+        // assert: the response is truncated to MTU - 1
+        assert_eq!(response.to_vec().unwrap(), (22..40).collect::<Vec<_>>());
+        let response = do_read_blob_request(3, 0, 23, &db);
+        assert_eq!(response.to_vec().unwrap(), (0..22).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_read_blob_offset_at_end() {
+        let db = make_db_with_handle_and_value(3, vec![1, 2, 3]);
+
+        let response = do_read_blob_request(3, 3, 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttReadBlobResponse(AttReadBlobResponseBuilder {
+                value: build_att_data(AttAttributeDataChild::RawData([].into()))
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_blob_invalid_offset() {
+        let db = make_db_with_handle_and_value(3, vec![1, 2, 3]);
+
+        let response = do_read_blob_request(3, 4, 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::READ_BLOB_REQUEST,
+                handle_in_error: AttHandle(3).into(),
+                error_code: AttErrorCode::INVALID_OFFSET.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_blob_missing_handle() {
+        let db = make_db_with_handle_and_value(3, vec![1, 2, 3]);
+
+        let response = do_read_blob_request(4, 0, 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::READ_BLOB_REQUEST,
+                handle_in_error: AttHandle(4).into(),
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
+            })
+        );
+    }
+}
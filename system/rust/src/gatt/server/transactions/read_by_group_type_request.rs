@@ -39,7 +39,7 @@ pub async fn handle_read_by_group_type_request(
         opcode_in_error: AttOpcode::READ_BY_GROUP_TYPE_REQUEST,
         handle_in_error: AttHandle::from(request.get_starting_handle()).into(),
         // the default error code if we just fail to find anything
-        error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+        error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
     };
 
     let Some(attrs) = filter_to_range(
@@ -47,7 +47,7 @@ pub async fn handle_read_by_group_type_request(
         request.get_ending_handle().into(),
         db.list_attributes().into_iter(),
     ) else {
-        failure_response.error_code = AttErrorCode::INVALID_HANDLE;
+        failure_response.error_code = AttErrorCode::INVALID_HANDLE.into();
         return Ok(failure_response.into());
     };
 
@@ -56,7 +56,7 @@ pub async fn handle_read_by_group_type_request(
     // UUIDs do exist)
     if !matches!(group_type, PRIMARY_SERVICE_DECLARATION_UUID | SECONDARY_SERVICE_DECLARATION_UUID)
     {
-        failure_response.error_code = AttErrorCode::UNSUPPORTED_GROUP_TYPE;
+        failure_response.error_code = AttErrorCode::UNSUPPORTED_GROUP_TYPE.into();
         return Ok(failure_response.into());
     }
 
@@ -80,7 +80,7 @@ pub async fn handle_read_by_group_type_request(
             }
         }
         Err(err) => {
-            failure_response.error_code = err;
+            failure_response.error_code = err.into();
             return Ok(failure_response.into());
         }
     }
@@ -117,7 +117,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(3),
                     type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -125,7 +130,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(4),
                     type_: CHARACTERISTIC_UUID,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![5, 6],
             ),
@@ -133,7 +143,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(5),
                     type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![6, 7],
             ),
@@ -197,7 +212,7 @@ mod test {
             AttErrorResponseBuilder {
                 handle_in_error: AttHandle(2).into(),
                 opcode_in_error: AttOpcode::READ_BY_GROUP_TYPE_REQUEST,
-                error_code: AttErrorCode::UNSUPPORTED_GROUP_TYPE,
+                error_code: AttErrorCode::UNSUPPORTED_GROUP_TYPE.into(),
             }
         );
     }
@@ -226,7 +241,7 @@ mod test {
             AttErrorResponseBuilder {
                 handle_in_error: AttHandle(3).into(),
                 opcode_in_error: AttOpcode::READ_BY_GROUP_TYPE_REQUEST,
-                error_code: AttErrorCode::INVALID_HANDLE,
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
             }
         )
     }
@@ -238,7 +253,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(3),
                 type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                permissions: AttPermissions { readable: true, writable: false },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![1, 2, 3, 4, 5],
         )]);
@@ -328,7 +348,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(3),
                     type_: PRIMARY_SERVICE_DECLARATION_UUID,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![4, 5],
             ),
@@ -336,7 +361,12 @@ mod test {
                 AttAttribute {
                     handle: AttHandle(4),
                     type_: CHARACTERISTIC_UUID,
-                    permissions: AttPermissions { readable: true, writable: false },
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: false,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
                 },
                 vec![5, 6],
             ),
@@ -411,7 +441,7 @@ mod test {
             AttErrorResponseBuilder {
                 handle_in_error: AttHandle(5).into(),
                 opcode_in_error: AttOpcode::READ_BY_GROUP_TYPE_REQUEST,
-                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
             }
         )
     }
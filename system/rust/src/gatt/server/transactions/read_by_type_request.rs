@@ -33,7 +33,7 @@ pub async fn handle_read_by_type_request(
         opcode_in_error: AttOpcode::READ_BY_TYPE_REQUEST,
         handle_in_error: AttHandle::from(request.get_starting_handle()).into(),
         // the default error code if we just fail to find anything
-        error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+        error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
     };
 
     let Some(attrs) = filter_to_range(
@@ -41,7 +41,7 @@ pub async fn handle_read_by_type_request(
         request.get_ending_handle().into(),
         db.list_attributes().into_iter(),
     ) else {
-        failure_response.error_code = AttErrorCode::INVALID_HANDLE;
+        failure_response.error_code = AttErrorCode::INVALID_HANDLE.into();
         return Ok(failure_response.into());
     };
 
@@ -61,7 +61,7 @@ pub async fn handle_read_by_type_request(
             }
         }
         Err(err) => {
-            failure_response.error_code = err;
+            failure_response.error_code = err.into();
             return Ok(failure_response.into());
         }
     }
@@ -279,7 +279,7 @@ mod test {
             AttErrorResponseBuilder {
                 handle_in_error: AttHandle(4).into(),
                 opcode_in_error: AttOpcode::READ_BY_TYPE_REQUEST,
-                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND,
+                error_code: AttErrorCode::ATTRIBUTE_NOT_FOUND.into(),
             }
         )
     }
@@ -307,7 +307,7 @@ mod test {
             AttErrorResponseBuilder {
                 handle_in_error: AttHandle(0).into(),
                 opcode_in_error: AttOpcode::READ_BY_TYPE_REQUEST,
-                error_code: AttErrorCode::INVALID_HANDLE,
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
             }
         )
     }
@@ -0,0 +1,253 @@
+use crate::{
+    gatt::{ids::AttHandle, server::att_database::AttDatabase},
+    packets::{
+        AttAttributeDataChild, AttChild, AttErrorCode, AttErrorResponseBuilder, AttHandleView,
+        AttOpcode, AttReadMultipleRequestView, AttReadMultipleResponseBuilder,
+        AttReadMultipleVariableRequestView, AttReadMultipleVariableResponseBuilder, Serializable,
+    },
+    utils::packet::build_att_data,
+};
+
+pub async fn handle_read_multiple_request<T: AttDatabase>(
+    request: AttReadMultipleRequestView<'_>,
+    mtu: usize,
+    db: &T,
+) -> AttChild {
+    match read_values(request.get_attribute_handles_iter(), db).await {
+        // as per 5.3 3F 3.4.4.8 ATT_READ_MULTIPLE_RSP, we concatenate the values
+        // and truncate to MTU - 1
+        Ok(values) => AttReadMultipleResponseBuilder {
+            values: build_att_data(truncate(values.concat(), mtu - 1)),
+        }
+        .into(),
+        Err((handle, error_code)) => AttErrorResponseBuilder {
+            opcode_in_error: AttOpcode::READ_MULTIPLE_REQUEST,
+            handle_in_error: handle.into(),
+            error_code: error_code.into(),
+        }
+        .into(),
+    }
+}
+
+pub async fn handle_read_multiple_variable_request<T: AttDatabase>(
+    request: AttReadMultipleVariableRequestView<'_>,
+    mtu: usize,
+    db: &T,
+) -> AttChild {
+    match read_values(request.get_attribute_handles_iter(), db).await {
+        // as per 5.3 3F 3.4.4.12 ATT_READ_MULTIPLE_VARIABLE_RSP, each value is
+        // prefixed by its full length, and the list is truncated to MTU - 1
+        // (possibly in the middle of a tuple)
+        Ok(values) => AttReadMultipleVariableResponseBuilder {
+            length_value_tuples: build_att_data(truncate(
+                values
+                    .into_iter()
+                    .flat_map(|value| {
+                        let len = u16::try_from(value.len()).unwrap_or(u16::MAX);
+                        len.to_le_bytes().into_iter().chain(value)
+                    })
+                    .collect(),
+                mtu - 1,
+            )),
+        }
+        .into(),
+        Err((handle, error_code)) => AttErrorResponseBuilder {
+            opcode_in_error: AttOpcode::READ_MULTIPLE_VARIABLE_REQUEST,
+            handle_in_error: handle.into(),
+            error_code: error_code.into(),
+        }
+        .into(),
+    }
+}
+
+/// Read each of the given handles (at least two are required), failing on the
+/// first one that cannot be read
+async fn read_values<'a, T: AttDatabase>(
+    handles: impl Iterator<Item = AttHandleView<'a>>,
+    db: &T,
+) -> Result<Vec<Vec<u8>>, (AttHandle, AttErrorCode)> {
+    let handles = handles.map(AttHandle::from).collect::<Vec<_>>();
+    if handles.len() < 2 {
+        return Err((AttHandle(0), AttErrorCode::INVALID_PDU));
+    }
+
+    let mut values = vec![];
+    for handle in handles {
+        let value = db
+            .read_attribute(handle)
+            .await
+            .and_then(|data| data.to_vec().map_err(|_| AttErrorCode::UNLIKELY_ERROR))
+            .map_err(|error_code| (handle, error_code))?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn truncate(mut data: Vec<u8>, len: usize) -> AttAttributeDataChild {
+    data.truncate(len);
+    AttAttributeDataChild::RawData(data.into_boxed_slice())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        core::uuid::Uuid,
+        gatt::server::{
+            att_database::{AttAttribute, AttPermissions},
+            test::test_att_db::TestAttDatabase,
+        },
+        packets::{AttReadMultipleRequestBuilder, AttReadMultipleVariableRequestBuilder},
+        utils::packet::build_view_or_crash,
+    };
+
+    fn make_db() -> TestAttDatabase {
+        TestAttDatabase::new(vec![
+            (
+                AttAttribute {
+                    handle: AttHandle(3),
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions::READONLY,
+                },
+                vec![1, 2, 3],
+            ),
+            (
+                AttAttribute {
+                    handle: AttHandle(5),
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions::READONLY,
+                },
+                vec![4, 5],
+            ),
+            (
+                AttAttribute {
+                    handle: AttHandle(6),
+                    type_: Uuid::new(0x1234),
+                    permissions: AttPermissions {
+                        readable: false,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                },
+                vec![6],
+            ),
+        ])
+    }
+
+    fn do_read_multiple_request(handles: &[u16], mtu: usize, db: &TestAttDatabase) -> AttChild {
+        let att_view = build_view_or_crash(AttReadMultipleRequestBuilder {
+            attribute_handles: handles.iter().map(|handle| AttHandle(*handle).into()).collect(),
+        });
+        tokio_test::block_on(handle_read_multiple_request(att_view.view(), mtu, db))
+    }
+
+    fn do_read_multiple_variable_request(
+        handles: &[u16],
+        mtu: usize,
+        db: &TestAttDatabase,
+    ) -> AttChild {
+        let att_view = build_view_or_crash(AttReadMultipleVariableRequestBuilder {
+            attribute_handles: handles.iter().map(|handle| AttHandle(*handle).into()).collect(),
+        });
+        tokio_test::block_on(handle_read_multiple_variable_request(att_view.view(), mtu, db))
+    }
+
+    #[test]
+    fn test_read_multiple() {
+        let db = make_db();
+
+        let response = do_read_multiple_request(&[5, 3], 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttReadMultipleResponse(AttReadMultipleResponseBuilder {
+                values: build_att_data(AttAttributeDataChild::RawData([4, 5, 1, 2, 3].into()))
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_multiple_truncated() {
+        let db = make_db();
+
+        let response = do_read_multiple_request(&[3, 5], 5, &db);
+
+        assert_eq!(response.to_vec().unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_read_multiple_error_reports_first_failing_handle() {
+        let db = make_db();
+
+        let response = do_read_multiple_request(&[3, 6, 7], 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::READ_MULTIPLE_REQUEST,
+                handle_in_error: AttHandle(6).into(),
+                error_code: AttErrorCode::READ_NOT_PERMITTED.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_multiple_single_handle() {
+        let db = make_db();
+
+        let response = do_read_multiple_request(&[3], 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::READ_MULTIPLE_REQUEST,
+                handle_in_error: AttHandle(0).into(),
+                error_code: AttErrorCode::INVALID_PDU.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_multiple_variable() {
+        let db = make_db();
+
+        let response = do_read_multiple_variable_request(&[3, 5], 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttReadMultipleVariableResponse(AttReadMultipleVariableResponseBuilder {
+                length_value_tuples: build_att_data(AttAttributeDataChild::RawData(
+                    [3, 0, 1, 2, 3, 2, 0, 4, 5].into()
+                ))
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_multiple_variable_truncated_within_tuple() {
+        let db = make_db();
+
+        let response = do_read_multiple_variable_request(&[3, 5], 8, &db);
+
+        // assert: the last tuple keeps the full length, but its value is cut off
+        assert_eq!(response.to_vec().unwrap(), vec![3, 0, 1, 2, 3, 2, 0]);
+    }
+
+    #[test]
+    fn test_read_multiple_variable_error() {
+        let db = make_db();
+
+        let response = do_read_multiple_variable_request(&[3, 4], 31, &db);
+
+        assert_eq!(
+            response,
+            AttChild::AttErrorResponse(AttErrorResponseBuilder {
+                opcode_in_error: AttOpcode::READ_MULTIPLE_VARIABLE_REQUEST,
+                handle_in_error: AttHandle(4).into(),
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
+            })
+        );
+    }
+}
@@ -24,7 +24,7 @@ pub async fn handle_read_request<T: AttDatabase>(
         Err(error_code) => AttErrorResponseBuilder {
             opcode_in_error: AttOpcode::READ_REQUEST,
             handle_in_error: handle.into(),
-            error_code,
+            error_code: error_code.into(),
         }
         .into(),
     }
@@ -52,7 +52,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(handle),
                 type_: Uuid::new(0x1234),
-                permissions: AttPermissions { readable: true, writable: false },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             value,
         )])
@@ -108,7 +113,7 @@ mod test {
             AttChild::AttErrorResponse(AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::READ_REQUEST,
                 handle_in_error: AttHandle(4).into(),
-                error_code: AttErrorCode::INVALID_HANDLE,
+                error_code: AttErrorCode::INVALID_HANDLE.into(),
             })
         );
     }
@@ -118,7 +123,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(handle),
                 type_: Uuid::new(0x1234),
-                permissions: AttPermissions { readable: false, writable: false },
+                permissions: AttPermissions {
+                    readable: false,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![],
         )])
@@ -137,7 +147,7 @@ mod test {
             AttChild::AttErrorResponse(AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::READ_REQUEST,
                 handle_in_error: AttHandle(3).into(),
-                error_code: AttErrorCode::READ_NOT_PERMITTED,
+                error_code: AttErrorCode::READ_NOT_PERMITTED.into(),
             })
         );
     }
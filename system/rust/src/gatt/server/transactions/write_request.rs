@@ -15,7 +15,7 @@ pub async fn handle_write_request<T: AttDatabase>(
         Err(error_code) => AttErrorResponseBuilder {
             opcode_in_error: AttOpcode::WRITE_REQUEST,
             handle_in_error: handle.into(),
-            error_code,
+            error_code: error_code.into(),
         }
         .into(),
     }
@@ -51,7 +51,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(1),
                 type_: Uuid::new(0x1234),
-                permissions: AttPermissions { readable: true, writable: true },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: true,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![],
         )]);
@@ -76,7 +81,12 @@ mod test {
             AttAttribute {
                 handle: AttHandle(1),
                 type_: Uuid::new(0x1234),
-                permissions: AttPermissions { readable: true, writable: false },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: false,
+                    writable_without_response: false,
+                    ..Default::default()
+                },
             },
             vec![],
         )]);
@@ -93,7 +103,7 @@ mod test {
             AttChild::from(AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::WRITE_REQUEST,
                 handle_in_error: AttHandle(1).into(),
-                error_code: AttErrorCode::WRITE_NOT_PERMITTED
+                error_code: AttErrorCode::WRITE_NOT_PERMITTED.into()
             })
         );
     }
@@ -54,6 +54,10 @@ pub struct ModuleViews<'a> {
 
 static GLOBAL_MODULE_REGISTRY: Mutex<Option<GlobalModuleRegistry>> = Mutex::new(None);
 
+/// Where the CCCD values of bonded peers are persisted, next to the rest of the
+/// stack's bonding state
+const CCCD_STORE_DIRECTORY: &str = "/data/misc/bluedroid/gatt_cccd";
+
 impl GlobalModuleRegistry {
     /// Handles bringup of all Rust modules. This occurs after GD C++ modules
     /// have started, but before the legacy stack has initialized.
@@ -83,6 +87,12 @@ impl GlobalModuleRegistry {
                 Rc::new(gatt::callbacks::CallbackTransactionManager::new(gatt_callbacks));
             let gatt_module =
                 &mut gatt::server::GattModule::new(gatt_callbacks.clone(), att_transport.clone());
+            gatt_module.set_sdp_publisher(Rc::new(gatt::ffi::SdpPublisherImpl()));
+            gatt_module
+                .set_signing_key_provider(Rc::new(gatt::ffi::SigningKeyProviderImpl::default()));
+            gatt_module.set_cccd_store(Rc::new(gatt::server::cccd_store::FileCccdStore::new(
+                CCCD_STORE_DIRECTORY,
+            )));
 
             // All modules that are visible from incoming JNI / top-level interfaces should
             // be exposed here
@@ -7,3 +7,87 @@
 #![feature(mixed_integer_ops)]
 
 include!(concat!(env!("OUT_DIR"), "/_packets.rs"));
+
+/// An ATT error code, from 5.3 3F 3.4.1.1. Besides the codes defined by ATT,
+/// those in 0x80-0x9F are defined by applications and those in 0xE0-0xFF by
+/// profiles (CSS Part B 1.2), so any value can be carried.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AttErrorCode(pub u8);
+
+macro_rules! att_error_codes {
+    ($($name:ident = $value:literal,)*) => {
+        impl AttErrorCode {
+            $(
+                #[allow(missing_docs)]
+                pub const $name: Self = Self($value);
+            )*
+
+            /// The name of this error code, if it is one of the named constants
+            pub fn name(self) -> Option<&'static str> {
+                match self {
+                    $(Self::$name => Some(stringify!($name)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+att_error_codes! {
+    INVALID_HANDLE = 0x01,
+    READ_NOT_PERMITTED = 0x02,
+    WRITE_NOT_PERMITTED = 0x03,
+    INVALID_PDU = 0x04,
+    INSUFFICIENT_AUTHENTICATION = 0x05,
+    REQUEST_NOT_SUPPORTED = 0x06,
+    INVALID_OFFSET = 0x07,
+    INSUFFICIENT_AUTHORIZATION = 0x08,
+    PREPARE_QUEUE_FULL = 0x09,
+    ATTRIBUTE_NOT_FOUND = 0x0A,
+    ATTRIBUTE_NOT_LONG = 0x0B,
+    INSUFFICIENT_ENCRYPTION_KEY_SIZE = 0x0C,
+    INVALID_ATTRIBUTE_VALUE_LENGTH = 0x0D,
+    UNLIKELY_ERROR = 0x0E,
+    INSUFFICIENT_ENCRYPTION = 0x0F,
+    UNSUPPORTED_GROUP_TYPE = 0x10,
+    DATABASE_OUT_OF_SYNC = 0x12,
+    VALUE_NOT_ALLOWED = 0x13,
+    // the common profile and service error codes, from CSS Part B 1.2
+    WRITE_REQUEST_REJECTED = 0xFC,
+    CCCD_IMPROPERLY_CONFIGURED = 0xFD,
+    PROCEDURE_ALREADY_IN_PROGRESS = 0xFE,
+    OUT_OF_RANGE = 0xFF,
+}
+
+impl AttErrorCode {
+    /// Whether this code is reserved for applications (0x80-0x9F)
+    pub fn is_application_error(self) -> bool {
+        (0x80..=0x9F).contains(&self.0)
+    }
+
+    /// Whether this code is reserved for profiles and services (0xE0-0xFF)
+    pub fn is_profile_error(self) -> bool {
+        self.0 >= 0xE0
+    }
+}
+
+impl std::fmt::Debug for AttErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "AttErrorCode({:#04x})", self.0),
+        }
+    }
+}
+
+impl From<u8> for AttErrorCode {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
+
+impl From<AttErrorCode> for u8 {
+    fn from(value: AttErrorCode) -> Self {
+        value.0
+    }
+}
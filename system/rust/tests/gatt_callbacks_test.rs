@@ -4,7 +4,10 @@ use std::rc::Rc;
 
 use bluetooth_core::{
     gatt::{
-        callbacks::{CallbackResponseError, CallbackTransactionManager, GattDatastore},
+        callbacks::{
+            CallbackResponseError, CallbackTransactionManager, GattDatastore, TransactionDecision,
+        },
+        channel::DisconnectReason,
         ids::{AttHandle, ConnectionId, ServerId, TransactionId, TransportIndex},
         mocks::mock_callbacks::{MockCallbackEvents, MockCallbacks},
     },
@@ -36,6 +39,8 @@ async fn pull_trans_id(events_rx: &mut UnboundedReceiver<MockCallbackEvents>) ->
     match events_rx.recv().await.unwrap() {
         MockCallbackEvents::OnServerReadCharacteristic(_, trans_id, _, _, _) => trans_id,
         MockCallbackEvents::OnServerWriteCharacteristic(_, trans_id, _, _, _, _, _) => trans_id,
+        MockCallbackEvents::OnServerExecuteWrite(_, trans_id, _) => trans_id,
+        _ => unreachable!(),
     }
 }
 
@@ -46,7 +51,9 @@ fn test_read_characteristic_callback() {
         let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
 
         // act: start read operation
-        spawn_local(async move { callback_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        spawn_local(async move {
+            callback_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
 
         // assert: verify the read callback is received
         let MockCallbackEvents::OnServerReadCharacteristic(
@@ -57,6 +64,26 @@ fn test_read_characteristic_callback() {
     });
 }
 
+#[test]
+fn test_read_blob_callback() {
+    start_test(async {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+
+        // act: start a read from an offset into the value
+        spawn_local(async move {
+            callback_manager.read_characteristic(CONN_ID, HANDLE_1, 22, true).await
+        });
+
+        // assert: verify the offset and is_long reach the callback
+        let MockCallbackEvents::OnServerReadCharacteristic(
+            CONN_ID, _, HANDLE_1, 22, true,
+        ) = callbacks_rx.recv().await.unwrap() else {
+          unreachable!()
+        };
+    });
+}
+
 #[test]
 fn test_read_characteristic_response() {
     start_test(async {
@@ -66,8 +93,9 @@ fn test_read_characteristic_response() {
 
         // act: start read operation
         let cloned_manager = callback_manager.clone();
-        let pending_read =
-            spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        let pending_read = spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
         // provide a response
         let trans_id = pull_trans_id(&mut callbacks_rx).await;
         callback_manager.send_response(CONN_ID, trans_id, data.clone()).unwrap();
@@ -87,16 +115,18 @@ fn test_sequential_reads() {
 
         // act: start read operation
         let cloned_manager = callback_manager.clone();
-        let pending_read_1 =
-            spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        let pending_read_1 = spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
         // respond to first
         let trans_id = pull_trans_id(&mut callbacks_rx).await;
         callback_manager.send_response(CONN_ID, trans_id, data1.clone()).unwrap();
 
         // do a second read operation
         let cloned_manager = callback_manager.clone();
-        let pending_read_2 =
-            spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        let pending_read_2 = spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
         // respond to second
         let trans_id = pull_trans_id(&mut callbacks_rx).await;
         callback_manager.send_response(CONN_ID, trans_id, data2.clone()).unwrap();
@@ -117,13 +147,15 @@ fn test_concurrent_reads() {
 
         // act: start read operation
         let cloned_manager = callback_manager.clone();
-        let pending_read_1 =
-            spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        let pending_read_1 = spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
 
         // do a second read operation
         let cloned_manager = callback_manager.clone();
-        let pending_read_2 =
-            spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        let pending_read_2 = spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
 
         // respond to first
         let trans_id = pull_trans_id(&mut callbacks_rx).await;
@@ -147,9 +179,13 @@ fn test_distinct_transaction_ids() {
 
         // act: start two read operations concurrently
         let cloned_manager = callback_manager.clone();
-        spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
         let cloned_manager = callback_manager.clone();
-        spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
 
         // pull both trans_ids
         let trans_id_1 = pull_trans_id(&mut callbacks_rx).await;
@@ -169,7 +205,9 @@ fn test_invalid_conn_id() {
 
         // act: start a read operation
         let cloned_manager = callback_manager.clone();
-        spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
         // respond with the correct trans_id but an invalid conn_id
         let trans_id = pull_trans_id(&mut callbacks_rx).await;
         let err = callback_manager.send_response(ANOTHER_CONN_ID, trans_id, data).unwrap_err();
@@ -188,7 +226,9 @@ fn test_invalid_trans_id() {
 
         // act: start a read operation
         let cloned_manager = callback_manager.clone();
-        spawn_local(async move { cloned_manager.read_characteristic(CONN_ID, HANDLE_1).await });
+        spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
         // respond with the correct conn_id but an invalid trans_id
         let trans_id = pull_trans_id(&mut callbacks_rx).await;
         let invalid_trans_id = TransactionId(trans_id.0 + 1);
@@ -226,6 +266,78 @@ fn test_write_characteristic_callback() {
     });
 }
 
+#[test]
+fn test_write_no_response_characteristic_callback() {
+    start_test(async move {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+        let data =
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData([1, 2].into())));
+
+        // act
+        callback_manager.write_no_response_characteristic(CONN_ID, HANDLE_1, data.view());
+
+        // assert: the callback is received, and no response is expected
+        let MockCallbackEvents::OnServerWriteCharacteristic(
+            CONN_ID,
+            trans_id,
+            HANDLE_1,
+            0,
+            /* needs_response = */ false,
+            false,
+            recv_data,
+        ) = callbacks_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(recv_data.view().get_raw_payload().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(
+            callback_manager.send_response(
+                CONN_ID,
+                trans_id,
+                Ok(AttAttributeDataChild::RawData([].into()))
+            ),
+            Err(CallbackResponseError::NonExistentTransaction(trans_id))
+        );
+    });
+}
+
+#[test]
+fn test_write_no_response_descriptor_callback() {
+    start_test(async move {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+        let data =
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData([1, 0].into())));
+
+        // act
+        callback_manager.write_no_response_descriptor(CONN_ID, HANDLE_1, data.view());
+
+        // assert: the callback is received, and no response is expected
+        let MockCallbackEvents::OnServerWriteDescriptor(
+            CONN_ID,
+            trans_id,
+            HANDLE_1,
+            0,
+            /* needs_response = */ false,
+            false,
+            recv_data,
+        ) = callbacks_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(recv_data.view().get_raw_payload().collect::<Vec<_>>(), vec![1, 0]);
+        assert_eq!(
+            callback_manager.send_response(
+                CONN_ID,
+                trans_id,
+                Ok(AttAttributeDataChild::RawData([].into()))
+            ),
+            Err(CallbackResponseError::NonExistentTransaction(trans_id))
+        );
+    });
+}
+
 #[test]
 fn test_write_characteristic_response() {
     start_test(async {
@@ -249,3 +361,129 @@ fn test_write_characteristic_response() {
         assert_eq!(pending_write.await.unwrap(), Err(AttErrorCode::WRITE_NOT_PERMITTED));
     });
 }
+
+#[test]
+fn test_profile_error_response() {
+    start_test(async {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+
+        // act: start read operation
+        let cloned_manager = callback_manager.clone();
+        let pending_read = spawn_local(async move {
+            cloned_manager.read_characteristic(CONN_ID, HANDLE_1, 0, false).await
+        });
+        // provide a response with an error code defined by a profile
+        let trans_id = pull_trans_id(&mut callbacks_rx).await;
+        callback_manager
+            .send_response(CONN_ID, trans_id, Err(AttErrorCode::PROCEDURE_ALREADY_IN_PROGRESS))
+            .unwrap();
+
+        // assert: that the error code was received
+        assert_eq!(pending_read.await.unwrap(), Err(AttErrorCode(0xFE)));
+    });
+}
+
+#[test]
+fn test_prepare_write_characteristic_callback() {
+    start_test(async {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+
+        // act: prepare a write
+        let data =
+            build_view_or_crash(build_att_data(AttAttributeDataChild::RawData([1, 2].into())));
+        let cloned_data = data.view().to_owned_packet();
+        let cloned_manager = callback_manager.clone();
+        let pending_write = spawn_local(async move {
+            cloned_manager
+                .prepare_write_characteristic(CONN_ID, HANDLE_1, 2, cloned_data.view())
+                .await
+        });
+
+        // assert: verify the write callback is received as a prepared write
+        let MockCallbackEvents::OnServerWriteCharacteristic(
+            CONN_ID,
+            trans_id,
+            HANDLE_1,
+            2,
+            /* needs_response = */ true,
+            /* is_prepare = */ true,
+            recv_data,
+        ) = callbacks_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            recv_data.view().get_raw_payload().collect::<Vec<_>>(),
+            data.view().get_raw_payload().collect::<Vec<_>>()
+        );
+        callback_manager
+            .send_response(CONN_ID, trans_id, Ok(AttAttributeDataChild::RawData([].into())))
+            .unwrap();
+        assert_eq!(pending_write.await.unwrap(), Ok(()));
+    });
+}
+
+#[test]
+fn test_execute_write_callback() {
+    start_test(async {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+
+        // act: execute the prepared writes
+        let cloned_manager = callback_manager.clone();
+        let pending_execution = spawn_local(async move {
+            cloned_manager.execute_write(CONN_ID, TransactionDecision::Cancel).await
+        });
+
+        // assert: verify the decision is forwarded, and the response is awaited
+        let MockCallbackEvents::OnServerExecuteWrite(
+            CONN_ID,
+            trans_id,
+            TransactionDecision::Cancel,
+        ) = callbacks_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        callback_manager
+            .send_response(CONN_ID, trans_id, Err(AttErrorCode::UNLIKELY_ERROR))
+            .unwrap();
+        assert_eq!(pending_execution.await.unwrap(), Err(AttErrorCode::UNLIKELY_ERROR));
+    });
+}
+
+#[test]
+fn test_mtu_changed_callback() {
+    start_test(async {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+
+        // act
+        callback_manager.on_mtu_changed(CONN_ID, 100);
+
+        // assert: verify the MTU change is forwarded to the callbacks
+        let MockCallbackEvents::OnMtuChanged(CONN_ID, 100) = callbacks_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+    });
+}
+
+#[test]
+fn test_link_dropped_callback() {
+    start_test(async {
+        // arrange
+        let (callback_manager, mut callbacks_rx) = initialize_manager_with_connection();
+
+        // act
+        callback_manager.on_link_dropped(CONN_ID, DisconnectReason::TransactionTimeout);
+
+        // assert: verify the reason is forwarded to the callbacks
+        let MockCallbackEvents::OnLinkDropped(CONN_ID, DisconnectReason::TransactionTimeout) =
+            callbacks_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+    });
+}
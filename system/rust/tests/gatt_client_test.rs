@@ -0,0 +1,542 @@
+use std::{collections::HashMap, rc::Rc};
+
+use bluetooth_core::{
+    core::uuid::Uuid,
+    gatt::{
+        callbacks::TransactionDecision,
+        client::{
+            att_client_bearer::{AttClientError, AttClientEvent},
+            CharacteristicProperties, DiscoveredCharacteristic, DiscoveredDescriptor,
+            DiscoveredService, GattClient,
+        },
+        ids::{AttHandle, ConnectionId, ServerId, TransportIndex},
+        mocks::{
+            mock_datastore::{MockDatastore, MockDatastoreEvents},
+            mock_transport::MockAttTransport,
+        },
+        server::{
+            gatt_database::{
+                AttPermissions, ClientConfiguration, GattCharacteristicWithHandle,
+                GattDescriptorWithHandle, GattServiceWithHandle,
+                CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+            },
+            GattModule, GATT_SERVICE_HANDLE,
+        },
+    },
+    packets::{
+        AttAttributeDataChild, AttBuilder, AttErrorCode, OwnedAttView, OwnedPacket, Serializable,
+    },
+};
+
+use tokio::{
+    select,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::spawn_local,
+};
+use utils::start_test;
+
+mod utils;
+
+const TCB_IDX: TransportIndex = TransportIndex(1);
+const SERVER_ID: ServerId = ServerId(2);
+const CONN_ID: ConnectionId = ConnectionId::new(TCB_IDX, SERVER_ID);
+
+const SERVICE_HANDLE: AttHandle = AttHandle(10);
+const CHARACTERISTIC_HANDLE: AttHandle = AttHandle(12);
+const CCCD_HANDLE: AttHandle = AttHandle(13);
+const READONLY_CHARACTERISTIC_HANDLE: AttHandle = AttHandle(15);
+
+const SERVICE_TYPE: Uuid = Uuid::new(0x0102);
+const CHARACTERISTIC_TYPE: Uuid = Uuid::new(0x0103);
+const READONLY_CHARACTERISTIC_TYPE: Uuid = Uuid::new(0x0104);
+
+const SERVER_RX_MTU: usize = 517;
+const CLIENT_RX_MTU: usize = 64;
+
+/// Actions the test can ask the server to take
+enum ServerCommand {
+    Notify(AttHandle, Vec<u8>),
+    Indicate(AttHandle, Vec<u8>),
+}
+
+fn to_view(packet: AttBuilder) -> OwnedAttView {
+    OwnedAttView::try_parse(packet.to_vec().unwrap().into_boxed_slice()).unwrap()
+}
+
+fn register_services(gatt: &mut GattModule) {
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+    gatt.register_gatt_service(
+        SERVER_ID,
+        GattServiceWithHandle {
+            handle: SERVICE_HANDLE,
+            type_: SERVICE_TYPE,
+            primary: true,
+            includes: vec![],
+            characteristics: vec![
+                GattCharacteristicWithHandle {
+                    handle: CHARACTERISTIC_HANDLE,
+                    type_: CHARACTERISTIC_TYPE,
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        writable_without_response: true,
+                        ..Default::default()
+                    },
+                    notify: true,
+                    indicate: true,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![GattDescriptorWithHandle {
+                        handle: CCCD_HANDLE,
+                        type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                        permissions: AttPermissions {
+                            readable: true,
+                            writable: true,
+                            ..Default::default()
+                        },
+                    }],
+                },
+                GattCharacteristicWithHandle {
+                    handle: READONLY_CHARACTERISTIC_HANDLE,
+                    type_: READONLY_CHARACTERISTIC_TYPE,
+                    permissions: AttPermissions::READONLY,
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
+                },
+            ],
+        },
+    )
+    .unwrap();
+}
+
+/// Start a GattModule and a GattClient, connected to each other through their
+/// mock transports. The characteristic values of the server are backed by a
+/// map, initialized with the supplied values.
+fn start_client_and_server(
+    values: impl IntoIterator<Item = (AttHandle, Vec<u8>)>,
+) -> (
+    Rc<GattClient>,
+    UnboundedSender<ServerCommand>,
+    UnboundedReceiver<AttClientEvent>,
+) {
+    let (datastore, mut data_rx) = MockDatastore::new();
+    let (server_transport, mut server_rx) = MockAttTransport::new();
+    let (client_transport, mut client_rx) = MockAttTransport::new();
+    let mut gatt = GattModule::new(Rc::new(datastore), Rc::new(server_transport));
+    register_services(&mut gatt);
+    gatt.on_le_connect(CONN_ID).unwrap();
+
+    let (client, event_rx) = GattClient::new(TCB_IDX, Rc::new(client_transport));
+    let client = Rc::new(client);
+    let (command_tx, mut command_rx) = unbounded_channel();
+
+    let mut values = values.into_iter().collect::<HashMap<_, _>>();
+    let mut prepared = vec![];
+    spawn_local({
+        let client = client.clone();
+        async move {
+            loop {
+                select! {
+                    Some((_, packet)) = client_rx.recv() => {
+                        gatt.handle_packet(CONN_ID, to_view(packet).view()).unwrap();
+                    }
+                    Some((_, packet)) = server_rx.recv() => {
+                        client.handle_packet(to_view(packet).view());
+                    }
+                    Some(event) = data_rx.recv() => match event {
+                        MockDatastoreEvents::ReadCharacteristic(_, handle, offset, _, tx) => {
+                            let value = values.get(&handle).cloned().unwrap_or_default();
+                            let value = value.get(offset..).ok_or(AttErrorCode::INVALID_OFFSET);
+                            tx.send(value.map(|value| AttAttributeDataChild::RawData(value.into()))).unwrap();
+                        }
+                        MockDatastoreEvents::WriteCharacteristic(_, handle, data, tx) => {
+                            values.insert(handle, data.view().get_raw_payload().collect());
+                            tx.send(Ok(())).unwrap();
+                        }
+                        MockDatastoreEvents::WriteNoResponseCharacteristic(_, handle, data) => {
+                            values.insert(handle, data.view().get_raw_payload().collect());
+                        }
+                        MockDatastoreEvents::PrepareWriteCharacteristic(_, handle, offset, data, tx) => {
+                            prepared.push((handle, offset, data.view().get_raw_payload().collect::<Vec<_>>()));
+                            tx.send(Ok(())).unwrap();
+                        }
+                        MockDatastoreEvents::ExecuteWrite(_, decision, tx) => {
+                            if decision == TransactionDecision::Execute {
+                                for (handle, offset, data) in prepared.drain(..) {
+                                    let value: &mut Vec<u8> = values.entry(handle).or_default();
+                                    let end = value.len().min(offset + data.len());
+                                    value.splice(offset..end, data);
+                                }
+                            }
+                            prepared.clear();
+                            tx.send(Ok(())).unwrap();
+                        }
+                        _ => {}
+                    },
+                    Some(command) = command_rx.recv() => match command {
+                        ServerCommand::Notify(handle, value) => gatt
+                            .send_notification(CONN_ID, handle, AttAttributeDataChild::RawData(value.into()))
+                            .unwrap(),
+                        ServerCommand::Indicate(handle, value) => {
+                            let indication = gatt
+                                .send_indication(CONN_ID, handle, AttAttributeDataChild::RawData(value.into()))
+                                .unwrap();
+                            spawn_local(indication);
+                        }
+                    },
+                    else => break,
+                }
+            }
+        }
+    });
+
+    (client, command_tx, event_rx)
+}
+
+#[test]
+fn test_discover_services() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        let primary = client.discover_primary_services().await.unwrap();
+        let secondary = client.discover_secondary_services().await.unwrap();
+
+        // assert
+        assert_eq!(primary.len(), 2);
+        assert_eq!(primary[0].handle, GATT_SERVICE_HANDLE);
+        assert_eq!(
+            primary[1],
+            DiscoveredService {
+                handle: SERVICE_HANDLE,
+                end_handle: READONLY_CHARACTERISTIC_HANDLE,
+                type_: SERVICE_TYPE,
+                primary: true
+            }
+        );
+        assert!(secondary.is_empty());
+    });
+}
+
+#[test]
+fn test_discover_characteristics_and_descriptors() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+        let service = client.discover_primary_services().await.unwrap()[1];
+
+        // act
+        let characteristics = client.discover_characteristics(&service).await.unwrap();
+        let descriptors = client.discover_descriptors(&characteristics[0]).await.unwrap();
+        let no_descriptors = client.discover_descriptors(&characteristics[1]).await.unwrap();
+
+        // assert
+        assert_eq!(
+            characteristics,
+            vec![
+                DiscoveredCharacteristic {
+                    declaration_handle: AttHandle(CHARACTERISTIC_HANDLE.0 - 1),
+                    value_handle: CHARACTERISTIC_HANDLE,
+                    end_handle: CCCD_HANDLE,
+                    type_: CHARACTERISTIC_TYPE,
+                    properties: CharacteristicProperties {
+                        read: true,
+                        write: true,
+                        write_without_response: true,
+                        notify: true,
+                        indicate: true,
+                        ..Default::default()
+                    }
+                },
+                DiscoveredCharacteristic {
+                    declaration_handle: AttHandle(READONLY_CHARACTERISTIC_HANDLE.0 - 1),
+                    value_handle: READONLY_CHARACTERISTIC_HANDLE,
+                    end_handle: READONLY_CHARACTERISTIC_HANDLE,
+                    type_: READONLY_CHARACTERISTIC_TYPE,
+                    properties: CharacteristicProperties { read: true, ..Default::default() }
+                }
+            ]
+        );
+        assert_eq!(
+            descriptors,
+            vec![DiscoveredDescriptor {
+                handle: CCCD_HANDLE,
+                type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID
+            }]
+        );
+        assert!(no_descriptors.is_empty());
+    });
+}
+
+#[test]
+fn test_read() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) =
+            start_client_and_server([(CHARACTERISTIC_HANDLE, vec![1, 2, 3])]);
+
+        // act
+        let value = client.read(CHARACTERISTIC_HANDLE).await.unwrap();
+
+        // assert
+        assert_eq!(value, vec![1, 2, 3]);
+    });
+}
+
+#[test]
+fn test_read_long() {
+    start_test(async {
+        // arrange: a value that spans several PDUs at the default MTU
+        let long_value = (0..100).collect::<Vec<u8>>();
+        let (client, _command_tx, _event_rx) =
+            start_client_and_server([(CHARACTERISTIC_HANDLE, long_value.clone())]);
+
+        // act
+        let truncated = client.read(CHARACTERISTIC_HANDLE).await.unwrap();
+        let value = client.read_long(CHARACTERISTIC_HANDLE).await.unwrap();
+
+        // assert
+        assert_eq!(truncated, long_value[..client.mtu() - 1]);
+        assert_eq!(value, long_value);
+    });
+}
+
+#[test]
+fn test_read_long_exact_multiple_of_part_length() {
+    start_test(async {
+        // arrange: a value that exactly fills two PDUs
+        let long_value = (0..44).collect::<Vec<u8>>();
+        let (client, _command_tx, _event_rx) =
+            start_client_and_server([(CHARACTERISTIC_HANDLE, long_value.clone())]);
+
+        // act
+        let value = client.read_long(CHARACTERISTIC_HANDLE).await.unwrap();
+
+        // assert
+        assert_eq!(value, long_value);
+    });
+}
+
+#[test]
+fn test_read_error() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        let res = client.read(AttHandle(100)).await;
+
+        // assert
+        assert!(matches!(
+            res,
+            Err(AttClientError::AttError {
+                handle: AttHandle(100),
+                error_code: AttErrorCode::INVALID_HANDLE
+            })
+        ));
+    });
+}
+
+#[test]
+fn test_write() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        client.write(CHARACTERISTIC_HANDLE, &[4, 5]).await.unwrap();
+
+        // assert
+        assert_eq!(client.read(CHARACTERISTIC_HANDLE).await.unwrap(), vec![4, 5]);
+    });
+}
+
+#[test]
+fn test_write_not_permitted() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        let res = client.write(READONLY_CHARACTERISTIC_HANDLE, &[4, 5]).await;
+
+        // assert
+        assert!(matches!(
+            res,
+            Err(AttClientError::AttError { error_code: AttErrorCode::WRITE_NOT_PERMITTED, .. })
+        ));
+    });
+}
+
+#[test]
+fn test_write_without_response() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        client.write_without_response(CHARACTERISTIC_HANDLE, &[6, 7]).unwrap();
+
+        // assert: the command is processed before the subsequent read
+        assert_eq!(client.read(CHARACTERISTIC_HANDLE).await.unwrap(), vec![6, 7]);
+    });
+}
+
+#[test]
+fn test_write_too_long_for_single_pdu() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        let res = client.write(CHARACTERISTIC_HANDLE, &[0; 21]).await;
+
+        // assert
+        assert!(matches!(res, Err(AttClientError::ValueTooLong)));
+    });
+}
+
+#[test]
+fn test_write_long() {
+    start_test(async {
+        // arrange
+        let long_value = (0..100).collect::<Vec<u8>>();
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        client.write_long(CHARACTERISTIC_HANDLE, &long_value).await.unwrap();
+
+        // assert
+        assert_eq!(client.read_long(CHARACTERISTIC_HANDLE).await.unwrap(), long_value);
+    });
+}
+
+#[test]
+fn test_exchange_mtu() {
+    start_test(async {
+        // arrange
+        let long_value = (0..100).collect::<Vec<u8>>();
+        let (client, _command_tx, _event_rx) =
+            start_client_and_server([(CHARACTERISTIC_HANDLE, long_value.clone())]);
+
+        // act
+        let mtu = client.exchange_mtu(CLIENT_RX_MTU).await.unwrap();
+
+        // assert: the smaller MTU is used, and reads are truncated to it
+        assert_eq!(mtu, CLIENT_RX_MTU.min(SERVER_RX_MTU));
+        assert_eq!(client.mtu(), mtu);
+        assert_eq!(client.read(CHARACTERISTIC_HANDLE).await.unwrap(), long_value[..mtu - 1]);
+    });
+}
+
+#[test]
+fn test_subscribe_to_notifications() {
+    start_test(async {
+        // arrange
+        let (client, command_tx, mut event_rx) = start_client_and_server([]);
+
+        // act
+        client
+            .subscribe(CCCD_HANDLE, ClientConfiguration { notify: true, indicate: false })
+            .await
+            .unwrap();
+        command_tx.send(ServerCommand::Notify(CHARACTERISTIC_HANDLE, vec![1, 2])).unwrap();
+
+        // assert
+        assert_eq!(client.read(CCCD_HANDLE).await.unwrap(), vec![1, 0]);
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            AttClientEvent::Notification { handle: CHARACTERISTIC_HANDLE, value: vec![1, 2] }
+        );
+    });
+}
+
+#[test]
+fn test_subscribe_to_indications() {
+    start_test(async {
+        // arrange
+        let (client, command_tx, mut event_rx) = start_client_and_server([]);
+
+        // act
+        client
+            .subscribe(CCCD_HANDLE, ClientConfiguration { notify: false, indicate: true })
+            .await
+            .unwrap();
+        command_tx.send(ServerCommand::Indicate(CHARACTERISTIC_HANDLE, vec![3])).unwrap();
+        command_tx.send(ServerCommand::Indicate(CHARACTERISTIC_HANDLE, vec![4])).unwrap();
+
+        // assert: the second indication is only sent once the first is confirmed
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            AttClientEvent::Indication { handle: CHARACTERISTIC_HANDLE, value: vec![3] }
+        );
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            AttClientEvent::Indication { handle: CHARACTERISTIC_HANDLE, value: vec![4] }
+        );
+    });
+}
+
+#[test]
+fn test_concurrent_procedures_are_queued() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([
+            (CHARACTERISTIC_HANDLE, vec![1]),
+            (READONLY_CHARACTERISTIC_HANDLE, vec![2]),
+        ]);
+
+        // act: start two reads at once
+        let (first, second) = tokio::join!(
+            client.read(CHARACTERISTIC_HANDLE),
+            client.read(READONLY_CHARACTERISTIC_HANDLE)
+        );
+
+        // assert
+        assert_eq!(first.unwrap(), vec![1]);
+        assert_eq!(second.unwrap(), vec![2]);
+    });
+}
+
+#[test]
+fn test_long_procedures_are_not_interleaved() {
+    start_test(async {
+        // arrange
+        let old_value = vec![1; 100];
+        let new_value = vec![2; 100];
+        let (client, _command_tx, _event_rx) =
+            start_client_and_server([(CHARACTERISTIC_HANDLE, old_value)]);
+
+        // act: start a long read while a long write is in progress
+        let (write, read) = tokio::join!(
+            client.write_long(CHARACTERISTIC_HANDLE, &new_value),
+            client.read_long(CHARACTERISTIC_HANDLE)
+        );
+
+        // assert: the read only starts once the write is executed, so never
+        // sees a mix of the two values
+        write.unwrap();
+        assert_eq!(read.unwrap(), new_value);
+    });
+}
+
+#[test]
+fn test_disconnect_fails_procedures() {
+    start_test(async {
+        // arrange
+        let (client, _command_tx, _event_rx) = start_client_and_server([]);
+
+        // act
+        client.on_disconnect();
+
+        // assert
+        assert!(matches!(
+            client.read(CHARACTERISTIC_HANDLE).await,
+            Err(AttClientError::BearerClosed)
+        ));
+    });
+}
@@ -0,0 +1,604 @@
+//! Conformance tests for the GATT server, replaying sequences of raw ATT PDUs
+//! modeled on the GATT test specification, and fuzzing it with malformed PDUs
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use bluetooth_core::{
+    core::uuid::Uuid,
+    gatt::{
+        ids::{AttHandle, ConnectionId, ServerId, TransportIndex},
+        mocks::{
+            mock_datastore::{MockDatastore, MockDatastoreEvents},
+            mock_transport::MockAttTransport,
+        },
+        server::{
+            gatt_database::{
+                AttPermissions, GattCharacteristicWithHandle, GattServiceWithHandle,
+                GattUserDescriptionWithHandle,
+            },
+            services::{LocalGattService, LocalValue},
+            GattModule,
+        },
+    },
+    packets::{
+        AttAttributeDataChild, AttBuilder, AttErrorCode, AttErrorResponseView,
+        AttExchangeMtuRequestView, AttExchangeMtuResponseView, AttExecuteWriteResponseView,
+        AttFindByTypeValueResponseView, AttFindInformationResponseView, AttOpcode,
+        AttPrepareWriteResponseView, AttReadBlobResponseView, AttReadByGroupTypeResponseView,
+        AttReadByTypeResponseView, AttReadMultipleResponseView,
+        AttReadMultipleVariableResponseView, AttReadResponseView, AttView, AttWriteResponseView,
+        OwnedAttView, OwnedPacket, Packet, Serializable,
+    },
+};
+use tokio::{sync::mpsc::UnboundedReceiver, task::spawn_local, time::timeout};
+use utils::start_test;
+
+mod utils;
+
+const TCB_IDX: TransportIndex = TransportIndex(1);
+const SERVER_ID: ServerId = ServerId(2);
+const CONN_ID: ConnectionId = ConnectionId::new(TCB_IDX, SERVER_ID);
+
+/// Long enough to be truncated at the default ATT_MTU
+const LONG_VALUE: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Returned by the datastore for every read
+const DATASTORE_VALUE: [u8; 2] = [0xD0, 0xD1];
+
+/// The database under test:
+///
+/// 0x0001-0x0008: the Generic Attribute service
+/// 0x0010-0x0017: a primary service (0xA000), with a long local value
+///                (0xA001), a value with a 128-bit UUID and a user
+///                description, and a value forwarded to the datastore (0xA002)
+/// 0x0020-0x0024: a secondary service (0xA100), with two values of the same
+///                type (0xA101) but different lengths
+fn create_server(gatt: &mut GattModule) {
+    let value = Rc::new(RefCell::new(LONG_VALUE.to_vec()));
+    let characteristic = |handle, type_, permissions| GattCharacteristicWithHandle {
+        handle: AttHandle(handle),
+        type_: Uuid::new(type_),
+        permissions,
+        notify: false,
+        indicate: false,
+        extended_properties: None,
+        user_description: None,
+        descriptors: vec![],
+    };
+    let read_write = AttPermissions { readable: true, writable: true, ..Default::default() };
+
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+    gatt.register_local_gatt_service(
+        SERVER_ID,
+        LocalGattService {
+            service: GattServiceWithHandle {
+                handle: AttHandle(0x10),
+                type_: Uuid::new(0xA000),
+                primary: true,
+                includes: vec![],
+                characteristics: vec![
+                    characteristic(0x12, 0xA001, read_write),
+                    GattCharacteristicWithHandle {
+                        user_description: Some(GattUserDescriptionWithHandle {
+                            handle: AttHandle(0x15),
+                            description: "temp".into(),
+                        }),
+                        ..characteristic(0x14, 0x12345678, AttPermissions::READONLY)
+                    },
+                    characteristic(0x17, 0xA002, read_write),
+                ],
+            },
+            values: HashMap::from([
+                (
+                    AttHandle(0x12),
+                    LocalValue::read_write(
+                        {
+                            let value = value.clone();
+                            move || value.borrow().clone()
+                        },
+                        move |new_value| {
+                            *value.borrow_mut() = new_value.to_vec();
+                            Ok(())
+                        },
+                    ),
+                ),
+                (AttHandle(0x14), LocalValue::Static(vec![0x01])),
+            ]),
+        },
+    )
+    .unwrap();
+    gatt.register_local_gatt_service(
+        SERVER_ID,
+        LocalGattService {
+            service: GattServiceWithHandle {
+                handle: AttHandle(0x20),
+                type_: Uuid::new(0xA100),
+                primary: false,
+                includes: vec![],
+                characteristics: vec![
+                    characteristic(0x22, 0xA101, AttPermissions::READONLY),
+                    characteristic(0x24, 0xA101, AttPermissions::READONLY),
+                ],
+            },
+            values: HashMap::from([
+                (AttHandle(0x22), LocalValue::Static(vec![0x02])),
+                (AttHandle(0x24), LocalValue::Static(vec![0x03, 0x04])),
+            ]),
+        },
+    )
+    .unwrap();
+    gatt.on_le_connect(CONN_ID).unwrap();
+}
+
+/// Answer every request to the datastore, so transactions forwarded to it
+/// always complete
+fn spawn_datastore_responder(mut data_rx: UnboundedReceiver<MockDatastoreEvents>) {
+    spawn_local(async move {
+        while let Some(event) = data_rx.recv().await {
+            match event {
+                MockDatastoreEvents::ReadCharacteristic(_, _, offset, _, reply) => {
+                    let value = DATASTORE_VALUE.get(offset..).ok_or(AttErrorCode::INVALID_OFFSET);
+                    let _ =
+                        reply.send(value.map(|value| AttAttributeDataChild::RawData(value.into())));
+                }
+                MockDatastoreEvents::WriteCharacteristic(_, _, _, reply)
+                | MockDatastoreEvents::PrepareWriteCharacteristic(_, _, _, _, reply)
+                | MockDatastoreEvents::ExecuteWrite(_, _, reply) => {
+                    let _ = reply.send(Ok(()));
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+fn start_server() -> (GattModule, UnboundedReceiver<(TransportIndex, AttBuilder)>) {
+    let (datastore, data_rx) = MockDatastore::new();
+    let (transport, transport_rx) = MockAttTransport::new();
+    let mut gatt = GattModule::new(Rc::new(datastore), Rc::new(transport));
+    spawn_datastore_responder(data_rx);
+    create_server(&mut gatt);
+    (gatt, transport_rx)
+}
+
+/// Send a raw PDU to the server, and return the raw PDU it sends back, if any
+async fn exchange(
+    gatt: &mut GattModule,
+    transport_rx: &mut UnboundedReceiver<(TransportIndex, AttBuilder)>,
+    request: &[u8],
+) -> Option<Vec<u8>> {
+    let request = OwnedAttView::try_parse(request.to_vec().into_boxed_slice()).ok()?;
+    gatt.handle_packet(CONN_ID, request.view()).unwrap();
+    let (tcb_idx, response) =
+        timeout(Duration::from_secs(1), transport_rx.recv()).await.ok()?.unwrap();
+    assert_eq!(tcb_idx, TCB_IDX);
+    Some(response.to_vec().unwrap())
+}
+
+/// The little-endian representation of a UUID in the Bluetooth base range
+fn uuid128(uuid: u32) -> Vec<u8> {
+    [0xFB, 0x34, 0x9B, 0x5F, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10, 0x00, 0x00]
+        .into_iter()
+        .chain(uuid.to_le_bytes())
+        .collect()
+}
+
+/// A named sequence of requests from the client, each with the response
+/// expected from the server
+struct ConformanceCase {
+    name: &'static str,
+    exchanges: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+fn case(name: &'static str, exchanges: Vec<(Vec<u8>, Vec<u8>)>) -> ConformanceCase {
+    ConformanceCase { name, exchanges }
+}
+
+fn conformance_cases() -> Vec<ConformanceCase> {
+    vec![
+        case(
+            "GATT/SR/GAD/BV-01-C discover all primary services",
+            vec![
+                (
+                    vec![0x10, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x28],
+                    [vec![0x11, 0x14, 0x01, 0x00, 0x08, 0x00], uuid128(0x1801)].concat(),
+                ),
+                (
+                    vec![0x10, 0x09, 0x00, 0xFF, 0xFF, 0x00, 0x28],
+                    [vec![0x11, 0x14, 0x10, 0x00, 0x17, 0x00], uuid128(0xA000)].concat(),
+                ),
+                (
+                    vec![0x10, 0x18, 0x00, 0xFF, 0xFF, 0x00, 0x28],
+                    vec![0x01, 0x10, 0x18, 0x00, 0x0A],
+                ),
+            ],
+        ),
+        case(
+            "GATT/SR/GAD/BV-02-C discover primary services by UUID",
+            vec![
+                (
+                    [vec![0x06, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x28], uuid128(0xA000)].concat(),
+                    vec![0x07, 0x10, 0x00, 0x17, 0x00],
+                ),
+                (
+                    [vec![0x06, 0x18, 0x00, 0xFF, 0xFF, 0x00, 0x28], uuid128(0xA000)].concat(),
+                    vec![0x01, 0x06, 0x18, 0x00, 0x0A],
+                ),
+            ],
+        ),
+        case(
+            "discover secondary services",
+            vec![
+                (
+                    vec![0x10, 0x01, 0x00, 0xFF, 0xFF, 0x01, 0x28],
+                    [vec![0x11, 0x14, 0x20, 0x00, 0x24, 0x00], uuid128(0xA100)].concat(),
+                ),
+                (
+                    vec![0x10, 0x25, 0x00, 0xFF, 0xFF, 0x01, 0x28],
+                    vec![0x01, 0x10, 0x25, 0x00, 0x0A],
+                ),
+            ],
+        ),
+        case(
+            "GATT/SR/GAD/BV-04-C discover all characteristics of a service",
+            vec![
+                (
+                    vec![0x08, 0x10, 0x00, 0x17, 0x00, 0x03, 0x28],
+                    [vec![0x09, 0x15, 0x11, 0x00, 0x0A, 0x12, 0x00], uuid128(0xA001)].concat(),
+                ),
+                (
+                    vec![0x08, 0x12, 0x00, 0x17, 0x00, 0x03, 0x28],
+                    [vec![0x09, 0x15, 0x13, 0x00, 0x02, 0x14, 0x00], uuid128(0x12345678)].concat(),
+                ),
+                (
+                    vec![0x08, 0x18, 0x00, 0x1F, 0x00, 0x03, 0x28],
+                    vec![0x01, 0x08, 0x18, 0x00, 0x0A],
+                ),
+            ],
+        ),
+        case(
+            "GATT/SR/GAD/BV-06-C discover descriptors, switching between UUID formats",
+            vec![
+                (
+                    vec![0x04, 0x10, 0x00, 0x17, 0x00],
+                    vec![
+                        0x05, 0x01, 0x10, 0x00, 0x00, 0x28, 0x11, 0x00, 0x03, 0x28, 0x12, 0x00,
+                        0x01, 0xA0, 0x13, 0x00, 0x03, 0x28,
+                    ],
+                ),
+                (
+                    vec![0x04, 0x14, 0x00, 0x17, 0x00],
+                    [vec![0x05, 0x02, 0x14, 0x00], uuid128(0x12345678)].concat(),
+                ),
+                (
+                    vec![0x04, 0x15, 0x00, 0x17, 0x00],
+                    vec![
+                        0x05, 0x01, 0x15, 0x00, 0x01, 0x29, 0x16, 0x00, 0x03, 0x28, 0x17, 0x00,
+                        0x02, 0xA0,
+                    ],
+                ),
+                (vec![0x04, 0x18, 0x00, 0x1F, 0x00], vec![0x01, 0x04, 0x18, 0x00, 0x0A]),
+            ],
+        ),
+        case(
+            "read by type stops when the value length changes",
+            vec![
+                (
+                    vec![0x08, 0x20, 0x00, 0x2F, 0x00, 0x01, 0xA1],
+                    vec![0x09, 0x03, 0x22, 0x00, 0x02],
+                ),
+                (
+                    vec![0x08, 0x23, 0x00, 0x2F, 0x00, 0x01, 0xA1],
+                    vec![0x09, 0x04, 0x24, 0x00, 0x03, 0x04],
+                ),
+            ],
+        ),
+        case(
+            "read by type with a 128-bit UUID",
+            vec![(
+                [vec![0x08, 0x20, 0x00, 0x2F, 0x00], uuid128(0xA101)].concat(),
+                vec![0x09, 0x03, 0x22, 0x00, 0x02],
+            )],
+        ),
+        case(
+            "GATT/SR/GAR/BI-02-C invalid handles",
+            vec![
+                (vec![0x0A, 0x00, 0x00], vec![0x01, 0x0A, 0x00, 0x00, 0x01]),
+                (vec![0x0A, 0x30, 0x00], vec![0x01, 0x0A, 0x30, 0x00, 0x01]),
+                (vec![0x0C, 0x30, 0x00, 0x00, 0x00], vec![0x01, 0x0C, 0x30, 0x00, 0x01]),
+                (vec![0x12, 0x30, 0x00, 0x01], vec![0x01, 0x12, 0x30, 0x00, 0x01]),
+            ],
+        ),
+        case(
+            "reversed and zero handle ranges",
+            vec![
+                (vec![0x04, 0x00, 0x00, 0xFF, 0xFF], vec![0x01, 0x04, 0x00, 0x00, 0x01]),
+                (vec![0x04, 0x05, 0x00, 0x01, 0x00], vec![0x01, 0x04, 0x05, 0x00, 0x01]),
+                (
+                    vec![0x06, 0x05, 0x00, 0x01, 0x00, 0x00, 0x28, 0x00, 0xA0],
+                    vec![0x01, 0x06, 0x05, 0x00, 0x01],
+                ),
+                (
+                    vec![0x08, 0x10, 0x00, 0x01, 0x00, 0x03, 0x28],
+                    vec![0x01, 0x08, 0x10, 0x00, 0x01],
+                ),
+                (
+                    vec![0x10, 0xFF, 0xFF, 0x01, 0x00, 0x00, 0x28],
+                    vec![0x01, 0x10, 0xFF, 0xFF, 0x01],
+                ),
+            ],
+        ),
+        case(
+            "read by group type with an unsupported group type",
+            vec![
+                (
+                    vec![0x10, 0x01, 0x00, 0xFF, 0xFF, 0x03, 0x28],
+                    vec![0x01, 0x10, 0x01, 0x00, 0x10],
+                ),
+                (
+                    vec![0x10, 0x01, 0x00, 0xFF, 0xFF, 0x01, 0xA0],
+                    vec![0x01, 0x10, 0x01, 0x00, 0x10],
+                ),
+            ],
+        ),
+        case(
+            "GATT/SR/GAR/BV-04-C long values are truncated at the ATT_MTU",
+            vec![
+                (vec![0x0A, 0x12, 0x00], [&[0x0B], &LONG_VALUE[..22]].concat()),
+                (vec![0x0C, 0x12, 0x00, 0x16, 0x00], [&[0x0D], &LONG_VALUE[22..]].concat()),
+                (vec![0x0C, 0x12, 0x00, 0x24, 0x00], vec![0x0D]),
+                (vec![0x0C, 0x12, 0x00, 0x25, 0x00], vec![0x01, 0x0C, 0x12, 0x00, 0x07]),
+                (
+                    vec![0x08, 0x10, 0x00, 0x17, 0x00, 0x01, 0xA0],
+                    [&[0x09, 0x15, 0x12, 0x00], &LONG_VALUE[..19]].concat(),
+                ),
+            ],
+        ),
+        case(
+            "responses grow with the exchanged ATT_MTU",
+            vec![
+                (vec![0x02, 0x40, 0x00], vec![0x03, 0x05, 0x02]),
+                (vec![0x0A, 0x12, 0x00], [&[0x0B], LONG_VALUE].concat()),
+                (
+                    vec![0x08, 0x10, 0x00, 0x17, 0x00, 0x03, 0x28],
+                    [
+                        vec![0x09, 0x15, 0x11, 0x00, 0x0A, 0x12, 0x00],
+                        uuid128(0xA001),
+                        vec![0x13, 0x00, 0x02, 0x14, 0x00],
+                        uuid128(0x12345678),
+                    ]
+                    .concat(),
+                ),
+            ],
+        ),
+        case(
+            "malformed MTU exchange is an invalid PDU",
+            vec![(vec![0x02, 0x40], vec![0x01, 0x02, 0x00, 0x00, 0x04])],
+        ),
+        case(
+            "GATT/SR/GAR/BI-01-C read not permitted",
+            vec![(vec![0x0A, 0x03, 0x00], vec![0x01, 0x0A, 0x03, 0x00, 0x02])],
+        ),
+        case(
+            "GATT/SR/GAW/BI-03-C write not permitted",
+            vec![(vec![0x12, 0x14, 0x00, 0x01], vec![0x01, 0x12, 0x14, 0x00, 0x03])],
+        ),
+        case(
+            "GATT/SR/GAW/BV-03-C write characteristic value",
+            vec![
+                (vec![0x12, 0x12, 0x00, 0x41, 0x42], vec![0x13]),
+                (vec![0x0A, 0x12, 0x00], vec![0x0B, 0x41, 0x42]),
+                (vec![0x12, 0x17, 0x00, 0x01], vec![0x13]),
+                (vec![0x0A, 0x17, 0x00], [&[0x0B], &DATASTORE_VALUE[..]].concat()),
+            ],
+        ),
+        case(
+            "read characteristic user description",
+            vec![(vec![0x0A, 0x15, 0x00], [&[0x0B], &b"temp"[..]].concat())],
+        ),
+        case(
+            "PDUs that are not requests are not supported",
+            vec![
+                (vec![0x13], vec![0x01, 0x13, 0x00, 0x00, 0x06]),
+                (vec![0x0B, 0x01], vec![0x01, 0x0B, 0x00, 0x00, 0x06]),
+                (vec![0x1B, 0x12, 0x00, 0x01], vec![0x01, 0x1B, 0x00, 0x00, 0x06]),
+            ],
+        ),
+    ]
+}
+
+#[test]
+fn test_conformance() {
+    start_test(async move {
+        for ConformanceCase { name, exchanges } in conformance_cases() {
+            // arrange
+            let (mut gatt, mut transport_rx) = start_server();
+
+            for (i, (request, expected)) in exchanges.into_iter().enumerate() {
+                // act
+                let response = exchange(&mut gatt, &mut transport_rx, &request).await;
+
+                // assert
+                assert_eq!(
+                    response.as_ref(),
+                    Some(&expected),
+                    "{name}: unexpected response to request {i} ({request:02X?})"
+                );
+            }
+        }
+    })
+}
+
+/// A small deterministic PRNG (xorshift64*), so any failure can be reproduced
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+const OPCODES: [AttOpcode; 24] = [
+    AttOpcode::ERROR_RESPONSE,
+    AttOpcode::EXCHANGE_MTU_REQUEST,
+    AttOpcode::EXCHANGE_MTU_RESPONSE,
+    AttOpcode::FIND_INFORMATION_REQUEST,
+    AttOpcode::FIND_INFORMATION_RESPONSE,
+    AttOpcode::FIND_BY_TYPE_VALUE_REQUEST,
+    AttOpcode::FIND_BY_TYPE_VALUE_RESPONSE,
+    AttOpcode::READ_BY_TYPE_REQUEST,
+    AttOpcode::READ_BY_TYPE_RESPONSE,
+    AttOpcode::READ_REQUEST,
+    AttOpcode::READ_RESPONSE,
+    AttOpcode::READ_BLOB_REQUEST,
+    AttOpcode::READ_MULTIPLE_REQUEST,
+    AttOpcode::READ_BY_GROUP_TYPE_REQUEST,
+    AttOpcode::WRITE_REQUEST,
+    AttOpcode::WRITE_RESPONSE,
+    AttOpcode::PREPARE_WRITE_REQUEST,
+    AttOpcode::EXECUTE_WRITE_REQUEST,
+    AttOpcode::WRITE_COMMAND,
+    AttOpcode::SIGNED_WRITE_COMMAND,
+    AttOpcode::READ_MULTIPLE_VARIABLE_REQUEST,
+    AttOpcode::HANDLE_VALUE_NOTIFICATION,
+    AttOpcode::HANDLE_VALUE_INDICATION,
+    AttOpcode::HANDLE_VALUE_CONFIRMATION,
+];
+
+/// Build a PDU out of fields that are plausible for ATT (handles near the
+/// attributes of the database, and 16-bit or 128-bit UUIDs of declarations),
+/// mixed with random bytes, and possibly truncated
+fn random_pdu(rng: &mut Rng) -> Vec<u8> {
+    let mut pdu =
+        vec![if rng.below(10) == 0 { rng.next() as u8 } else { u8::from(rng.pick(&OPCODES)) }];
+    for _ in 0..rng.below(7) {
+        match rng.below(5) {
+            0 => pdu.extend(
+                rng.pick(&[0x0000u16, 0x0001, 0x0012, 0x0017, 0x0024, 0xFFFF]).to_le_bytes(),
+            ),
+            1 => pdu.extend((rng.below(0x30) as u16).to_le_bytes()),
+            2 => pdu.extend(
+                rng.pick(&[0x2800u16, 0x2801, 0x2802, 0x2803, 0x2902, 0xA101]).to_le_bytes(),
+            ),
+            3 => pdu.extend(uuid128(rng.pick(&[0x2800, 0x2803, 0xA001, 0x12345678]))),
+            _ => pdu.extend((0..rng.below(4)).map(|_| rng.next() as u8)),
+        }
+    }
+    if rng.below(4) == 0 {
+        pdu.truncate(1 + rng.below(pdu.len()));
+    }
+    pdu
+}
+
+/// Whether the PDU parses fully as the PDU indicated by its opcode
+fn is_well_formed_response(response: AttView<'_>) -> bool {
+    match response.get_opcode() {
+        AttOpcode::ERROR_RESPONSE => AttErrorResponseView::try_parse(response).is_ok(),
+        AttOpcode::EXCHANGE_MTU_RESPONSE => AttExchangeMtuResponseView::try_parse(response).is_ok(),
+        AttOpcode::FIND_INFORMATION_RESPONSE => {
+            AttFindInformationResponseView::try_parse(response).is_ok()
+        }
+        AttOpcode::FIND_BY_TYPE_VALUE_RESPONSE => {
+            AttFindByTypeValueResponseView::try_parse(response).is_ok()
+        }
+        AttOpcode::READ_BY_TYPE_RESPONSE => AttReadByTypeResponseView::try_parse(response).is_ok(),
+        AttOpcode::READ_RESPONSE => AttReadResponseView::try_parse(response).is_ok(),
+        AttOpcode::READ_BLOB_RESPONSE => AttReadBlobResponseView::try_parse(response).is_ok(),
+        AttOpcode::READ_MULTIPLE_RESPONSE => {
+            AttReadMultipleResponseView::try_parse(response).is_ok()
+        }
+        AttOpcode::READ_BY_GROUP_TYPE_RESPONSE => {
+            AttReadByGroupTypeResponseView::try_parse(response).is_ok()
+        }
+        AttOpcode::WRITE_RESPONSE => AttWriteResponseView::try_parse(response).is_ok(),
+        AttOpcode::PREPARE_WRITE_RESPONSE => {
+            AttPrepareWriteResponseView::try_parse(response).is_ok()
+        }
+        AttOpcode::EXECUTE_WRITE_RESPONSE => {
+            AttExecuteWriteResponseView::try_parse(response).is_ok()
+        }
+        AttOpcode::READ_MULTIPLE_VARIABLE_RESPONSE => {
+            AttReadMultipleVariableResponseView::try_parse(response).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[test]
+fn test_fuzzed_pdus() {
+    start_test(async move {
+        let (mut gatt, mut transport_rx) = start_server();
+        let mut rng = Rng(0x5EED_0FA7_75E4_E4E4);
+        let mut mtu = 23;
+
+        for _ in 0..5000 {
+            let pdu = random_pdu(&mut rng);
+            // PDUs with unknown opcodes are dropped before reaching the server
+            let Ok(request) = OwnedAttView::try_parse(pdu.clone().into_boxed_slice()) else {
+                continue;
+            };
+            let opcode = request.view().get_opcode();
+
+            gatt.handle_packet(CONN_ID, request.view()).unwrap();
+
+            // commands and confirmations are never answered...
+            if !matches!(
+                opcode,
+                AttOpcode::WRITE_COMMAND
+                    | AttOpcode::SIGNED_WRITE_COMMAND
+                    | AttOpcode::HANDLE_VALUE_CONFIRMATION
+            ) {
+                // ...but everything else gets exactly one well-formed response,
+                // either of the matching type, or an error about this PDU
+                let (_, response) = timeout(Duration::from_secs(1), transport_rx.recv())
+                    .await
+                    .unwrap_or_else(|_| panic!("no response to {pdu:02X?}"))
+                    .unwrap();
+                let response = response.to_vec().unwrap();
+                assert!(
+                    response.len() <= mtu,
+                    "response {response:02X?} to {pdu:02X?} exceeds the ATT_MTU"
+                );
+                let response = OwnedAttView::try_parse(response.into_boxed_slice()).unwrap();
+                let response = response.view();
+                assert!(is_well_formed_response(response), "malformed response to {pdu:02X?}");
+                match response.get_opcode() {
+                    AttOpcode::ERROR_RESPONSE => assert_eq!(
+                        AttErrorResponseView::try_parse(response).unwrap().get_opcode_in_error(),
+                        opcode,
+                        "error response to {pdu:02X?} is about another opcode"
+                    ),
+                    AttOpcode::EXCHANGE_MTU_RESPONSE => {
+                        // track the largest ATT_MTU the exchange could have set
+                        let client_rx_mtu =
+                            AttExchangeMtuRequestView::try_parse(request.view()).unwrap().get_mtu();
+                        mtu = mtu.max(usize::from(client_rx_mtu).min(517));
+                    }
+                    response_opcode => assert_eq!(
+                        u8::from(response_opcode),
+                        u8::from(opcode) + 1,
+                        "response to {pdu:02X?} is of the wrong type"
+                    ),
+                }
+            }
+
+            // the server is still responsive, and sent nothing else
+            let ping = exchange(&mut gatt, &mut transport_rx, &[0x0A, 0x10, 0x00]).await;
+            assert_eq!(
+                ping.as_ref().map(|response| response[0]),
+                Some(u8::from(AttOpcode::READ_RESPONSE)),
+                "unexpected response to a ping after {pdu:02X?}"
+            );
+        }
+    })
+}
@@ -4,25 +4,56 @@ use bluetooth_core::{
     core::uuid::Uuid,
     gatt::{
         self,
-        ids::{AttHandle, ConnectionId, ServerId, TransportIndex},
+        callbacks::TransactionDecision,
+        channel::DisconnectReason,
+        ids::{
+            AttHandle, BearerId, ConnectionId, EnhancedChannelId, PeerIdentity, ServerId,
+            TransportIndex,
+        },
         mocks::{
             mock_datastore::{MockDatastore, MockDatastoreEvents},
-            mock_transport::MockAttTransport,
+            mock_sdp_publisher::{MockSdpEvents, MockSdpPublisher},
+            mock_transport::{MockAttTransport, MockEnhancedChannelEvents},
         },
         server::{
-            gatt_database::{AttPermissions, GattCharacteristicWithHandle, GattServiceWithHandle},
-            GattModule,
+            att_server_bearer::{MIN_BR_EDR_ATT_MTU, MIN_ENHANCED_ATT_MTU},
+            cccd_store::FileCccdStore,
+            gatt_database::{
+                AttPermissions, AttSecurityRequirements, ClientConfiguration, ConnectionSecurity,
+                GattCharacteristicWithHandle, GattDescriptorWithHandle, GattIncludeWithHandle,
+                GattServiceWithHandle, CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                INCLUDE_DECLARATION_UUID, SECONDARY_SERVICE_DECLARATION_UUID,
+            },
+            sdp::SdpServiceRecord,
+            services::{
+                battery::{battery_level_handle, battery_service},
+                gap::generic_access_service,
+            },
+            signing::{compute_mac, PeerSigningKey, SigningKeyProvider},
+            GattModule, GATT_SERVICE_HANDLE, MAX_ENHANCED_BEARERS,
         },
     },
     packets::{
-        AttAttributeDataChild, AttBuilder, AttErrorCode, AttErrorResponseBuilder, AttOpcode,
-        AttReadRequestBuilder, AttReadResponseBuilder, AttWriteRequestBuilder,
-        AttWriteResponseBuilder, GattServiceDeclarationValueBuilder, Serializable,
+        AttAttributeDataChild, AttBuilder, AttErrorCode, AttErrorResponseBuilder,
+        AttExchangeMtuRequestBuilder, AttExchangeMtuResponseBuilder, AttExecuteWriteRequestBuilder,
+        AttExecuteWriteResponseBuilder, AttHandleValueConfirmationBuilder,
+        AttHandleValueIndicationBuilder, AttHandleValueNotificationBuilder, AttOpcode,
+        AttPrepareWriteRequestBuilder, AttPrepareWriteResponseBuilder, AttReadBlobRequestBuilder,
+        AttReadBlobResponseBuilder, AttReadByGroupTypeDataElementBuilder,
+        AttReadByGroupTypeRequestBuilder, AttReadByGroupTypeResponseBuilder,
+        AttReadByTypeDataElementBuilder, AttReadByTypeRequestBuilder, AttReadByTypeResponseBuilder,
+        AttReadRequestBuilder, AttReadResponseBuilder, AttSignedWriteCommandBuilder,
+        AttWriteCommandBuilder, AttWriteRequestBuilder, AttWriteResponseBuilder,
+        GattClientCharacteristicConfigurationBuilder, GattServiceChangedBuilder,
+        GattServiceDeclarationValueBuilder, Serializable,
     },
     utils::packet::{build_att_data, build_att_view_or_crash},
 };
 
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::{
+    sync::mpsc::{error::TryRecvError, UnboundedReceiver},
+    task::spawn_local,
+};
 use utils::start_test;
 
 mod utils;
@@ -30,10 +61,12 @@ mod utils;
 const TCB_IDX: TransportIndex = TransportIndex(1);
 const SERVER_ID: ServerId = ServerId(2);
 const CONN_ID: ConnectionId = ConnectionId::new(TCB_IDX, SERVER_ID);
-const HANDLE_1: AttHandle = AttHandle(3);
-const HANDLE_2: AttHandle = AttHandle(5);
+const HANDLE_1: AttHandle = AttHandle(10);
+const HANDLE_2: AttHandle = AttHandle(12);
+const CCCD_HANDLE: AttHandle = AttHandle(13);
 const UUID_1: Uuid = Uuid::new(0x0102);
 const UUID_2: Uuid = Uuid::new(0x0103);
+const PEER: PeerIdentity = PeerIdentity([1, 2, 3, 4, 5, 6]);
 
 fn start_gatt_module() -> (
     gatt::server::GattModule,
@@ -54,10 +87,23 @@ fn create_server_and_open_connection(gatt: &mut GattModule) {
         GattServiceWithHandle {
             handle: HANDLE_1,
             type_: UUID_1,
+            primary: true,
+            includes: vec![],
             characteristics: vec![GattCharacteristicWithHandle {
                 handle: HANDLE_2,
                 type_: UUID_2,
-                permissions: AttPermissions { readable: true, writable: true },
+                permissions: AttPermissions {
+                    readable: true,
+                    writable: true,
+                    writable_without_response: true,
+                    writable_signed: true,
+                    ..Default::default()
+                },
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
             }],
         },
     )
@@ -65,6 +111,51 @@ fn create_server_and_open_connection(gatt: &mut GattModule) {
     gatt.on_le_connect(CONN_ID).unwrap();
 }
 
+fn create_server_with_cccd(gatt: &mut GattModule) {
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+    gatt.register_gatt_service(
+        SERVER_ID,
+        GattServiceWithHandle {
+            handle: HANDLE_1,
+            type_: UUID_1,
+            primary: true,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: HANDLE_2,
+                type_: UUID_2,
+                permissions: AttPermissions::READONLY,
+                notify: true,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![GattDescriptorWithHandle {
+                    handle: CCCD_HANDLE,
+                    type_: CLIENT_CHARACTERISTIC_CONFIGURATION_UUID,
+                    permissions: AttPermissions {
+                        readable: true,
+                        writable: true,
+                        writable_without_response: false,
+                        ..Default::default()
+                    },
+                }],
+            }],
+        },
+    )
+    .unwrap();
+}
+
+fn write_cccd(gatt: &mut GattModule, value: [u8; 2]) {
+    gatt.handle_packet(
+        CONN_ID,
+        build_att_view_or_crash(AttWriteRequestBuilder {
+            handle: CCCD_HANDLE.into(),
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        })
+        .view(),
+    )
+    .unwrap();
+}
+
 #[test]
 fn test_connection_creation() {
     start_test(async move {
@@ -74,7 +165,13 @@ fn test_connection_creation() {
         gatt.open_gatt_server(SERVER_ID).unwrap();
         gatt.register_gatt_service(
             SERVER_ID,
-            GattServiceWithHandle { handle: HANDLE_1, type_: UUID_1, characteristics: vec![] },
+            GattServiceWithHandle {
+                handle: HANDLE_1,
+                type_: UUID_1,
+                primary: true,
+                includes: vec![],
+                characteristics: vec![],
+            },
         )
         .unwrap();
 
@@ -97,7 +194,13 @@ fn test_disconnection() {
         gatt.open_gatt_server(SERVER_ID).unwrap();
         gatt.register_gatt_service(
             SERVER_ID,
-            GattServiceWithHandle { handle: HANDLE_1, type_: UUID_1, characteristics: vec![] },
+            GattServiceWithHandle {
+                handle: HANDLE_1,
+                type_: UUID_1,
+                primary: true,
+                includes: vec![],
+                characteristics: vec![],
+            },
         )
         .unwrap();
         gatt.on_le_connect(CONN_ID).unwrap();
@@ -177,7 +280,7 @@ fn test_server_closed_while_connected() {
             AttErrorResponseBuilder {
                 opcode_in_error: AttOpcode::READ_REQUEST,
                 handle_in_error: HANDLE_1.into(),
-                error_code: AttErrorCode::INVALID_HANDLE
+                error_code: AttErrorCode::INVALID_HANDLE.into()
             }
             .into()
         )
@@ -202,7 +305,7 @@ fn test_characteristic_read() {
                 .view(),
         )
         .unwrap();
-        let tx = if let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, tx) =
+        let tx = if let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, 0, false, tx) =
             data_rx.recv().await.unwrap()
         {
             tx
@@ -224,6 +327,41 @@ fn test_characteristic_read() {
     })
 }
 
+#[test]
+fn test_characteristic_read_application_error() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act: the upper layer fails the read with an application-defined error code
+        gatt.handle_packet(CONN_ID, build_att_view_or_crash(build_read_request(HANDLE_2)).view())
+            .unwrap();
+        let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, 0, false, tx) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        tx.send(Err(AttErrorCode(0x85))).unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: the code is sent to the peer unchanged
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::ERROR_RESPONSE,
+                _child_: AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::READ_REQUEST,
+                    handle_in_error: HANDLE_2.into(),
+                    error_code: 0x85,
+                }
+                .into()
+            }
+        );
+    })
+}
+
 #[test]
 fn test_characteristic_write() {
     start_test(async move {
@@ -271,3 +409,1547 @@ fn test_characteristic_write() {
         )
     })
 }
+
+#[test]
+fn test_mtu_exchange() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        gatt.set_server_rx_mtu(100).unwrap();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttExchangeMtuRequestBuilder { mtu: 64 }).view(),
+        )
+        .unwrap();
+        let (tcb_idx, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: the server Rx MTU is sent back, and the upper layer learns the new MTU
+        assert_eq!(tcb_idx, TCB_IDX);
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::EXCHANGE_MTU_RESPONSE,
+                _child_: AttExchangeMtuResponseBuilder { mtu: 100 }.into()
+            }
+        );
+        assert!(matches!(
+            data_rx.recv().await.unwrap(),
+            MockDatastoreEvents::MtuChanged(CONN_ID, 64)
+        ));
+    })
+}
+
+#[test]
+fn test_mtu_exchanged_by_legacy_stack() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        gatt.set_server_rx_mtu(100).unwrap();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act: the legacy stack answered an exchange with a larger client Rx MTU
+        gatt.on_mtu_exchanged(CONN_ID, 200).unwrap();
+
+        // assert: nothing is sent, and the ATT_MTU is capped by the server Rx MTU
+        assert!(matches!(
+            data_rx.recv().await.unwrap(),
+            MockDatastoreEvents::MtuChanged(CONN_ID, 100)
+        ));
+        assert_eq!(transport_rx.try_recv(), Err(TryRecvError::Empty));
+    })
+}
+
+#[test]
+fn test_invalid_server_rx_mtu() {
+    let (mut gatt, _, _) = start_gatt_module();
+
+    assert!(gatt.set_server_rx_mtu(22).is_err());
+    assert!(gatt.set_server_rx_mtu(518).is_err());
+}
+
+#[test]
+fn test_notification() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        let data = AttAttributeDataChild::RawData([1, 2].into());
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act
+        gatt.send_notification(CONN_ID, HANDLE_2, data.clone()).unwrap();
+        let (tcb_idx, resp) = transport_rx.recv().await.unwrap();
+
+        // assert
+        assert_eq!(tcb_idx, TCB_IDX);
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::HANDLE_VALUE_NOTIFICATION,
+                _child_: AttHandleValueNotificationBuilder {
+                    handle: HANDLE_2.into(),
+                    value: build_att_data(data)
+                }
+                .into()
+            }
+        );
+    })
+}
+
+#[test]
+fn test_indication_confirmed() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        let data = AttAttributeDataChild::RawData([1, 2].into());
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act: send an indication, then confirm it from the peer
+        let pending_indication =
+            spawn_local(gatt.send_indication(CONN_ID, HANDLE_2, data.clone()).unwrap());
+        let (tcb_idx, resp) = transport_rx.recv().await.unwrap();
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttHandleValueConfirmationBuilder {}).view(),
+        )
+        .unwrap();
+
+        // assert
+        assert_eq!(tcb_idx, TCB_IDX);
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::HANDLE_VALUE_INDICATION,
+                _child_: AttHandleValueIndicationBuilder {
+                    handle: HANDLE_2.into(),
+                    value: build_att_data(data)
+                }
+                .into()
+            }
+        );
+        assert!(pending_indication.await.unwrap().is_ok());
+    })
+}
+
+#[test]
+fn test_indication_fails_on_disconnect() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act: send an indication, then disconnect before it is confirmed
+        let pending_indication = spawn_local(
+            gatt.send_indication(CONN_ID, HANDLE_2, AttAttributeDataChild::RawData([1].into()))
+                .unwrap(),
+        );
+        transport_rx.recv().await.unwrap();
+        gatt.on_le_disconnect(CONN_ID);
+
+        // assert
+        assert!(pending_indication.await.unwrap().is_err());
+    })
+}
+
+#[test]
+fn test_notification_on_unknown_connection() {
+    let (gatt, _, _) = start_gatt_module();
+
+    let res = gatt.send_notification(CONN_ID, HANDLE_2, AttAttributeDataChild::RawData([].into()));
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_cccd_write_and_read() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_cccd(&mut gatt);
+        gatt.on_le_connect(CONN_ID).unwrap();
+        data_rx.recv().await.unwrap();
+
+        // act: subscribe to notifications, then read back the CCCD
+        write_cccd(&mut gatt, [0x01, 0x00]);
+        let (_, write_resp) = transport_rx.recv().await.unwrap();
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttReadRequestBuilder { attribute_handle: CCCD_HANDLE.into() })
+                .view(),
+        )
+        .unwrap();
+        let (_, read_resp) = transport_rx.recv().await.unwrap();
+
+        // assert: the write was handled by the stack, and only reported to the datastore
+        assert_eq!(
+            write_resp,
+            AttBuilder {
+                opcode: AttOpcode::WRITE_RESPONSE,
+                _child_: AttWriteResponseBuilder {}.into()
+            }
+        );
+        assert_eq!(
+            read_resp,
+            AttBuilder {
+                opcode: AttOpcode::READ_RESPONSE,
+                _child_: AttReadResponseBuilder {
+                    value: build_att_data(GattClientCharacteristicConfigurationBuilder {
+                        notification: 1,
+                        indication: 0
+                    })
+                }
+                .into()
+            }
+        );
+        assert_eq!(
+            gatt.get_client_configuration(CONN_ID, HANDLE_2).unwrap(),
+            ClientConfiguration { notify: true, indicate: false }
+        );
+        let MockDatastoreEvents::WriteNoResponseDescriptor(CONN_ID, CCCD_HANDLE, data) =
+            data_rx.try_recv().unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(data.view().get_raw_payload().collect::<Vec<_>>(), vec![0x01, 0x00]);
+        assert!(data_rx.try_recv().is_err());
+    })
+}
+
+#[test]
+fn test_cccd_rejects_unsupported_value() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_cccd(&mut gatt);
+        gatt.on_le_connect(CONN_ID).unwrap();
+        data_rx.recv().await.unwrap();
+
+        // act: try to subscribe to indications, which are not supported
+        write_cccd(&mut gatt, [0x02, 0x00]);
+        let (_, resp) = transport_rx.recv().await.unwrap();
+
+        // assert
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::ERROR_RESPONSE,
+                _child_: AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::WRITE_REQUEST,
+                    handle_in_error: CCCD_HANDLE.into(),
+                    error_code: AttErrorCode::CCCD_IMPROPERLY_CONFIGURED.into()
+                }
+                .into()
+            }
+        );
+        assert_eq!(
+            gatt.get_client_configuration(CONN_ID, HANDLE_2).unwrap(),
+            ClientConfiguration::default()
+        );
+    })
+}
+
+#[test]
+fn test_cccd_persisted_for_bonded_peer() {
+    start_test(async move {
+        // arrange: a bonded peer subscribes, then disconnects
+        let directory = std::env::temp_dir()
+            .join(format!("gatt_server_test_cccd_persisted_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        gatt.set_cccd_store(Rc::new(FileCccdStore::new(directory)));
+        create_server_with_cccd(&mut gatt);
+        gatt.on_le_connect(CONN_ID).unwrap();
+        gatt.on_bonded_peer(CONN_ID, PEER).unwrap();
+        write_cccd(&mut gatt, [0x01, 0x00]);
+        transport_rx.recv().await.unwrap();
+        gatt.on_le_disconnect(CONN_ID);
+        data_rx.recv().await.unwrap();
+
+        // act: the same peer reconnects
+        gatt.on_le_connect(CONN_ID).unwrap();
+        let before_bonding = gatt.get_client_configuration(CONN_ID, HANDLE_2).unwrap();
+        gatt.on_bonded_peer(CONN_ID, PEER).unwrap();
+        let after_bonding = gatt.get_client_configuration(CONN_ID, HANDLE_2).unwrap();
+
+        // assert: the subscription is restored once the peer is known
+        assert_eq!(before_bonding, ClientConfiguration::default());
+        assert_eq!(after_bonding, ClientConfiguration { notify: true, indicate: false });
+    })
+}
+
+#[test]
+fn test_cccd_forgotten_after_unbonding() {
+    start_test(async move {
+        // arrange
+        let directory = std::env::temp_dir()
+            .join(format!("gatt_server_test_cccd_unbonded_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        gatt.set_cccd_store(Rc::new(FileCccdStore::new(directory)));
+        create_server_with_cccd(&mut gatt);
+        gatt.on_le_connect(CONN_ID).unwrap();
+        gatt.on_bonded_peer(CONN_ID, PEER).unwrap();
+        write_cccd(&mut gatt, [0x01, 0x00]);
+        transport_rx.recv().await.unwrap();
+        gatt.on_le_disconnect(CONN_ID);
+        data_rx.recv().await.unwrap();
+
+        // act: the bond is removed before the peer reconnects
+        gatt.on_bond_removed(PEER).unwrap();
+        gatt.on_le_connect(CONN_ID).unwrap();
+        gatt.on_bonded_peer(CONN_ID, PEER).unwrap();
+
+        // assert
+        assert_eq!(
+            gatt.get_client_configuration(CONN_ID, HANDLE_2).unwrap(),
+            ClientConfiguration::default()
+        );
+    })
+}
+
+#[test]
+fn test_long_characteristic_read_blob() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act: read the remainder of a value longer than the default MTU, with
+        // the upper layer supplying the part of the value from the offset
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttReadBlobRequestBuilder {
+                attribute_handle: HANDLE_2.into(),
+                offset: 22,
+            })
+            .view(),
+        )
+        .unwrap();
+        let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, offset, is_long, tx) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        tx.send(Ok(AttAttributeDataChild::RawData((22..40).collect()))).unwrap();
+        let (tcb_idx, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: the offset reaches the upper layer
+        assert_eq!(offset, 22);
+        assert!(is_long);
+        assert_eq!(tcb_idx, TCB_IDX);
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::READ_BLOB_RESPONSE,
+                _child_: AttReadBlobResponseBuilder {
+                    value: build_att_data(AttAttributeDataChild::RawData((22..40).collect()))
+                }
+                .into()
+            }
+        );
+    })
+}
+
+#[test]
+fn test_read_blob_of_declaration_not_long() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttReadBlobRequestBuilder {
+                attribute_handle: HANDLE_1.into(),
+                offset: 1,
+            })
+            .view(),
+        )
+        .unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+
+        // assert
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::ERROR_RESPONSE,
+                _child_: AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::READ_BLOB_REQUEST,
+                    handle_in_error: HANDLE_1.into(),
+                    error_code: AttErrorCode::ATTRIBUTE_NOT_LONG.into()
+                }
+                .into()
+            }
+        );
+    })
+}
+
+fn prepare_write(gatt: &mut GattModule, offset: u16, value: &[u8]) {
+    gatt.handle_packet(
+        CONN_ID,
+        build_att_view_or_crash(AttPrepareWriteRequestBuilder {
+            handle: HANDLE_2.into(),
+            offset,
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        })
+        .view(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_long_characteristic_write() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act: write a value in two parts
+        prepare_write(&mut gatt, 0, &[1, 2, 3]);
+        let (_, first_resp) = transport_rx.recv().await.unwrap();
+        prepare_write(&mut gatt, 3, &[4, 5]);
+        transport_rx.recv().await.unwrap();
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttExecuteWriteRequestBuilder { flags: 1 }).view(),
+        )
+        .unwrap();
+        let MockDatastoreEvents::PrepareWriteCharacteristic(CONN_ID, HANDLE_2, 0, first_part, tx) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        tx.send(Ok(())).unwrap();
+        let MockDatastoreEvents::PrepareWriteCharacteristic(CONN_ID, HANDLE_2, 3, second_part, tx) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        tx.send(Ok(())).unwrap();
+        let MockDatastoreEvents::ExecuteWrite(CONN_ID, TransactionDecision::Execute, tx) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        tx.send(Ok(())).unwrap();
+        let (tcb_idx, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: each part is echoed, then handed over with its offset
+        assert_eq!(
+            first_resp,
+            AttBuilder {
+                opcode: AttOpcode::PREPARE_WRITE_RESPONSE,
+                _child_: AttPrepareWriteResponseBuilder {
+                    handle: HANDLE_2.into(),
+                    offset: 0,
+                    value: build_att_data(AttAttributeDataChild::RawData([1, 2, 3].into()))
+                }
+                .into()
+            }
+        );
+        assert_eq!(first_part.view().get_raw_payload().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(second_part.view().get_raw_payload().collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(tcb_idx, TCB_IDX);
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::EXECUTE_WRITE_RESPONSE,
+                _child_: AttExecuteWriteResponseBuilder {}.into()
+            }
+        );
+    })
+}
+
+#[test]
+fn test_cancelled_long_write() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+        prepare_write(&mut gatt, 0, &[1, 2, 3]);
+        transport_rx.recv().await.unwrap();
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttExecuteWriteRequestBuilder { flags: 0 }).view(),
+        )
+        .unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: the upper layer never sees the discarded writes
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::EXECUTE_WRITE_RESPONSE,
+                _child_: AttExecuteWriteResponseBuilder {}.into()
+            }
+        );
+        assert!(data_rx.try_recv().is_err());
+    })
+}
+
+#[test]
+fn test_write_command() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttWriteCommandBuilder {
+                handle: HANDLE_2.into(),
+                value: build_att_data(AttAttributeDataChild::RawData([1, 2].into())),
+            })
+            .view(),
+        )
+        .unwrap();
+
+        // assert: the write is forwarded, and no response is sent
+        let MockDatastoreEvents::WriteNoResponseCharacteristic(CONN_ID, HANDLE_2, data) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(data.view().get_raw_payload().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(transport_rx.try_recv(), Err(TryRecvError::Empty));
+    })
+}
+
+const CSRK: [u8; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+
+struct FixedSigningKeyProvider;
+
+impl SigningKeyProvider for FixedSigningKeyProvider {
+    fn get_peer_signing_key(&self, _: ConnectionId) -> Option<PeerSigningKey> {
+        Some(PeerSigningKey { csrk: CSRK, min_sign_counter: 0 })
+    }
+
+    fn on_sign_counter_used(&self, _: ConnectionId, _: u32) {}
+}
+
+#[test]
+fn test_signed_write_command() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        gatt.set_signing_key_provider(Rc::new(FixedSigningKeyProvider));
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+        let sign_counter = 1u32;
+        let mut signed_data = vec![u8::from(AttOpcode::SIGNED_WRITE_COMMAND)];
+        signed_data.extend(HANDLE_2.0.to_le_bytes());
+        signed_data.extend([1, 2]);
+        signed_data.extend(sign_counter.to_le_bytes());
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttSignedWriteCommandBuilder {
+                handle: HANDLE_2.into(),
+                value: build_att_data(AttAttributeDataChild::RawData([1, 2].into())),
+                sign_counter,
+                mac: compute_mac(&CSRK, &signed_data).into(),
+            })
+            .view(),
+        )
+        .unwrap();
+
+        // assert: the write is forwarded, and no response is sent
+        let MockDatastoreEvents::WriteNoResponseCharacteristic(CONN_ID, HANDLE_2, data) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(data.view().get_raw_payload().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(transport_rx.try_recv(), Err(TryRecvError::Empty));
+    })
+}
+
+#[test]
+fn test_characteristic_read_requires_encryption() {
+    start_test(async move {
+        // arrange: a characteristic that can only be read over an encrypted link
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        gatt.open_gatt_server(SERVER_ID).unwrap();
+        gatt.register_gatt_service(
+            SERVER_ID,
+            GattServiceWithHandle {
+                handle: HANDLE_1,
+                type_: UUID_1,
+                primary: true,
+                includes: vec![],
+                characteristics: vec![GattCharacteristicWithHandle {
+                    handle: HANDLE_2,
+                    type_: UUID_2,
+                    permissions: AttPermissions {
+                        read_security: AttSecurityRequirements {
+                            encryption: true,
+                            ..Default::default()
+                        },
+                        ..AttPermissions::READONLY
+                    },
+                    notify: false,
+                    indicate: false,
+                    extended_properties: None,
+                    user_description: None,
+                    descriptors: vec![],
+                }],
+            },
+        )
+        .unwrap();
+        gatt.on_le_connect(CONN_ID).unwrap();
+        data_rx.recv().await.unwrap();
+        let read_request =
+            build_att_view_or_crash(AttReadRequestBuilder { attribute_handle: HANDLE_2.into() });
+
+        // act: read before and after the link is encrypted
+        gatt.handle_packet(CONN_ID, read_request.view()).unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+        gatt.on_security_changed(
+            CONN_ID,
+            ConnectionSecurity {
+                encrypted: true,
+                key_size: 16,
+                bonded: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        gatt.handle_packet(CONN_ID, read_request.view()).unwrap();
+
+        // assert: the first read is rejected, and the second is forwarded
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::ERROR_RESPONSE,
+                _child_: AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::READ_REQUEST,
+                    handle_in_error: HANDLE_2.into(),
+                    error_code: AttErrorCode::INSUFFICIENT_AUTHENTICATION.into(),
+                }
+                .into()
+            }
+        );
+        let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, 0, false, _) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+    })
+}
+
+const SERVICE_CHANGED_HANDLE: AttHandle = AttHandle(GATT_SERVICE_HANDLE.0 + 2);
+const SERVICE_CHANGED_CCCD_HANDLE: AttHandle = AttHandle(GATT_SERVICE_HANDLE.0 + 3);
+const CLIENT_SUPPORTED_FEATURES_HANDLE: AttHandle = AttHandle(GATT_SERVICE_HANDLE.0 + 5);
+
+fn create_server_with_gatt_service(gatt: &mut GattModule) {
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+    gatt.on_le_connect(CONN_ID).unwrap();
+}
+
+fn write(gatt: &mut GattModule, handle: AttHandle, value: &[u8]) {
+    gatt.handle_packet(
+        CONN_ID,
+        build_att_view_or_crash(AttWriteRequestBuilder {
+            handle: handle.into(),
+            value: build_att_data(AttAttributeDataChild::RawData(value.into())),
+        })
+        .view(),
+    )
+    .unwrap();
+}
+
+fn register_service(gatt: &mut GattModule) {
+    gatt.register_gatt_service(
+        SERVER_ID,
+        GattServiceWithHandle {
+            handle: HANDLE_1,
+            type_: UUID_1,
+            primary: true,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: HANDLE_2,
+                type_: UUID_2,
+                permissions: AttPermissions::READONLY,
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
+            }],
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_service_changed_indicated_on_registration() {
+    start_test(async move {
+        // arrange: subscribe to Service Changed
+        let (mut gatt, _data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_gatt_service(&mut gatt);
+        write(&mut gatt, SERVICE_CHANGED_CCCD_HANDLE, &[0x02, 0x00]);
+        transport_rx.recv().await.unwrap();
+
+        // act
+        register_service(&mut gatt);
+        let (_, indication) = transport_rx.recv().await.unwrap();
+
+        // assert: the indication covers the whole new service
+        assert_eq!(
+            indication,
+            AttBuilder {
+                opcode: AttOpcode::HANDLE_VALUE_INDICATION,
+                _child_: AttHandleValueIndicationBuilder {
+                    handle: SERVICE_CHANGED_HANDLE.into(),
+                    value: build_att_data(GattServiceChangedBuilder {
+                        start_handle: HANDLE_1.into(),
+                        end_handle: HANDLE_2.into(),
+                    }),
+                }
+                .into()
+            }
+        );
+    })
+}
+
+#[test]
+fn test_service_changed_not_indicated_if_not_subscribed() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_gatt_service(&mut gatt);
+
+        // act
+        register_service(&mut gatt);
+
+        // assert
+        assert_eq!(transport_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+    })
+}
+
+#[test]
+fn test_robust_caching_database_out_of_sync() {
+    start_test(async move {
+        // arrange: enable robust caching, then change the database
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_gatt_service(&mut gatt);
+        data_rx.recv().await.unwrap();
+        write(&mut gatt, CLIENT_SUPPORTED_FEATURES_HANDLE, &[0x01]);
+        transport_rx.recv().await.unwrap();
+        register_service(&mut gatt);
+        let read_request =
+            build_att_view_or_crash(AttReadRequestBuilder { attribute_handle: HANDLE_2.into() });
+
+        // act
+        gatt.handle_packet(CONN_ID, read_request.view()).unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+        gatt.handle_packet(CONN_ID, read_request.view()).unwrap();
+
+        // assert: the first request is rejected, and the second is processed
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::ERROR_RESPONSE,
+                _child_: AttErrorResponseBuilder {
+                    opcode_in_error: AttOpcode::READ_REQUEST,
+                    handle_in_error: AttHandle(0).into(),
+                    error_code: AttErrorCode::DATABASE_OUT_OF_SYNC.into(),
+                }
+                .into()
+            }
+        );
+        let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, 0, false, _) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+    })
+}
+
+const CID_1: EnhancedChannelId = EnhancedChannelId(0x40);
+const CID_2: EnhancedChannelId = EnhancedChannelId(0x41);
+const PEER_RX_MTU: usize = 100;
+const ENCRYPTED: ConnectionSecurity = ConnectionSecurity {
+    encrypted: true,
+    key_size: 16,
+    bonded: true,
+    authenticated: false,
+    authorized: false,
+};
+
+type UnenhancedTransportRx = UnboundedReceiver<(TransportIndex, AttBuilder)>;
+
+fn start_gatt_module_with_enhanced_channels() -> (
+    gatt::server::GattModule,
+    UnboundedReceiver<MockDatastoreEvents>,
+    UnenhancedTransportRx,
+    UnboundedReceiver<MockEnhancedChannelEvents>,
+) {
+    let (datastore, data_rx) = MockDatastore::new();
+    let (transport, transport_rx, enhanced_rx) = MockAttTransport::new_with_enhanced_channels();
+    let gatt = GattModule::new(Rc::new(datastore), Rc::new(transport));
+
+    (gatt, data_rx, transport_rx, enhanced_rx)
+}
+
+fn build_read_request(handle: AttHandle) -> AttReadRequestBuilder {
+    AttReadRequestBuilder { attribute_handle: handle.into() }
+}
+
+#[test]
+fn test_enhanced_channels_accepted_when_encrypted() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, _, mut enhanced_rx) = start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+        gatt.on_security_changed(CONN_ID, ENCRYPTED).unwrap();
+
+        // act
+        gatt.on_enhanced_channels_requested(CONN_ID, &[CID_1, CID_2], PEER_RX_MTU).unwrap();
+
+        // assert
+        let MockEnhancedChannelEvents::AcceptChannels(TCB_IDX, accepted, _) =
+            enhanced_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(accepted, vec![CID_1, CID_2]);
+    })
+}
+
+#[test]
+fn test_enhanced_channels_refused_when_not_encrypted() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, _, mut enhanced_rx) = start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+
+        // act
+        gatt.on_enhanced_channels_requested(CONN_ID, &[CID_1], PEER_RX_MTU).unwrap();
+
+        // assert
+        let MockEnhancedChannelEvents::AcceptChannels(TCB_IDX, accepted, _) =
+            enhanced_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert!(accepted.is_empty());
+        assert!(gatt
+            .handle_packet_on_bearer(
+                CONN_ID,
+                BearerId::Enhanced(CID_1),
+                build_att_view_or_crash(build_read_request(HANDLE_1)).view()
+            )
+            .is_err());
+    })
+}
+
+#[test]
+fn test_enhanced_channels_refused_when_peer_mtu_too_small() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, _, mut enhanced_rx) = start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+        gatt.on_security_changed(CONN_ID, ENCRYPTED).unwrap();
+
+        // act
+        gatt.on_enhanced_channels_requested(CONN_ID, &[CID_1, CID_2], MIN_ENHANCED_ATT_MTU - 1)
+            .unwrap();
+
+        // assert: nothing is accepted, rather than accepting channels with no bearer
+        let MockEnhancedChannelEvents::AcceptChannels(TCB_IDX, accepted, _) =
+            enhanced_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert!(accepted.is_empty());
+        assert!(gatt
+            .handle_packet_on_bearer(
+                CONN_ID,
+                BearerId::Enhanced(CID_1),
+                build_att_view_or_crash(build_read_request(HANDLE_1)).view()
+            )
+            .is_err());
+    })
+}
+
+#[test]
+fn test_enhanced_channels_limited_per_connection() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, _, mut enhanced_rx) = start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+        gatt.on_security_changed(CONN_ID, ENCRYPTED).unwrap();
+        gatt.on_enhanced_channel_opened(CONN_ID, CID_1, PEER_RX_MTU).unwrap();
+        let cids = (0..MAX_ENHANCED_BEARERS as u16)
+            .map(|i| EnhancedChannelId(CID_2.0 + i))
+            .collect::<Vec<_>>();
+
+        // act
+        gatt.on_enhanced_channels_requested(CONN_ID, &cids, PEER_RX_MTU).unwrap();
+
+        // assert: only enough to reach the limit are accepted
+        let MockEnhancedChannelEvents::AcceptChannels(TCB_IDX, accepted, _) =
+            enhanced_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(accepted, cids[..MAX_ENHANCED_BEARERS - 1]);
+    })
+}
+
+#[test]
+fn test_open_enhanced_channels() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, _, mut enhanced_rx) = start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+
+        // act
+        gatt.open_enhanced_channels(CONN_ID, 2).unwrap();
+
+        // assert
+        let MockEnhancedChannelEvents::OpenChannels(TCB_IDX, 2, _) =
+            enhanced_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+    })
+}
+
+#[test]
+fn test_read_on_enhanced_bearer() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, _, mut enhanced_rx) = start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+        gatt.on_enhanced_channel_opened(CONN_ID, CID_1, PEER_RX_MTU).unwrap();
+
+        // act
+        gatt.handle_packet_on_bearer(
+            CONN_ID,
+            BearerId::Enhanced(CID_1),
+            build_att_view_or_crash(build_read_request(HANDLE_1)).view(),
+        )
+        .unwrap();
+
+        // assert: the response is sent on the same bearer
+        let MockEnhancedChannelEvents::SendPacket(TCB_IDX, CID_1, resp) =
+            enhanced_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::READ_RESPONSE,
+                _child_: AttReadResponseBuilder {
+                    value: build_att_data(GattServiceDeclarationValueBuilder {
+                        uuid: UUID_1.into()
+                    })
+                }
+                .into()
+            }
+        );
+    })
+}
+
+#[test]
+fn test_concurrent_transactions_on_separate_bearers() {
+    start_test(async move {
+        // arrange: start a transaction on the unenhanced bearer
+        let (mut gatt, mut data_rx, mut transport_rx, mut enhanced_rx) =
+            start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap();
+        gatt.on_enhanced_channel_opened(CONN_ID, CID_1, PEER_RX_MTU).unwrap();
+        gatt.handle_packet(CONN_ID, build_att_view_or_crash(build_read_request(HANDLE_2)).view())
+            .unwrap();
+        let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, 0, false, tx) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+
+        // act: another transaction on the enhanced bearer, while the first is pending
+        gatt.handle_packet_on_bearer(
+            CONN_ID,
+            BearerId::Enhanced(CID_1),
+            build_att_view_or_crash(build_read_request(HANDLE_1)).view(),
+        )
+        .unwrap();
+        let MockEnhancedChannelEvents::SendPacket(_, CID_1, enhanced_resp) =
+            enhanced_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        tx.send(Ok(AttAttributeDataChild::RawData([1].into()))).unwrap();
+        let (_, unenhanced_resp) = transport_rx.recv().await.unwrap();
+
+        // assert: both transactions complete
+        assert_eq!(enhanced_resp.opcode, AttOpcode::READ_RESPONSE);
+        assert_eq!(unenhanced_resp.opcode, AttOpcode::READ_RESPONSE);
+    })
+}
+
+#[test]
+fn test_enhanced_channel_closed() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, _, _enhanced_rx) = start_gatt_module_with_enhanced_channels();
+        create_server_and_open_connection(&mut gatt);
+        gatt.on_enhanced_channel_opened(CONN_ID, CID_1, PEER_RX_MTU).unwrap();
+
+        // act
+        gatt.on_enhanced_channel_closed(CONN_ID, CID_1);
+
+        // assert: the unenhanced bearer remains
+        assert!(gatt
+            .handle_packet_on_bearer(
+                CONN_ID,
+                BearerId::Enhanced(CID_1),
+                build_att_view_or_crash(build_read_request(HANDLE_1)).view()
+            )
+            .is_err());
+        assert!(gatt
+            .handle_packet(CONN_ID, build_att_view_or_crash(build_read_request(HANDLE_1)).view())
+            .is_ok());
+    })
+}
+
+const GAP_SERVICE_HANDLE: AttHandle = AttHandle(20);
+const BATTERY_SERVICE_HANDLE: AttHandle = AttHandle(30);
+
+fn create_server_with_local_services(gatt: &mut GattModule) {
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+    gatt.register_local_gatt_service(
+        SERVER_ID,
+        generic_access_service(GAP_SERVICE_HANDLE, || "device".into(), 0x0340, None),
+    )
+    .unwrap();
+    gatt.register_local_gatt_service(SERVER_ID, battery_service(BATTERY_SERVICE_HANDLE, || 80))
+        .unwrap();
+}
+
+#[test]
+fn test_local_service_read() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_local_services(&mut gatt);
+        gatt.on_le_connect(CONN_ID).unwrap();
+        data_rx.recv().await.unwrap();
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttReadRequestBuilder {
+                attribute_handle: AttHandle(GAP_SERVICE_HANDLE.0 + 2).into(),
+            })
+            .view(),
+        )
+        .unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: the value is served without involving the datastore
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::READ_RESPONSE,
+                _child_: AttReadResponseBuilder {
+                    value: build_att_data(AttAttributeDataChild::RawData(
+                        b"device".to_vec().into()
+                    ))
+                }
+                .into()
+            }
+        );
+        assert!(data_rx.try_recv().is_err());
+    })
+}
+
+#[test]
+fn test_notify_subscribers() {
+    start_test(async move {
+        // arrange: subscribe to the battery level on only one of two connections
+        let (mut gatt, _data_rx, mut transport_rx) = start_gatt_module();
+        let other_conn_id = ConnectionId::new(TransportIndex(TCB_IDX.0 + 1), SERVER_ID);
+        let level_handle = battery_level_handle(BATTERY_SERVICE_HANDLE);
+        create_server_with_local_services(&mut gatt);
+        gatt.on_le_connect(CONN_ID).unwrap();
+        gatt.on_le_connect(other_conn_id).unwrap();
+        write(&mut gatt, AttHandle(level_handle.0 + 1), &[0x01, 0x00]);
+        transport_rx.recv().await.unwrap();
+
+        // act
+        gatt.notify_subscribers(
+            SERVER_ID,
+            level_handle,
+            AttAttributeDataChild::RawData([79].into()),
+        )
+        .unwrap();
+        let (tcb_idx, notification) = transport_rx.recv().await.unwrap();
+
+        // assert: only the subscribed connection is notified
+        assert_eq!(tcb_idx, TCB_IDX);
+        assert_eq!(
+            notification,
+            AttBuilder {
+                opcode: AttOpcode::HANDLE_VALUE_NOTIFICATION,
+                _child_: AttHandleValueNotificationBuilder {
+                    handle: level_handle.into(),
+                    value: build_att_data(AttAttributeDataChild::RawData([79].into()))
+                }
+                .into()
+            }
+        );
+        assert_eq!(transport_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+    })
+}
+
+const SECONDARY_SERVICE_HANDLE: AttHandle = AttHandle(20);
+const INCLUDING_SERVICE_HANDLE: AttHandle = AttHandle(30);
+const INCLUDE_HANDLE: AttHandle = AttHandle(31);
+
+fn create_server_with_included_service(gatt: &mut GattModule) {
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+    for service in [
+        GattServiceWithHandle {
+            handle: SECONDARY_SERVICE_HANDLE,
+            type_: UUID_1,
+            primary: false,
+            includes: vec![],
+            characteristics: vec![GattCharacteristicWithHandle {
+                handle: AttHandle(SECONDARY_SERVICE_HANDLE.0 + 2),
+                type_: UUID_2,
+                permissions: AttPermissions::READONLY,
+                notify: false,
+                indicate: false,
+                extended_properties: None,
+                user_description: None,
+                descriptors: vec![],
+            }],
+        },
+        GattServiceWithHandle {
+            handle: INCLUDING_SERVICE_HANDLE,
+            type_: UUID_2,
+            primary: true,
+            includes: vec![GattIncludeWithHandle {
+                handle: INCLUDE_HANDLE,
+                service_handle: SECONDARY_SERVICE_HANDLE,
+            }],
+            characteristics: vec![],
+        },
+    ] {
+        gatt.register_gatt_service(SERVER_ID, service).unwrap();
+    }
+    gatt.on_le_connect(CONN_ID).unwrap();
+}
+
+#[test]
+fn test_secondary_service_discovery() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_included_service(&mut gatt);
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttReadByGroupTypeRequestBuilder {
+                starting_handle: AttHandle(1).into(),
+                ending_handle: AttHandle(0xFFFF).into(),
+                attribute_group_type: SECONDARY_SERVICE_DECLARATION_UUID.into(),
+            })
+            .view(),
+        )
+        .unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: only the secondary service is found, with its whole group
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::READ_BY_GROUP_TYPE_RESPONSE,
+                _child_: AttReadByGroupTypeResponseBuilder {
+                    data: [AttReadByGroupTypeDataElementBuilder {
+                        handle: SECONDARY_SERVICE_HANDLE.into(),
+                        end_group_handle: AttHandle(SECONDARY_SERVICE_HANDLE.0 + 2).into(),
+                        value: build_att_data(GattServiceDeclarationValueBuilder {
+                            uuid: UUID_1.into()
+                        }),
+                    }]
+                    .into()
+                }
+                .into()
+            }
+        );
+    })
+}
+
+#[test]
+fn test_included_service_discovery() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, mut transport_rx) = start_gatt_module();
+        create_server_with_included_service(&mut gatt);
+
+        // act
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttReadByTypeRequestBuilder {
+                starting_handle: INCLUDING_SERVICE_HANDLE.into(),
+                ending_handle: AttHandle(0xFFFF).into(),
+                attribute_type: INCLUDE_DECLARATION_UUID.into(),
+            })
+            .view(),
+        )
+        .unwrap();
+        let (_, resp) = transport_rx.recv().await.unwrap();
+
+        // assert: the include declaration covers the included service
+        assert_eq!(
+            resp,
+            AttBuilder {
+                opcode: AttOpcode::READ_BY_TYPE_RESPONSE,
+                _child_: AttReadByTypeResponseBuilder {
+                    data: [AttReadByTypeDataElementBuilder {
+                        handle: INCLUDE_HANDLE.into(),
+                        value: build_att_data(AttAttributeDataChild::RawData(
+                            [20, 0, 22, 0, 0x02, 0x01].into()
+                        )),
+                    }]
+                    .into()
+                }
+                .into()
+            }
+        );
+    })
+}
+
+fn start_gatt_module_with_disconnects() -> (
+    gatt::server::GattModule,
+    UnboundedReceiver<MockDatastoreEvents>,
+    UnenhancedTransportRx,
+    UnboundedReceiver<(TransportIndex, DisconnectReason)>,
+) {
+    let (datastore, data_rx) = MockDatastore::new();
+    let (transport, transport_rx, disconnect_rx) = MockAttTransport::new_with_disconnects();
+    let gatt = GattModule::new(Rc::new(datastore), Rc::new(transport));
+
+    (gatt, data_rx, transport_rx, disconnect_rx)
+}
+
+#[test]
+fn test_concurrent_requests_drop_link() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, mut transport_rx, mut disconnect_rx) =
+            start_gatt_module_with_disconnects();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap(); // drop the AddConnection message
+
+        // act: send a second request while the first is still outstanding
+        gatt.handle_packet(CONN_ID, build_att_view_or_crash(build_read_request(HANDLE_2)).view())
+            .unwrap();
+        let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, 0, false, _) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        gatt.handle_packet(CONN_ID, build_att_view_or_crash(build_read_request(HANDLE_1)).view())
+            .unwrap();
+
+        // assert: the reason is reported to the upper layer, and the link is dropped
+        let MockDatastoreEvents::LinkDropped(CONN_ID, DisconnectReason::ProtocolViolation) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            disconnect_rx.recv().await.unwrap(),
+            (TCB_IDX, DisconnectReason::ProtocolViolation)
+        );
+        assert_eq!(transport_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+    })
+}
+
+#[test]
+fn test_transaction_timeout_drops_link() {
+    start_test(async move {
+        // arrange
+        tokio::time::pause();
+        let (mut gatt, mut data_rx, mut transport_rx, mut disconnect_rx) =
+            start_gatt_module_with_disconnects();
+        create_server_and_open_connection(&mut gatt);
+        data_rx.recv().await.unwrap(); // drop the AddConnection message
+
+        // act: send a request that the datastore never answers
+        gatt.handle_packet(CONN_ID, build_att_view_or_crash(build_read_request(HANDLE_2)).view())
+            .unwrap();
+        let MockDatastoreEvents::ReadCharacteristic(CONN_ID, HANDLE_2, 0, false, _pending_read) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+
+        // assert: the reason is reported to the upper layer, and the link is dropped
+        assert_eq!(
+            disconnect_rx.recv().await.unwrap(),
+            (TCB_IDX, DisconnectReason::TransactionTimeout)
+        );
+        let MockDatastoreEvents::LinkDropped(CONN_ID, DisconnectReason::TransactionTimeout) =
+            data_rx.recv().await.unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(transport_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+    })
+}
+
+const BR_EDR_TCB_IDX: TransportIndex = TransportIndex(3);
+const BR_EDR_CONN_ID: ConnectionId = ConnectionId::new(BR_EDR_TCB_IDX, SERVER_ID);
+
+fn start_gatt_module_with_sdp() -> (
+    gatt::server::GattModule,
+    UnboundedReceiver<MockDatastoreEvents>,
+    UnenhancedTransportRx,
+    UnboundedReceiver<MockSdpEvents>,
+) {
+    let (mut gatt, data_rx, transport_rx) = start_gatt_module();
+    let (sdp_publisher, sdp_rx) = MockSdpPublisher::new();
+    gatt.set_sdp_publisher(Rc::new(sdp_publisher));
+
+    (gatt, data_rx, transport_rx, sdp_rx)
+}
+
+fn create_server_exposed_over_br_edr(gatt: &mut GattModule) {
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+    register_service(gatt);
+    gatt.expose_over_br_edr(SERVER_ID).unwrap();
+}
+
+const SERVICE_RECORD: SdpServiceRecord =
+    SdpServiceRecord { uuid: UUID_1, start_handle: HANDLE_1, end_handle: HANDLE_2 };
+/// The Generic Attribute service, which every server exposes
+const GATT_SERVICE_RECORD: SdpServiceRecord = SdpServiceRecord {
+    uuid: Uuid::new(0x1801),
+    start_handle: GATT_SERVICE_HANDLE,
+    end_handle: AttHandle(GATT_SERVICE_HANDLE.0 + 7),
+};
+
+#[test]
+fn test_br_edr_connection_uses_l2cap_mtu() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, mut data_rx, _) = start_gatt_module();
+        create_server_exposed_over_br_edr(&mut gatt);
+
+        // act
+        gatt.on_br_edr_connect(BR_EDR_CONN_ID, 100).unwrap();
+
+        // assert: there is no MTU exchange, so the upper layer learns the MTU immediately
+        assert!(matches!(
+            data_rx.recv().await.unwrap(),
+            MockDatastoreEvents::AddConnection(BR_EDR_CONN_ID)
+        ));
+        assert!(matches!(
+            data_rx.recv().await.unwrap(),
+            MockDatastoreEvents::MtuChanged(BR_EDR_CONN_ID, 100)
+        ));
+    })
+}
+
+#[test]
+fn test_br_edr_connection_rejected_if_not_exposed() {
+    let (mut gatt, _, _) = start_gatt_module();
+    gatt.open_gatt_server(SERVER_ID).unwrap();
+
+    let res = gatt.on_br_edr_connect(BR_EDR_CONN_ID, MIN_BR_EDR_ATT_MTU);
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_br_edr_connection_rejected_below_minimum_mtu() {
+    let (mut gatt, _, _) = start_gatt_module();
+    create_server_exposed_over_br_edr(&mut gatt);
+
+    let res = gatt.on_br_edr_connect(BR_EDR_CONN_ID, MIN_BR_EDR_ATT_MTU - 1);
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_br_edr_rejects_mtu_exchange() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _data_rx, mut transport_rx) = start_gatt_module();
+        create_server_exposed_over_br_edr(&mut gatt);
+        gatt.on_br_edr_connect(BR_EDR_CONN_ID, MIN_BR_EDR_ATT_MTU).unwrap();
+
+        // act
+        gatt.handle_packet(
+            BR_EDR_CONN_ID,
+            build_att_view_or_crash(AttExchangeMtuRequestBuilder { mtu: 64 }).view(),
+        )
+        .unwrap();
+
+        // assert: the ATT_MTU is set by L2CAP on BR/EDR
+        assert_eq!(
+            transport_rx.recv().await.unwrap(),
+            (
+                BR_EDR_TCB_IDX,
+                AttBuilder {
+                    opcode: AttOpcode::ERROR_RESPONSE,
+                    _child_: AttErrorResponseBuilder {
+                        opcode_in_error: AttOpcode::EXCHANGE_MTU_REQUEST,
+                        handle_in_error: AttHandle(0).into(),
+                        error_code: AttErrorCode::REQUEST_NOT_SUPPORTED.into(),
+                    }
+                    .into(),
+                }
+            )
+        );
+    })
+}
+
+#[test]
+fn test_connections_isolated_across_transports() {
+    start_test(async move {
+        // arrange: the same server is connected over both transports
+        let (mut gatt, _data_rx, mut transport_rx) = start_gatt_module();
+        create_server_exposed_over_br_edr(&mut gatt);
+        gatt.on_le_connect(CONN_ID).unwrap();
+        gatt.on_br_edr_connect(BR_EDR_CONN_ID, MIN_BR_EDR_ATT_MTU).unwrap();
+
+        // act: the BR/EDR link goes away, then both transports send a request
+        gatt.on_br_edr_disconnect(BR_EDR_CONN_ID);
+        let br_edr_res = gatt.handle_packet(
+            BR_EDR_CONN_ID,
+            build_att_view_or_crash(AttReadRequestBuilder { attribute_handle: HANDLE_1.into() })
+                .view(),
+        );
+        gatt.handle_packet(
+            CONN_ID,
+            build_att_view_or_crash(AttReadRequestBuilder { attribute_handle: HANDLE_1.into() })
+                .view(),
+        )
+        .unwrap();
+
+        // assert: only the LE link is still served
+        assert!(br_edr_res.is_err());
+        let (tcb_idx, _) = transport_rx.recv().await.unwrap();
+        assert_eq!(tcb_idx, TCB_IDX);
+        assert_eq!(transport_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+    })
+}
+
+#[test]
+fn test_sdp_records_published_when_exposed() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _, _, mut sdp_rx) = start_gatt_module_with_sdp();
+        gatt.open_gatt_server(SERVER_ID).unwrap();
+        register_service(&mut gatt);
+
+        // act
+        gatt.expose_over_br_edr(SERVER_ID).unwrap();
+
+        // assert
+        assert_eq!(
+            sdp_rx.recv().await.unwrap(),
+            MockSdpEvents::Publish(SERVER_ID, GATT_SERVICE_RECORD)
+        );
+        assert_eq!(sdp_rx.recv().await.unwrap(), MockSdpEvents::Publish(SERVER_ID, SERVICE_RECORD));
+    })
+}
+
+#[test]
+fn test_sdp_records_follow_registration() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _, _, mut sdp_rx) = start_gatt_module_with_sdp();
+        gatt.open_gatt_server(SERVER_ID).unwrap();
+        gatt.expose_over_br_edr(SERVER_ID).unwrap();
+        sdp_rx.recv().await.unwrap(); // drop the Publish event of the GATT service
+
+        // act
+        register_service(&mut gatt);
+        gatt.unregister_gatt_service(SERVER_ID, HANDLE_1).unwrap();
+
+        // assert
+        assert_eq!(sdp_rx.recv().await.unwrap(), MockSdpEvents::Publish(SERVER_ID, SERVICE_RECORD));
+        assert_eq!(sdp_rx.recv().await.unwrap(), MockSdpEvents::Withdraw(SERVER_ID, HANDLE_1));
+    })
+}
+
+#[test]
+fn test_sdp_records_not_published_for_secondary_services() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _, _, mut sdp_rx) = start_gatt_module_with_sdp();
+        gatt.open_gatt_server(SERVER_ID).unwrap();
+        gatt.expose_over_br_edr(SERVER_ID).unwrap();
+        sdp_rx.recv().await.unwrap(); // drop the Publish event of the GATT service
+
+        // act
+        gatt.register_gatt_service(
+            SERVER_ID,
+            GattServiceWithHandle {
+                handle: HANDLE_1,
+                type_: UUID_1,
+                primary: false,
+                includes: vec![],
+                characteristics: vec![],
+            },
+        )
+        .unwrap();
+        gatt.unregister_gatt_service(SERVER_ID, HANDLE_1).unwrap();
+
+        // assert
+        assert_eq!(sdp_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+    })
+}
+
+#[test]
+fn test_sdp_records_withdrawn_when_server_closed() {
+    start_test(async move {
+        // arrange
+        let (mut gatt, _, _, mut sdp_rx) = start_gatt_module_with_sdp();
+        gatt.open_gatt_server(SERVER_ID).unwrap();
+        register_service(&mut gatt);
+        gatt.expose_over_br_edr(SERVER_ID).unwrap();
+        sdp_rx.recv().await.unwrap(); // drop the Publish events
+        sdp_rx.recv().await.unwrap();
+
+        // act
+        gatt.close_gatt_server(SERVER_ID).unwrap();
+
+        // assert: the server can no longer accept BR/EDR connections either
+        assert_eq!(
+            sdp_rx.recv().await.unwrap(),
+            MockSdpEvents::Withdraw(SERVER_ID, GATT_SERVICE_HANDLE)
+        );
+        assert_eq!(sdp_rx.recv().await.unwrap(), MockSdpEvents::Withdraw(SERVER_ID, HANDLE_1));
+        gatt.open_gatt_server(SERVER_ID).unwrap();
+        assert!(gatt.on_br_edr_connect(BR_EDR_CONN_ID, MIN_BR_EDR_ATT_MTU).is_err());
+    })
+}
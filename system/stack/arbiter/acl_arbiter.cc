@@ -24,6 +24,7 @@
 #include "common/init_flags.h"
 #include "os/log.h"
 #include "osi/include/allocator.h"
+#include "stack/btm/btm_sec.h"
 #include "stack/gatt/gatt_int.h"
 #include "stack/include/btu.h"  // do_in_main_thread
 #include "stack/include/l2c_api.h"
@@ -42,6 +43,26 @@ class PassthroughAclArbiter : public AclArbiter {
     // no-op
   }
 
+  virtual void OnMtuExchanged(uint8_t tcb_idx, uint16_t mtu) override {
+    // no-op
+  }
+
+  virtual void OnBrEdrConnect(uint8_t tcb_idx, uint16_t mtu) override {
+    // no-op
+  }
+
+  virtual void OnBrEdrDisconnect(uint8_t tcb_idx) override {
+    // no-op
+  }
+
+  virtual void OnSecurityChanged(uint8_t tcb_idx) override {
+    // no-op
+  }
+
+  virtual void OnBondRemoved(const RawAddress& address) override {
+    // no-op
+  }
+
   virtual InterceptAction InterceptAttPacket(uint8_t tcb_idx,
                                              const BT_HDR* packet) override {
     return InterceptAction::FORWARD;
@@ -57,11 +78,27 @@ namespace {
 struct RustArbiterCallbacks {
   ::rust::Fn<void(uint8_t tcb_idx, uint8_t advertiser)> on_le_connect;
   ::rust::Fn<void(uint8_t tcb_idx)> on_le_disconnect;
+  ::rust::Fn<void(uint8_t tcb_idx, uint16_t mtu)> on_mtu_exchanged;
+  ::rust::Fn<void(uint8_t tcb_idx, uint16_t mtu)> on_br_edr_connect;
+  ::rust::Fn<void(uint8_t tcb_idx)> on_br_edr_disconnect;
+  ::rust::Fn<void(uint8_t tcb_idx, bool encrypted, uint8_t key_size,
+                  bool authenticated, bool bonded)>
+      on_security_changed;
+  ::rust::Fn<void(uint8_t tcb_idx, std::array<uint8_t, 6> peer)>
+      on_bonded_peer;
+  ::rust::Fn<void(std::array<uint8_t, 6> peer)> on_bond_removed;
   ::rust::Fn<InterceptAction(uint8_t tcb_idx, ::rust::Vec<uint8_t> buffer)>
       intercept_packet;
 };
 
 RustArbiterCallbacks callbacks_{};
+
+std::array<uint8_t, 6> ToRustAddress(const RawAddress& address) {
+  std::array<uint8_t, 6> out;
+  std::copy(std::begin(address.address), std::end(address.address),
+            out.begin());
+  return out;
+}
 }  // namespace
 
 class RustGattAclArbiter : public AclArbiter {
@@ -76,6 +113,47 @@ class RustGattAclArbiter : public AclArbiter {
     callbacks_.on_le_disconnect(tcb_idx);
   }
 
+  virtual void OnMtuExchanged(uint8_t tcb_idx, uint16_t mtu) override {
+    LOG_INFO("Notifying Rust of MTU exchange");
+    callbacks_.on_mtu_exchanged(tcb_idx, mtu);
+  }
+
+  virtual void OnBrEdrConnect(uint8_t tcb_idx, uint16_t mtu) override {
+    LOG_INFO("Notifying Rust of BR/EDR ATT connection");
+    callbacks_.on_br_edr_connect(tcb_idx, mtu);
+  }
+
+  virtual void OnBrEdrDisconnect(uint8_t tcb_idx) override {
+    LOG_INFO("Notifying Rust of BR/EDR ATT disconnection");
+    callbacks_.on_br_edr_disconnect(tcb_idx);
+  }
+
+  virtual void OnSecurityChanged(uint8_t tcb_idx) override {
+    tGATT_TCB* p_tcb = gatt_get_tcb_by_idx(tcb_idx);
+    if (p_tcb == nullptr) {
+      return;
+    }
+
+    tGATT_SEC_FLAG sec_flag;
+    uint8_t key_size;
+    gatt_sr_get_sec_info(p_tcb->peer_bda, p_tcb->transport, &sec_flag,
+                         &key_size);
+    LOG_INFO("Notifying Rust of security change");
+    callbacks_.on_security_changed(tcb_idx, sec_flag.is_encrypted, key_size,
+                                   sec_flag.is_link_key_authed,
+                                   sec_flag.is_link_key_known);
+
+    if (btm_sec_is_a_bonded_dev(p_tcb->peer_bda)) {
+      LOG_INFO("Notifying Rust of bonded peer");
+      callbacks_.on_bonded_peer(tcb_idx, ToRustAddress(p_tcb->peer_bda));
+    }
+  }
+
+  virtual void OnBondRemoved(const RawAddress& address) override {
+    LOG_INFO("Notifying Rust of bond removal");
+    callbacks_.on_bond_removed(ToRustAddress(address));
+  }
+
   virtual InterceptAction InterceptAttPacket(uint8_t tcb_idx,
                                              const BT_HDR* packet) override {
     LOG_DEBUG("Intercepting ATT packet and forwarding to Rust");
@@ -100,12 +178,25 @@ class RustGattAclArbiter : public AclArbiter {
       std::copy(buffer.begin(), buffer.end(), p);
       p_buf->offset = L2CAP_MIN_OFFSET;
       p_buf->len = buffer.size();
-      L2CA_SendFixedChnlData(L2CAP_ATT_CID, p_tcb->peer_bda, p_buf);
+      if (p_tcb->transport == BT_TRANSPORT_BR_EDR) {
+        L2CA_DataWrite(p_tcb->att_lcid, p_buf);
+      } else {
+        L2CA_SendFixedChnlData(L2CAP_ATT_CID, p_tcb->peer_bda, p_buf);
+      }
     } else {
       LOG_ERROR("Dropping packet since connection no longer exists");
     }
   }
 
+  void DisconnectPeer(uint8_t tcb_idx) {
+    tGATT_TCB* p_tcb = gatt_get_tcb_by_idx(tcb_idx);
+    if (p_tcb != nullptr) {
+      gatt_disconnect(p_tcb);
+    } else {
+      LOG_WARN("Not disconnecting since connection no longer exists");
+    }
+  }
+
   static RustGattAclArbiter& Get() {
     static auto singleton = RustGattAclArbiter();
     return singleton;
@@ -115,10 +206,21 @@ class RustGattAclArbiter : public AclArbiter {
 void StoreCallbacksFromRust(
     ::rust::Fn<void(uint8_t tcb_idx, uint8_t advertiser)> on_le_connect,
     ::rust::Fn<void(uint8_t tcb_idx)> on_le_disconnect,
+    ::rust::Fn<void(uint8_t tcb_idx, uint16_t mtu)> on_mtu_exchanged,
+    ::rust::Fn<void(uint8_t tcb_idx, uint16_t mtu)> on_br_edr_connect,
+    ::rust::Fn<void(uint8_t tcb_idx)> on_br_edr_disconnect,
+    ::rust::Fn<void(uint8_t tcb_idx, bool encrypted, uint8_t key_size,
+                    bool authenticated, bool bonded)>
+        on_security_changed,
+    ::rust::Fn<void(uint8_t tcb_idx, std::array<uint8_t, 6> peer)>
+        on_bonded_peer,
+    ::rust::Fn<void(std::array<uint8_t, 6> peer)> on_bond_removed,
     ::rust::Fn<InterceptAction(uint8_t tcb_idx, ::rust::Vec<uint8_t> buffer)>
         intercept_packet) {
   LOG_INFO("Received callbacks from Rust, registering in Arbiter");
-  callbacks_ = {on_le_connect, on_le_disconnect, intercept_packet};
+  callbacks_ = {on_le_connect,        on_le_disconnect,    on_mtu_exchanged,
+                on_br_edr_connect,    on_br_edr_disconnect, on_security_changed,
+                on_bonded_peer,       on_bond_removed,      intercept_packet};
 }
 
 void SendPacketToPeer(uint8_t tcb_idx, ::rust::Vec<uint8_t> buffer) {
@@ -128,6 +230,13 @@ void SendPacketToPeer(uint8_t tcb_idx, ::rust::Vec<uint8_t> buffer) {
                                tcb_idx, std::move(buffer)));
 }
 
+void DisconnectPeer(uint8_t tcb_idx) {
+  do_in_main_thread(FROM_HERE,
+                    base::Bind(&RustGattAclArbiter::DisconnectPeer,
+                               base::Unretained(&RustGattAclArbiter::Get()),
+                               tcb_idx));
+}
+
 AclArbiter& GetArbiter() {
   return common::init_flags::private_gatt_is_enabled()
              ? static_cast<AclArbiter&>(RustGattAclArbiter::Get())
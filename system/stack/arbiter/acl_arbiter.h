@@ -22,6 +22,8 @@
 
 #pragma once
 
+#include <array>
+
 #include "rust/cxx.h"
 #include "stack/include/bt_hdr.h"
 #include "types/raw_address.h"
@@ -41,6 +43,11 @@ class AclArbiter {
  public:
   virtual void OnLeConnect(uint8_t tcb_idx, uint16_t advertiser_id) = 0;
   virtual void OnLeDisconnect(uint8_t tcb_idx) = 0;
+  virtual void OnMtuExchanged(uint8_t tcb_idx, uint16_t mtu) = 0;
+  virtual void OnBrEdrConnect(uint8_t tcb_idx, uint16_t mtu) = 0;
+  virtual void OnBrEdrDisconnect(uint8_t tcb_idx) = 0;
+  virtual void OnSecurityChanged(uint8_t tcb_idx) = 0;
+  virtual void OnBondRemoved(const RawAddress& address) = 0;
   virtual InterceptAction InterceptAttPacket(uint8_t tcb_idx,
                                              const BT_HDR* packet) = 0;
 
@@ -53,11 +60,22 @@ class AclArbiter {
 void StoreCallbacksFromRust(
     ::rust::Fn<void(uint8_t tcb_idx, uint8_t advertiser)> on_le_connect,
     ::rust::Fn<void(uint8_t tcb_idx)> on_le_disconnect,
+    ::rust::Fn<void(uint8_t tcb_idx, uint16_t mtu)> on_mtu_exchanged,
+    ::rust::Fn<void(uint8_t tcb_idx, uint16_t mtu)> on_br_edr_connect,
+    ::rust::Fn<void(uint8_t tcb_idx)> on_br_edr_disconnect,
+    ::rust::Fn<void(uint8_t tcb_idx, bool encrypted, uint8_t key_size,
+                    bool authenticated, bool bonded)>
+        on_security_changed,
+    ::rust::Fn<void(uint8_t tcb_idx, std::array<uint8_t, 6> peer)>
+        on_bonded_peer,
+    ::rust::Fn<void(std::array<uint8_t, 6> peer)> on_bond_removed,
     ::rust::Fn<InterceptAction(uint8_t tcb_idx, ::rust::Vec<uint8_t> buffer)>
         intercept_packet);
 
 void SendPacketToPeer(uint8_t tcb_idx, ::rust::Vec<uint8_t> buffer);
 
+void DisconnectPeer(uint8_t tcb_idx);
+
 AclArbiter& GetArbiter();
 
 }  // namespace arbiter
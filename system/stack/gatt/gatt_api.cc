@@ -278,6 +278,10 @@ tGATT_STATUS GATTS_AddService(tGATT_IF gatt_if, btgatt_db_element_t* service,
         return GATT_INTERNAL_ERROR;
       }
 
+      // keep the range of the included service, as its handle is replaced by
+      // that of the include declaration
+      el->start_handle = p_incl_decl->asgn_range.s_handle;
+      el->end_handle = p_incl_decl->asgn_range.e_handle;
       el->attribute_handle = gatts_add_included_service(
           list.svc_db, p_incl_decl->asgn_range.s_handle,
           p_incl_decl->asgn_range.e_handle, p_incl_decl->asgn_range.svc_uuid);
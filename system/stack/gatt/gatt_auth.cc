@@ -29,6 +29,7 @@
 #include "gatt_int.h"
 #include "osi/include/allocator.h"
 #include "osi/include/osi.h"
+#include "stack/arbiter/acl_arbiter.h"
 #include "stack/btm/btm_ble_int.h"
 #include "stack/btm/btm_ble_int_types.h"
 #include "stack/btm/btm_sec.h"
@@ -220,6 +221,8 @@ void gatt_notify_enc_cmpl(const RawAddress& bd_addr) {
     return;
   }
 
+  bluetooth::shim::arbiter::GetArbiter().OnSecurityChanged(p_tcb->tcb_idx);
+
   for (uint8_t i = 0; i < GATT_MAX_APPS; i++) {
     if (gatt_cb.cl_rcb[i].in_use && gatt_cb.cl_rcb[i].app_cb.p_enc_cmpl_cb) {
       (*gatt_cb.cl_rcb[i].app_cb.p_enc_cmpl_cb)(gatt_cb.cl_rcb[i].gatt_if,
@@ -240,6 +243,21 @@ void gatt_notify_enc_cmpl(const RawAddress& bd_addr) {
     p_tcb->pending_enc_clcb = new_pending_clcbs;
   }
 }
+
+/*******************************************************************************
+ *
+ * Function         gatt_notify_bond_removed
+ *
+ * Description      bond removal notification, so that state persisted for the
+ *                  peer (e.g. its CCCD values) is forgotten.
+ *
+ * Returns
+ *
+ ******************************************************************************/
+void gatt_notify_bond_removed(const RawAddress& bd_addr) {
+  bluetooth::shim::arbiter::GetArbiter().OnBondRemoved(bd_addr);
+}
+
 /*******************************************************************************
  *
  * Function         gatt_set_sec_act
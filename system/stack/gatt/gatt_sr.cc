@@ -29,6 +29,7 @@
 #include "osi/include/allocator.h"
 #include "osi/include/log.h"
 #include "osi/include/osi.h"
+#include "stack/arbiter/acl_arbiter.h"
 #include "stack/eatt/eatt.h"
 #include "stack/include/bt_hdr.h"
 #include "stack/include/bt_types.h"
@@ -831,6 +832,9 @@ static void gatts_process_mtu_req(tGATT_TCB& tcb, uint16_t cid, uint16_t len,
       attp_build_sr_msg(tcb, GATT_RSP_MTU, &gatt_sr_msg, tcb.payload_size);
   attp_send_sr_msg(tcb, cid, p_buf);
 
+  bluetooth::shim::arbiter::GetArbiter().OnMtuExchanged(tcb.tcb_idx,
+                                                        tcb.payload_size);
+
   tGATTS_DATA gatts_data;
   gatts_data.mtu = tcb.payload_size;
   /* Notify all registered applicaiton with new MTU size. Us a transaction ID */
@@ -1201,6 +1201,9 @@ extern void gatt_free(void);
 // initiated outside GATT.
 extern void gatt_notify_enc_cmpl(const RawAddress& bd_addr);
 
+// Bond removal notification, so state persisted for the peer is forgotten.
+extern void gatt_notify_bond_removed(const RawAddress& bd_addr);
+
 /** Reset bg device list. If called after controller reset, set |after_reset| to
  * true, as there is no need to wipe controller acceptlist in this case. */
 extern void gatt_reset_bgdev_list(bool after_reset);
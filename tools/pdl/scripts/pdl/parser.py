@@ -137,6 +137,7 @@ def parse_declarations(data):
             'kind': 'endianness_declaration',
             'value': 'little_endian'
         },
+        'imports': [],
         'declarations': []
     }
 
@@ -147,6 +148,10 @@ def parse_declarations(data):
             ast['endianness']['value'] = 'little_endian'
         elif verb == 'big_endian_packets':
             ast['endianness']['value'] = 'big_endian'
+        elif verb == 'import':
+            m = re.match(rule(f' "{g(string, "path")}"'), data)
+            data = data[m.end():]
+            ast['imports'].append({'kind': 'import_declaration', 'path': m['path']})
         elif verb == 'checksum':
             raise Exception('checksum')
         elif verb == 'custom_field':
@@ -213,6 +218,15 @@ def parse_declarations(data):
         elif verb == 'test':
             m = re.match(rule(f' {g(identifier, "id")} {{ {g("[^}]*", "tests")} }}'), data)
             data = data[m.end():]
+            test_cases = re.findall(f'"({string})"', m['tests'])
+            ast['declarations'].append({
+                'kind': 'test_declaration',
+                'type_id': m['id'],
+                'test_cases': [{
+                    'kind': 'test_case',
+                    'input': input
+                } for input in test_cases]
+            })
         else:
             break
             raise Exception(f'unknown "{verb}"')
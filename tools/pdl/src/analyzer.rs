@@ -130,6 +130,19 @@ pub enum ErrorCode {
     DuplicatePayloadField = 36,
     MissingPayloadField = 37,
     RedundantArraySize = 38,
+    UndeclaredChecksumIdentifier = 39,
+    InvalidChecksumIdentifier = 40,
+    DuplicateChecksumField = 41,
+    InvalidChecksumStartPosition = 42,
+    MissingChecksumStart = 43,
+    InvalidPaddingField = 44,
+    InvalidTestInput = 45,
+    ImportedParentIdentifier = 46,
+    UndeclaredConditionIdentifier = 47,
+    InvalidConditionIdentifier = 48,
+    InvalidConditionPosition = 49,
+    InvalidConditionValue = 50,
+    InvalidOptionalField = 51,
 }
 
 impl From<ErrorCode> for String {
@@ -239,6 +252,36 @@ impl<'d, A: Annotation + Default> Scope<'d, A> {
         std::iter::successors(Some(decl), |decl| self.get_parent(decl)).flat_map(Decl::fields)
     }
 
+    /// Return the declaration and its parent's fields, with group
+    /// fields replaced by the fields of the group declaration. Fields
+    /// already constrained by a group field are skipped.
+    pub fn iter_inlined_fields(&self, decl: &'d crate::ast::Decl<A>) -> Vec<&'d Field<A>> {
+        fn inline_fields<'d, A: Annotation + Default>(
+            scope: &Scope<'d, A>,
+            fields: impl Iterator<Item = &'d Field<A>>,
+            constraints: &[&str],
+            inlined_fields: &mut Vec<&'d Field<A>>,
+        ) {
+            for field in fields {
+                match &field.desc {
+                    FieldDesc::Group { group_id, constraints: group_constraints } => {
+                        let mut constraints = constraints.to_vec();
+                        constraints.extend(group_constraints.iter().map(|c| c.id.as_str()));
+                        if let Some(group_decl) = scope.typedef.get(group_id) {
+                            inline_fields(scope, group_decl.fields(), &constraints, inlined_fields)
+                        }
+                    }
+                    _ if matches!(field.id(), Some(id) if constraints.contains(&id)) => (),
+                    _ => inlined_fields.push(field),
+                }
+            }
+        }
+
+        let mut inlined_fields = vec![];
+        inline_fields(self, self.iter_fields(decl), &[], &mut inlined_fields);
+        inlined_fields
+    }
+
     /// Return the type declaration for the selected field, if applicable.
     #[allow(dead_code)]
     pub fn get_declaration(
@@ -257,10 +300,12 @@ impl<'d, A: Annotation + Default> Scope<'d, A> {
             | FieldDesc::Reserved { .. }
             | FieldDesc::Group { .. }
             | FieldDesc::Scalar { .. }
+            | FieldDesc::OptionalScalar { .. }
             | FieldDesc::Array { type_id: None, .. } => None,
             FieldDesc::FixedEnum { enum_id: type_id, .. }
             | FieldDesc::Array { type_id: Some(type_id), .. }
-            | FieldDesc::Typedef { type_id, .. } => self.typedef.get(type_id).cloned(),
+            | FieldDesc::Typedef { type_id, .. }
+            | FieldDesc::OptionalTypedef { type_id, .. } => self.typedef.get(type_id).cloned(),
         }
     }
 }
@@ -350,6 +395,7 @@ fn check_decl_identifiers(
                 // If the type is a struct recurse the struct definition.
                 // Append the field to the packet re-definition.
                 FieldDesc::Typedef { type_id, .. }
+                | FieldDesc::OptionalTypedef { type_id, .. }
                 | FieldDesc::Array { type_id: Some(type_id), .. } => {
                     match scope.typedef.get(type_id) {
                         None => diagnostics.push(
@@ -376,6 +422,7 @@ fn check_decl_identifiers(
                             // Not recursing on array type since it is allowed to
                             // have recursive structures, e.g. nested TLV types.
                             if matches!(&field.desc, FieldDesc::Typedef { .. }) ||
+                               matches!(&field.desc, FieldDesc::OptionalTypedef { .. }) ||
                                matches!(&field.desc, FieldDesc::Array { size: Some(_), .. }) {
                                 bfs(typedef_decl, context, scope, diagnostics)
                             }
@@ -563,7 +610,11 @@ fn check_constraints(
         scope: &Scope<parser_ast::Annotation>,
         diagnostics: &mut Diagnostics,
     ) {
-        match scope.iter_fields(decl).find(|field| field.id() == Some(&constraint.id)) {
+        match scope
+            .iter_inlined_fields(decl)
+            .into_iter()
+            .find(|field| field.id() == Some(&constraint.id))
+        {
             None => diagnostics.push(
                 Diagnostic::error()
                     .with_code(ErrorCode::UndeclaredConstraintIdentifier)
@@ -571,15 +622,24 @@ fn check_constraints(
                     .with_labels(vec![constraint.loc.primary()])
                     .with_notes(vec!["hint: expected scalar or typedef identifier".to_owned()]),
             ),
-            Some(field @ Field { desc: FieldDesc::Array { .. }, .. }) => diagnostics.push(
+            Some(
+                field @ Field {
+                    desc:
+                        FieldDesc::Array { .. }
+                        | FieldDesc::OptionalScalar { .. }
+                        | FieldDesc::OptionalTypedef { .. },
+                    ..
+                },
+            ) => diagnostics.push(
                 Diagnostic::error()
                     .with_code(ErrorCode::InvalidConstraintIdentifier)
                     .with_message(format!("invalid constraint identifier `{}`", constraint.id))
                     .with_labels(vec![
                         constraint.loc.primary(),
                         field.loc.secondary().with_message(format!(
-                            "`{}` is declared here as array field",
-                            constraint.id
+                            "`{}` is declared here as {} field",
+                            constraint.id,
+                            field.kind()
                         )),
                     ])
                     .with_notes(vec!["hint: expected scalar or typedef identifier".to_owned()]),
@@ -1037,6 +1097,304 @@ fn check_array_fields(file: &parser_ast::File) -> Result<(), Diagnostics> {
     diagnostics.err_or(())
 }
 
+/// Check checksum fields.
+/// Raises error diagnostics for the following cases:
+///      - undeclared checksum identifier
+///      - invalid checksum identifier
+///      - duplicate checksum start
+///      - checksum value field declared before the checksum start
+///      - checksum value field without checksum start
+fn check_checksum_fields(
+    file: &parser_ast::File,
+    scope: &Scope<parser_ast::Annotation>,
+) -> Result<(), Diagnostics> {
+    let is_checksum_field = |field: &parser_ast::Field| match &field.desc {
+        FieldDesc::Typedef { type_id, .. } => {
+            matches!(scope.typedef.get(type_id), Some(Decl { desc: DeclDesc::Checksum { .. }, .. }))
+        }
+        _ => false,
+    };
+
+    let mut diagnostics: Diagnostics = Default::default();
+    for decl in &file.declarations {
+        let mut checksum_start_for_id = HashMap::new();
+        for (index, field) in decl.fields().enumerate() {
+            let field_id = match &field.desc {
+                FieldDesc::Checksum { field_id } => field_id,
+                _ => continue,
+            };
+
+            if let Some(prev) = checksum_start_for_id.insert(field_id.as_str(), field) {
+                diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::DuplicateChecksumField)
+                        .with_message(format!("duplicate checksum start for `{}`", field_id))
+                        .with_labels(vec![
+                            field.loc.primary(),
+                            prev.loc
+                                .secondary()
+                                .with_message("checksum start is first declared here"),
+                        ]),
+                )
+            }
+
+            match decl.fields().enumerate().find(|(_, field)| field.id() == Some(field_id)) {
+                None => diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::UndeclaredChecksumIdentifier)
+                        .with_message(format!("undeclared checksum identifier `{}`", field_id))
+                        .with_labels(vec![field.loc.primary()])
+                        .with_notes(vec!["hint: expected checksum field identifier".to_owned()]),
+                ),
+                Some((_, value_field)) if !is_checksum_field(value_field) => diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::InvalidChecksumIdentifier)
+                        .with_message(format!("invalid checksum identifier `{}`", field_id))
+                        .with_labels(vec![field.loc.primary(), value_field.loc.secondary()])
+                        .with_notes(vec!["hint: expected checksum field identifier".to_owned()]),
+                ),
+                Some((value_index, value_field)) if value_index < index => diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::InvalidChecksumStartPosition)
+                        .with_message(format!(
+                            "checksum start for `{}` is declared after the checksum field",
+                            field_id
+                        ))
+                        .with_labels(vec![
+                            field.loc.primary(),
+                            value_field
+                                .loc
+                                .secondary()
+                                .with_message(format!("`{}` is declared here", field_id)),
+                        ]),
+                ),
+                Some(_) => (),
+            }
+        }
+
+        for field in decl.fields().filter(|field| is_checksum_field(field)) {
+            let id = field.id().unwrap();
+            if !checksum_start_for_id.contains_key(id) {
+                diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::MissingChecksumStart)
+                        .with_message(format!("missing checksum start for `{}`", id))
+                        .with_labels(vec![field.loc.primary()])
+                        .with_notes(vec![format!("hint: add `_checksum_start_({})`", id)]),
+                )
+            }
+        }
+    }
+
+    diagnostics.err_or(())
+}
+
+/// Check padding fields.
+/// Raises error diagnostics for the following cases:
+///      - padding field not following an array field
+fn check_padding_fields(file: &parser_ast::File) -> Result<(), Diagnostics> {
+    let mut diagnostics: Diagnostics = Default::default();
+    for decl in &file.declarations {
+        let mut preceding_field: Option<&parser_ast::Field> = None;
+        for field in decl.fields() {
+            match (&field.desc, preceding_field) {
+                (FieldDesc::Padding { .. }, Some(Field { desc: FieldDesc::Array { .. }, .. })) => {}
+                (FieldDesc::Padding { .. }, _) => diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::InvalidPaddingField)
+                        .with_message("padding field does not follow an array field")
+                        .with_labels(vec![field.loc.primary()])
+                        .with_notes(vec![
+                            "hint: padding fields must be placed after an array field".to_owned(),
+                        ]),
+                ),
+                _ => (),
+            }
+            preceding_field = Some(field);
+        }
+    }
+
+    diagnostics.err_or(())
+}
+
+/// Check optional fields.
+/// Raises error diagnostics for the following cases:
+///      - undeclared condition identifier
+///      - invalid condition identifier (not a scalar or enum field)
+///      - condition field declared after the optional field
+///      - invalid condition value (bad type, overflow, undeclared tag)
+///      - optional field with a size which is not a multiple of 8 bits
+///      - optional field with a checksum type
+fn check_optional_fields(
+    file: &parser_ast::File,
+    scope: &Scope<parser_ast::Annotation>,
+) -> Result<(), Diagnostics> {
+    let mut diagnostics: Diagnostics = Default::default();
+    for decl in &file.declarations {
+        let mut preceding_fields = HashMap::new();
+        for field in decl.fields() {
+            let condition = match &field.desc {
+                FieldDesc::OptionalScalar { condition, .. }
+                | FieldDesc::OptionalTypedef { condition, .. } => condition,
+                _ => {
+                    if let Some(id) = field.id() {
+                        preceding_fields.insert(id, field);
+                    }
+                    continue;
+                }
+            };
+
+            match &field.desc {
+                FieldDesc::OptionalScalar { width, .. } if width % 8 != 0 => diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::InvalidOptionalField)
+                        .with_message(format!("invalid optional field `{}`", field.id().unwrap()))
+                        .with_labels(vec![field.loc.primary()])
+                        .with_notes(vec!["hint: expected a width multiple of 8".to_owned()]),
+                ),
+                FieldDesc::OptionalTypedef { type_id, .. } => match scope.typedef.get(type_id) {
+                    Some(Decl { desc: DeclDesc::Checksum { .. }, .. }) => diagnostics.push(
+                        Diagnostic::error()
+                            .with_code(ErrorCode::InvalidOptionalField)
+                            .with_message(format!(
+                                "invalid optional field `{}`",
+                                field.id().unwrap()
+                            ))
+                            .with_labels(vec![field.loc.primary()])
+                            .with_notes(vec![
+                                "hint: expected enum, struct, or custom_field type".to_owned()
+                            ]),
+                    ),
+                    Some(Decl { desc: DeclDesc::Enum { width, .. }, .. })
+                    | Some(Decl {
+                        desc: DeclDesc::CustomField { width: Some(width), .. }, ..
+                    }) if width % 8 != 0 => diagnostics.push(
+                        Diagnostic::error()
+                            .with_code(ErrorCode::InvalidOptionalField)
+                            .with_message(format!(
+                                "invalid optional field `{}`",
+                                field.id().unwrap()
+                            ))
+                            .with_labels(vec![field.loc.primary()])
+                            .with_notes(vec!["hint: expected a width multiple of 8".to_owned()]),
+                    ),
+                    _ => (),
+                },
+                _ => (),
+            }
+
+            match preceding_fields.get(condition.id.as_str()) {
+                None => match decl.fields().find(|f| f.id() == Some(&condition.id)) {
+                    None => diagnostics.push(
+                        Diagnostic::error()
+                            .with_code(ErrorCode::UndeclaredConditionIdentifier)
+                            .with_message(format!(
+                                "undeclared condition identifier `{}`",
+                                condition.id
+                            ))
+                            .with_labels(vec![condition.loc.primary()])
+                            .with_notes(vec![
+                                "hint: expected scalar or enum field identifier".to_owned()
+                            ]),
+                    ),
+                    Some(condition_field) => diagnostics.push(
+                        Diagnostic::error()
+                            .with_code(ErrorCode::InvalidConditionPosition)
+                            .with_message(format!(
+                                "condition field `{}` is declared after the optional field",
+                                condition.id
+                            ))
+                            .with_labels(vec![
+                                condition.loc.primary(),
+                                condition_field
+                                    .loc
+                                    .secondary()
+                                    .with_message(format!("`{}` is declared here", condition.id)),
+                            ]),
+                    ),
+                },
+                Some(condition_field @ Field { desc: FieldDesc::Scalar { width, .. }, .. }) => {
+                    match condition.value {
+                        Some(value) if bit_width(value) <= *width => (),
+                        _ => diagnostics.push(
+                            Diagnostic::error()
+                                .with_code(ErrorCode::InvalidConditionValue)
+                                .with_message(format!(
+                                    "invalid condition value for `{}`",
+                                    condition.id
+                                ))
+                                .with_labels(vec![
+                                    condition.loc.primary(),
+                                    condition_field.loc.secondary().with_message(format!(
+                                        "`{}` is declared here as {}-bit scalar field",
+                                        condition.id, width
+                                    )),
+                                ])
+                                .with_notes(vec![format!(
+                                    "hint: expected scalar value fitting in {} bits",
+                                    width
+                                )]),
+                        ),
+                    }
+                }
+                Some(condition_field @ Field { desc: FieldDesc::Typedef { type_id, .. }, .. })
+                    if matches!(
+                        scope.typedef.get(type_id),
+                        Some(Decl { desc: DeclDesc::Enum { .. }, .. })
+                    ) =>
+                {
+                    let tags = match &scope.typedef[type_id].desc {
+                        DeclDesc::Enum { tags, .. } => tags,
+                        _ => unreachable!(),
+                    };
+                    match &condition.tag_id {
+                        Some(tag_id) if tags.iter().any(|tag| &tag.id == tag_id) => (),
+                        _ => diagnostics.push(
+                            Diagnostic::error()
+                                .with_code(ErrorCode::InvalidConditionValue)
+                                .with_message(format!(
+                                    "invalid condition value for `{}`",
+                                    condition.id
+                                ))
+                                .with_labels(vec![
+                                    condition.loc.primary(),
+                                    condition_field.loc.secondary().with_message(format!(
+                                        "`{}` is declared here as enum `{}`",
+                                        condition.id, type_id
+                                    )),
+                                ])
+                                .with_notes(vec![format!(
+                                    "hint: expected tag of enum `{}`",
+                                    type_id
+                                )]),
+                        ),
+                    }
+                }
+                Some(condition_field) => diagnostics.push(
+                    Diagnostic::error()
+                        .with_code(ErrorCode::InvalidConditionIdentifier)
+                        .with_message(format!("invalid condition identifier `{}`", condition.id))
+                        .with_labels(vec![
+                            condition.loc.primary(),
+                            condition_field.loc.secondary().with_message(format!(
+                                "`{}` is declared here as {} field",
+                                condition.id,
+                                condition_field.kind()
+                            )),
+                        ])
+                        .with_notes(vec![
+                            "hint: expected scalar or enum field identifier".to_owned()
+                        ]),
+                ),
+            }
+
+            preceding_fields.insert(field.id().unwrap(), field);
+        }
+    }
+
+    diagnostics.err_or(())
+}
+
 /// Check correct definition of packet sizes.
 /// Annotate fields and declarations with the size in bits.
 fn compute_field_sizes(file: &parser_ast::File) -> ast::File {
@@ -1119,6 +1477,17 @@ fn compute_field_sizes(file: &parser_ast::File) -> ast::File {
                 let type_annot = scope.get(type_id).unwrap();
                 ast::FieldAnnotation { size: type_annot.size + type_annot.payload_size }
             }
+            // The presence of optional fields depends on the value of
+            // their condition field.
+            FieldDesc::OptionalScalar { .. } | FieldDesc::OptionalTypedef { .. } => {
+                ast::FieldAnnotation { size: ast::Size::Dynamic }
+            }
+            FieldDesc::Array { .. } if decl.padded_size(field).is_some() => {
+                // Padded arrays occupy the padding size, whatever the
+                // size of the array itself.
+                let padded_size = decl.padded_size(field).unwrap();
+                ast::FieldAnnotation { size: ast::Size::Static(8 * padded_size) }
+            }
             FieldDesc::Array { width: Some(width), size: Some(size), .. } => {
                 ast::FieldAnnotation { size: ast::Size::Static(*size * *width) }
             }
@@ -1162,14 +1531,171 @@ fn compute_field_sizes(file: &parser_ast::File) -> ast::File {
         file: file.file,
         comments: file.comments.clone(),
         endianness: file.endianness,
+        imports: file.imports.clone(),
         declarations,
     }
 }
 
 /// Inline group fields and remove group declarations.
-fn inline_groups(_file: &mut ast::File) -> Result<(), Diagnostics> {
-    // TODO
-    Ok(())
+/// Scalar and typedef fields constrained by the group field are replaced
+/// by the matching fixed fields. Optional fields whose condition field
+/// is constrained become regular fields when the constraint matches the
+/// condition, and are removed otherwise.
+pub fn inline_groups(file: &parser_ast::File) -> parser_ast::File {
+    fn inline_fields<'a>(
+        fields: impl Iterator<Item = &'a parser_ast::Field>,
+        groups: &HashMap<String, &'a parser_ast::Decl>,
+        constraints: &HashMap<String, &'a Constraint>,
+    ) -> Vec<parser_ast::Field> {
+        fields
+            .flat_map(|field| match &field.desc {
+                FieldDesc::Group { group_id, constraints: group_constraints } => {
+                    let mut constraints = constraints.clone();
+                    constraints.extend(
+                        group_constraints
+                            .iter()
+                            .map(|constraint| (constraint.id.to_string(), constraint)),
+                    );
+                    inline_fields(groups.get(group_id).unwrap().fields(), groups, &constraints)
+                }
+                FieldDesc::Scalar { id, width } if constraints.contains_key(id) => {
+                    vec![Field {
+                        loc: field.loc,
+                        annot: field.annot,
+                        desc: FieldDesc::FixedScalar {
+                            width: *width,
+                            value: constraints.get(id).unwrap().value.unwrap(),
+                        },
+                    }]
+                }
+                FieldDesc::Typedef { id, type_id } if constraints.contains_key(id) => {
+                    vec![Field {
+                        loc: field.loc,
+                        annot: field.annot,
+                        desc: FieldDesc::FixedEnum {
+                            enum_id: type_id.clone(),
+                            tag_id: constraints.get(id).unwrap().tag_id.clone().unwrap(),
+                        },
+                    }]
+                }
+                FieldDesc::OptionalScalar { condition, .. }
+                | FieldDesc::OptionalTypedef { condition, .. }
+                    if constraints.contains_key(&condition.id) =>
+                {
+                    let constraint = constraints.get(&condition.id).unwrap();
+                    if constraint.value != condition.value || constraint.tag_id != condition.tag_id
+                    {
+                        return vec![];
+                    }
+                    vec![Field {
+                        loc: field.loc,
+                        annot: field.annot,
+                        desc: match &field.desc {
+                            FieldDesc::OptionalScalar { id, width, .. } => {
+                                FieldDesc::Scalar { id: id.clone(), width: *width }
+                            }
+                            FieldDesc::OptionalTypedef { id, type_id, .. } => {
+                                FieldDesc::Typedef { id: id.clone(), type_id: type_id.clone() }
+                            }
+                            _ => unreachable!(),
+                        },
+                    }]
+                }
+                _ => vec![field.annotate(())],
+            })
+            .collect()
+    }
+
+    let groups = file
+        .declarations
+        .iter()
+        .filter_map(|decl| match &decl.desc {
+            DeclDesc::Group { id, .. } => Some((id.to_string(), decl)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    File {
+        version: file.version.clone(),
+        file: file.file,
+        comments: file.comments.clone(),
+        endianness: file.endianness,
+        imports: file.imports.clone(),
+        declarations: file
+            .declarations
+            .iter()
+            .filter(|decl| !matches!(decl.desc, DeclDesc::Group { .. }))
+            .map(|decl| {
+                decl.annotate((), |fields| inline_fields(fields.iter(), &groups, &HashMap::new()))
+            })
+            .collect(),
+    }
+}
+
+/// Check test declarations.
+/// Raises error diagnostics for the following cases:
+///      - invalid test input
+fn check_test_declarations(file: &parser_ast::File) -> Result<(), Diagnostics> {
+    let mut diagnostics: Diagnostics = Default::default();
+    for decl in &file.declarations {
+        if let DeclDesc::Test { test_cases, .. } = &decl.desc {
+            for test_case in test_cases {
+                if let Err(err) = test_case.bytes() {
+                    diagnostics.push(
+                        Diagnostic::error()
+                            .with_code(ErrorCode::InvalidTestInput)
+                            .with_message(format!("invalid test input: {}", err))
+                            .with_labels(vec![test_case.loc.primary()])
+                            .with_notes(vec![
+                                "hint: expected a byte string such as \"\\x01\\x02\"".to_owned(),
+                            ]),
+                    )
+                }
+            }
+        }
+    }
+
+    diagnostics.err_or(())
+}
+
+/// Check declarations imported from other files.
+/// Raises error diagnostics for the following cases:
+///      - packet or struct extending an imported declaration
+fn check_imported_declarations(
+    file: &parser_ast::File,
+    scope: &Scope<parser_ast::Annotation>,
+) -> Result<(), Diagnostics> {
+    let mut diagnostics: Diagnostics = Default::default();
+    for decl in &file.declarations {
+        let parent_decl = decl.parent_id().and_then(|parent_id| scope.typedef.get(parent_id));
+        match parent_decl {
+            Some(parent_decl) if parent_decl.loc.file != decl.loc.file => diagnostics.push(
+                Diagnostic::error()
+                    .with_code(ErrorCode::ImportedParentIdentifier)
+                    .with_message(format!(
+                        "{} `{}` extends the imported {} `{}`",
+                        decl.kind(),
+                        decl.id().unwrap(),
+                        parent_decl.kind(),
+                        parent_decl.id().unwrap()
+                    ))
+                    .with_labels(vec![
+                        decl.loc.primary(),
+                        parent_decl.loc.secondary().with_message(format!(
+                            "`{}` is declared here",
+                            parent_decl.id().unwrap()
+                        )),
+                    ])
+                    .with_notes(vec![
+                        "hint: declarations can only extend declarations from the same file"
+                            .to_owned(),
+                    ]),
+            ),
+            _ => (),
+        }
+    }
+
+    diagnostics.err_or(())
 }
 
 /// Analyzer entry point, produces a new AST with annotations resulting
@@ -1184,17 +1710,20 @@ pub fn analyze(file: &parser_ast::File) -> Result<ast::File, Diagnostics> {
     check_fixed_fields(file, &scope)?;
     check_payload_fields(file)?;
     check_array_fields(file)?;
-    // TODO check_checksum_fields(file, &scope)?;
-    // TODO check_padding_fields(file, &scope)?;
-    let mut file = compute_field_sizes(file);
-    inline_groups(&mut file)?;
-    Ok(file)
+    check_checksum_fields(file, &scope)?;
+    check_padding_fields(file)?;
+    check_optional_fields(file, &scope)?;
+    check_test_declarations(file)?;
+    check_imported_declarations(file, &scope)?;
+    let file = inline_groups(file);
+    Ok(compute_field_sizes(&file))
 }
 
 #[cfg(test)]
 mod test {
     use crate::analyzer;
     use crate::ast::*;
+    use crate::parser;
     use crate::parser::parse_inline;
     use codespan_reporting::term::termcolor;
 
@@ -1391,7 +1920,6 @@ mod test {
         );
     }
 
-    #[ignore]
     #[test]
     fn test_e9() {
         raises!(
@@ -1403,7 +1931,6 @@ mod test {
         );
     }
 
-    #[ignore]
     #[test]
     fn test_e10() {
         raises!(
@@ -1969,4 +2496,344 @@ mod test {
         "#
         );
     }
+
+    #[test]
+    fn test_e39() {
+        raises!(
+            UndeclaredChecksumIdentifier,
+            r#"
+        little_endian_packets
+        checksum Crc : 8 "Crc"
+        packet A {
+            _checksum_start_(crc),
+            a : 8,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e40() {
+        raises!(
+            InvalidChecksumIdentifier,
+            r#"
+        little_endian_packets
+        packet A {
+            _checksum_start_(crc),
+            crc : 8,
+        }
+        "#
+        );
+
+        raises!(
+            InvalidChecksumIdentifier,
+            r#"
+        little_endian_packets
+        enum Crc : 8 { X = 0 }
+        packet A {
+            _checksum_start_(crc),
+            crc : Crc,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e41() {
+        raises!(
+            DuplicateChecksumField,
+            r#"
+        little_endian_packets
+        checksum Crc : 8 "Crc"
+        packet A {
+            _checksum_start_(crc),
+            a : 8,
+            _checksum_start_(crc),
+            crc : Crc,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e42() {
+        raises!(
+            InvalidChecksumStartPosition,
+            r#"
+        little_endian_packets
+        checksum Crc : 8 "Crc"
+        packet A {
+            crc : Crc,
+            _checksum_start_(crc),
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e43() {
+        raises!(
+            MissingChecksumStart,
+            r#"
+        little_endian_packets
+        checksum Crc : 8 "Crc"
+        packet A {
+            a : 8,
+            crc : Crc,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e44() {
+        raises!(
+            InvalidPaddingField,
+            r#"
+        little_endian_packets
+        packet A {
+            _padding_ [16],
+        }
+        "#
+        );
+
+        raises!(
+            InvalidPaddingField,
+            r#"
+        little_endian_packets
+        packet A {
+            a : 8,
+            _padding_ [16],
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e45() {
+        raises!(
+            InvalidTestInput,
+            r#"
+        little_endian_packets
+        packet A {
+            a : 8,
+        }
+        test A {
+            "\x0",
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e46() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_source = |name: &str, source: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, source).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        write_source("a.pdl", "little_endian_packets packet A { a: 8, _payload_ }");
+        let path =
+            write_source("b.pdl", "little_endian_packets import \"a.pdl\" packet B : A (a = 1) {}");
+
+        let mut db = SourceDatabase::new();
+        let file = parser::parse_file(&mut db, path).expect("parsing failure");
+        let file = parser::parse_imports(&mut db, &file).expect("parsing failure");
+        let diagnostics = analyzer::analyze(&file).err().unwrap();
+        assert_eq!(diagnostics.diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics.diagnostics[0].code,
+            Some(analyzer::ErrorCode::ImportedParentIdentifier.into())
+        );
+    }
+
+    #[test]
+    fn test_e47() {
+        raises!(
+            UndeclaredConditionIdentifier,
+            r#"
+        little_endian_packets
+        packet A {
+            a : 8 if b = 1,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e48() {
+        raises!(
+            InvalidConditionIdentifier,
+            r#"
+        little_endian_packets
+        struct S { x : 8 }
+        packet A {
+            s : S,
+            a : 8 if s = 1,
+        }
+        "#
+        );
+
+        raises!(
+            InvalidConditionIdentifier,
+            r#"
+        little_endian_packets
+        packet A {
+            b : 1,
+            _reserved_ : 7,
+            c : 8 if b = 1,
+            a : 8 if c = 1,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e49() {
+        raises!(
+            InvalidConditionPosition,
+            r#"
+        little_endian_packets
+        packet A {
+            a : 8 if b = 1,
+            b : 8,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e50() {
+        raises!(
+            InvalidConditionValue,
+            r#"
+        little_endian_packets
+        packet A {
+            b : 1,
+            _reserved_ : 7,
+            a : 8 if b = 2,
+        }
+        "#
+        );
+
+        raises!(
+            InvalidConditionValue,
+            r#"
+        little_endian_packets
+        enum E : 8 { X = 0 }
+        packet A {
+            b : 8,
+            a : 8 if b = X,
+        }
+        "#
+        );
+
+        raises!(
+            InvalidConditionValue,
+            r#"
+        little_endian_packets
+        enum E : 8 { X = 0 }
+        packet A {
+            b : E,
+            a : 8 if b = Y,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_e51() {
+        raises!(
+            InvalidOptionalField,
+            r#"
+        little_endian_packets
+        packet A {
+            b : 8,
+            a : 4 if b = 1,
+        }
+        "#
+        );
+
+        raises!(
+            InvalidOptionalField,
+            r#"
+        little_endian_packets
+        enum E : 4 { X = 0 }
+        packet A {
+            b : 8,
+            a : E if b = 1,
+        }
+        "#
+        );
+
+        raises!(
+            InvalidOptionalField,
+            r#"
+        little_endian_packets
+        checksum C : 8 "crc"
+        packet A {
+            b : 8,
+            a : C if b = 1,
+        }
+        "#
+        );
+    }
+
+    #[test]
+    fn test_inline_groups() {
+        let mut db = SourceDatabase::new();
+        let file = parse_inline(
+            &mut db,
+            "stdin".to_owned(),
+            r#"
+        little_endian_packets
+        enum E : 8 { X = 0, Y = 1 }
+        group G {
+            a : 8,
+            b : E,
+            c : 8,
+        }
+        group H {
+            G { a = 1 },
+            d : 8,
+        }
+        packet A {
+            H { b = Y },
+            _payload_,
+        }
+        packet B : A (c = 2, d = 3) {}
+        "#
+            .to_owned(),
+        )
+        .expect("parsing failure");
+        let file = analyzer::analyze(&file).expect("analyzer failure");
+
+        assert!(!file.declarations.iter().any(|decl| matches!(decl.desc, DeclDesc::Group { .. })));
+        let a = file.declarations.iter().find(|decl| decl.id() == Some("A")).unwrap();
+        assert_eq!(
+            a.fields().map(|field| &field.desc).collect::<Vec<_>>(),
+            vec![
+                &FieldDesc::FixedScalar { width: 8, value: 1 },
+                &FieldDesc::FixedEnum { enum_id: "E".to_owned(), tag_id: "Y".to_owned() },
+                &FieldDesc::Scalar { id: "c".to_owned(), width: 8 },
+                &FieldDesc::Scalar { id: "d".to_owned(), width: 8 },
+                &FieldDesc::Payload { size_modifier: None },
+            ]
+        );
+
+        // Fields set by group constraints cannot be constrained again.
+        raises!(
+            UndeclaredConstraintIdentifier,
+            r#"
+        little_endian_packets
+        group G { a : 8, b : 8 }
+        packet A {
+            G { a = 1 },
+            _payload_,
+        }
+        packet B : A (a = 2) {}
+        "#
+        );
+    }
 }
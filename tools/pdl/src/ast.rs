@@ -108,6 +108,10 @@ pub enum FieldDesc {
     Scalar { id: String, width: usize },
     #[serde(rename = "typedef_field")]
     Typedef { id: String, type_id: String },
+    #[serde(rename = "optional_field")]
+    OptionalScalar { id: String, width: usize, condition: Constraint },
+    #[serde(rename = "optional_field")]
+    OptionalTypedef { id: String, type_id: String, condition: Constraint },
     #[serde(rename = "group_field")]
     Group { group_id: String, constraints: Vec<Constraint> },
 }
@@ -121,6 +125,13 @@ pub struct Field<A: Annotation> {
     pub desc: FieldDesc,
 }
 
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "kind", rename = "import_declaration")]
+pub struct Import {
+    pub loc: SourceRange,
+    pub path: String,
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(tag = "kind", rename = "test_case")]
 pub struct TestCase {
@@ -128,6 +139,16 @@ pub struct TestCase {
     pub input: String,
 }
 
+impl TestCase {
+    /// Decode the test input. The input is written with the escape
+    /// sequences of a Rust byte string literal, e.g. `"\x01\x02"`.
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        syn::parse_str::<syn::LitByteStr>(&format!("b\"{}\"", self.input))
+            .map(|lit| lit.value())
+            .map_err(|err| err.to_string())
+    }
+}
+
 #[derive(Debug, Serialize)]
 #[serde(tag = "kind")]
 pub enum DeclDesc<A: Annotation> {
@@ -172,6 +193,7 @@ pub struct File<A: Annotation> {
     pub file: FileId,
     pub comments: Vec<Comment>,
     pub endianness: Endianness,
+    pub imports: Vec<Import>,
     pub declarations: Vec<Decl<A>>,
 }
 
@@ -246,6 +268,7 @@ impl<A: Annotation> File<A> {
                 loc: SourceRange::default(),
                 value: EndiannessValue::LittleEndian,
             },
+            imports: vec![],
             declarations: vec![],
             file,
         }
@@ -255,7 +278,9 @@ impl<A: Annotation> File<A> {
     /// /!\ This method is unsafe to use if the file contains cyclic
     /// declarations, use with caution.
     pub fn iter_children<'d>(&'d self, decl: &'d Decl<A>) -> impl Iterator<Item = &'d Decl<A>> {
-        self.declarations.iter().filter(|other_decl| other_decl.parent_id() == decl.id())
+        self.declarations
+            .iter()
+            .filter(|other_decl| decl.id().is_some() && other_decl.parent_id() == decl.id())
     }
 }
 
@@ -351,8 +376,16 @@ impl<A: Annotation> Decl<A> {
                     None => 0,
                     Some(id) => scope.typedef.get(id.as_str())?.width(scope, true)?,
                 };
-                for field in fields.iter() {
-                    packet_size += field.width(scope, skip_payload)?;
+                let mut fields = fields.iter().peekable();
+                while let Some(field) = fields.next() {
+                    // Padded arrays occupy the padding size, whatever
+                    // the size of the array itself.
+                    if let Some(Field { desc: FieldDesc::Padding { size }, .. }) = fields.peek() {
+                        packet_size += size * 8;
+                        fields.next();
+                    } else {
+                        packet_size += field.width(scope, skip_payload)?;
+                    }
                 }
                 Some(packet_size)
             }
@@ -369,6 +402,16 @@ impl<A: Annotation> Decl<A> {
         }
     }
 
+    /// Return the size in octets of the padding field immediately
+    /// following the selected field, if any.
+    pub fn padded_size(&self, field: &Field<A>) -> Option<usize> {
+        let mut fields = self.fields().skip_while(|f| !std::ptr::eq(*f, field)).skip(1);
+        match fields.next()?.desc {
+            FieldDesc::Padding { size } => Some(size),
+            _ => None,
+        }
+    }
+
     pub fn kind(&self) -> &str {
         match &self.desc {
             DeclDesc::Checksum { .. } => "checksum",
@@ -402,7 +445,9 @@ impl<A: Annotation> Field<A> {
             | FieldDesc::Group { .. } => None,
             FieldDesc::Array { id, .. }
             | FieldDesc::Scalar { id, .. }
-            | FieldDesc::Typedef { id, .. } => Some(id),
+            | FieldDesc::Typedef { id, .. }
+            | FieldDesc::OptionalScalar { id, .. }
+            | FieldDesc::OptionalTypedef { id, .. } => Some(id),
         }
     }
 
@@ -430,15 +475,17 @@ impl<A: Annotation> Field<A> {
         match &self.desc {
             FieldDesc::FixedEnum { enum_id, .. } => scope.typedef.get(enum_id).copied(),
             FieldDesc::Array { type_id: Some(type_id), .. } => scope.typedef.get(type_id).copied(),
-            FieldDesc::Typedef { type_id, .. } => scope.typedef.get(type_id.as_str()).copied(),
+            FieldDesc::Typedef { type_id, .. } | FieldDesc::OptionalTypedef { type_id, .. } => {
+                scope.typedef.get(type_id.as_str()).copied()
+            }
             _ => None,
         }
     }
 
     /// Determine the size of a field in bits, if possible.
     ///
-    /// If the field is dynamically sized (e.g. unsized array or
-    /// payload field), `None` is returned. If `skip_payload` is set,
+    /// If the field is dynamically sized (e.g. unsized array,
+    /// optional or payload field), `None` is returned. If `skip_payload` is set,
     /// payload and body fields are counted as having size `0` rather
     /// than a variable size.
     pub fn width(&self, scope: &lint::Scope<'_>, skip_payload: bool) -> Option<usize> {
@@ -450,7 +497,8 @@ impl<A: Annotation> Field<A> {
             | FieldDesc::Reserved { width, .. }
             | FieldDesc::FixedScalar { width, .. } => Some(*width),
             FieldDesc::FixedEnum { .. } => self.declaration(scope)?.width(scope, false),
-            FieldDesc::Padding { .. } => todo!(),
+            // The padding size is accounted for by the parent declaration.
+            FieldDesc::Padding { .. } => None,
             FieldDesc::Array { size: Some(size), width, .. } => {
                 let width = width.or_else(|| self.declaration(scope)?.width(scope, false))?;
                 Some(width * size)
@@ -464,7 +512,7 @@ impl<A: Annotation> Field<A> {
 
     pub fn kind(&self) -> &str {
         match &self.desc {
-            FieldDesc::Checksum { .. } => "payload",
+            FieldDesc::Checksum { .. } => "checksum",
             FieldDesc::Padding { .. } => "padding",
             FieldDesc::Size { .. } => "size",
             FieldDesc::Count { .. } => "count",
@@ -477,6 +525,7 @@ impl<A: Annotation> Field<A> {
             FieldDesc::Array { .. } => "array",
             FieldDesc::Scalar { .. } => "scalar",
             FieldDesc::Typedef { .. } => "typedef",
+            FieldDesc::OptionalScalar { .. } | FieldDesc::OptionalTypedef { .. } => "optional",
         }
     }
 }
@@ -2,5 +2,6 @@
 
 pub mod intermediate;
 pub mod json;
+pub mod pdl;
 pub mod rust;
 pub mod rust_no_allocation;
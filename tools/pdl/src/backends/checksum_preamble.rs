@@ -0,0 +1,75 @@
+/// Checksum computed over the bytes between a `_checksum_start_` field
+/// and the checksum value field.
+///
+/// `checksum` declarations name the type implementing this trait, either
+/// one of the implementations below or a type supplied alongside the
+/// generated code.
+pub trait ChecksumAlgorithm: Default {
+    /// Unsigned integer type of the checksum value.
+    type Value: Copy + PartialEq + Into<u64>;
+
+    /// Add `bytes` to the checksum.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Return the checksum of the bytes added so far.
+    fn get_checksum(&self) -> Self::Value;
+}
+
+/// CRC-16 with polynomial x^16 + x^15 + x^2 + 1 and initial value 0,
+/// used by the L2CAP Frame Check Sequence (Core Specification Vol 3,
+/// Part A, 3.3.5).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Crc16(u16);
+
+impl ChecksumAlgorithm for Crc16 {
+    type Value = u16;
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u16;
+            for _ in 0..8 {
+                self.0 = if self.0 & 1 != 0 { (self.0 >> 1) ^ 0xa001 } else { self.0 >> 1 };
+            }
+        }
+    }
+
+    fn get_checksum(&self) -> u16 {
+        self.0
+    }
+}
+
+/// Sum of the bytes, modulo 2^8.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sum8(u8);
+
+impl ChecksumAlgorithm for Sum8 {
+    type Value = u8;
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.wrapping_add(*byte);
+        }
+    }
+
+    fn get_checksum(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Sum of the bytes, modulo 2^16.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sum16(u16);
+
+impl ChecksumAlgorithm for Sum16 {
+    type Value = u16;
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.wrapping_add(*byte as u16);
+        }
+    }
+
+    fn get_checksum(&self) -> u16 {
+        self.0
+    }
+}
@@ -6,6 +6,8 @@ use crate::parser;
 pub struct Schema<'a> {
     pub packets_and_structs: HashMap<&'a str, PacketOrStruct<'a>>,
     pub enums: HashMap<&'a str, Enum<'a>>,
+    pub checksums: HashMap<&'a str, Checksum>,
+    pub endianness: ast::EndiannessValue,
 }
 
 pub struct PacketOrStruct<'a> {
@@ -27,6 +29,10 @@ pub struct Enum<'a> {
     pub width: usize,
 }
 
+pub struct Checksum {
+    pub width: usize,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum ComputedValueId<'a> {
     // needed for array fields + varlength structs - note that this is in OCTETS, not BITS
@@ -51,6 +57,7 @@ pub enum ComputedOffsetId<'a> {
     // these quantities will be computed and stored in computed_values
     FieldOffset(&'a str),    // needed for all fields, measured in BITS
     FieldEndOffset(&'a str), // needed only for Payload + Body fields, as well as variable-size structs (not arrays), measured in BITS
+    ChecksumStart(&'a str),  // start of the bytes covered by the checksum field, measured in BITS
     Custom(u16),
     TrailerStart,
 }
@@ -84,28 +91,92 @@ pub enum ComputedOffset<'a> {
 }
 
 pub fn generate(file: &parser::ast::File) -> Result<Schema, String> {
-    let mut schema = Schema { packets_and_structs: HashMap::new(), enums: HashMap::new() };
-    match file.endianness.value {
-        ast::EndiannessValue::LittleEndian => {}
-        _ => unimplemented!("Only little_endian endianness supported"),
+    let mut schema = Schema {
+        packets_and_structs: HashMap::new(),
+        enums: HashMap::new(),
+        checksums: HashMap::new(),
+        endianness: file.endianness.value,
     };
 
     for decl in &file.declarations {
-        process_decl(&mut schema, decl);
+        process_decl(&mut schema, decl)?;
     }
 
     Ok(schema)
 }
 
-fn process_decl<'a>(schema: &mut Schema<'a>, decl: &'a parser::ast::Decl) {
+impl<'a> Schema<'a> {
+    /// Return the fields in the order in which their bits are laid out
+    /// on the wire.
+    ///
+    /// Consecutive bit fields are packed into octet-aligned integers,
+    /// with the first field in the least significant bits. In big-endian
+    /// files these integers are written most significant bit first, so
+    /// the fields packed into the same integer appear in reverse order.
+    pub fn wire_order<'f>(&self, fields: &'f [parser::ast::Field]) -> Vec<&'f parser::ast::Field> {
+        let big_endian = self.endianness == ast::EndiannessValue::BigEndian;
+        let flush = |chunk: &mut Vec<_>, wire_fields: &mut Vec<_>| {
+            if big_endian {
+                chunk.reverse();
+            }
+            wire_fields.append(chunk);
+        };
+
+        let mut wire_fields = vec![];
+        let mut chunk = vec![];
+        let mut chunk_width = 0;
+        for field in fields {
+            match self.bit_field_width(field) {
+                Some(width) => {
+                    chunk.push(field);
+                    chunk_width += width;
+                    if chunk_width % 8 == 0 {
+                        flush(&mut chunk, &mut wire_fields);
+                    }
+                }
+                None => {
+                    flush(&mut chunk, &mut wire_fields);
+                    wire_fields.push(field);
+                }
+            }
+        }
+        flush(&mut chunk, &mut wire_fields);
+        wire_fields
+    }
+
+    /// Return the width of the field if it is packed with the
+    /// neighbouring bit fields.
+    fn bit_field_width(&self, field: &parser::ast::Field) -> Option<usize> {
+        match &field.desc {
+            ast::FieldDesc::Size { width, .. }
+            | ast::FieldDesc::Count { width, .. }
+            | ast::FieldDesc::ElementSize { width, .. }
+            | ast::FieldDesc::FixedScalar { width, .. }
+            | ast::FieldDesc::Reserved { width }
+            | ast::FieldDesc::Scalar { width, .. } => Some(*width),
+            ast::FieldDesc::FixedEnum { enum_id: type_id, .. }
+            | ast::FieldDesc::Typedef { type_id, .. } => {
+                self.enums.get(type_id.as_str()).map(|decl| decl.width)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn process_decl<'a>(schema: &mut Schema<'a>, decl: &'a parser::ast::Decl) -> Result<(), String> {
     match &decl.desc {
         ast::DeclDesc::Enum { id, tags, width, .. } => process_enum(schema, id, tags, *width),
         ast::DeclDesc::Packet { id, fields, .. } | ast::DeclDesc::Struct { id, fields, .. } => {
-            process_packet_or_struct(schema, id, fields)
+            process_packet_or_struct(schema, id, fields)?
         }
-        ast::DeclDesc::Group { .. } => todo!(),
+        ast::DeclDesc::Checksum { id, width, .. } => process_checksum(schema, id, *width),
+        // Groups are inlined by the analyzer.
+        ast::DeclDesc::Group { .. } => unreachable!(),
+        // Tests do not declare any type.
+        ast::DeclDesc::Test { .. } => (),
         _ => unimplemented!("type {decl:?} not supported"),
     }
+    Ok(())
 }
 
 fn process_enum<'a>(schema: &mut Schema<'a>, id: &'a str, tags: &'a [ast::Tag], width: usize) {
@@ -120,12 +191,38 @@ fn process_enum<'a>(schema: &mut Schema<'a>, id: &'a str, tags: &'a [ast::Tag],
     );
 }
 
+fn process_checksum<'a>(schema: &mut Schema<'a>, id: &'a str, width: usize) {
+    schema.checksums.insert(id, Checksum { width });
+    schema.packets_and_structs.insert(
+        id,
+        PacketOrStruct {
+            computed_offsets: HashMap::new(),
+            computed_values: HashMap::new(),
+            length: PacketOrStructLength::Static(width),
+        },
+    );
+}
+
 fn process_packet_or_struct<'a>(
     schema: &mut Schema<'a>,
     id: &'a str,
     fields: &'a [parser::ast::Field],
-) {
+) -> Result<(), String> {
+    // The offsets of the fields following an optional field would
+    // depend on its condition, which the computed offsets cannot express.
+    if let Some(field) = fields.iter().find(|field| {
+        matches!(
+            field.desc,
+            ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. }
+        )
+    }) {
+        return Err(format!(
+            "{id}: optional field {} is not supported by the rust_no_alloc backend",
+            field.id().unwrap()
+        ));
+    }
     schema.packets_and_structs.insert(id, compute_getters(schema, fields));
+    Ok(())
 }
 
 fn compute_getters<'a>(
@@ -146,7 +243,7 @@ fn compute_getters<'a>(
 
     let mut needs_length = false;
 
-    for field in fields {
+    for field in schema.wire_order(fields) {
         // populate this only if we are an array with a knowable size
         let mut next_prev_pos_id = None;
 
@@ -188,10 +285,17 @@ fn compute_getters<'a>(
                 );
                 ComputedOffset::ConstantPlusOffsetInBits(curr_pos_id, *width as i64)
             }
-            ast::FieldDesc::Group { .. } => {
-                unimplemented!("this should be removed by the linter...")
+            ast::FieldDesc::Group { .. } => unreachable!("groups are inlined by the analyzer"),
+            ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. } => {
+                unreachable!("optional fields are rejected by process_packet_or_struct")
+            }
+            ast::FieldDesc::Checksum { field_id } => {
+                computed_offsets.insert(
+                    ComputedOffsetId::ChecksumStart(field_id),
+                    ComputedOffset::Alias(curr_pos_id),
+                );
+                ComputedOffset::ConstantPlusOffsetInBits(curr_pos_id, 0)
             }
-            ast::FieldDesc::Checksum { .. } => unimplemented!("checksum not supported"),
             ast::FieldDesc::Body => {
                 computed_offsets.insert(
                     ComputedOffsetId::FieldOffset("_body_"),
@@ -404,7 +508,7 @@ fn compute_getters<'a>(
             }
             ast::FieldDesc::Padding { size } => {
                 if let Some(prev_pos_id) = prev_pos_id {
-                    ComputedOffset::ConstantPlusOffsetInBits(prev_pos_id, *size as i64)
+                    ComputedOffset::ConstantPlusOffsetInBits(prev_pos_id, 8 * *size as i64)
                 } else {
                     panic!("padding must follow array field with known total size")
                 }
@@ -0,0 +1,469 @@
+//! PDL backend: print a parsed file as canonical PDL source.
+//!
+//! The output keeps the comments and the spelling of the integer
+//! literals of the input. Whitespace is normalized: declaration
+//! bodies are indented with two spaces, list elements are printed one
+//! per line with a trailing comma, and runs of blank lines are
+//! collapsed to a single blank line.
+
+use crate::ast;
+use crate::parser::ast as parser_ast;
+use std::iter::Peekable;
+
+const INDENT: &str = "  ";
+
+/// Pretty-printer state.
+struct Printer<'a> {
+    /// Source of the printed file, for recovering integer literals.
+    source: &'a str,
+    /// Comments which have not yet been printed, in source order.
+    comments: Peekable<std::slice::Iter<'a, ast::Comment>>,
+    /// Source line of the last printed element.
+    last_line: Option<usize>,
+    indent: usize,
+    out: String,
+}
+
+/// Find the integer literals in `text`, skipping over comments and
+/// strings.
+fn integer_literals(text: &str) -> Vec<&str> {
+    let mut literals = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("//") {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        } else if rest.starts_with("/*") {
+            rest = rest[2..].find("*/").map_or("", |end| &rest[end + 4..]);
+        } else if c == '"' {
+            rest = rest[1..].find('"').map_or("", |end| &rest[end + 2..]);
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_');
+            let (word, tail) = rest.split_at(end.unwrap_or(rest.len()));
+            if c.is_ascii_digit() {
+                literals.push(word);
+            }
+            rest = tail;
+        } else {
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    literals
+}
+
+/// Parse an integer literal, as written in PDL source.
+fn parse_integer(literal: &str) -> Option<usize> {
+    match literal.strip_prefix("0x").or_else(|| literal.strip_prefix("0X")) {
+        Some(digits) => usize::from_str_radix(digits, 16).ok(),
+        None => literal.parse().ok(),
+    }
+}
+
+impl<'a> Printer<'a> {
+    /// Format the `index`-th integer literal of the element at `loc`.
+    ///
+    /// The literal is printed as written in the source when it
+    /// matches `value`, and in decimal otherwise.
+    fn integer(&self, loc: &ast::SourceRange, index: usize, value: usize) -> String {
+        let text = self.source.get(loc.start.offset..loc.end.offset).unwrap_or_default();
+        match integer_literals(text).get(index) {
+            Some(literal) if parse_integer(literal) == Some(value) => literal.to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Start a new line for an element starting at `line` in the
+    /// source, keeping a blank line if the source had one.
+    fn start_line(&mut self, line: usize) {
+        if matches!(self.last_line, Some(last_line) if line > last_line + 1) {
+            self.blank_line();
+        }
+        self.out.push_str(&INDENT.repeat(self.indent));
+    }
+
+    /// Print the comments starting before `offset`.
+    ///
+    /// Comments on the same line as the last printed element are
+    /// appended to that line.
+    fn comments(&mut self, offset: usize) {
+        while let Some(comment) = self.comments.next_if(|c| c.loc.start.offset < offset) {
+            let text = comment.text.trim_end();
+            if self.last_line == Some(comment.loc.start.line) && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push(' ');
+            } else {
+                self.start_line(comment.loc.start.line);
+            }
+            self.out.push_str(text);
+            self.out.push('\n');
+            self.last_line = Some(comment.loc.end.line);
+        }
+    }
+
+    /// Print the comments on the same line as the last printed
+    /// element.
+    fn trailing_comments(&mut self) {
+        let last_line = self.last_line;
+        let trailing_comment = self.comments.peek().filter(|c| Some(c.loc.start.line) == last_line);
+        if let Some(offset) = trailing_comment.map(|c| c.loc.end.offset) {
+            self.comments(offset);
+        }
+    }
+
+    /// Print a line of code for the element at `loc`.
+    fn line(&mut self, loc: &ast::SourceRange, code: &str) {
+        self.comments(loc.start.offset);
+        self.start_line(loc.start.line);
+        self.out.push_str(code);
+        self.out.push('\n');
+        self.last_line = Some(loc.end.line);
+    }
+
+    /// Print a block of elements, enclosed in braces.
+    ///
+    /// The elements are printed one per line with a trailing comma.
+    /// Empty blocks are printed on the header line.
+    fn block(
+        &mut self,
+        loc: &ast::SourceRange,
+        header: &str,
+        elements: Vec<(&ast::SourceRange, String)>,
+    ) {
+        let empty = elements.is_empty()
+            && !matches!(self.comments.peek(), Some(c) if c.loc.start.offset < loc.end.offset);
+        if empty {
+            self.line(loc, &format!("{header} {{}}"));
+            return;
+        }
+
+        self.comments(loc.start.offset);
+        self.start_line(loc.start.line);
+        self.out.push_str(&format!("{header} {{\n"));
+        self.last_line = Some(loc.start.line);
+        self.indent += 1;
+        for (loc, code) in elements {
+            self.line(loc, &format!("{code},"));
+        }
+        self.comments(loc.end.offset);
+        self.indent -= 1;
+        self.out.push_str("}\n");
+        self.last_line = Some(loc.end.line);
+    }
+
+    fn constraint(&self, constraint: &ast::Constraint) -> String {
+        let value = match constraint {
+            ast::Constraint { tag_id: Some(tag_id), .. } => tag_id.clone(),
+            ast::Constraint { value: Some(value), .. } => self.integer(&constraint.loc, 0, *value),
+            _ => unreachable!("Invalid constraint: {constraint:?}"),
+        };
+        format!("{} = {value}", constraint.id)
+    }
+
+    fn constraints(&self, constraints: &[ast::Constraint]) -> String {
+        constraints.iter().map(|c| self.constraint(c)).collect::<Vec<_>>().join(", ")
+    }
+
+    fn field(&self, field: &parser_ast::Field) -> String {
+        let loc = &field.loc;
+        match &field.desc {
+            ast::FieldDesc::Checksum { field_id } => format!("_checksum_start_({field_id})"),
+            ast::FieldDesc::Padding { size } => {
+                format!("_padding_[{}]", self.integer(loc, 0, *size))
+            }
+            ast::FieldDesc::Size { field_id, width } => {
+                format!("_size_({field_id}): {}", self.integer(loc, 0, *width))
+            }
+            ast::FieldDesc::Count { field_id, width } => {
+                format!("_count_({field_id}): {}", self.integer(loc, 0, *width))
+            }
+            ast::FieldDesc::ElementSize { field_id, width } => {
+                format!("_elementsize_({field_id}): {}", self.integer(loc, 0, *width))
+            }
+            ast::FieldDesc::Body => "_body_".to_owned(),
+            ast::FieldDesc::Payload { size_modifier: Some(size_modifier) } => {
+                format!("_payload_: [{size_modifier}]")
+            }
+            ast::FieldDesc::Payload { size_modifier: None } => "_payload_".to_owned(),
+            ast::FieldDesc::FixedScalar { width, value } => format!(
+                "_fixed_ = {} : {}",
+                self.integer(loc, 0, *value),
+                self.integer(loc, 1, *width)
+            ),
+            ast::FieldDesc::FixedEnum { enum_id, tag_id } => {
+                format!("_fixed_ = {tag_id} : {enum_id}")
+            }
+            ast::FieldDesc::Reserved { width } => {
+                format!("_reserved_: {}", self.integer(loc, 0, *width))
+            }
+            ast::FieldDesc::Array { id, width, type_id, size_modifier, size } => {
+                let element = match (width, type_id) {
+                    (Some(width), _) => self.integer(loc, 0, *width),
+                    (None, Some(type_id)) => type_id.clone(),
+                    _ => unreachable!("Invalid array field: {field:?}"),
+                };
+                let size = match (size, size_modifier) {
+                    (Some(size), _) => self.integer(loc, width.iter().count(), *size),
+                    (None, Some(size_modifier)) => size_modifier.clone(),
+                    (None, None) => String::new(),
+                };
+                format!("{id}: {element}[{size}]")
+            }
+            ast::FieldDesc::Scalar { id, width } => {
+                format!("{id}: {}", self.integer(loc, 0, *width))
+            }
+            ast::FieldDesc::Typedef { id, type_id } => format!("{id}: {type_id}"),
+            ast::FieldDesc::OptionalScalar { id, width, condition } => {
+                format!("{id}: {} if {}", self.integer(loc, 0, *width), self.constraint(condition))
+            }
+            ast::FieldDesc::OptionalTypedef { id, type_id, condition } => {
+                format!("{id}: {type_id} if {}", self.constraint(condition))
+            }
+            ast::FieldDesc::Group { group_id, constraints } if constraints.is_empty() => {
+                group_id.clone()
+            }
+            ast::FieldDesc::Group { group_id, constraints } => {
+                format!("{group_id} {{ {} }}", self.constraints(constraints))
+            }
+        }
+    }
+
+    fn fields(&self, fields: &'a [parser_ast::Field]) -> Vec<(&'a ast::SourceRange, String)> {
+        fields.iter().map(|field| (&field.loc, self.field(field))).collect()
+    }
+
+    fn decl(&mut self, decl: &'a parser_ast::Decl) {
+        let loc = &decl.loc;
+        match &decl.desc {
+            ast::DeclDesc::Checksum { id, function, width } => {
+                let width = self.integer(loc, 0, *width);
+                self.line(loc, &format!("checksum {id} : {width} \"{function}\""))
+            }
+            ast::DeclDesc::CustomField { id, width: Some(width), function } => {
+                let width = self.integer(loc, 0, *width);
+                self.line(loc, &format!("custom_field {id} : {width} \"{function}\""))
+            }
+            ast::DeclDesc::CustomField { id, width: None, function } => {
+                self.line(loc, &format!("custom_field {id} \"{function}\""))
+            }
+            ast::DeclDesc::Enum { id, tags, width } => {
+                let header = format!("enum {id} : {}", self.integer(loc, 0, *width));
+                let tags = tags
+                    .iter()
+                    .map(|tag| {
+                        (&tag.loc, format!("{} = {}", tag.id, self.integer(&tag.loc, 0, tag.value)))
+                    })
+                    .collect();
+                self.block(loc, &header, tags)
+            }
+            ast::DeclDesc::Packet { id, constraints, fields, parent_id }
+            | ast::DeclDesc::Struct { id, constraints, fields, parent_id } => {
+                let keyword = if matches!(decl.desc, ast::DeclDesc::Packet { .. }) {
+                    "packet"
+                } else {
+                    "struct"
+                };
+                let mut header = format!("{keyword} {id}");
+                if let Some(parent_id) = parent_id {
+                    header.push_str(&format!(" : {parent_id}"));
+                }
+                if !constraints.is_empty() {
+                    header.push_str(&format!(" ({})", self.constraints(constraints)));
+                }
+                let fields = self.fields(fields);
+                self.block(loc, &header, fields)
+            }
+            ast::DeclDesc::Group { id, fields } => {
+                let fields = self.fields(fields);
+                self.block(loc, &format!("group {id}"), fields)
+            }
+            ast::DeclDesc::Test { type_id, test_cases } => {
+                let test_cases = test_cases
+                    .iter()
+                    .map(|test_case| (&test_case.loc, format!("\"{}\"", test_case.input)))
+                    .collect();
+                self.block(loc, &format!("test {type_id}"), test_cases)
+            }
+        }
+    }
+}
+
+/// Check whether `decl` is printed on a single line.
+fn is_single_line(decl: &parser_ast::Decl) -> bool {
+    matches!(decl.desc, ast::DeclDesc::Checksum { .. } | ast::DeclDesc::CustomField { .. })
+}
+
+/// Print `file` as canonical PDL source.
+///
+/// Blank lines separate the endianness declaration, the imports, and
+/// the declarations. Consecutive single line declarations, such as
+/// custom fields, can be grouped without blank lines.
+pub fn generate(sources: &ast::SourceDatabase, file: &parser_ast::File) -> String {
+    let source = sources.get(file.file).expect("could not read source");
+    let mut printer = Printer {
+        source: source.source(),
+        comments: file.comments.iter().peekable(),
+        last_line: None,
+        indent: 0,
+        out: String::new(),
+    };
+
+    let endianness = match file.endianness.value {
+        ast::EndiannessValue::LittleEndian => "little_endian_packets",
+        ast::EndiannessValue::BigEndian => "big_endian_packets",
+    };
+    printer.line(&file.endianness.loc, endianness);
+
+    for (idx, import) in file.imports.iter().enumerate() {
+        if idx == 0 {
+            printer.trailing_comments();
+            printer.blank_line();
+        }
+        printer.line(&import.loc, &format!("import \"{}\"", import.path));
+    }
+
+    let mut previous: Option<&parser_ast::Decl> = None;
+    for decl in &file.declarations {
+        if !matches!(previous, Some(previous) if is_single_line(previous) && is_single_line(decl)) {
+            printer.trailing_comments();
+            printer.blank_line();
+        }
+        printer.decl(decl);
+        previous = Some(decl);
+    }
+    printer.comments(usize::MAX);
+
+    printer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_inline;
+    use crate::test_utils::assert_eq_with_diff;
+
+    fn format(text: &str) -> String {
+        let mut db = ast::SourceDatabase::new();
+        let file = parse_inline(&mut db, String::from("stdin"), String::from(text)).unwrap();
+        generate(&db, &file)
+    }
+
+    #[test]
+    fn test_integer_literals() {
+        assert_eq!(integer_literals("a8: 0x1F[+2] // 42"), vec!["0x1F", "2"]);
+        assert_eq!(integer_literals("checksum C : 16 \"crc32\""), vec!["16"]);
+        assert_eq!(integer_literals("enum E : 8 { /* 1 */ A = 3 }"), vec!["8", "3"]);
+    }
+
+    #[test]
+    fn test_format_declarations() {
+        let input = r#"little_endian_packets
+import "other.pdl"
+custom_field Address : 48 "hci/"
+custom_field ClassOfDevice : 24 "hci/"
+checksum Crc : 0x10 "crc16"
+enum Op:8{A=0x01,B = 2}
+packet Empty{}
+packet Parent { op: Op, _size_(_payload_) : 8, _payload_ : [+2] }
+packet Child:Parent(op=A){_fixed_=0x7:7,_fixed_=B:Op,_reserved_:1,
+  a:8[],b:Address[4],_count_(c):8,c:16[],_padding_[8],_checksum_start_(crc),crc:Crc}
+group G { x: 8 }
+struct S { G { x = 1 }, _body_ }
+test Child { "\x01\x02", "" }
+"#;
+        let expected = r#"little_endian_packets
+
+import "other.pdl"
+
+custom_field Address : 48 "hci/"
+custom_field ClassOfDevice : 24 "hci/"
+checksum Crc : 0x10 "crc16"
+
+enum Op : 8 {
+  A = 0x01,
+  B = 2,
+}
+
+packet Empty {}
+
+packet Parent {
+  op: Op,
+  _size_(_payload_): 8,
+  _payload_: [+2],
+}
+
+packet Child : Parent (op = A) {
+  _fixed_ = 0x7 : 7,
+  _fixed_ = B : Op,
+  _reserved_: 1,
+  a: 8[],
+  b: Address[4],
+  _count_(c): 8,
+  c: 16[],
+  _padding_[8],
+  _checksum_start_(crc),
+  crc: Crc,
+}
+
+group G {
+  x: 8,
+}
+
+struct S {
+  G { x = 1 },
+  _body_,
+}
+
+test Child {
+  "\x01\x02",
+  "",
+}
+"#;
+        assert_eq_with_diff("expected", expected, "actual", &format(input));
+        assert_eq_with_diff("expected", expected, "actual", &format(expected));
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let input = r#"// License header.
+
+little_endian_packets // Trailing comment.
+
+
+// Leading comment.
+enum E : 8 {
+    A = 1, // First tag.
+
+    /* Second tag. */
+    B = 2,
+    // Dangling comment.
+}
+packet P { a: 8 } // After the packet.
+// End of file.
+"#;
+        let expected = r#"// License header.
+
+little_endian_packets // Trailing comment.
+
+// Leading comment.
+enum E : 8 {
+  A = 1, // First tag.
+
+  /* Second tag. */
+  B = 2,
+  // Dangling comment.
+}
+
+packet P {
+  a: 8,
+} // After the packet.
+// End of file.
+"#;
+        assert_eq_with_diff("expected", expected, "actual", &format(input));
+        assert_eq_with_diff("expected", expected, "actual", &format(expected));
+    }
+}
@@ -9,12 +9,14 @@
 #![allow(clippy::format_push_string)]
 
 use crate::{ast, lint};
-use heck::ToUpperCamelCase;
+use heck::{ToSnakeCase, ToUpperCamelCase};
 use quote::{format_ident, quote};
+use std::collections::HashMap;
 use std::path::Path;
 
 use crate::parser::ast as parser_ast;
 
+mod arbitrary;
 mod declarations;
 mod parser;
 mod preamble;
@@ -25,6 +27,15 @@ use declarations::FieldDeclarations;
 use parser::FieldParser;
 use serializer::FieldSerializer;
 
+/// Optional trait implementations for the generated types.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    /// Implement `serde::Serialize` and `serde::Deserialize`.
+    pub serde: bool,
+    /// Implement `arbitrary::Arbitrary`.
+    pub arbitrary: bool,
+}
+
 /// Generate a block of code.
 ///
 /// Like `quote!`, but the code block will be followed by an empty
@@ -43,15 +54,58 @@ pub fn mask_bits(n: usize) -> syn::LitInt {
     syn::parse_str::<syn::LitInt>(&format!("{:#x}{suffix}", (1u64 << n) - 1)).unwrap()
 }
 
+/// Generate the value of `constraint`, or of the condition of an
+/// optional field, on one of the named `fields` of a packet.
+pub fn constraint_value(
+    fields: &HashMap<String, &parser_ast::Field>,
+    constraint: &ast::Constraint,
+) -> proc_macro2::TokenStream {
+    match constraint {
+        ast::Constraint { value: Some(value), .. } => {
+            let value = proc_macro2::Literal::usize_unsuffixed(*value);
+            quote!(#value)
+        }
+        ast::Constraint { id, tag_id: Some(tag_id), .. } => {
+            let type_id = match fields.get(id).map(|f| &f.desc) {
+                Some(ast::FieldDesc::Typedef { type_id, .. }) => format_ident!("{type_id}"),
+                _ => unreachable!("Invalid constraint: {constraint:?}"),
+            };
+            let tag_id = format_ident!("{}", tag_id.to_upper_camel_case());
+            quote!(#type_id::#tag_id)
+        }
+        _ => unreachable!("Invalid constraint: {constraint:?}"),
+    }
+}
+
+/// Check whether `field` holds the value of a checksum.
+///
+/// Checksum values are computed when serializing and verified when
+/// parsing, they are not stored in the packet data.
+pub fn is_checksum_value(scope: &lint::Scope<'_>, field: &parser_ast::Field) -> bool {
+    matches!(field.desc, ast::FieldDesc::Typedef { .. })
+        && matches!(
+            field.declaration(scope),
+            Some(parser_ast::Decl { desc: ast::DeclDesc::Checksum { .. }, .. })
+        )
+}
+
 fn generate_packet_size_getter(
     scope: &lint::Scope<'_>,
+    decl: &parser_ast::Decl,
     fields: &[parser_ast::Field],
 ) -> (usize, proc_macro2::TokenStream) {
     let mut constant_width = 0;
     let mut dynamic_widths = Vec::new();
 
     for field in fields {
-        if let Some(width) = field.width(scope, false) {
+        if let ast::FieldDesc::Padding { .. } = &field.desc {
+            continue;
+        }
+        if let Some(width) = decl
+            .padded_size(field)
+            .map(|padded_size| 8 * padded_size)
+            .or_else(|| field.width(scope, false))
+        {
             constant_width += width;
             continue;
         }
@@ -65,6 +119,21 @@ fn generate_packet_size_getter(
                 let id = format_ident!("{id}");
                 quote!(self.#id.get_size())
             }
+            ast::FieldDesc::OptionalScalar { id, width, .. } => {
+                let id = format_ident!("{id}");
+                let width = syn::Index::from(width / 8);
+                quote!(self.#id.map_or(0, |_| #width))
+            }
+            ast::FieldDesc::OptionalTypedef { id, .. } => {
+                let id = format_ident!("{id}");
+                match &decl {
+                    Some(parser_ast::Decl { desc: ast::DeclDesc::Enum { width, .. }, .. }) => {
+                        let width = syn::Index::from(width / 8);
+                        quote!(self.#id.map_or(0, |_| #width))
+                    }
+                    _ => quote!(self.#id.as_ref().map_or(0, |#id| #id.get_size())),
+                }
+            }
             ast::FieldDesc::Array { id, width, .. } => {
                 let id = format_ident!("{id}");
                 match &decl {
@@ -133,6 +202,7 @@ fn generate_packet_decl(
     id: &str,
     _constraints: &[ast::Constraint],
     fields: &[parser_ast::Field],
+    options: &Options,
 ) -> proc_macro2::TokenStream {
     let packet_scope = &scope.scopes[&scope.typedef[id]];
 
@@ -164,7 +234,10 @@ fn generate_packet_decl(
     let id_data_child = format_ident!("{id}DataChild");
     let id_builder = format_ident!("{id}Builder");
 
-    let fields_with_ids = fields.iter().filter(|f| f.id().is_some()).collect::<Vec<_>>();
+    let fields_with_ids = fields
+        .iter()
+        .filter(|f| f.id().is_some() && !is_checksum_value(scope, f))
+        .collect::<Vec<_>>();
     let field_names =
         fields_with_ids.iter().map(|f| format_ident!("{}", f.id().unwrap())).collect::<Vec<_>>();
 
@@ -188,7 +261,11 @@ fn generate_packet_decl(
     let parent_data_child = parent_ids.iter().map(|id| format_ident!("{id}DataChild"));
 
     let all_fields = {
-        let mut fields = packet_scope.all_fields.values().collect::<Vec<_>>();
+        let mut fields = packet_scope
+            .all_fields
+            .values()
+            .filter(|f| !is_checksum_value(scope, f))
+            .collect::<Vec<_>>();
         fields.sort_by_key(|f| f.id());
         fields
     };
@@ -226,7 +303,12 @@ fn generate_packet_decl(
         let parent_packet_scope = &scope.scopes[&scope.typedef[parent_id]];
 
         let named_fields = {
-            let mut names = parent_packet_scope.named.keys().collect::<Vec<_>>();
+            let mut names = parent_packet_scope
+                .named
+                .iter()
+                .filter(|(_, f)| !is_checksum_value(scope, f))
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
             names.sort();
             names
         };
@@ -307,10 +389,14 @@ fn generate_packet_decl(
             }
         }
     });
+    let serde_derive = options.serde.then(|| {
+        quote! {
+            #[derive(serde::Serialize, serde::Deserialize)]
+        }
+    });
     let child_declaration = has_children_or_payload.then(|| {
         quote! {
             #[derive(Debug)]
-            #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
             pub enum #id_data_child {
                 #(#child(Arc<#child_data>),)*
                 Payload(Bytes),
@@ -328,7 +414,7 @@ fn generate_packet_decl(
             }
 
             #[derive(Debug)]
-            #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+            #serde_derive
             pub enum #id_child {
                 #(#child(#child),)*
                 Payload(Bytes),
@@ -364,7 +450,8 @@ fn generate_packet_decl(
         }
     });
 
-    let (constant_width, packet_size) = generate_packet_size_getter(scope, fields);
+    let (constant_width, packet_size) =
+        generate_packet_size_getter(scope, scope.typedef[id], fields);
     let conforms = if constant_width == 0 {
         quote! { true }
     } else {
@@ -372,26 +459,154 @@ fn generate_packet_decl(
         quote! { #span.len() >= #constant_width }
     };
 
+    // Packets are converted to and from builders, which only hold the
+    // unconstrained fields. This ensures that deserialized packets
+    // satisfy their constraints.
+    let id_builder_name = id_builder.to_string();
+    let serde_packet_attributes = options.serde.then(|| {
+        quote! {
+            #[derive(serde::Serialize, serde::Deserialize)]
+            #[serde(from = #id_builder_name, into = #id_builder_name)]
+        }
+    });
+    let serde_impls = options.serde.then(|| {
+        let field_getters = unconstrained_fields.iter().map(|f| {
+            let getter = format_ident!("get_{}", f.id().unwrap());
+            if types::rust_borrow(f, scope).is_empty() {
+                quote!(packet.#getter())
+            } else {
+                quote!(packet.#getter().clone())
+            }
+        });
+        let payload = has_children_or_payload.then(|| {
+            quote! {
+                payload: match &packet.#id_lower.child {
+                    #(#id_data_child::#child(child) => {
+                        let mut buffer = BytesMut::with_capacity(child.get_total_size());
+                        child.write_to(&mut buffer);
+                        Some(buffer.freeze())
+                    })*
+                    #id_data_child::Payload(bytes) => Some(bytes.clone()),
+                    #id_data_child::None => None,
+                }
+            }
+        });
+        quote! {
+            impl From<#id_packet> for #id_builder {
+                fn from(packet: #id_packet) -> #id_builder {
+                    #id_builder {
+                        #(#unconstrained_field_names: #field_getters,)*
+                        #payload
+                    }
+                }
+            }
+        }
+    });
+    let arbitrary_impls = options.arbitrary.then(|| {
+        // Optional fields are generated last, after the value of
+        // their condition field.
+        let mut arbitrary_fields = unconstrained_fields.clone();
+        arbitrary_fields.sort_by_key(|f| {
+            matches!(
+                f.desc,
+                ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. }
+            )
+        });
+        let field_values = arbitrary_fields.iter().map(|f| {
+            let decl = parents
+                .iter()
+                .find(|parent| scope.scopes[*parent].fields.contains(f))
+                .unwrap_or_else(|| unreachable!("Could not find {f:?} in parent chain"));
+            let id = format_ident!("{}", f.id().unwrap());
+            let field_type = types::rust_type(f);
+            let value =
+                arbitrary::generate_field_value(scope, decl, f, &packet_scope.all_constraints);
+            quote!(let #id: #field_type = #value;)
+        });
+        let unstructured = if unconstrained_fields.is_empty() && !has_children_or_payload {
+            format_ident!("_u")
+        } else {
+            format_ident!("u")
+        };
+        let builder = if has_children_or_payload {
+            let child_constraint_check =
+                arbitrary::generate_child_constraint_check(scope, scope.typedef[id]);
+            let payload_value = arbitrary::generate_payload_value(scope, &parents);
+            quote! {
+                #(#field_values)*
+                let mut builder = #id_builder {
+                    #(#unconstrained_field_names,)*
+                    payload: None,
+                };
+                #child_constraint_check
+                #payload_value
+                Ok(builder)
+            }
+        } else if let Some(payload_size_check) =
+            arbitrary::generate_payload_size_check(scope, &parents)
+        {
+            quote! {
+                #(#field_values)*
+                let builder = #id_builder {
+                    #(#unconstrained_field_names,)*
+                };
+                #payload_size_check
+                Ok(builder)
+            }
+        } else {
+            quote! {
+                #(#field_values)*
+                Ok(#id_builder {
+                    #(#unconstrained_field_names,)*
+                })
+            }
+        };
+        // Each child is picked as often as the packet's own builder,
+        // which never matches the child constraints.
+        let arbitrary_child = (!children.is_empty()).then(|| {
+            let child_count = proc_macro2::Literal::usize_suffixed(children.len());
+            let child_index = (0..children.len()).map(proc_macro2::Literal::usize_unsuffixed);
+            quote! {
+                match u.int_in_range(0..=#child_count)? {
+                    #(#child_index => return Ok(u.arbitrary::<#child>()?.into()),)*
+                    _ => (),
+                }
+            }
+        });
+        quote! {
+            impl<'a> arbitrary::Arbitrary<'a> for #id_builder {
+                fn arbitrary(#unstructured: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+                    #builder
+                }
+            }
+
+            impl<'a> arbitrary::Arbitrary<'a> for #id_packet {
+                fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+                    #arbitrary_child
+                    Ok(u.arbitrary::<#id_builder>()?.build())
+                }
+            }
+        }
+    });
+
     quote! {
         #child_declaration
 
         #[derive(Debug)]
-        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub struct #id_data {
             #field_declarations
         }
 
         #[derive(Debug, Clone)]
-        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #serde_packet_attributes
         pub struct #id_packet {
             #(
-                #[cfg_attr(feature = "serde", serde(flatten))]
                 #parent_lower_ids: Arc<#parent_data>,
             )*
         }
 
-        #[derive(Debug)]
-        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[derive(Debug, Clone)]
+        #serde_derive
         pub struct #id_builder {
             #(pub #unconstrained_field_names: #unconstrained_field_types,)*
             #builder_payload_field
@@ -459,7 +674,7 @@ fn generate_packet_decl(
                 Ok(packet)
             }
 
-            fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+            pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
                 let data = #top_level_data::parse(&mut bytes)?;
                 Ok(Self::new(Arc::new(data)).unwrap())
             }
@@ -480,7 +695,7 @@ fn generate_packet_decl(
 
             #get_payload
 
-            fn write_to(&self, buffer: &mut BytesMut) {
+            pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
                 self.#id_lower.write_to(buffer)
             }
 
@@ -503,10 +718,13 @@ fn generate_packet_decl(
                 }
             }
         )*
+
+        #serde_impls
+        #arbitrary_impls
     }
 }
 
-fn generate_enum_decl(id: &str, tags: &[ast::Tag]) -> proc_macro2::TokenStream {
+fn generate_enum_decl(id: &str, tags: &[ast::Tag], options: &Options) -> proc_macro2::TokenStream {
     let name = format_ident!("{id}");
     let variants =
         tags.iter().map(|t| format_ident!("{}", t.id.to_upper_camel_case())).collect::<Vec<_>>();
@@ -516,6 +734,58 @@ fn generate_enum_decl(id: &str, tags: &[ast::Tag]) -> proc_macro2::TokenStream {
         .collect::<Vec<_>>();
     let visitor_name = format_ident!("{id}Visitor");
 
+    let serde_impls = options.serde.then(|| {
+        quote! {
+            impl serde::Serialize for #name {
+                fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    serializer.serialize_u64(*self as u64)
+                }
+            }
+
+            struct #visitor_name;
+
+            impl<'de> serde::de::Visitor<'de> for #visitor_name {
+                type Value = #name;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    formatter.write_str("a valid discriminant")
+                }
+
+                fn visit_u64<E>(self, value: u64) -> std::result::Result<Self::Value, E>
+                where
+                    E: serde::de::Error,
+                {
+                    match value {
+                        #(#values => Ok(#name::#variants),)*
+                        _ => Err(E::custom(format!("invalid discriminant: {value}"))),
+                    }
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for #name {
+                fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    deserializer.deserialize_u64(#visitor_name)
+                }
+            }
+        }
+    });
+    // Only the declared tags are valid enum values.
+    let arbitrary_impls = options.arbitrary.then(|| {
+        quote! {
+            impl<'a> arbitrary::Arbitrary<'a> for #name {
+                fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+                    Ok(*u.choose(&[#(#name::#variants),*])?)
+                }
+            }
+        }
+    });
+
     quote! {
         #[derive(FromPrimitive, ToPrimitive, Debug, Hash, Eq, PartialEq, Clone, Copy)]
         #[repr(u64)]
@@ -523,61 +793,114 @@ fn generate_enum_decl(id: &str, tags: &[ast::Tag]) -> proc_macro2::TokenStream {
             #(#variants = #values,)*
         }
 
-        #[cfg(feature = "serde")]
-        impl serde::Serialize for #name {
-            fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-            where
-                S: serde::Serializer,
-            {
-                serializer.serialize_u64(*self as u64)
-            }
-        }
+        #serde_impls
+        #arbitrary_impls
+    }
+}
 
-        #[cfg(feature = "serde")]
-        struct #visitor_name;
+/// Generate code for `ast::Decl::Checksum` values.
+///
+/// The checksum function is the path to a type implementing
+/// `ChecksumAlgorithm`. The declaration aliases that type, unless
+/// both have the same name. Other generators read the function
+/// differently, e.g. as a C++ include path: when it is not a Rust
+/// path, the module including the code must provide a type named
+/// after the declaration.
+pub fn generate_checksum_decl(id: &str, function: &str) -> proc_macro2::TokenStream {
+    if id == function {
+        return quote!();
+    }
+
+    let id = format_ident!("{id}");
+    let Ok(function) = syn::parse_str::<syn::Path>(function) else {
+        return quote!();
+    };
+    quote! {
+        pub type #id = #function;
+    }
+}
 
-        #[cfg(feature = "serde")]
-        impl<'de> serde::de::Visitor<'de> for #visitor_name {
-            type Value = #name;
+/// Format the location of a test case as `file:line:column`, with
+/// line and column counted from one.
+pub fn format_test_location(sources: &ast::SourceDatabase, loc: &ast::SourceRange) -> String {
+    let source = sources.get(loc.file).expect("could not read source");
+    format!("{}:{}:{}", source.name(), loc.start.line + 1, loc.start.column + 1)
+}
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("a valid discriminant")
+/// Generate code for `ast::Decl::Test` values.
+///
+/// Each test case becomes a unit test parsing the input as the named
+/// packet, serializing it again, and comparing the bytes with the
+/// input. Failures report the location of the test case. The cases
+/// are numbered from `first_idx` so that several test declarations
+/// for the same packet produce distinct test names.
+fn generate_test_decl(
+    scope: &lint::Scope<'_>,
+    sources: &ast::SourceDatabase,
+    type_id: &str,
+    test_cases: &[ast::TestCase],
+    first_idx: usize,
+) -> proc_macro2::TokenStream {
+    let top_level_id = top_level_packet(scope, type_id).id().unwrap();
+    let top_level_packet = format_ident!("{top_level_id}");
+    let packet = format_ident!("{type_id}");
+
+    let tests = test_cases.iter().enumerate().map(|(idx, test_case)| {
+        let name = format_ident!("test_{}_{}", type_id.to_snake_case(), first_idx + idx);
+        let location = format_test_location(sources, &test_case.loc);
+        // The test inputs are validated by the analyzer.
+        let input = proc_macro2::Literal::byte_string(&test_case.bytes().unwrap());
+        let specialize = (top_level_id != type_id).then(|| {
+            quote! {
+                let packet = match #packet::try_from(packet) {
+                    Ok(packet) => packet,
+                    Err(err) => panic!("{}: could not parse {}: {}", #location, #type_id, err),
+                };
             }
-
-            fn visit_u64<E>(self, value: u64) -> std::result::Result<Self::Value, E>
-            where
-                E: serde::de::Error,
-            {
-                match value {
-                    #(#values => Ok(#name::#variants),)*
-                    _ => Err(E::custom(format!("invalid discriminant: {value}"))),
-                }
+        });
+        quote! {
+            #[test]
+            fn #name() {
+                let input: &[u8] = #input;
+                let packet = match #top_level_packet::parse(input) {
+                    Ok(packet) => packet,
+                    Err(err) => panic!("{}: could not parse {}: {}", #location, #type_id, err),
+                };
+                #specialize
+                assert_eq!(packet.to_vec(), input, "{}: {} does not round-trip", #location, #type_id);
             }
         }
+    });
 
-        #[cfg(feature = "serde")]
-        impl<'de> serde::Deserialize<'de> for #name {
-            fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
-            where
-                D: serde::Deserializer<'de>,
-            {
-                deserializer.deserialize_u64(#visitor_name)
-            }
-        }
+    quote! {
+        #(#tests)*
     }
 }
 
 fn generate_decl(
     scope: &lint::Scope<'_>,
+    sources: &ast::SourceDatabase,
     file: &parser_ast::File,
     decl: &parser_ast::Decl,
+    test_counts: &mut HashMap<String, usize>,
+    options: &Options,
 ) -> String {
     match &decl.desc {
         ast::DeclDesc::Packet { id, constraints, fields, .. }
         | ast::DeclDesc::Struct { id, constraints, fields, .. } => {
-            generate_packet_decl(scope, file.endianness.value, id, constraints, fields).to_string()
+            generate_packet_decl(scope, file.endianness.value, id, constraints, fields, options)
+                .to_string()
+        }
+        ast::DeclDesc::Enum { id, tags, .. } => generate_enum_decl(id, tags, options).to_string(),
+        ast::DeclDesc::Checksum { id, function, .. } => {
+            generate_checksum_decl(id, function).to_string()
+        }
+        ast::DeclDesc::Test { type_id, test_cases } => {
+            let first_idx = test_counts.entry(type_id.clone()).or_default();
+            let code = generate_test_decl(scope, sources, type_id, test_cases, *first_idx);
+            *first_idx += test_cases.len();
+            code.to_string()
         }
-        ast::DeclDesc::Enum { id, tags, .. } => generate_enum_decl(id, tags).to_string(),
         _ => todo!("unsupported Decl::{:?}", decl),
     }
 }
@@ -585,16 +908,36 @@ fn generate_decl(
 /// Generate Rust code from an AST.
 ///
 /// The code is not formatted, pipe it through `rustfmt` to get
-/// readable source code.
-pub fn generate(sources: &ast::SourceDatabase, file: &parser_ast::File) -> String {
+/// readable source code. The `options` select the optional trait
+/// implementations added to the generated types.
+pub fn generate(
+    sources: &ast::SourceDatabase,
+    file: &parser_ast::File,
+    options: &Options,
+) -> String {
     let mut code = String::new();
 
     let source = sources.get(file.file).expect("could not read source");
     code.push_str(&preamble::generate(Path::new(source.name())));
 
+    // Imported declarations, the error types, and the checksum
+    // algorithms are generated in the module of the imported files.
+    // The module including this code must bring them into scope.
+    let (declarations, imported_declarations): (Vec<_>, Vec<_>) =
+        file.declarations.iter().partition(|decl| decl.loc.file == file.file);
+    let is_checksum =
+        |decl: &&parser_ast::Decl| matches!(decl.desc, ast::DeclDesc::Checksum { .. });
+    if file.imports.is_empty() {
+        code.push_str(&preamble::generate_types());
+    }
+    if declarations.iter().any(is_checksum) && !imported_declarations.iter().any(is_checksum) {
+        code.push_str(&preamble::generate_checksums());
+    }
+
     let scope = lint::Scope::new(file).unwrap();
-    for decl in &file.declarations {
-        code.push_str(&generate_decl(&scope, file, decl));
+    let mut test_counts = HashMap::new();
+    for decl in declarations {
+        code.push_str(&generate_decl(&scope, sources, file, decl, &mut test_counts, options));
         code.push_str("\n\n");
     }
 
@@ -624,7 +967,7 @@ mod tests {
     /// must supply either `little_endian` or `big_endian` as
     /// `endianness`.
     macro_rules! make_pdl_test {
-        ($name:ident, $code:expr, $endianness:ident) => {
+        ($name:ident, $code:expr, $endianness:ident, $options:expr) => {
             paste! {
                 #[test]
                 fn [< test_ $name _ $endianness >]() {
@@ -633,7 +976,7 @@ mod tests {
                     let code = format!("{endianness}_packets\n{}", $code);
                     let mut db = ast::SourceDatabase::new();
                     let file = parse_inline(&mut db, String::from("test"), code).unwrap();
-                    let actual_code = generate(&db, &file);
+                    let actual_code = generate(&db, &file, &$options);
                     assert_snapshot_eq(
                         &format!("tests/generated/{name}_{endianness}.rs"),
                         &rustfmt(&actual_code),
@@ -647,11 +990,16 @@ mod tests {
     ///
     /// The `code` cannot have an endianness declaration: we will
     /// automatically generate unit tests for both
-    /// "little_endian_packets" and "big_endian_packets".
+    /// "little_endian_packets" and "big_endian_packets". The code is
+    /// generated with the default `Options`, unless `options` are
+    /// given.
     macro_rules! test_pdl {
         ($name:ident, $code:expr $(,)?) => {
-            make_pdl_test!($name, $code, little_endian);
-            make_pdl_test!($name, $code, big_endian);
+            test_pdl!($name, $code, Options::default());
+        };
+        ($name:ident, $code:expr, $options:expr $(,)?) => {
+            make_pdl_test!($name, $code, little_endian, $options);
+            make_pdl_test!($name, $code, big_endian, $options);
         };
     }
 
@@ -808,6 +1156,21 @@ mod tests {
         "
     );
 
+    test_pdl!(
+        packet_decl_array_with_padding,
+        "
+          struct Foo {
+            _count_(a): 40,
+            a: 16[],
+          }
+
+          packet Bar {
+            a: Foo[],
+            _padding_ [128],
+          }
+        "
+    );
+
     test_pdl!(
         packet_decl_reserved_field,
         "
@@ -874,6 +1237,35 @@ mod tests {
         "
     );
 
+    test_pdl!(
+        packet_decl_checksum_field,
+        r#"
+          checksum Fcs : 16 "Crc16"
+
+          packet Foo {
+              _checksum_start_(fcs),
+              a: 8,
+              _size_(_payload_): 8,
+              _payload_,
+              fcs: Fcs,
+          }
+        "#
+    );
+
+    #[test]
+    fn test_checksum_decl_function_not_rust_path() {
+        // The L2CAP packets use the checksum function as a C++ include
+        // path, the including module provides the `Fcs` type.
+        assert!(generate_checksum_decl("Fcs", "l2cap/").is_empty());
+        assert_eq!(
+            generate_checksum_decl("Fcs", "Crc16").to_string(),
+            quote!(
+                pub type Fcs = Crc16;
+            )
+            .to_string()
+        );
+    }
+
     test_pdl!(
         packet_decl_child_packets,
         "
@@ -898,4 +1290,90 @@ mod tests {
           }
         "
     );
+
+    test_pdl!(
+        test_decl,
+        r#"
+          packet Foo {
+              a: 8,
+              _payload_
+          }
+
+          packet Bar : Foo (a = 1) {
+              x: 16,
+          }
+
+          test Foo {
+              "\x00",
+              "\x02\x03\x04",
+          }
+
+          test Bar {
+              "\x01\x02\x03",
+          }
+
+          test Foo {
+              "\x05\x06",
+          }
+        "#
+    );
+
+    test_pdl!(
+        packet_decl_derive_options,
+        "
+          enum Enum8 : 8 {
+            A = 1,
+            B = 2,
+          }
+
+          struct Elem {
+              x: 4,
+              _reserved_: 4,
+          }
+
+          packet Foo {
+              a: Enum8,
+              b: 8,
+              _count_(c): 8,
+              c: Elem[],
+              _size_(_payload_): 8,
+              _payload_
+          }
+
+          packet Bar : Foo (a = A) {
+              x: 8[2],
+          }
+        ",
+        Options { serde: true, arbitrary: true },
+    );
+
+    test_pdl!(
+        packet_decl_optional_fields,
+        "
+          enum Enum16 : 16 {
+            A = 1,
+            B = 2,
+          }
+
+          struct Elem {
+              x: 8,
+              y: 8[],
+          }
+
+          packet Foo {
+              a: 1,
+              _reserved_: 7,
+              b: Enum16,
+              c: 24 if a = 1,
+              d: Enum16 if b = B,
+              e: Elem if a = 0,
+              _payload_,
+          }
+
+          packet Bar : Foo (a = 1) {
+              x: 8,
+          }
+        ",
+        Options { serde: true, arbitrary: true },
+    );
 }
@@ -0,0 +1,281 @@
+//! Generation of `arbitrary::Arbitrary` implementations.
+//!
+//! Arbitrary values must serialize without error: scalars stay within
+//! their declared width, and arrays and payloads stay within the
+//! limits of their size fields, count fields, and padding.
+
+use crate::backends::rust::{constraint_value, mask_bits};
+use crate::parser::ast as parser_ast;
+use crate::{ast, lint};
+use heck::ToUpperCamelCase;
+use quote::{format_ident, quote};
+use std::collections::{BTreeSet, HashMap};
+
+/// Generate an expression producing an arbitrary value for the
+/// unconstrained `field` of `decl`.
+///
+/// Optional fields are present when their condition holds. The
+/// condition field is either set in `constraints`, or bound to a
+/// local variable of the same name.
+pub fn generate_field_value(
+    scope: &lint::Scope<'_>,
+    decl: &parser_ast::Decl,
+    field: &parser_ast::Field,
+    constraints: &HashMap<String, &ast::Constraint>,
+) -> proc_macro2::TokenStream {
+    match &field.desc {
+        ast::FieldDesc::Scalar { width, .. } => {
+            let max_value = mask_bits(*width);
+            quote!(u.int_in_range(0..=#max_value)?)
+        }
+        ast::FieldDesc::Typedef { .. } => quote!(u.arbitrary()?),
+        ast::FieldDesc::Array { id, size: Some(size), .. } => {
+            let element = generate_element_value(field);
+            let size = proc_macro2::Literal::usize_unsuffixed(*size);
+            let message = format!("Invalid length for {}", id);
+            quote! {
+                {
+                    let mut array = Vec::with_capacity(#size);
+                    for _ in 0..#size {
+                        array.push(#element);
+                    }
+                    array.try_into().expect(#message)
+                }
+            }
+        }
+        ast::FieldDesc::Array { id, .. } => {
+            let element = generate_element_value(field);
+            let packet_scope = &scope.scopes[&decl];
+            let mut max_sizes = Vec::new();
+            let mut max_count = None;
+            match packet_scope.sizes.get(id).map(|f| &f.desc) {
+                Some(ast::FieldDesc::Count { width, .. }) => max_count = Some(mask_bits(*width)),
+                Some(ast::FieldDesc::Size { width, .. }) => {
+                    let max_size = mask_bits(*width);
+                    max_sizes.push(quote!(#max_size));
+                }
+                _ => (),
+            }
+            // Padded arrays must also fit in their padding.
+            if let Some(padded_size) = decl.padded_size(field) {
+                let padded_size = proc_macro2::Literal::usize_unsuffixed(padded_size);
+                max_sizes.push(quote!(#padded_size));
+            }
+
+            let count_check = max_count.map(|max_count| quote!(array.len() < #max_count &&));
+            let size_check = (!max_sizes.is_empty()).then(|| {
+                let element_size = generate_element_size(scope, field);
+                quote! {
+                    size += #element_size;
+                    if #(size > #max_sizes)||* {
+                        break;
+                    }
+                }
+            });
+            let size_declaration = size_check.is_some().then(|| quote!(let mut size = 0;));
+            quote! {
+                {
+                    let mut array = Vec::new();
+                    #size_declaration
+                    while #count_check u.arbitrary()? {
+                        let elem = #element;
+                        #size_check
+                        array.push(elem);
+                    }
+                    array
+                }
+            }
+        }
+        ast::FieldDesc::OptionalScalar { condition, .. }
+        | ast::FieldDesc::OptionalTypedef { condition, .. } => {
+            let value = match &field.desc {
+                ast::FieldDesc::OptionalScalar { width, .. } => {
+                    let max_value = mask_bits(*width);
+                    quote!(u.int_in_range(0..=#max_value)?)
+                }
+                _ => quote!(u.arbitrary()?),
+            };
+            match constraints.get(&condition.id) {
+                Some(constraint)
+                    if constraint.value == condition.value
+                        && constraint.tag_id == condition.tag_id =>
+                {
+                    quote!(Some(#value))
+                }
+                Some(_) => quote!(None),
+                None => {
+                    let condition_id = format_ident!("{}", condition.id);
+                    let condition_value = constraint_value(&scope.scopes[&decl].named, condition);
+                    quote! {
+                        if #condition_id == #condition_value {
+                            Some(#value)
+                        } else {
+                            None
+                        }
+                    }
+                }
+            }
+        }
+        // Only named fields are unconstrained, and checksum values are
+        // computed on serialization: the other fields have no value.
+        _ => unreachable!("{field:?} is not a named field with a value"),
+    }
+}
+
+/// Generate an expression producing an arbitrary element of the
+/// array `field`.
+fn generate_element_value(field: &parser_ast::Field) -> proc_macro2::TokenStream {
+    match &field.desc {
+        ast::FieldDesc::Array { width: Some(width), .. } => {
+            let max_value = mask_bits(*width);
+            quote!(u.int_in_range(0..=#max_value)?)
+        }
+        ast::FieldDesc::Array { type_id: Some(type_id), .. } => {
+            let type_id = format_ident!("{type_id}");
+            quote!(u.arbitrary::<#type_id>()?)
+        }
+        _ => unreachable!("Invalid array field: {field:?}"),
+    }
+}
+
+/// Generate an expression computing the size in octets of the array
+/// element `elem`.
+fn generate_element_size(
+    scope: &lint::Scope<'_>,
+    field: &parser_ast::Field,
+) -> proc_macro2::TokenStream {
+    match (&field.desc, field.declaration(scope).map(|decl| &decl.desc)) {
+        (ast::FieldDesc::Array { width: Some(width), .. }, _)
+        | (ast::FieldDesc::Array { .. }, Some(ast::DeclDesc::Enum { width, .. })) => {
+            let byte_width = syn::Index::from(width / 8);
+            quote!(#byte_width)
+        }
+        _ => quote!(elem.get_size()),
+    }
+}
+
+/// Generate the expressions computing the space left in the payloads
+/// of `parents` which have a size field.
+///
+/// The expressions return `arbitrary::Error::IncorrectFormat` if a
+/// payload is already too large for its size field.
+fn generate_max_payload_sizes(
+    scope: &lint::Scope<'_>,
+    parents: &[&parser_ast::Decl],
+) -> Vec<proc_macro2::TokenStream> {
+    parents
+        .iter()
+        .filter_map(|parent| {
+            let size_field = scope.scopes[parent].get_payload_size_field()?;
+            let width = match &size_field.desc {
+                ast::FieldDesc::Size { width, .. } => *width,
+                _ => return None,
+            };
+            let max_value = mask_bits(width);
+            let parent_lower = format_ident!("{}", parent.id().unwrap().to_lowercase());
+            Some(quote! {
+                (#max_value as usize)
+                    .checked_sub(packet.#parent_lower.child.get_total_size())
+                    .ok_or(arbitrary::Error::IncorrectFormat)?
+            })
+        })
+        .collect()
+}
+
+/// Generate the statements setting an arbitrary payload for
+/// `builder`.
+///
+/// The payload size is limited by the size fields of the payloads
+/// of `parents`, which include the packet itself. The limits are
+/// computed by building the packet without payload.
+pub fn generate_payload_value(
+    scope: &lint::Scope<'_>,
+    parents: &[&parser_ast::Decl],
+) -> proc_macro2::TokenStream {
+    let max_payload_sizes = generate_max_payload_sizes(scope, parents);
+    let size = if max_payload_sizes.is_empty() {
+        quote!(u.arbitrary_len::<u8>()?)
+    } else {
+        quote! {
+            {
+                let packet = builder.clone().build();
+                let max_payload_size = [#(#max_payload_sizes),*].into_iter().min().unwrap();
+                u.arbitrary_len::<u8>()?.min(max_payload_size)
+            }
+        }
+    };
+
+    quote! {
+        let size = #size;
+        builder.payload = Some(Bytes::copy_from_slice(u.bytes(size)?));
+    }
+}
+
+/// Generate the statement rejecting builders of packets without
+/// payload which do not fit in the payloads of `parents`.
+pub fn generate_payload_size_check(
+    scope: &lint::Scope<'_>,
+    parents: &[&parser_ast::Decl],
+) -> Option<proc_macro2::TokenStream> {
+    let max_payload_sizes = generate_max_payload_sizes(scope, parents);
+    (!max_payload_sizes.is_empty()).then(|| {
+        quote! {
+            let packet = builder.clone().build();
+            #(#max_payload_sizes;)*
+        }
+    })
+}
+
+/// Generate the statement rejecting builders which match the
+/// constraints of a child of `decl`.
+///
+/// Such builders would be parsed as the child, which can fail or
+/// yield a different packet. Arbitrary children are instead
+/// generated from the child builders.
+pub fn generate_child_constraint_check(
+    scope: &lint::Scope<'_>,
+    decl: &parser_ast::Decl,
+) -> Option<proc_macro2::TokenStream> {
+    let children = scope.children.get(decl.id().unwrap())?;
+    let packet_scope = &scope.scopes[&decl];
+
+    // Set of field names (sorted by name).
+    let mut constrained_fields = BTreeSet::new();
+    // Maps (child name, field name) -> value.
+    let mut constraint_values = HashMap::new();
+    for child in children {
+        for constraint in child.constraints() {
+            let value = match constraint {
+                ast::Constraint { value: Some(value), .. } => {
+                    let value = proc_macro2::Literal::usize_unsuffixed(*value);
+                    quote!(#value)
+                }
+                ast::Constraint { id, tag_id: Some(tag_id), .. } => {
+                    let type_id = match &packet_scope.named[id].desc {
+                        ast::FieldDesc::Typedef { type_id, .. } => format_ident!("{type_id}"),
+                        _ => unreachable!("Invalid constraint: {constraint:?}"),
+                    };
+                    let tag_id = format_ident!("{}", tag_id.to_upper_camel_case());
+                    quote!(#type_id::#tag_id)
+                }
+                _ => unreachable!("Invalid constraint: {constraint:?}"),
+            };
+            constrained_fields.insert(&constraint.id);
+            constraint_values.insert((child.id().unwrap(), &constraint.id), value);
+        }
+    }
+
+    let wildcard = quote!(_);
+    let match_values = children.iter().map(|child| {
+        let values = constrained_fields.iter().map(|field_name| {
+            constraint_values.get(&(child.id().unwrap(), field_name)).unwrap_or(&wildcard)
+        });
+        quote!((#(#values),*))
+    });
+    let constrained_field_idents = constrained_fields.iter().map(|field| format_ident!("{field}"));
+    Some(quote! {
+        if matches!((#(builder.#constrained_field_idents),*), #(#match_values)|*) {
+            return Err(arbitrary::Error::IncorrectFormat);
+        }
+    })
+}
@@ -1,4 +1,4 @@
-use crate::backends::rust::types;
+use crate::backends::rust::{is_checksum_value, types};
 use crate::lint;
 use crate::parser::ast as parser_ast;
 use quote::{format_ident, quote};
@@ -19,6 +19,9 @@ impl<'a> FieldDeclarations<'a> {
             Some(id) => format_ident!("{id}"),
             None => return, // No id => field not stored.
         };
+        if is_checksum_value(self.scope, field) {
+            return; // Checksum values are computed, not stored.
+        }
 
         let field_type = types::rust_type(field);
         self.code.push(quote! {
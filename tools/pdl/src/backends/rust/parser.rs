@@ -1,4 +1,4 @@
-use crate::backends::rust::{mask_bits, types};
+use crate::backends::rust::{constraint_value, mask_bits, types};
 use crate::parser::ast as parser_ast;
 use crate::{ast, lint};
 use heck::ToUpperCamelCase;
@@ -48,19 +48,25 @@ impl<'a> FieldParser<'a> {
     pub fn add(&mut self, field: &'a parser_ast::Field) {
         match &field.desc {
             _ if field.is_bitfield(self.scope) => self.add_bit_field(field),
-            ast::FieldDesc::Padding { .. } => todo!("Padding fields are not supported"),
+            // Padding fields are parsed with the array they follow.
+            ast::FieldDesc::Padding { .. } => (),
             ast::FieldDesc::Array { id, width, type_id, size, .. } => self.add_array_field(
                 id,
                 *width,
                 type_id.as_deref(),
                 *size,
+                self.scope.typedef[self.packet_name].padded_size(field),
                 field.declaration(self.scope),
             ),
             ast::FieldDesc::Typedef { id, type_id } => self.add_typedef_field(id, type_id),
+            ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. } => {
+                self.add_optional_field(field)
+            }
             ast::FieldDesc::Payload { size_modifier, .. } => {
                 self.add_payload_field(size_modifier.as_deref())
             }
             ast::FieldDesc::Body { .. } => self.add_payload_field(None),
+            ast::FieldDesc::Checksum { field_id } => self.add_checksum_start(field_id),
             _ => todo!("{field:?}"),
         }
     }
@@ -248,6 +254,9 @@ impl<'a> FieldParser<'a> {
         // `size`: the size of the array in number of elements (if
         // known). If None, the array is a Vec with a dynamic size.
         size: Option<usize>,
+        // `padded_size`: the size in octets of the padding field
+        // following the array (if Some).
+        padded_size: Option<usize>,
         decl: Option<&parser_ast::Decl>,
     ) {
         enum ElementWidth {
@@ -283,10 +292,20 @@ impl<'a> FieldParser<'a> {
 
         // TODO size modifier
 
-        // TODO padded_size
+        let span = self.span;
+        let padding_tail = format_ident!("{id}_padding");
+        if let Some(padded_size) = padded_size {
+            // The array is parsed from the padded span, the remaining
+            // octets are the padding.
+            let padded_size = proc_macro2::Literal::usize_unsuffixed(padded_size);
+            self.check_size(&quote!(#padded_size));
+            self.code.push(quote! {
+                let (head, #padding_tail) = #span.get().split_at(#padded_size);
+                #span.replace(head);
+            });
+        }
 
         let id = format_ident!("{id}");
-        let span = self.span;
 
         let parse_element = self.parse_array_element(self.span, width, type_id, decl);
         match (element_width, &array_shape) {
@@ -403,6 +422,12 @@ impl<'a> FieldParser<'a> {
                 });
             }
         }
+
+        if padded_size.is_some() {
+            self.code.push(quote! {
+                #span.replace(#padding_tail);
+            });
+        }
     }
 
     /// Parse typedef fields.
@@ -416,6 +441,9 @@ impl<'a> FieldParser<'a> {
         if let ast::DeclDesc::Struct { parent_id: Some(_), .. } = &decl.desc {
             panic!("Derived struct used in typedef field");
         }
+        if let ast::DeclDesc::Checksum { width, .. } = &decl.desc {
+            return self.add_checksum_value_field(id, type_id, *width);
+        }
 
         let span = self.span;
         let id = format_ident!("{id}");
@@ -428,20 +456,115 @@ impl<'a> FieldParser<'a> {
             Some(width) => {
                 assert_eq!(width % 8, 0, "Typedef field type size is not a multiple of 8");
                 let width = syn::Index::from(width / 8);
-                self.code.push(if let ast::DeclDesc::Checksum { .. } = &decl.desc {
-                    // TODO: handle checksum fields.
-                    quote! {
-                        #span.get_mut().advance(#width);
-                    }
-                } else {
-                    quote! {
-                        let (head, tail) = #span.get().split_at(#width);
-                        #span.replace(tail);
-                        let #id = #type_id::parse(head)?;
-                    }
+                self.code.push(quote! {
+                    let (head, tail) = #span.get().split_at(#width);
+                    #span.replace(tail);
+                    let #id = #type_id::parse(head)?;
+                });
+            }
+        }
+    }
+
+    /// Parse optional fields.
+    ///
+    /// The field is parsed only when its condition field holds the
+    /// condition value, and is `None` otherwise.
+    fn add_optional_field(&mut self, field: &'a parser_ast::Field) {
+        assert_eq!(self.shift, 0, "Optional field does not start on an octet boundary");
+        let (id, condition) = match &field.desc {
+            ast::FieldDesc::OptionalScalar { id, condition, .. }
+            | ast::FieldDesc::OptionalTypedef { id, condition, .. } => (id, condition),
+            _ => unreachable!("Invalid optional field: {field:?}"),
+        };
+
+        // The code parsing the value is generated aside, to be wrapped
+        // in the condition check.
+        let code = std::mem::take(&mut self.code);
+        let decl = field.declaration(self.scope);
+        match (&field.desc, decl.map(|decl| &decl.desc)) {
+            (ast::FieldDesc::OptionalScalar { width, .. }, _) => {
+                let wanted = proc_macro2::Literal::usize_unsuffixed(width / 8);
+                self.check_size(&quote!(#wanted));
+                let get = types::get_uint(self.endianness, *width, self.span);
+                let id = format_ident!("{id}");
+                self.code.push(quote! {
+                    let #id = #get;
+                });
+            }
+            (
+                ast::FieldDesc::OptionalTypedef { type_id, .. },
+                Some(ast::DeclDesc::Enum { width, .. }),
+            ) => {
+                let wanted = proc_macro2::Literal::usize_unsuffixed(width / 8);
+                self.check_size(&quote!(#wanted));
+                let value = self.parse_array_element(self.span, None, Some(type_id), decl);
+                let id = format_ident!("{id}");
+                self.code.push(quote! {
+                    let #id = #value?;
                 });
             }
+            (ast::FieldDesc::OptionalTypedef { type_id, .. }, _) => {
+                self.add_typedef_field(id, type_id)
+            }
+            _ => unreachable!(),
         }
+        let value_code = std::mem::replace(&mut self.code, code);
+
+        let packet_scope = self.packet_scope().unwrap();
+        let condition_id = format_ident!("{}", condition.id);
+        let condition_value = constraint_value(&packet_scope.named, condition);
+        let id = format_ident!("{id}");
+        self.code.push(quote! {
+            let #id = if #condition_id == #condition_value {
+                #(#value_code)*
+                Some(#id)
+            } else {
+                None
+            };
+        });
+    }
+
+    /// Parse checksum start fields.
+    ///
+    /// The span is saved to compute the checksum once the checksum
+    /// value field is reached.
+    fn add_checksum_start(&mut self, field_id: &str) {
+        assert_eq!(self.shift, 0, "Checksum start does not start on an octet boundary");
+        let span = self.span;
+        let checksum_start = format_ident!("{field_id}_start");
+        self.code.push(quote! {
+            let #checksum_start = #span.get();
+        });
+    }
+
+    /// Parse checksum value fields.
+    ///
+    /// The value is checked against the checksum of the octets parsed
+    /// since the checksum start.
+    fn add_checksum_value_field(&mut self, id: &str, type_id: &str, width: usize) {
+        assert_eq!(self.shift, 0, "Checksum field does not start on an octet boundary");
+        assert_eq!(width % 8, 0, "Checksum field size is not a multiple of 8");
+        let wanted = proc_macro2::Literal::usize_unsuffixed(width / 8);
+        self.check_size(&quote!(#wanted));
+
+        let span = self.span;
+        let packet_name = &self.packet_name;
+        let checksum_start = format_ident!("{id}_start");
+        let id = format_ident!("{id}");
+        let type_id = format_ident!("{type_id}");
+        let get = types::get_uint(self.endianness, width, span);
+        self.code.push(quote! {
+            let mut checksum = #type_id::default();
+            checksum.update(&#checksum_start[..#checksum_start.len() - #span.get().len()]);
+            let #id = #get;
+            if #id != checksum.get_checksum() {
+                return Err(Error::InvalidChecksum {
+                    obj: #packet_name.to_string(),
+                    expected: checksum.get_checksum().into(),
+                    actual: #id.into(),
+                });
+            }
+        });
     }
 
     /// Parse body and payload fields.
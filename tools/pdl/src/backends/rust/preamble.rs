@@ -37,6 +37,16 @@ pub fn generate(path: &Path) -> String {
         type Result<T> = std::result::Result<T, Error>;
     });
 
+    code
+}
+
+/// Generate the error types and the `Packet` trait.
+///
+/// Files importing other files use the definitions generated for the
+/// imported files instead.
+pub fn generate_types() -> String {
+    let mut code = String::new();
+
     code.push_str(&quote_block! {
         #[derive(Debug, Error)]
         pub enum Error {
@@ -54,6 +64,8 @@ pub fn generate(path: &Path) -> String {
             ImpossibleStructError,
             #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
             InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+            #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+            InvalidChecksum { obj: String, expected: u64, actual: u64 },
         }
     });
 
@@ -73,14 +85,44 @@ pub fn generate(path: &Path) -> String {
     code
 }
 
+/// Generate the checksum trait and the provided checksum
+/// implementations, for files declaring checksums.
+pub fn generate_checksums() -> String {
+    format!("{}\n", include_str!("../checksum_preamble.rs"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::test_utils::{assert_snapshot_eq, rustfmt};
 
+    mod checksums {
+        include!("../checksum_preamble.rs");
+    }
+    use checksums::*;
+
+    fn checksum<C: ChecksumAlgorithm>(bytes: &[u8]) -> C::Value {
+        let mut checksum = C::default();
+        checksum.update(bytes);
+        checksum.get_checksum()
+    }
+
     #[test]
     fn test_generate_preamble() {
-        let actual_code = generate(Path::new("some/path/foo.pdl"));
+        let actual_code = generate(Path::new("some/path/foo.pdl")) + &generate_types();
         assert_snapshot_eq("tests/generated/preamble.rs", &rustfmt(&actual_code));
     }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(checksum::<Crc16>(b""), 0);
+        assert_eq!(checksum::<Crc16>(b"123456789"), 0xbb3d);
+    }
+
+    #[test]
+    fn test_additive_sums() {
+        assert_eq!(checksum::<Sum8>(b"123456789"), 0xdd);
+        assert_eq!(checksum::<Sum16>(b"123456789"), 0x01dd);
+        assert_eq!(checksum::<Sum8>(&[0xff, 0x02]), 0x01);
+    }
 }
@@ -1,4 +1,4 @@
-use crate::backends::rust::{mask_bits, types};
+use crate::backends::rust::{constraint_value, mask_bits, types};
 use crate::parser::ast as parser_ast;
 use crate::{ast, lint};
 use heck::ToUpperCamelCase;
@@ -42,15 +42,24 @@ impl<'a> FieldSerializer<'a> {
     pub fn add(&mut self, field: &parser_ast::Field) {
         match &field.desc {
             _ if field.is_bitfield(self.scope) => self.add_bit_field(field),
-            ast::FieldDesc::Array { id, width, .. } => {
-                self.add_array_field(id, *width, field.declaration(self.scope))
-            }
+            // Padding fields are serialized with the array they follow.
+            ast::FieldDesc::Padding { .. } => (),
+            ast::FieldDesc::Array { id, width, .. } => self.add_array_field(
+                id,
+                *width,
+                self.scope.typedef[self.packet_name].padded_size(field),
+                field.declaration(self.scope),
+            ),
             ast::FieldDesc::Typedef { id, type_id } => {
                 self.add_typedef_field(id, type_id);
             }
+            ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. } => {
+                self.add_optional_field(field)
+            }
             ast::FieldDesc::Payload { .. } | ast::FieldDesc::Body { .. } => {
                 self.add_payload_field()
             }
+            ast::FieldDesc::Checksum { field_id } => self.add_checksum_start(field_id),
             _ => todo!("Cannot yet serialize {field:?}"),
         }
     }
@@ -81,7 +90,11 @@ impl<'a> FieldSerializer<'a> {
                 let field_type = types::Integer::new(width);
                 let enum_id = format_ident!("{enum_id}");
                 let tag_id = format_ident!("{}", tag_id.to_upper_camel_case());
-                self.chunk.push(BitField { value: quote!(#enum_id::#tag_id), field_type, shift });
+                self.chunk.push(BitField {
+                    value: quote!(#enum_id::#tag_id as #field_type),
+                    field_type,
+                    shift,
+                });
             }
             ast::FieldDesc::FixedScalar { value, .. } => {
                 let field_type = types::Integer::new(width);
@@ -237,9 +250,13 @@ impl<'a> FieldSerializer<'a> {
         self.shift = 0;
     }
 
-    fn add_array_field(&mut self, id: &str, width: Option<usize>, decl: Option<&parser_ast::Decl>) {
-        // TODO: padding
-
+    fn add_array_field(
+        &mut self,
+        id: &str,
+        width: Option<usize>,
+        padded_size: Option<usize>,
+        decl: Option<&parser_ast::Decl>,
+    ) {
         let serialize = match width {
             Some(width) => {
                 let value = quote!(*elem);
@@ -264,12 +281,34 @@ impl<'a> FieldSerializer<'a> {
             }
         };
 
+        let packet_name = &self.packet_name;
+        let name = id;
         let id = format_ident!("{id}");
-        self.code.push(quote! {
-            for elem in &self.#id {
-                #serialize;
+        match padded_size {
+            Some(padded_size) => {
+                let span = format_ident!("{}", self.span);
+                let padded_size = proc_macro2::Literal::usize_unsuffixed(padded_size);
+                self.code.push(quote! {
+                    let current_size = #span.len();
+                    for elem in &self.#id {
+                        #serialize;
+                    }
+                    let array_size = #span.len() - current_size;
+                    if array_size > #padded_size {
+                        panic!(
+                            "Invalid length for {}::{}: {} > {}",
+                            #packet_name, #name, array_size, #padded_size
+                        );
+                    }
+                    #span.put_bytes(0, #padded_size - array_size);
+                });
             }
-        });
+            None => self.code.push(quote! {
+                for elem in &self.#id {
+                    #serialize;
+                }
+            }),
+        }
     }
 
     fn add_typedef_field(&mut self, id: &str, type_id: &str) {
@@ -279,6 +318,10 @@ impl<'a> FieldSerializer<'a> {
             panic!("Derived struct used in typedef field");
         }
 
+        if let ast::DeclDesc::Checksum { width, .. } = &decl.desc {
+            return self.add_checksum_value_field(id, type_id, *width);
+        }
+
         let id = format_ident!("{id}");
         let span = format_ident!("{}", self.span);
         self.code.push(quote! {
@@ -286,6 +329,106 @@ impl<'a> FieldSerializer<'a> {
         });
     }
 
+    /// Serialize optional fields.
+    ///
+    /// The value must be present if and only if the condition field
+    /// holds the condition value.
+    fn add_optional_field(&mut self, field: &parser_ast::Field) {
+        assert_eq!(self.shift, 0, "Optional field does not start on an octet boundary");
+        let (name, condition) = match &field.desc {
+            ast::FieldDesc::OptionalScalar { id, condition, .. }
+            | ast::FieldDesc::OptionalTypedef { id, condition, .. } => (id, condition),
+            _ => unreachable!("Invalid optional field: {field:?}"),
+        };
+
+        let packet_name = &self.packet_name;
+        let id = format_ident!("{name}");
+        let span = format_ident!("{}", self.span);
+        let serialize = match (&field.desc, field.declaration(self.scope).map(|decl| &decl.desc)) {
+            (ast::FieldDesc::OptionalScalar { width, .. }, _) => {
+                let field_type = types::Integer::new(*width);
+                let put = types::put_uint(self.endianness, &quote!(*#id), *width, self.span);
+                if field_type.width > *width {
+                    let max_value = mask_bits(*width);
+                    quote! {
+                        if *#id > #max_value {
+                            panic!(
+                                "Invalid value for {}::{}: {} > {}",
+                                #packet_name, #name, #id, #max_value
+                            );
+                        }
+                        #put;
+                    }
+                } else {
+                    quote!(#put;)
+                }
+            }
+            (ast::FieldDesc::OptionalTypedef { .. }, Some(ast::DeclDesc::Enum { width, .. })) => {
+                let field_type = types::Integer::new(*width);
+                let to_u = format_ident!("to_u{}", field_type.width);
+                let put = types::put_uint(
+                    self.endianness,
+                    &quote!(#id.#to_u().unwrap()),
+                    *width,
+                    self.span,
+                );
+                quote!(#put;)
+            }
+            _ => quote!(#id.write_to(#span);),
+        };
+
+        let packet_scope = &self.scope.scopes[&self.scope.typedef[self.packet_name]];
+        let condition_id = format_ident!("{}", condition.id);
+        let condition_value = constraint_value(&packet_scope.named, condition);
+        let expected = match condition {
+            ast::Constraint { tag_id: Some(tag_id), .. } => tag_id.clone(),
+            ast::Constraint { value: Some(value), .. } => value.to_string(),
+            _ => unreachable!("Invalid condition: {condition:?}"),
+        };
+        let message = format!(
+            "Invalid value for {}::{}: expected a value if and only if {} = {}",
+            self.packet_name, name, condition.id, expected
+        );
+        self.code.push(quote! {
+            match (&self.#id, self.#condition_id == #condition_value) {
+                (Some(#id), true) => {
+                    #serialize
+                }
+                (None, false) => {}
+                _ => panic!(#message),
+            }
+        });
+    }
+
+    /// Serialize checksum start fields.
+    ///
+    /// The current offset is saved to compute the checksum once the
+    /// checksum value field is reached.
+    fn add_checksum_start(&mut self, field_id: &str) {
+        assert_eq!(self.shift, 0, "Checksum start does not start on an octet boundary");
+        let span = format_ident!("{}", self.span);
+        let checksum_start = format_ident!("{field_id}_start");
+        self.code.push(quote! {
+            let #checksum_start = #span.len();
+        });
+    }
+
+    /// Serialize checksum value fields, computed over the octets
+    /// written since the checksum start.
+    fn add_checksum_value_field(&mut self, id: &str, type_id: &str, width: usize) {
+        assert_eq!(width % 8, 0, "Checksum field size is not a multiple of 8");
+        let span = format_ident!("{}", self.span);
+        let checksum_start = format_ident!("{id}_start");
+        let type_id = format_ident!("{type_id}");
+        let put =
+            types::put_uint(self.endianness, &quote!(checksum.get_checksum()), width, self.span);
+        self.code.push(quote! {
+            let mut checksum = #type_id::default();
+            checksum.update(&#span[#checksum_start..]);
+            #put;
+        });
+    }
+
     fn add_payload_field(&mut self) {
         if self.shift != 0 && self.endianness == ast::EndiannessValue::BigEndian {
             panic!("Payload field does not start on an octet boundary");
@@ -62,6 +62,14 @@ pub fn rust_type(field: &parser_ast::Field) -> proc_macro2::TokenStream {
             let field_type = format_ident!("{type_id}");
             quote!(Vec<#field_type>)
         }
+        ast::FieldDesc::OptionalScalar { width, .. } => {
+            let field_type = Integer::new(*width);
+            quote!(Option<#field_type>)
+        }
+        ast::FieldDesc::OptionalTypedef { type_id, .. } => {
+            let field_type = format_ident!("{type_id}");
+            quote!(Option<#field_type>)
+        }
         //ast::Field::Size { .. } | ast::Field::Count { .. } => quote!(),
         _ => todo!("{field:?}"),
     }
@@ -69,8 +77,9 @@ pub fn rust_type(field: &parser_ast::Field) -> proc_macro2::TokenStream {
 
 pub fn rust_borrow(field: &parser_ast::Field, scope: &lint::Scope<'_>) -> proc_macro2::TokenStream {
     match &field.desc {
-        ast::FieldDesc::Scalar { .. } => quote!(),
-        ast::FieldDesc::Typedef { type_id, .. } => match &scope.typedef[type_id].desc {
+        ast::FieldDesc::Scalar { .. } | ast::FieldDesc::OptionalScalar { .. } => quote!(),
+        ast::FieldDesc::Typedef { type_id, .. }
+        | ast::FieldDesc::OptionalTypedef { type_id, .. } => match &scope.typedef[type_id].desc {
             ast::DeclDesc::Enum { .. } => quote!(),
             ast::DeclDesc::Struct { .. } => quote!(&),
             desc => unreachable!("unexpected declaration: {desc:?}"),
@@ -47,6 +47,7 @@ impl Declarable for ComputedOffsetId<'_> {
             ComputedOffsetId::PacketEnd => "packet_end_offset".to_string(),
             ComputedOffsetId::FieldOffset(field) => format!("{field}_offset"),
             ComputedOffsetId::FieldEndOffset(field) => format!("{field}_end_offset"),
+            ComputedOffsetId::ChecksumStart(field) => format!("{field}_checksum_start_offset"),
             ComputedOffsetId::Custom(i) => format!("custom_offset_{i}"),
             ComputedOffsetId::TrailerStart => "trailer_start_offset".to_string(),
         }
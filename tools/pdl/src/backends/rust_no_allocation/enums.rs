@@ -32,7 +32,7 @@ pub fn generate_enum(id: &str, tags: &[ast::Tag], width: usize) -> TokenStream {
                 }
             }
 
-            fn try_parse(buf: BitSlice) -> Result<Self, ParseError> {
+            pub(crate) fn try_parse(buf: BitSlice) -> Result<Self, ParseError> {
                 let value = buf.slice(#width)?.try_parse()?;
                 match value {
                     #(#tag_values => Ok(Self::#tag_ids)),*,
@@ -19,8 +19,9 @@ mod utils;
 
 use std::collections::HashMap;
 
+use heck::ToSnakeCase;
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote};
 
 use crate::ast;
 use crate::parser;
@@ -31,32 +32,52 @@ use self::{
 };
 
 use super::intermediate::Schema;
+use super::rust::{format_test_location, generate_checksum_decl};
 
-pub fn generate(file: &parser::ast::File, schema: &Schema) -> Result<String, String> {
-    match file.endianness.value {
-        ast::EndiannessValue::LittleEndian => {}
-        _ => unimplemented!("Only little_endian endianness supported"),
-    };
-
+pub fn generate(
+    sources: &ast::SourceDatabase,
+    file: &parser::ast::File,
+    schema: &Schema,
+) -> Result<String, String> {
     let mut out = String::new();
 
-    out.push_str(include_str!("preamble.rs"));
+    // Imported declarations, the preamble, and the checksum algorithms
+    // are generated in the module of the imported files. The module
+    // including this code must bring them into scope.
+    let (declarations, imported_declarations): (Vec<_>, Vec<_>) =
+        file.declarations.iter().partition(|decl| decl.loc.file == file.file);
+    let is_checksum =
+        |decl: &&parser::ast::Decl| matches!(decl.desc, ast::DeclDesc::Checksum { .. });
+
+    if file.imports.is_empty() {
+        out.push_str(include_str!("preamble.rs"));
+
+        // selects the bit order used by the preamble readers and writers
+        let big_endian = file.endianness.value == ast::EndiannessValue::BigEndian;
+        out.push_str(&quote! { const BIG_ENDIAN: bool = #big_endian; }.to_string());
+    }
+
+    if declarations.iter().any(is_checksum) && !imported_declarations.iter().any(is_checksum) {
+        out.push_str(include_str!("../checksum_preamble.rs"));
+    }
 
     let mut children = HashMap::<&str, Vec<&str>>::new();
+    let mut parents = HashMap::<&str, &str>::new();
     for decl in &file.declarations {
         match &decl.desc {
             ast::DeclDesc::Packet { id, parent_id: Some(parent_id), .. }
             | ast::DeclDesc::Struct { id, parent_id: Some(parent_id), .. } => {
                 children.entry(parent_id.as_str()).or_default().push(id.as_str());
+                parents.insert(id.as_str(), parent_id.as_str());
             }
             _ => {}
         }
     }
 
-    let declarations = file
-        .declarations
-        .iter()
-        .map(|decl| generate_decl(decl, schema, &children))
+    let mut test_counts = HashMap::new();
+    let declarations = declarations
+        .into_iter()
+        .map(|decl| generate_decl(sources, decl, schema, &children, &parents, &mut test_counts))
         .collect::<Result<TokenStream, _>>()?;
 
     out.push_str(
@@ -70,9 +91,12 @@ pub fn generate(file: &parser::ast::File, schema: &Schema) -> Result<String, Str
 }
 
 fn generate_decl(
+    sources: &ast::SourceDatabase,
     decl: &parser::ast::Decl,
     schema: &Schema,
     children: &HashMap<&str, Vec<&str>>,
+    parents: &HashMap<&str, &str>,
+    test_counts: &mut HashMap<String, usize>,
 ) -> Result<TokenStream, String> {
     match &decl.desc {
         ast::DeclDesc::Enum { id, tags, width, .. } => Ok(generate_enum(id, tags, *width)),
@@ -98,6 +122,64 @@ fn generate_decl(
                 #serializer
             })
         }
+        ast::DeclDesc::Checksum { id, function, .. } => Ok(generate_checksum_decl(id, function)),
+        ast::DeclDesc::Test { type_id, test_cases } => {
+            let first_idx = test_counts.entry(type_id.clone()).or_default();
+            let code = generate_test_decl(sources, type_id, test_cases, *first_idx, parents);
+            *first_idx += test_cases.len();
+            Ok(code)
+        }
         _ => unimplemented!("Unsupported decl type"),
     }
 }
+
+/// Generate unit tests for the cases of a test declaration.
+///
+/// Views cannot be converted back to builders, so the tests only
+/// check that the input parses as the named packet. The cases are
+/// numbered from `first_idx`, as in the default backend.
+fn generate_test_decl(
+    sources: &ast::SourceDatabase,
+    type_id: &str,
+    test_cases: &[ast::TestCase],
+    first_idx: usize,
+    parents: &HashMap<&str, &str>,
+) -> TokenStream {
+    let mut packet_ids = vec![type_id];
+    while let Some(parent_id) = parents.get(packet_ids.last().unwrap()) {
+        packet_ids.push(parent_id);
+    }
+    packet_ids.reverse();
+
+    let top_level_view = format_ident!("{}View", packet_ids[0]);
+    let child_views =
+        packet_ids[1..].iter().map(|id| format_ident!("{id}View")).collect::<Vec<_>>();
+
+    let tests = test_cases.iter().enumerate().map(|(idx, test_case)| {
+        let name = format_ident!("test_{}_{}", type_id.to_snake_case(), first_idx + idx);
+        let location = format_test_location(sources, &test_case.loc);
+        // The test inputs are validated by the analyzer.
+        let input = proc_macro2::Literal::byte_string(&test_case.bytes().unwrap());
+        quote! {
+            #[test]
+            fn #name() {
+                let input: &[u8] = #input;
+                let packet = match #top_level_view::try_parse_from_buffer(input) {
+                    Ok(packet) => packet,
+                    Err(err) => panic!("{}: could not parse {}: {:?}", #location, #type_id, err),
+                };
+                #(
+                    let packet = match #child_views::try_parse(packet) {
+                        Ok(packet) => packet,
+                        Err(err) => panic!("{}: could not parse {}: {:?}", #location, #type_id, err),
+                    };
+                )*
+                let _ = packet;
+            }
+        }
+    });
+
+    quote! {
+        #(#tests)*
+    }
+}
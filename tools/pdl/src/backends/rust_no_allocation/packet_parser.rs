@@ -46,13 +46,14 @@ pub fn generate_packet(
             | ast::FieldDesc::FixedEnum { .. }
             | ast::FieldDesc::ElementSize { .. }
             | ast::FieldDesc::Count { .. }
-            | ast::FieldDesc::Size { .. } => {
+            | ast::FieldDesc::Size { .. }
+            | ast::FieldDesc::Checksum { .. } => {
                 // no-op, no getter generated for this type
                 quote! {}
             }
             ast::FieldDesc::Group { .. } => unreachable!(),
-            ast::FieldDesc::Checksum { .. } => {
-                unimplemented!("checksums not yet supported with this backend")
+            ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. } => {
+                unreachable!("optional fields are rejected by the schema generation")
             }
             ast::FieldDesc::Payload { .. } | ast::FieldDesc::Body => {
                 let name = if matches!(field.desc, ast::FieldDesc::Payload { .. }) { "_payload_"} else { "_body_"};
@@ -158,6 +159,24 @@ pub fn generate_packet(
                     }
                 }
             }
+            ast::FieldDesc::Typedef { id, type_id } if schema.checksums.contains_key(type_id.as_str()) => {
+                // the checksum value is exposed as a scalar, it is verified by validate()
+                let try_getter_name = format_ident!("try_get_{id}");
+                let getter_name = format_ident!("get_{id}");
+                let offset = ComputedOffsetId::FieldOffset(id).call_fn();
+                let width = schema.checksums[type_id.as_str()].width;
+                let scalar_type = get_integer_type(width);
+                quote! {
+                    fn #try_getter_name(&self) -> Result<#scalar_type, ParseError> {
+                        self.buf.offset(#offset)?.slice(#width)?.try_parse()
+                    }
+
+                    #[inline]
+                    pub fn #getter_name(&self) -> #scalar_type {
+                        self.#try_getter_name().unwrap()
+                    }
+                }
+            }
             ast::FieldDesc::Typedef { id, type_id } => {
                 let try_getter_name = format_ident!("try_get_{id}");
                 let getter_name = format_ident!("get_{id}");
@@ -216,8 +235,38 @@ pub fn generate_packet(
     };
 
     let field_validators = fields.iter().map(|field| match &field.desc {
-        ast::FieldDesc::Checksum { .. } => unimplemented!(),
+        ast::FieldDesc::Checksum { field_id } => {
+            let checksum_ident = fields
+                .iter()
+                .find_map(|field| match &field.desc {
+                    ast::FieldDesc::Typedef { id, type_id } if id == field_id => {
+                        Some(format_ident!("{type_id}"))
+                    }
+                    _ => None,
+                })
+                .unwrap();
+            let getter_ident = format_ident!("try_get_{field_id}");
+            let start_offset = ComputedOffsetId::ChecksumStart(field_id).call_fn();
+            let end_offset = ComputedOffsetId::FieldOffset(field_id).call_fn();
+            quote! {
+                {
+                    let start_offset = #start_offset;
+                    let covered = self
+                        .buf
+                        .offset(start_offset)?
+                        .slice(#end_offset.checked_sub(start_offset).ok_or(ParseError::ArithmeticOverflow)?)?;
+                    let mut checksum = #checksum_ident::default();
+                    checksum.update(covered.try_get_bytes()?);
+                    if self.#getter_ident()? != checksum.get_checksum() {
+                        return Err(ParseError::InvalidChecksum);
+                    }
+                }
+            }
+        }
         ast::FieldDesc::Group { .. } => unreachable!(),
+        ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. } => {
+            unreachable!("optional fields are rejected by the schema generation")
+        }
         ast::FieldDesc::Padding { .. }
         | ast::FieldDesc::Size { .. }
         | ast::FieldDesc::Count { .. }
@@ -39,13 +39,20 @@ pub fn generate_packet_serializer(
                 | ast::FieldDesc::FixedEnum { .. }
                 | ast::FieldDesc::ElementSize { .. }
                 | ast::FieldDesc::Count { .. }
-                | ast::FieldDesc::Size { .. } => {
+                | ast::FieldDesc::Size { .. }
+                | ast::FieldDesc::Checksum { .. } => {
                     // no-op, no getter generated for this type
                     None
                 }
                 ast::FieldDesc::Group { .. } => unreachable!(),
-                ast::FieldDesc::Checksum { .. } => {
-                    unimplemented!("checksums not yet supported with this backend")
+                ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. } => {
+                    unreachable!("optional fields are rejected by the schema generation")
+                }
+                ast::FieldDesc::Typedef { type_id, .. }
+                    if schema.checksums.contains_key(type_id.as_str()) =>
+                {
+                    // the checksum value is computed during serialization
+                    None
                 }
                 ast::FieldDesc::Body | ast::FieldDesc::Payload { .. } => {
                     let type_ident = format_ident!("{id}Child");
@@ -86,9 +93,30 @@ pub fn generate_packet_serializer(
 
     let mut has_child = false;
 
-    let serializer = fields.iter().map(|field| {
+    let serializer = schema.wire_order(fields).into_iter().map(|field| {
         match &field.desc {
-            ast::FieldDesc::Checksum { .. } | ast::FieldDesc::Group { .. } => unimplemented!(),
+            ast::FieldDesc::Group { .. } => unreachable!(),
+            ast::FieldDesc::OptionalScalar { .. } | ast::FieldDesc::OptionalTypedef { .. } => {
+                unreachable!("optional fields are rejected by the schema generation")
+            }
+            ast::FieldDesc::Checksum { field_id } => {
+                let checksum_writer_ident = format_ident!("{field_id}_checksum_writer");
+                quote! {
+                    let mut #checksum_writer_ident = ChecksumWriter::new(writer);
+                    let writer = &mut #checksum_writer_ident;
+                }
+            }
+            ast::FieldDesc::Typedef { id, type_id } if schema.checksums.contains_key(type_id.as_str()) => {
+                let checksum_writer_ident = format_ident!("{id}_checksum_writer");
+                let checksum_ident = format_ident!("{type_id}");
+                let width = schema.checksums[type_id.as_str()].width;
+                quote! {
+                    let (writer, covered) = #checksum_writer_ident.finish()?;
+                    let mut checksum = #checksum_ident::default();
+                    checksum.update(&covered);
+                    writer.write_bits(#width, || Ok(checksum.get_checksum()))?;
+                }
+            }
             ast::FieldDesc::Padding { size, .. } => {
                 quote! {
                     if (most_recent_array_size_in_bits > #size * 8) {
@@ -9,21 +9,22 @@ pub enum ParseError {
     ArithmeticOverflow,
     OutOfBoundsAccess,
     MisalignedPayload,
+    InvalidChecksum,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct BitSlice<'a> {
     // note: the offsets are ENTIRELY UNRELATED to the size of this struct,
     // so indexing needs to be checked to avoid panics
-    backing: &'a [u8],
+    pub(crate) backing: &'a [u8],
 
     // invariant: end_bit_offset >= start_bit_offset, so subtraction will NEVER wrap
-    start_bit_offset: usize,
-    end_bit_offset: usize,
+    pub(crate) start_bit_offset: usize,
+    pub(crate) end_bit_offset: usize,
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct SizedBitSlice<'a>(BitSlice<'a>);
+pub struct SizedBitSlice<'a>(pub(crate) BitSlice<'a>);
 
 impl<'a> BitSlice<'a> {
     pub fn offset(&self, offset: usize) -> Result<BitSlice<'a>, ParseError> {
@@ -90,7 +91,27 @@ impl<'a> SizedBitSlice<'a> {
         let size_in_bits = self.end_bit_offset - self.start_bit_offset;
 
         // fields that fit into a u64 don't need to be byte-aligned
-        if size_in_bits <= 64 {
+        if size_in_bits <= 64 && BIG_ENDIAN {
+            // bits are numbered from the most significant bit of each byte
+            let mut accumulator = 0u64;
+
+            let mut curr_byte_index = self.start_bit_offset / 8;
+            let mut curr_bit_offset = self.start_bit_offset % 8;
+            let mut remaining_bits = size_in_bits;
+
+            while remaining_bits > 0 {
+                // take as many bits as we can from the current byte
+                let bits_to_consume = (8 - curr_bit_offset).min(remaining_bits);
+                let tmp = (self.byte_at(curr_byte_index)?
+                    >> (8 - curr_bit_offset - bits_to_consume)) as u64
+                    & ((1u64 << bits_to_consume) - 1);
+                accumulator = (accumulator << bits_to_consume) | tmp;
+                curr_bit_offset = 0;
+                curr_byte_index += 1;
+                remaining_bits -= bits_to_consume;
+            }
+            T::try_from(accumulator).map_err(|_| ParseError::ArithmeticOverflow)
+        } else if size_in_bits <= 64 {
             let mut accumulator = 0u64;
 
             // where we are in our accumulation
@@ -123,6 +144,15 @@ impl<'a> SizedBitSlice<'a> {
         }
     }
 
+    pub fn try_get_bytes(&self) -> Result<&'a [u8], ParseError> {
+        if self.start_bit_offset % 8 != 0 || self.end_bit_offset % 8 != 0 {
+            return Err(ParseError::MisalignedPayload);
+        }
+        self.backing
+            .get(self.start_bit_offset / 8..self.end_bit_offset / 8)
+            .ok_or(ParseError::OutOfBoundsAccess)
+    }
+
     pub fn get_size_in_bits(&self) -> usize {
         self.end_bit_offset - self.start_bit_offset
     }
@@ -230,7 +260,10 @@ impl<'a> Serializer<'a> {
     }
 
     fn flush(self) {
-        if self.curr_bit_offset > 0 {
+        if self.curr_bit_offset > 0 && BIG_ENDIAN {
+            // partial byte remaining, already in the most significant bits
+            self.buf.push(self.curr_byte);
+        } else if self.curr_bit_offset > 0 {
             // partial byte remaining
             self.buf.push(self.curr_byte << (8 - self.curr_bit_offset));
         }
@@ -249,6 +282,30 @@ impl<'a> BitWriter for Serializer<'a> {
             return Err(SerializeError::ValueTooLarge);
         }
 
+        if BIG_ENDIAN {
+            // bits are written from the most significant bit of each byte
+            let mut remaining_bits = num_bits;
+            while remaining_bits > 0 {
+                let remaining_bits_in_curr_byte = (8 - self.curr_bit_offset) as usize;
+                let bits_to_write = remaining_bits_in_curr_byte.min(remaining_bits);
+                // writes wider than 64 bits (e.g. padding) start with zero bits
+                let val_for_this_byte =
+                    (val.checked_shr((remaining_bits - bits_to_write) as u32).unwrap_or(0)
+                        & ((1 << bits_to_write) - 1)) as u8;
+                self.curr_byte +=
+                    val_for_this_byte << (remaining_bits_in_curr_byte - bits_to_write);
+                self.curr_bit_offset += bits_to_write as u8;
+                remaining_bits -= bits_to_write;
+
+                if self.curr_bit_offset == 8 {
+                    self.buf.push(self.curr_byte);
+                    self.curr_bit_offset = 0;
+                    self.curr_byte = 0;
+                }
+            }
+            return Ok(());
+        }
+
         let mut remaining_val = val;
         let mut remaining_bits = num_bits;
         while remaining_bits > 0 {
@@ -278,3 +335,46 @@ impl<'a> BitWriter for Serializer<'a> {
         Ok(())
     }
 }
+
+/// Writer forwarding the bits to an inner writer, while keeping a copy of
+/// the octets covered by a checksum.
+pub struct ChecksumWriter<'a, W: BitWriter> {
+    writer: &'a mut W,
+    covered: Vec<u8>,
+    curr_byte: u8,
+    curr_bit_offset: u8,
+}
+
+impl<'a, W: BitWriter> ChecksumWriter<'a, W> {
+    pub fn new(writer: &'a mut W) -> Self {
+        Self { writer, covered: vec![], curr_byte: 0, curr_bit_offset: 0 }
+    }
+
+    pub fn finish(self) -> Result<(&'a mut W, Vec<u8>), SerializeError> {
+        if self.curr_bit_offset != 0 {
+            return Err(SerializeError::AlignmentError);
+        }
+        Ok((self.writer, self.covered))
+    }
+}
+
+impl<'a, W: BitWriter> BitWriter for ChecksumWriter<'a, W> {
+    fn write_bits<T: Into<u64>>(
+        &mut self,
+        num_bits: usize,
+        gen_contents: impl FnOnce() -> Result<T, SerializeError>,
+    ) -> Result<(), SerializeError> {
+        let val = gen_contents()?.into();
+
+        let mut serializer = Serializer {
+            buf: &mut self.covered,
+            curr_byte: self.curr_byte,
+            curr_bit_offset: self.curr_bit_offset,
+        };
+        serializer.write_bits(num_bits, || Ok(val))?;
+        self.curr_byte = serializer.curr_byte;
+        self.curr_bit_offset = serializer.curr_bit_offset;
+
+        self.writer.write_bits(num_bits, || Ok(val))
+    }
+}
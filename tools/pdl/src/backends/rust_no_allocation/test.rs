@@ -1,10 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use serde::Deserialize;
 
-use crate::{ast, parser::parse_inline, quote_block};
+use crate::{ast, parser, quote_block};
 
 #[derive(Deserialize)]
 struct PacketTest {
@@ -101,11 +101,14 @@ fn generate_matchers(
                 }
             }
             Field::Struct(fields) => {
+                let struct_type = type_lookup[curr_type][field_name.as_str()].unwrap();
                 out.push(generate_matchers(
                     quote! { #base.#getter_ident() },
                     fields,
-                    &|_| Ok(true),
-                    type_lookup[curr_type][field_name.as_str()].unwrap(),
+                    // checksum values are not part of the lookup, they are
+                    // verified when parsing
+                    &|field| Ok(type_lookup[struct_type].contains_key(field)),
+                    struct_type,
                     type_lookup,
                 )?);
             }
@@ -191,18 +194,30 @@ fn generate_builder(
     }
 }
 
-pub fn generate_test_file() -> Result<String, String> {
+pub fn generate_test_file(ast: &parser::ast::File) -> Result<String, String> {
     let mut out = String::new();
 
     out.push_str(include_str!("test_preamble.rs"));
 
-    let file = include_str!("../../../tests/canonical/le_test_vectors.json");
+    let file = match ast.endianness.value {
+        ast::EndiannessValue::LittleEndian => {
+            include_str!("../../../tests/canonical/le_test_vectors.json")
+        }
+        ast::EndiannessValue::BigEndian => {
+            include_str!("../../../tests/canonical/be_test_vectors.json")
+        }
+    };
     let test_vectors: Box<[_]> =
         serde_json::from_str(file).map_err(|_| "could not parse test vectors")?;
 
-    let pdl = include_str!("../../../tests/canonical/le_rust_noalloc_test_file.pdl");
-    let ast = parse_inline(&mut ast::SourceDatabase::new(), "test.pdl".to_owned(), pdl.to_owned())
-        .expect("could not parse reference PDL");
+    let checksums = ast
+        .declarations
+        .iter()
+        .filter_map(|decl| match &decl.desc {
+            ast::DeclDesc::Checksum { id, .. } => Some(id.as_str()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
     let packet_lookup =
         ast.declarations
             .iter()
@@ -220,7 +235,9 @@ pub fn generate_test_file() -> Result<String, String> {
                                 Some(type_id) => Some((id.as_str(), Some(type_id.as_str()))),
                                 None => Some((id.as_str(), None)),
                             },
-                            ast::FieldDesc::Typedef { id, type_id, .. } => {
+                            ast::FieldDesc::Typedef { id, type_id, .. }
+                                if !checksums.contains(type_id.as_str()) =>
+                            {
                                 Some((id.as_str(), Some(type_id.as_str())))
                             }
                             ast::FieldDesc::Scalar { id, .. } => Some((id.as_str(), None)),
@@ -233,16 +250,16 @@ pub fn generate_test_file() -> Result<String, String> {
             .collect::<HashMap<_, _>>();
 
     for PacketTest { packet, tests } in test_vectors.iter() {
-        if !pdl.contains(packet) {
-            // huge brain hack to skip unsupported test vectors
+        if !packet_lookup.contains_key(packet.as_str()) {
+            // skip test vectors for packets not declared in the test file
             continue;
         }
 
         for (i, PacketTestCase { packed, unpacked, packet: sub_packet }) in tests.iter().enumerate()
         {
             if let Some(sub_packet) = sub_packet {
-                if !pdl.contains(sub_packet) {
-                    // huge brain hack to skip unsupported test vectors
+                if !packet_lookup.contains_key(sub_packet.as_str()) {
+                    // skip test vectors for packets not declared in the test file
                     continue;
                 }
             }
@@ -156,15 +156,18 @@ fn main() {
             "Packet_Array_Field_SizedElement_UnknownSize",
             "Packet_Array_Field_SizedElement_VariableCount",
             "Packet_Array_Field_SizedElement_VariableSize",
+            "Packet_Array_Field_SizedElement_VariableSize_Padded",
             "Packet_Array_Field_UnsizedElement_ConstantSize",
             "Packet_Array_Field_UnsizedElement_UnknownSize",
             "Packet_Array_Field_UnsizedElement_VariableCount",
+            "Packet_Array_Field_UnsizedElement_VariableCount_Padded",
             "Packet_Array_Field_UnsizedElement_VariableSize",
             "Packet_Body_Field_UnknownSize",
             "Packet_Body_Field_UnknownSize_Terminal",
             "Packet_Body_Field_VariableSize",
             "Packet_Count_Field",
             "Packet_Enum8_Field",
+            "Packet_EnumGroup_Field",
             "Packet_Enum_Field",
             "Packet_FixedEnum_Field",
             "Packet_FixedScalar_Field",
@@ -172,6 +175,7 @@ fn main() {
             "Packet_Payload_Field_UnknownSize_Terminal",
             "Packet_Payload_Field_VariableSize",
             "Packet_Reserved_Field",
+            "Packet_ScalarGroup_Field",
             "Packet_Scalar_Field",
             "Packet_Size_Field",
             "Packet_Struct_Field",
@@ -206,16 +210,20 @@ fn main() {
             "Struct_Array_Field_SizedElement_VariableCount",
             "Struct_Array_Field_SizedElement_VariableSize",
             "Struct_Array_Field_SizedElement_VariableSize",
+            "Struct_Array_Field_SizedElement_VariableSize_Padded",
             "Struct_Array_Field_UnsizedElement_ConstantSize",
             "Struct_Array_Field_UnsizedElement_UnknownSize",
             "Struct_Array_Field_UnsizedElement_UnknownSize",
             "Struct_Array_Field_UnsizedElement_VariableCount",
             "Struct_Array_Field_UnsizedElement_VariableCount",
+            "Struct_Array_Field_UnsizedElement_VariableCount_Padded",
             "Struct_Array_Field_UnsizedElement_VariableSize",
             "Struct_Array_Field_UnsizedElement_VariableSize",
+            "Struct_EnumGroup_Field",
             "Struct_Enum_Field",
             "Struct_FixedEnum_Field",
             "Struct_FixedScalar_Field",
+            "Struct_ScalarGroup_Field",
             "Struct_Size_Field",
             "Struct_Struct_Field",
         ],
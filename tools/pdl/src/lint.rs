@@ -222,7 +222,9 @@ impl<'d> PacketScope<'d> {
 
             FieldDesc::Array { id, .. }
             | FieldDesc::Scalar { id, .. }
-            | FieldDesc::Typedef { id, .. } => self
+            | FieldDesc::Typedef { id, .. }
+            | FieldDesc::OptionalScalar { id, .. }
+            | FieldDesc::OptionalTypedef { id, .. } => self
                 .named
                 .insert(id.clone(), field)
                 .map(|prev| result.err_redeclared(id, "field", &field.loc, &prev.loc)),
@@ -719,7 +721,7 @@ fn lint_checksum(
     field_id: &str,
     result: &mut LintDiagnostics,
 ) {
-    // Checksum field must be declared before
+    // Checksum field must be declared after
     // the checksum start. The field must be a typedef with
     // a valid checksum type.
     let checksum_loc = &decl.loc;
@@ -1104,12 +1106,13 @@ fn lint_field(
         FieldDesc::Array { width, type_id, size_modifier, size, .. } => {
             lint_array(scope, packet_scope, decl, width, type_id, size_modifier, size, result)
         }
-        FieldDesc::Typedef { type_id, .. } => {
+        FieldDesc::Typedef { type_id, .. } | FieldDesc::OptionalTypedef { type_id, .. } => {
             lint_typedef(scope, packet_scope, decl, type_id, result)
         }
         FieldDesc::Padding { size, .. } => lint_padding(scope, packet_scope, decl, *size, result),
         FieldDesc::Reserved { .. }
         | FieldDesc::Scalar { .. }
+        | FieldDesc::OptionalScalar { .. }
         | FieldDesc::Body { .. }
         | FieldDesc::Payload { .. } => (),
         FieldDesc::Group { .. } => unreachable!(),
@@ -1,6 +1,6 @@
 //! PDL parser and analyzer.
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use codespan_reporting::term::{self, termcolor};
 
 mod analyzer;
@@ -33,8 +33,24 @@ impl std::str::FromStr for OutputFormat {
     }
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the input file as canonical PDL source.
+    Fmt {
+        /// Check that the input file is formatted, instead of
+        /// printing it. Exit with an error if it is not.
+        #[clap(long)]
+        check: bool,
+
+        /// Input file.
+        #[clap(name = "FILE")]
+        input_file: String,
+    },
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "pdl-parser", about = "Packet Description Language parser tool.")]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Opt {
     /// Print tool version and exit.
     #[clap(short, long = "version")]
@@ -45,9 +61,22 @@ struct Opt {
     #[clap(short, long = "output-format", name = "FORMAT", default_value = "JSON")]
     output_format: OutputFormat,
 
+    /// Implement `serde::Serialize` and `serde::Deserialize` for the
+    /// generated types. Only supported by the "rust" output format.
+    #[clap(long)]
+    serde: bool,
+
+    /// Implement `arbitrary::Arbitrary` for the generated types. Only
+    /// supported by the "rust" output format.
+    #[clap(long)]
+    arbitrary: bool,
+
     /// Input file.
-    #[clap(name = "FILE")]
-    input_file: String,
+    #[clap(name = "FILE", required = true)]
+    input_file: Option<String>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
 fn main() -> std::process::ExitCode {
@@ -58,10 +87,25 @@ fn main() -> std::process::ExitCode {
         return std::process::ExitCode::SUCCESS;
     }
 
+    if opt.output_format != OutputFormat::Rust && opt.command.is_none() {
+        let unsupported = [("--serde", opt.serde), ("--arbitrary", opt.arbitrary)];
+        if let Some((flag, _)) = unsupported.iter().find(|(_, enabled)| *enabled) {
+            eprintln!("{flag} is only supported by the 'rust' output format");
+            return std::process::ExitCode::FAILURE;
+        }
+    }
+
+    let input_file = match &opt.command {
+        Some(Command::Fmt { input_file, .. }) => input_file.clone(),
+        None => opt.input_file.expect("missing input file"),
+    };
+
     let mut sources = ast::SourceDatabase::new();
-    match parser::parse_file(&mut sources, opt.input_file) {
-        Ok(file) => {
-            let _analyzed_file = match analyzer::analyze(&file) {
+    match parser::parse_file(&mut sources, input_file).and_then(|file| {
+        parser::parse_imports(&mut sources, &file).map(|resolved_file| (file, resolved_file))
+    }) {
+        Ok((file, resolved_file)) => {
+            let _analyzed_file = match analyzer::analyze(&resolved_file) {
                 Ok(file) => file,
                 Err(diagnostics) => {
                     diagnostics
@@ -75,21 +119,41 @@ fn main() -> std::process::ExitCode {
                 }
             };
 
+            if let Some(Command::Fmt { check, .. }) = opt.command {
+                return format_file(&sources, &file, check);
+            }
+
+            // The JSON output is a dump of the parsed file, without the
+            // imported declarations. The code generators do not handle
+            // group fields, pass them the file with all groups inlined.
+            let inlined_file = analyzer::inline_groups(&resolved_file);
+
             match opt.output_format {
                 OutputFormat::JSON => {
                     println!("{}", backends::json::generate(&file).unwrap())
                 }
                 OutputFormat::Rust => {
-                    println!("{}", backends::rust::generate(&sources, &file))
+                    let options =
+                        backends::rust::Options { serde: opt.serde, arbitrary: opt.arbitrary };
+                    println!("{}", backends::rust::generate(&sources, &inlined_file, &options))
                 }
                 OutputFormat::RustNoAlloc => {
-                    let schema = backends::intermediate::generate(&file).unwrap();
-                    println!("{}", backends::rust_no_allocation::generate(&file, &schema).unwrap())
+                    let code = backends::intermediate::generate(&inlined_file).and_then(|schema| {
+                        backends::rust_no_allocation::generate(&sources, &inlined_file, &schema)
+                    });
+                    match code {
+                        Ok(code) => println!("{}", code),
+                        Err(err) => {
+                            eprintln!("{err}");
+                            return std::process::ExitCode::FAILURE;
+                        }
+                    }
                 }
                 OutputFormat::RustNoAllocTest => {
                     println!(
                         "{}",
-                        backends::rust_no_allocation::test::generate_test_file().unwrap()
+                        backends::rust_no_allocation::test::generate_test_file(&inlined_file)
+                            .unwrap()
                     )
                 }
             }
@@ -105,6 +169,38 @@ fn main() -> std::process::ExitCode {
     }
 }
 
+/// Print `file` as canonical PDL source, or check that its source
+/// is already formatted.
+fn format_file(
+    sources: &ast::SourceDatabase,
+    file: &parser::ast::File,
+    check: bool,
+) -> std::process::ExitCode {
+    let formatted = backends::pdl::generate(sources, file);
+    if !check {
+        print!("{}", formatted);
+        return std::process::ExitCode::SUCCESS;
+    }
+
+    let source = sources.get(file.file).expect("could not read source");
+    let mismatch = source
+        .source()
+        .lines()
+        .zip(formatted.lines())
+        .position(|(original, formatted)| original != formatted)
+        .or_else(|| {
+            (source.source() != &formatted)
+                .then(|| source.source().lines().count().min(formatted.lines().count()))
+        });
+    match mismatch {
+        None => std::process::ExitCode::SUCCESS,
+        Some(line) => {
+            eprintln!("{}:{}: file is not formatted", source.name(), line + 1);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
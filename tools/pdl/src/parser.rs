@@ -2,7 +2,9 @@ use codespan_reporting::diagnostic::Diagnostic;
 use codespan_reporting::files;
 use pest::iterators::{Pair, Pairs};
 use pest::{Parser, Token};
+use std::collections::HashSet;
 use std::iter::{Filter, Peekable};
+use std::path::{Path, PathBuf};
 
 pub mod ast {
     use serde::Serialize;
@@ -48,6 +50,8 @@ size_modifier = @{ "+" ~ intvalue }
 
 endianness_declaration = { "little_endian_packets" | "big_endian_packets" }
 
+import_declaration = { "import" ~ string }
+
 enum_tag = { identifier ~ "=" ~ integer }
 enum_tag_list = { enum_tag ~ ("," ~ enum_tag)* ~ ","? }
 enum_declaration = {
@@ -76,6 +80,7 @@ array_field = { identifier ~ ":" ~ (integer|identifier) ~
 }
 scalar_field = { identifier ~ ":" ~ integer }
 typedef_field = { identifier ~ ":" ~ identifier }
+optional_field = { identifier ~ ":" ~ (integer|identifier) ~ "if" ~ constraint }
 group_field = { identifier ~ ("{" ~ constraint_list ~ "}")? }
 
 field = _{
@@ -88,6 +93,7 @@ field = _{
     payload_field |
     fixed_field |
     reserved_field |
+    optional_field |
     array_field |
     scalar_field |
     typedef_field |
@@ -146,6 +152,7 @@ declaration = _{
 file = {
     SOI ~
     endianness_declaration ~
+    import_declaration* ~
     declaration* ~
     EOI
 }
@@ -402,6 +409,21 @@ fn parse_field(node: Node<'_>, context: &Context) -> Result<ast::Field, String>
                 let type_id = parse_identifier(&mut children)?;
                 crate::ast::FieldDesc::Typedef { id, type_id }
             }
+            Rule::optional_field => {
+                let id = parse_identifier(&mut children)?;
+                let (type_id, width) = parse_identifier_or_integer(&mut children)?;
+                let condition =
+                    parse_constraint(expect(&mut children, Rule::constraint)?, context)?;
+                match (type_id, width) {
+                    (None, Some(width)) => {
+                        crate::ast::FieldDesc::OptionalScalar { id, width, condition }
+                    }
+                    (Some(type_id), None) => {
+                        crate::ast::FieldDesc::OptionalTypedef { id, type_id, condition }
+                    }
+                    _ => unreachable!(),
+                }
+            }
             Rule::group_field => {
                 let group_id = parse_identifier(&mut children)?;
                 let constraints = parse_constraint_list_opt(&mut children, context)?;
@@ -453,6 +475,10 @@ fn parse_toplevel(root: Node<'_>, context: &Context) -> Result<ast::File, String
         let rule = node.as_rule();
         match rule {
             Rule::endianness_declaration => file.endianness = parse_endianness(node, context)?,
+            Rule::import_declaration => {
+                let path = parse_string(&mut node.children())?;
+                file.imports.push(crate::ast::Import { loc, path })
+            }
             Rule::checksum_declaration => {
                 let mut children = node.children();
                 let id = parse_identifier(&mut children)?;
@@ -512,7 +538,21 @@ fn parse_toplevel(root: Node<'_>, context: &Context) -> Result<ast::File, String
                 file.declarations
                     .push(crate::ast::Decl::new(loc, crate::ast::DeclDesc::Group { id, fields }))
             }
-            Rule::test_declaration => {}
+            Rule::test_declaration => {
+                let mut children = node.children();
+                let type_id = parse_identifier(&mut children)?;
+                let test_cases = children
+                    .map(|n| {
+                        let loc = n.as_loc(context);
+                        let input = parse_string(&mut n.children())?;
+                        Ok(crate::ast::TestCase { loc, input })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                file.declarations.push(crate::ast::Decl::new(
+                    loc,
+                    crate::ast::DeclDesc::Test { type_id, test_cases },
+                ))
+            }
             Rule::EOI => (),
             _ => unreachable!(),
         }
@@ -557,6 +597,96 @@ pub fn parse_file(
     parse_inline(sources, name, source)
 }
 
+/// Parse the files imported by a source file, recursively.
+///
+/// Import paths are relative to the directory of the importing file.
+/// Returns a copy of `file` where the imported declarations are listed
+/// before the declarations of the file itself. The declarations keep
+/// their source location, which tells imported declarations apart.
+pub fn parse_imports(
+    sources: &mut crate::ast::SourceDatabase,
+    file: &ast::File,
+) -> Result<ast::File, Diagnostic<crate::ast::FileId>> {
+    fn copy_decl(decl: &ast::Decl) -> ast::Decl {
+        decl.annotate((), |fields| fields.iter().map(|field| field.annotate(())).collect())
+    }
+
+    fn visit(
+        sources: &mut crate::ast::SourceDatabase,
+        file: &ast::File,
+        endianness: &crate::ast::Endianness,
+        stack: &mut Vec<PathBuf>,
+        visited: &mut HashSet<PathBuf>,
+        declarations: &mut Vec<ast::Decl>,
+    ) -> Result<(), Diagnostic<crate::ast::FileId>> {
+        let name = sources.get(file.file).expect("could not read source").name().clone();
+        let dir = Path::new(&name).parent().unwrap_or_else(|| Path::new(""));
+        for import in &file.imports {
+            let path = dir.join(&import.path);
+            let canonical_path = std::fs::canonicalize(&path).map_err(|e| {
+                Diagnostic::error()
+                    .with_message(format!(
+                        "failed to read imported file '{}': {}",
+                        path.display(),
+                        e
+                    ))
+                    .with_labels(vec![import.loc.primary()])
+            })?;
+            if let Some(pos) = stack.iter().position(|other| *other == canonical_path) {
+                let cycle = stack[pos..]
+                    .iter()
+                    .chain(std::iter::once(&canonical_path))
+                    .map(|path| format!("'{}'", path.display()))
+                    .collect::<Vec<_>>();
+                return Err(Diagnostic::error()
+                    .with_message(format!("import cycle detected for '{}'", import.path))
+                    .with_labels(vec![import.loc.primary()])
+                    .with_notes(vec![format!("hint: {}", cycle.join(" imports "))]));
+            }
+            if !visited.insert(canonical_path.clone()) {
+                continue;
+            }
+
+            let imported = parse_file(sources, path.to_string_lossy().into_owned())?;
+            if imported.endianness.value != endianness.value {
+                return Err(Diagnostic::error()
+                    .with_message(format!(
+                        "imported file '{}' has a different endianness",
+                        import.path
+                    ))
+                    .with_labels(vec![
+                        import.loc.primary(),
+                        imported.endianness.loc.secondary(),
+                        endianness.loc.secondary(),
+                    ]));
+            }
+
+            stack.push(canonical_path);
+            visit(sources, &imported, endianness, stack, visited, declarations)?;
+            stack.pop();
+            declarations.extend(imported.declarations.iter().map(copy_decl));
+        }
+        Ok(())
+    }
+
+    let name = sources.get(file.file).expect("could not read source").name();
+    let path = std::fs::canonicalize(name).unwrap_or_else(|_| PathBuf::from(name));
+    let mut stack = vec![path.clone()];
+    let mut visited = HashSet::from([path]);
+    let mut declarations = vec![];
+    visit(sources, file, &file.endianness, &mut stack, &mut visited, &mut declarations)?;
+    declarations.extend(file.declarations.iter().map(copy_decl));
+
+    Ok(crate::ast::File {
+        version: file.version.clone(),
+        file: file.file,
+        comments: file.comments.clone(),
+        endianness: file.endianness,
+        imports: file.imports.clone(),
+        declarations,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -597,4 +727,67 @@ mod test {
         assert_eq!(parse_string(&mut pairs).as_deref(), Ok(r#""test""#));
         assert_eq!(pairs.next(), None, "pairs is empty");
     }
+
+    /// Write the source files to a new temporary directory.
+    fn write_sources(sources: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, source) in sources {
+            std::fs::write(dir.path().join(name), source).unwrap();
+        }
+        dir
+    }
+
+    fn parse_with_imports(
+        dir: &tempfile::TempDir,
+        name: &str,
+    ) -> Result<ast::File, Diagnostic<crate::ast::FileId>> {
+        let mut db = crate::ast::SourceDatabase::new();
+        let path = dir.path().join(name).to_string_lossy().into_owned();
+        let file = parse_file(&mut db, path)?;
+        parse_imports(&mut db, &file)
+    }
+
+    #[test]
+    fn test_parse_imports() {
+        let dir = write_sources(&[
+            ("a.pdl", "little_endian_packets import \"b.pdl\" import \"c.pdl\" packet A { b: B }"),
+            ("b.pdl", "little_endian_packets import \"c.pdl\" struct B { c: C }"),
+            ("c.pdl", "little_endian_packets struct C { x: 8 }"),
+        ]);
+        let file = parse_with_imports(&dir, "a.pdl").unwrap();
+        let ids = file.declarations.iter().map(|decl| decl.id().unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["C", "B", "A"]);
+        let files = file.declarations.iter().map(|decl| decl.loc.file).collect::<Vec<_>>();
+        assert_ne!(files[0], files[1]);
+        assert_ne!(files[0], file.file);
+        assert_ne!(files[1], file.file);
+        assert_eq!(files[2], file.file);
+    }
+
+    #[test]
+    fn test_parse_imports_cycle() {
+        let dir = write_sources(&[
+            ("a.pdl", "little_endian_packets import \"b.pdl\""),
+            ("b.pdl", "little_endian_packets import \"a.pdl\""),
+        ]);
+        let err = parse_with_imports(&dir, "a.pdl").unwrap_err();
+        assert!(err.message.contains("import cycle"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_parse_imports_missing_file() {
+        let dir = write_sources(&[("a.pdl", "little_endian_packets import \"b.pdl\"")]);
+        let err = parse_with_imports(&dir, "a.pdl").unwrap_err();
+        assert!(err.message.contains("failed to read imported file"), "{}", err.message);
+    }
+
+    #[test]
+    fn test_parse_imports_endianness() {
+        let dir = write_sources(&[
+            ("a.pdl", "little_endian_packets import \"b.pdl\""),
+            ("b.pdl", "big_endian_packets"),
+        ]);
+        let err = parse_with_imports(&dir, "a.pdl").unwrap_err();
+        assert!(err.message.contains("different endianness"), "{}", err.message);
+    }
 }
@@ -27,6 +27,8 @@ pub enum Error {
     ImpossibleStructError,
     #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
     InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+    #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+    InvalidChecksum { obj: String, expected: u64, actual: u64 },
 }
 
 #[derive(Debug, Error)]
@@ -39,18 +41,14 @@ pub trait Packet {
 }
 
 #[derive(Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FooData {
     x: [u32; 5],
 }
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Foo {
-    #[cfg_attr(feature = "serde", serde(flatten))]
     foo: Arc<FooData>,
 }
-#[derive(Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone)]
 pub struct FooBuilder {
     pub x: [u32; 5],
 }
@@ -111,7 +109,7 @@ impl Foo {
         }
         Ok(packet)
     }
-    fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
         let data = FooData::parse(&mut bytes)?;
         Ok(Self::new(Arc::new(data)).unwrap())
     }
@@ -121,7 +119,7 @@ impl Foo {
     pub fn get_x(&self) -> &[u32; 5] {
         &self.foo.as_ref().x
     }
-    fn write_to(&self, buffer: &mut BytesMut) {
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
         self.foo.write_to(buffer)
     }
     pub fn get_size(&self) -> usize {
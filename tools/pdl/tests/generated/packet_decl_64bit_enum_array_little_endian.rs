@@ -27,6 +27,8 @@ pub enum Error {
     ImpossibleStructError,
     #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
     InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+    #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+    InvalidChecksum { obj: String, expected: u64, actual: u64 },
 }
 
 #[derive(Debug, Error)]
@@ -44,57 +46,16 @@ pub enum Foo {
     FooBar = 0x1,
     Baz = 0x2,
 }
-#[cfg(feature = "serde")]
-impl serde::Serialize for Foo {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_u64(*self as u64)
-    }
-}
-#[cfg(feature = "serde")]
-struct FooVisitor;
-#[cfg(feature = "serde")]
-impl<'de> serde::de::Visitor<'de> for FooVisitor {
-    type Value = Foo;
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a valid discriminant")
-    }
-    fn visit_u64<E>(self, value: u64) -> std::result::Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        match value {
-            0x1 => Ok(Foo::FooBar),
-            0x2 => Ok(Foo::Baz),
-            _ => Err(E::custom(format!("invalid discriminant: {value}"))),
-        }
-    }
-}
-#[cfg(feature = "serde")]
-impl<'de> serde::Deserialize<'de> for Foo {
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        deserializer.deserialize_u64(FooVisitor)
-    }
-}
 
 #[derive(Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BarData {
     x: [Foo; 7],
 }
 #[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bar {
-    #[cfg_attr(feature = "serde", serde(flatten))]
     bar: Arc<BarData>,
 }
-#[derive(Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone)]
 pub struct BarBuilder {
     pub x: [Foo; 7],
 }
@@ -163,7 +124,7 @@ impl Bar {
         }
         Ok(packet)
     }
-    fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
         let data = BarData::parse(&mut bytes)?;
         Ok(Self::new(Arc::new(data)).unwrap())
     }
@@ -173,7 +134,7 @@ impl Bar {
     pub fn get_x(&self) -> &[Foo; 7] {
         &self.bar.as_ref().x
     }
-    fn write_to(&self, buffer: &mut BytesMut) {
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
         self.bar.write_to(buffer)
     }
     pub fn get_size(&self) -> usize {
@@ -0,0 +1,261 @@
+// @generated rust packets from test
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::cell::Cell;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Packet parsing failed")]
+    InvalidPacketError,
+    #[error("{field} was {value:x}, which is not known")]
+    ConstraintOutOfBounds { field: String, value: u64 },
+    #[error("Got {actual:x}, expected {expected:x}")]
+    InvalidFixedValue { expected: u64, actual: u64 },
+    #[error("when parsing {obj} needed length of {wanted} but got {got}")]
+    InvalidLengthError { obj: String, wanted: usize, got: usize },
+    #[error("array size ({array} bytes) is not a multiple of the element size ({element} bytes)")]
+    InvalidArraySize { array: usize, element: usize },
+    #[error("Due to size restrictions a struct could not be parsed.")]
+    ImpossibleStructError,
+    #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
+    InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+    #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+    InvalidChecksum { obj: String, expected: u64, actual: u64 },
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct TryFromError(&'static str);
+
+pub trait Packet {
+    fn to_bytes(self) -> Bytes;
+    fn to_vec(self) -> Vec<u8>;
+}
+
+#[derive(Debug)]
+pub struct FooData {
+    a: Vec<u16>,
+}
+#[derive(Debug, Clone)]
+pub struct Foo {
+    foo: Arc<FooData>,
+}
+#[derive(Debug, Clone)]
+pub struct FooBuilder {
+    pub a: Vec<u16>,
+}
+impl FooData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 5
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 5 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 5,
+                got: bytes.get().remaining(),
+            });
+        }
+        let a_count = bytes.get_mut().get_uint(5) as usize;
+        if bytes.get().remaining() < a_count {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: a_count,
+                got: bytes.get().remaining(),
+            });
+        }
+        let a = (0..a_count)
+            .map(|_| Ok::<_, Error>(bytes.get_mut().get_u16()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { a })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        if self.a.len() > 0xffffffffffu64 {
+            panic!("Invalid length for {}::{}: {} > {}", "Foo", "a", self.a.len(), 0xffffffffffu64);
+        }
+        buffer.put_uint(self.a.len() as u64, 5);
+        for elem in &self.a {
+            buffer.put_u16(*elem);
+        }
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        5 + self.a.len() * 2
+    }
+}
+impl Packet for Foo {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Foo> for Bytes {
+    fn from(packet: Foo) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Foo> for Vec<u8> {
+    fn from(packet: Foo) -> Self {
+        packet.to_vec()
+    }
+}
+impl Foo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { foo })
+    }
+    pub fn get_a(&self) -> &Vec<u16> {
+        &self.foo.as_ref().a
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.foo.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl FooBuilder {
+    pub fn build(self) -> Foo {
+        let foo = Arc::new(FooData { a: self.a });
+        Foo::new(foo).unwrap()
+    }
+}
+impl From<FooBuilder> for Foo {
+    fn from(builder: FooBuilder) -> Foo {
+        builder.build().into()
+    }
+}
+
+#[derive(Debug)]
+pub struct BarData {
+    a: Vec<Foo>,
+}
+#[derive(Debug, Clone)]
+pub struct Bar {
+    bar: Arc<BarData>,
+}
+#[derive(Debug, Clone)]
+pub struct BarBuilder {
+    pub a: Vec<Foo>,
+}
+impl BarData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 128
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 128 {
+            return Err(Error::InvalidLengthError {
+                obj: "Bar".to_string(),
+                wanted: 128,
+                got: bytes.get().remaining(),
+            });
+        }
+        let (head, a_padding) = bytes.get().split_at(128);
+        bytes.replace(head);
+        let mut a = Vec::new();
+        while !bytes.get().is_empty() {
+            a.push(Foo::parse_inner(bytes)?);
+        }
+        bytes.replace(a_padding);
+        Ok(Self { a })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        let current_size = buffer.len();
+        for elem in &self.a {
+            elem.write_to(buffer);
+        }
+        let array_size = buffer.len() - current_size;
+        if array_size > 128 {
+            panic!("Invalid length for {}::{}: {} > {}", "Bar", "a", array_size, 128);
+        }
+        buffer.put_bytes(0, 128 - array_size);
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        128
+    }
+}
+impl Packet for Bar {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.bar.get_size());
+        self.bar.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Bar> for Bytes {
+    fn from(packet: Bar) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Bar> for Vec<u8> {
+    fn from(packet: Bar) -> Self {
+        packet.to_vec()
+    }
+}
+impl Bar {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = BarData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(bar: Arc<BarData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { bar })
+    }
+    pub fn get_a(&self) -> &Vec<Foo> {
+        &self.bar.as_ref().a
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.bar.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.bar.get_size()
+    }
+}
+impl BarBuilder {
+    pub fn build(self) -> Bar {
+        let bar = Arc::new(BarData { a: self.a });
+        Bar::new(bar).unwrap()
+    }
+}
+impl From<BarBuilder> for Bar {
+    fn from(builder: BarBuilder) -> Bar {
+        builder.build().into()
+    }
+}
@@ -0,0 +1,303 @@
+// @generated rust packets from test
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::cell::Cell;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Packet parsing failed")]
+    InvalidPacketError,
+    #[error("{field} was {value:x}, which is not known")]
+    ConstraintOutOfBounds { field: String, value: u64 },
+    #[error("Got {actual:x}, expected {expected:x}")]
+    InvalidFixedValue { expected: u64, actual: u64 },
+    #[error("when parsing {obj} needed length of {wanted} but got {got}")]
+    InvalidLengthError { obj: String, wanted: usize, got: usize },
+    #[error("array size ({array} bytes) is not a multiple of the element size ({element} bytes)")]
+    InvalidArraySize { array: usize, element: usize },
+    #[error("Due to size restrictions a struct could not be parsed.")]
+    ImpossibleStructError,
+    #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
+    InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+    #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+    InvalidChecksum { obj: String, expected: u64, actual: u64 },
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct TryFromError(&'static str);
+
+pub trait Packet {
+    fn to_bytes(self) -> Bytes;
+    fn to_vec(self) -> Vec<u8>;
+}
+
+/// Checksum computed over the bytes between a `_checksum_start_` field
+/// and the checksum value field.
+///
+/// `checksum` declarations name the type implementing this trait, either
+/// one of the implementations below or a type supplied alongside the
+/// generated code.
+pub trait ChecksumAlgorithm: Default {
+    /// Unsigned integer type of the checksum value.
+    type Value: Copy + PartialEq + Into<u64>;
+
+    /// Add `bytes` to the checksum.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Return the checksum of the bytes added so far.
+    fn get_checksum(&self) -> Self::Value;
+}
+
+/// CRC-16 with polynomial x^16 + x^15 + x^2 + 1 and initial value 0,
+/// used by the L2CAP Frame Check Sequence (Core Specification Vol 3,
+/// Part A, 3.3.5).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Crc16(u16);
+
+impl ChecksumAlgorithm for Crc16 {
+    type Value = u16;
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u16;
+            for _ in 0..8 {
+                self.0 = if self.0 & 1 != 0 { (self.0 >> 1) ^ 0xa001 } else { self.0 >> 1 };
+            }
+        }
+    }
+
+    fn get_checksum(&self) -> u16 {
+        self.0
+    }
+}
+
+/// Sum of the bytes, modulo 2^8.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sum8(u8);
+
+impl ChecksumAlgorithm for Sum8 {
+    type Value = u8;
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.wrapping_add(*byte);
+        }
+    }
+
+    fn get_checksum(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Sum of the bytes, modulo 2^16.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sum16(u16);
+
+impl ChecksumAlgorithm for Sum16 {
+    type Value = u16;
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.wrapping_add(*byte as u16);
+        }
+    }
+
+    fn get_checksum(&self) -> u16 {
+        self.0
+    }
+}
+
+pub type Fcs = Crc16;
+
+#[derive(Debug)]
+pub enum FooDataChild {
+    Payload(Bytes),
+    None,
+}
+impl FooDataChild {
+    fn get_total_size(&self) -> usize {
+        match self {
+            FooDataChild::Payload(bytes) => bytes.len(),
+            FooDataChild::None => 0,
+        }
+    }
+}
+#[derive(Debug)]
+pub enum FooChild {
+    Payload(Bytes),
+    None,
+}
+#[derive(Debug)]
+pub struct FooData {
+    a: u8,
+    child: FooDataChild,
+}
+#[derive(Debug, Clone)]
+pub struct Foo {
+    foo: Arc<FooData>,
+}
+#[derive(Debug, Clone)]
+pub struct FooBuilder {
+    pub a: u8,
+    pub payload: Option<Bytes>,
+}
+impl FooData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 4
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let fcs_start = bytes.get();
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let a = bytes.get_mut().get_u8();
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let payload_size = bytes.get_mut().get_u8() as usize;
+        if bytes.get().remaining() < payload_size {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: payload_size,
+                got: bytes.get().remaining(),
+            });
+        }
+        let payload = &bytes.get()[..payload_size];
+        bytes.get_mut().advance(payload_size);
+        if bytes.get().remaining() < 2 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 2,
+                got: bytes.get().remaining(),
+            });
+        }
+        let mut checksum = Fcs::default();
+        checksum.update(&fcs_start[..fcs_start.len() - bytes.get().len()]);
+        let fcs = bytes.get_mut().get_u16();
+        if fcs != checksum.get_checksum() {
+            return Err(Error::InvalidChecksum {
+                obj: "Foo".to_string(),
+                expected: checksum.get_checksum().into(),
+                actual: fcs.into(),
+            });
+        }
+        let child = match () {
+            _ if !payload.is_empty() => FooDataChild::Payload(Bytes::copy_from_slice(payload)),
+            _ => FooDataChild::None,
+        };
+        Ok(Self { a, child })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        let fcs_start = buffer.len();
+        buffer.put_u8(self.a);
+        if self.child.get_total_size() > 0xff {
+            panic!(
+                "Invalid length for {}::{}: {} > {}",
+                "Foo",
+                "_payload_",
+                self.child.get_total_size(),
+                0xff
+            );
+        }
+        buffer.put_u8(self.child.get_total_size() as u8);
+        match &self.child {
+            FooDataChild::Payload(payload) => buffer.put_slice(payload),
+            FooDataChild::None => {}
+        }
+        let mut checksum = Fcs::default();
+        checksum.update(&buffer[fcs_start..]);
+        buffer.put_u16(checksum.get_checksum());
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        4 + self.child.get_total_size()
+    }
+}
+impl Packet for Foo {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Foo> for Bytes {
+    fn from(packet: Foo) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Foo> for Vec<u8> {
+    fn from(packet: Foo) -> Self {
+        packet.to_vec()
+    }
+}
+impl Foo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { foo })
+    }
+    pub fn get_a(&self) -> u8 {
+        self.foo.as_ref().a
+    }
+    pub fn get_payload(&self) -> &[u8] {
+        match &self.foo.child {
+            FooDataChild::Payload(bytes) => &bytes,
+            FooDataChild::None => &[],
+        }
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.foo.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl FooBuilder {
+    pub fn build(self) -> Foo {
+        let foo = Arc::new(FooData {
+            a: self.a,
+            child: match self.payload {
+                None => FooDataChild::None,
+                Some(bytes) => FooDataChild::Payload(bytes),
+            },
+        });
+        Foo::new(foo).unwrap()
+    }
+}
+impl From<FooBuilder> for Foo {
+    fn from(builder: FooBuilder) -> Foo {
+        builder.build().into()
+    }
+}
@@ -0,0 +1,637 @@
+// @generated rust packets from test
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::cell::Cell;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Packet parsing failed")]
+    InvalidPacketError,
+    #[error("{field} was {value:x}, which is not known")]
+    ConstraintOutOfBounds { field: String, value: u64 },
+    #[error("Got {actual:x}, expected {expected:x}")]
+    InvalidFixedValue { expected: u64, actual: u64 },
+    #[error("when parsing {obj} needed length of {wanted} but got {got}")]
+    InvalidLengthError { obj: String, wanted: usize, got: usize },
+    #[error("array size ({array} bytes) is not a multiple of the element size ({element} bytes)")]
+    InvalidArraySize { array: usize, element: usize },
+    #[error("Due to size restrictions a struct could not be parsed.")]
+    ImpossibleStructError,
+    #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
+    InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+    #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+    InvalidChecksum { obj: String, expected: u64, actual: u64 },
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct TryFromError(&'static str);
+
+pub trait Packet {
+    fn to_bytes(self) -> Bytes;
+    fn to_vec(self) -> Vec<u8>;
+}
+
+#[derive(FromPrimitive, ToPrimitive, Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[repr(u64)]
+pub enum Enum8 {
+    A = 0x1,
+    B = 0x2,
+}
+impl serde::Serialize for Enum8 {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u64(*self as u64)
+    }
+}
+struct Enum8Visitor;
+impl<'de> serde::de::Visitor<'de> for Enum8Visitor {
+    type Value = Enum8;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a valid discriminant")
+    }
+    fn visit_u64<E>(self, value: u64) -> std::result::Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        match value {
+            0x1 => Ok(Enum8::A),
+            0x2 => Ok(Enum8::B),
+            _ => Err(E::custom(format!("invalid discriminant: {value}"))),
+        }
+    }
+}
+impl<'de> serde::Deserialize<'de> for Enum8 {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_u64(Enum8Visitor)
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Enum8 {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[Enum8::A, Enum8::B])?)
+    }
+}
+
+#[derive(Debug)]
+pub struct ElemData {
+    x: u8,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+#[serde(from = "ElemBuilder", into = "ElemBuilder")]
+pub struct Elem {
+    elem: Arc<ElemData>,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+pub struct ElemBuilder {
+    pub x: u8,
+}
+impl ElemData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 1
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Elem".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let chunk = bytes.get_mut().get_u8();
+        let x = (chunk & 0xf);
+        Ok(Self { x })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        if self.x > 0xf {
+            panic!("Invalid value for {}::{}: {} > {}", "Elem", "x", self.x, 0xf);
+        }
+        buffer.put_u8(self.x);
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        1
+    }
+}
+impl Packet for Elem {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.elem.get_size());
+        self.elem.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Elem> for Bytes {
+    fn from(packet: Elem) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Elem> for Vec<u8> {
+    fn from(packet: Elem) -> Self {
+        packet.to_vec()
+    }
+}
+impl Elem {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = ElemData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(elem: Arc<ElemData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { elem })
+    }
+    pub fn get_x(&self) -> u8 {
+        self.elem.as_ref().x
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.elem.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.elem.get_size()
+    }
+}
+impl ElemBuilder {
+    pub fn build(self) -> Elem {
+        let elem = Arc::new(ElemData { x: self.x });
+        Elem::new(elem).unwrap()
+    }
+}
+impl From<ElemBuilder> for Elem {
+    fn from(builder: ElemBuilder) -> Elem {
+        builder.build().into()
+    }
+}
+impl From<Elem> for ElemBuilder {
+    fn from(packet: Elem) -> ElemBuilder {
+        ElemBuilder { x: packet.get_x() }
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for ElemBuilder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let x: u8 = u.int_in_range(0..=0xf)?;
+        Ok(ElemBuilder { x })
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Elem {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(u.arbitrary::<ElemBuilder>()?.build())
+    }
+}
+
+#[derive(Debug)]
+pub enum FooDataChild {
+    Bar(Arc<BarData>),
+    Payload(Bytes),
+    None,
+}
+impl FooDataChild {
+    fn get_total_size(&self) -> usize {
+        match self {
+            FooDataChild::Bar(value) => value.get_total_size(),
+            FooDataChild::Payload(bytes) => bytes.len(),
+            FooDataChild::None => 0,
+        }
+    }
+}
+#[derive(Debug, serde :: Serialize, serde :: Deserialize)]
+pub enum FooChild {
+    Bar(Bar),
+    Payload(Bytes),
+    None,
+}
+#[derive(Debug)]
+pub struct FooData {
+    a: Enum8,
+    b: u8,
+    c: Vec<Elem>,
+    child: FooDataChild,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+#[serde(from = "FooBuilder", into = "FooBuilder")]
+pub struct Foo {
+    foo: Arc<FooData>,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+pub struct FooBuilder {
+    pub a: Enum8,
+    pub b: u8,
+    pub c: Vec<Elem>,
+    pub payload: Option<Bytes>,
+}
+impl FooData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 4
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let a = Enum8::from_u8(bytes.get_mut().get_u8()).unwrap();
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let b = bytes.get_mut().get_u8();
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let c_count = bytes.get_mut().get_u8() as usize;
+        if bytes.get().remaining() < c_count {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: c_count,
+                got: bytes.get().remaining(),
+            });
+        }
+        let c = (0..c_count).map(|_| Elem::parse_inner(bytes)).collect::<Result<Vec<_>>>()?;
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let payload_size = bytes.get_mut().get_u8() as usize;
+        if bytes.get().remaining() < payload_size {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: payload_size,
+                got: bytes.get().remaining(),
+            });
+        }
+        let payload = &bytes.get()[..payload_size];
+        bytes.get_mut().advance(payload_size);
+        let child = match (a) {
+            (Enum8::A) => {
+                let mut cell = Cell::new(payload);
+                let child_data = BarData::parse(&mut cell)?;
+                if !cell.get().is_empty() {
+                    return Err(Error::InvalidPacketError);
+                }
+                FooDataChild::Bar(Arc::new(child_data))
+            }
+            _ if !payload.is_empty() => FooDataChild::Payload(Bytes::copy_from_slice(payload)),
+            _ => FooDataChild::None,
+        };
+        Ok(Self { a, b, c, child })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        buffer.put_u8(self.a.to_u8().unwrap());
+        buffer.put_u8(self.b);
+        buffer.put_u8(self.c.len() as u8);
+        for elem in &self.c {
+            elem.write_to(buffer);
+        }
+        if self.child.get_total_size() > 0xff {
+            panic!(
+                "Invalid length for {}::{}: {} > {}",
+                "Foo",
+                "_payload_",
+                self.child.get_total_size(),
+                0xff
+            );
+        }
+        buffer.put_u8(self.child.get_total_size() as u8);
+        match &self.child {
+            FooDataChild::Bar(child) => child.write_to(buffer),
+            FooDataChild::Payload(payload) => buffer.put_slice(payload),
+            FooDataChild::None => {}
+        }
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        4 + self.c.iter().map(|elem| elem.get_size()).sum::<usize>() + self.child.get_total_size()
+    }
+}
+impl Packet for Foo {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Foo> for Bytes {
+    fn from(packet: Foo) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Foo> for Vec<u8> {
+    fn from(packet: Foo) -> Self {
+        packet.to_vec()
+    }
+}
+impl Foo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { foo })
+    }
+    pub fn get_a(&self) -> Enum8 {
+        self.foo.as_ref().a
+    }
+    pub fn get_b(&self) -> u8 {
+        self.foo.as_ref().b
+    }
+    pub fn get_c(&self) -> &Vec<Elem> {
+        &self.foo.as_ref().c
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.foo.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl FooBuilder {
+    pub fn build(self) -> Foo {
+        let foo = Arc::new(FooData {
+            a: self.a,
+            b: self.b,
+            c: self.c,
+            child: match self.payload {
+                None => FooDataChild::None,
+                Some(bytes) => FooDataChild::Payload(bytes),
+            },
+        });
+        Foo::new(foo).unwrap()
+    }
+}
+impl From<FooBuilder> for Foo {
+    fn from(builder: FooBuilder) -> Foo {
+        builder.build().into()
+    }
+}
+impl From<Foo> for FooBuilder {
+    fn from(packet: Foo) -> FooBuilder {
+        FooBuilder {
+            a: packet.get_a(),
+            b: packet.get_b(),
+            c: packet.get_c().clone(),
+            payload: match &packet.foo.child {
+                FooDataChild::Bar(child) => {
+                    let mut buffer = BytesMut::with_capacity(child.get_total_size());
+                    child.write_to(&mut buffer);
+                    Some(buffer.freeze())
+                }
+                FooDataChild::Payload(bytes) => Some(bytes.clone()),
+                FooDataChild::None => None,
+            },
+        }
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for FooBuilder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let a: Enum8 = u.arbitrary()?;
+        let b: u8 = u.int_in_range(0..=0xff)?;
+        let c: Vec<Elem> = {
+            let mut array = Vec::new();
+            while array.len() < 0xff && u.arbitrary()? {
+                let elem = u.arbitrary::<Elem>()?;
+                array.push(elem);
+            }
+            array
+        };
+        let mut builder = FooBuilder { a, b, c, payload: None };
+        if matches!((builder.a), (Enum8::A)) {
+            return Err(arbitrary::Error::IncorrectFormat);
+        }
+        let size = {
+            let packet = builder.clone().build();
+            let max_payload_size = [(0xff as usize)
+                .checked_sub(packet.foo.child.get_total_size())
+                .ok_or(arbitrary::Error::IncorrectFormat)?]
+            .into_iter()
+            .min()
+            .unwrap();
+            u.arbitrary_len::<u8>()?.min(max_payload_size)
+        };
+        builder.payload = Some(Bytes::copy_from_slice(u.bytes(size)?));
+        Ok(builder)
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Foo {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        match u.int_in_range(0..=1usize)? {
+            0 => return Ok(u.arbitrary::<Bar>()?.into()),
+            _ => (),
+        }
+        Ok(u.arbitrary::<FooBuilder>()?.build())
+    }
+}
+
+#[derive(Debug)]
+pub struct BarData {
+    x: [u8; 2],
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+#[serde(from = "BarBuilder", into = "BarBuilder")]
+pub struct Bar {
+    foo: Arc<FooData>,
+    bar: Arc<BarData>,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+pub struct BarBuilder {
+    pub b: u8,
+    pub c: Vec<Elem>,
+    pub x: [u8; 2],
+}
+impl BarData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 2
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 2 {
+            return Err(Error::InvalidLengthError {
+                obj: "Bar".to_string(),
+                wanted: 2,
+                got: bytes.get().remaining(),
+            });
+        }
+        let x = std::array::from_fn(|_| Ok::<_, Error>(bytes.get_mut().get_u8()).unwrap());
+        Ok(Self { x })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        for elem in &self.x {
+            buffer.put_u8(*elem);
+        }
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        2
+    }
+}
+impl Packet for Bar {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Bar> for Bytes {
+    fn from(packet: Bar) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Bar> for Vec<u8> {
+    fn from(packet: Bar) -> Self {
+        packet.to_vec()
+    }
+}
+impl From<Bar> for Foo {
+    fn from(packet: Bar) -> Foo {
+        Foo::new(packet.foo).unwrap()
+    }
+}
+impl TryFrom<Foo> for Bar {
+    type Error = TryFromError;
+    fn try_from(packet: Foo) -> std::result::Result<Bar, TryFromError> {
+        Bar::new(packet.foo).map_err(TryFromError)
+    }
+}
+impl Bar {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        let bar = match &foo.child {
+            FooDataChild::Bar(value) => value.clone(),
+            _ => return Err("Could not parse data, wrong child type"),
+        };
+        Ok(Self { foo, bar })
+    }
+    pub fn get_a(&self) -> Enum8 {
+        self.foo.as_ref().a
+    }
+    pub fn get_b(&self) -> u8 {
+        self.foo.as_ref().b
+    }
+    pub fn get_c(&self) -> &Vec<Elem> {
+        &self.foo.as_ref().c
+    }
+    pub fn get_x(&self) -> &[u8; 2] {
+        &self.bar.as_ref().x
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.bar.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl BarBuilder {
+    pub fn build(self) -> Bar {
+        let bar = Arc::new(BarData { x: self.x });
+        let foo =
+            Arc::new(FooData { a: Enum8::A, b: self.b, c: self.c, child: FooDataChild::Bar(bar) });
+        Bar::new(foo).unwrap()
+    }
+}
+impl From<BarBuilder> for Foo {
+    fn from(builder: BarBuilder) -> Foo {
+        builder.build().into()
+    }
+}
+impl From<BarBuilder> for Bar {
+    fn from(builder: BarBuilder) -> Bar {
+        builder.build().into()
+    }
+}
+impl From<Bar> for BarBuilder {
+    fn from(packet: Bar) -> BarBuilder {
+        BarBuilder { b: packet.get_b(), c: packet.get_c().clone(), x: packet.get_x().clone() }
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for BarBuilder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let b: u8 = u.int_in_range(0..=0xff)?;
+        let c: Vec<Elem> = {
+            let mut array = Vec::new();
+            while array.len() < 0xff && u.arbitrary()? {
+                let elem = u.arbitrary::<Elem>()?;
+                array.push(elem);
+            }
+            array
+        };
+        let x: [u8; 2] = {
+            let mut array = Vec::with_capacity(2);
+            for _ in 0..2 {
+                array.push(u.int_in_range(0..=0xff)?);
+            }
+            array.try_into().expect("Invalid length for x")
+        };
+        let builder = BarBuilder { b, c, x };
+        let packet = builder.clone().build();
+        (0xff as usize)
+            .checked_sub(packet.foo.child.get_total_size())
+            .ok_or(arbitrary::Error::IncorrectFormat)?;
+        Ok(builder)
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Bar {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(u.arbitrary::<BarBuilder>()?.build())
+    }
+}
@@ -0,0 +1,680 @@
+// @generated rust packets from test
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::cell::Cell;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Packet parsing failed")]
+    InvalidPacketError,
+    #[error("{field} was {value:x}, which is not known")]
+    ConstraintOutOfBounds { field: String, value: u64 },
+    #[error("Got {actual:x}, expected {expected:x}")]
+    InvalidFixedValue { expected: u64, actual: u64 },
+    #[error("when parsing {obj} needed length of {wanted} but got {got}")]
+    InvalidLengthError { obj: String, wanted: usize, got: usize },
+    #[error("array size ({array} bytes) is not a multiple of the element size ({element} bytes)")]
+    InvalidArraySize { array: usize, element: usize },
+    #[error("Due to size restrictions a struct could not be parsed.")]
+    ImpossibleStructError,
+    #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
+    InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+    #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+    InvalidChecksum { obj: String, expected: u64, actual: u64 },
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct TryFromError(&'static str);
+
+pub trait Packet {
+    fn to_bytes(self) -> Bytes;
+    fn to_vec(self) -> Vec<u8>;
+}
+
+#[derive(FromPrimitive, ToPrimitive, Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[repr(u64)]
+pub enum Enum16 {
+    A = 0x1,
+    B = 0x2,
+}
+impl serde::Serialize for Enum16 {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u64(*self as u64)
+    }
+}
+struct Enum16Visitor;
+impl<'de> serde::de::Visitor<'de> for Enum16Visitor {
+    type Value = Enum16;
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a valid discriminant")
+    }
+    fn visit_u64<E>(self, value: u64) -> std::result::Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        match value {
+            0x1 => Ok(Enum16::A),
+            0x2 => Ok(Enum16::B),
+            _ => Err(E::custom(format!("invalid discriminant: {value}"))),
+        }
+    }
+}
+impl<'de> serde::Deserialize<'de> for Enum16 {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_u64(Enum16Visitor)
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Enum16 {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[Enum16::A, Enum16::B])?)
+    }
+}
+
+#[derive(Debug)]
+pub struct ElemData {
+    x: u8,
+    y: Vec<u8>,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+#[serde(from = "ElemBuilder", into = "ElemBuilder")]
+pub struct Elem {
+    elem: Arc<ElemData>,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+pub struct ElemBuilder {
+    pub x: u8,
+    pub y: Vec<u8>,
+}
+impl ElemData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 1
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Elem".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let x = bytes.get_mut().get_u8();
+        let mut y = Vec::with_capacity(bytes.get().remaining());
+        for _ in 0..bytes.get().remaining() {
+            y.push(Ok::<_, Error>(bytes.get_mut().get_u8())?);
+        }
+        Ok(Self { x, y })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        buffer.put_u8(self.x);
+        for elem in &self.y {
+            buffer.put_u8(*elem);
+        }
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        1 + self.y.len()
+    }
+}
+impl Packet for Elem {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.elem.get_size());
+        self.elem.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Elem> for Bytes {
+    fn from(packet: Elem) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Elem> for Vec<u8> {
+    fn from(packet: Elem) -> Self {
+        packet.to_vec()
+    }
+}
+impl Elem {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = ElemData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(elem: Arc<ElemData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { elem })
+    }
+    pub fn get_x(&self) -> u8 {
+        self.elem.as_ref().x
+    }
+    pub fn get_y(&self) -> &Vec<u8> {
+        &self.elem.as_ref().y
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.elem.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.elem.get_size()
+    }
+}
+impl ElemBuilder {
+    pub fn build(self) -> Elem {
+        let elem = Arc::new(ElemData { x: self.x, y: self.y });
+        Elem::new(elem).unwrap()
+    }
+}
+impl From<ElemBuilder> for Elem {
+    fn from(builder: ElemBuilder) -> Elem {
+        builder.build().into()
+    }
+}
+impl From<Elem> for ElemBuilder {
+    fn from(packet: Elem) -> ElemBuilder {
+        ElemBuilder { x: packet.get_x(), y: packet.get_y().clone() }
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for ElemBuilder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let x: u8 = u.int_in_range(0..=0xff)?;
+        let y: Vec<u8> = {
+            let mut array = Vec::new();
+            while u.arbitrary()? {
+                let elem = u.int_in_range(0..=0xff)?;
+                array.push(elem);
+            }
+            array
+        };
+        Ok(ElemBuilder { x, y })
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Elem {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(u.arbitrary::<ElemBuilder>()?.build())
+    }
+}
+
+#[derive(Debug)]
+pub enum FooDataChild {
+    Bar(Arc<BarData>),
+    Payload(Bytes),
+    None,
+}
+impl FooDataChild {
+    fn get_total_size(&self) -> usize {
+        match self {
+            FooDataChild::Bar(value) => value.get_total_size(),
+            FooDataChild::Payload(bytes) => bytes.len(),
+            FooDataChild::None => 0,
+        }
+    }
+}
+#[derive(Debug, serde :: Serialize, serde :: Deserialize)]
+pub enum FooChild {
+    Bar(Bar),
+    Payload(Bytes),
+    None,
+}
+#[derive(Debug)]
+pub struct FooData {
+    a: u8,
+    b: Enum16,
+    c: Option<u32>,
+    d: Option<Enum16>,
+    e: Option<Elem>,
+    child: FooDataChild,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+#[serde(from = "FooBuilder", into = "FooBuilder")]
+pub struct Foo {
+    foo: Arc<FooData>,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+pub struct FooBuilder {
+    pub a: u8,
+    pub b: Enum16,
+    pub c: Option<u32>,
+    pub d: Option<Enum16>,
+    pub e: Option<Elem>,
+    pub payload: Option<Bytes>,
+}
+impl FooData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 3
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let chunk = bytes.get_mut().get_u8();
+        let a = (chunk & 0x1);
+        if bytes.get().remaining() < 2 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 2,
+                got: bytes.get().remaining(),
+            });
+        }
+        let b = Enum16::from_u16(bytes.get_mut().get_u16()).unwrap();
+        let c = if a == 1 {
+            if bytes.get().remaining() < 3 {
+                return Err(Error::InvalidLengthError {
+                    obj: "Foo".to_string(),
+                    wanted: 3,
+                    got: bytes.get().remaining(),
+                });
+            }
+            let c = bytes.get_mut().get_uint(3) as u32;
+            Some(c)
+        } else {
+            None
+        };
+        let d = if b == Enum16::B {
+            if bytes.get().remaining() < 2 {
+                return Err(Error::InvalidLengthError {
+                    obj: "Foo".to_string(),
+                    wanted: 2,
+                    got: bytes.get().remaining(),
+                });
+            }
+            let d = Enum16::from_u16(bytes.get_mut().get_u16()).ok_or_else(|| {
+                Error::InvalidEnumValueError {
+                    obj: "Foo".to_string(),
+                    field: String::new(),
+                    value: 0,
+                    type_: "Enum16".to_string(),
+                }
+            })?;
+            Some(d)
+        } else {
+            None
+        };
+        let e = if a == 0 {
+            let e = Elem::parse_inner(&mut bytes)?;
+            Some(e)
+        } else {
+            None
+        };
+        let payload = bytes.get();
+        bytes.get_mut().advance(payload.len());
+        let child = match (a) {
+            (1) => {
+                let mut cell = Cell::new(payload);
+                let child_data = BarData::parse(&mut cell)?;
+                if !cell.get().is_empty() {
+                    return Err(Error::InvalidPacketError);
+                }
+                FooDataChild::Bar(Arc::new(child_data))
+            }
+            _ if !payload.is_empty() => FooDataChild::Payload(Bytes::copy_from_slice(payload)),
+            _ => FooDataChild::None,
+        };
+        Ok(Self { a, b, c, d, e, child })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        if self.a > 0x1 {
+            panic!("Invalid value for {}::{}: {} > {}", "Foo", "a", self.a, 0x1);
+        }
+        buffer.put_u8(self.a);
+        buffer.put_u16(self.b.to_u16().unwrap());
+        match (&self.c, self.a == 1) {
+            (Some(c), true) => {
+                if *c > 0xffffff {
+                    panic!("Invalid value for {}::{}: {} > {}", "Foo", "c", c, 0xffffff);
+                }
+                buffer.put_uint(*c as u64, 3);
+            }
+            (None, false) => {}
+            _ => panic!("Invalid value for Foo::c: expected a value if and only if a = 1"),
+        }
+        match (&self.d, self.b == Enum16::B) {
+            (Some(d), true) => {
+                buffer.put_u16(d.to_u16().unwrap());
+            }
+            (None, false) => {}
+            _ => panic!("Invalid value for Foo::d: expected a value if and only if b = B"),
+        }
+        match (&self.e, self.a == 0) {
+            (Some(e), true) => {
+                e.write_to(buffer);
+            }
+            (None, false) => {}
+            _ => panic!("Invalid value for Foo::e: expected a value if and only if a = 0"),
+        }
+        match &self.child {
+            FooDataChild::Bar(child) => child.write_to(buffer),
+            FooDataChild::Payload(payload) => buffer.put_slice(payload),
+            FooDataChild::None => {}
+        }
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        3 + self.c.map_or(0, |_| 3)
+            + self.d.map_or(0, |_| 2)
+            + self.e.as_ref().map_or(0, |e| e.get_size())
+            + self.child.get_total_size()
+    }
+}
+impl Packet for Foo {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Foo> for Bytes {
+    fn from(packet: Foo) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Foo> for Vec<u8> {
+    fn from(packet: Foo) -> Self {
+        packet.to_vec()
+    }
+}
+impl Foo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { foo })
+    }
+    pub fn get_a(&self) -> u8 {
+        self.foo.as_ref().a
+    }
+    pub fn get_b(&self) -> Enum16 {
+        self.foo.as_ref().b
+    }
+    pub fn get_c(&self) -> Option<u32> {
+        self.foo.as_ref().c
+    }
+    pub fn get_d(&self) -> Option<Enum16> {
+        self.foo.as_ref().d
+    }
+    pub fn get_e(&self) -> &Option<Elem> {
+        &self.foo.as_ref().e
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.foo.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl FooBuilder {
+    pub fn build(self) -> Foo {
+        let foo = Arc::new(FooData {
+            a: self.a,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            child: match self.payload {
+                None => FooDataChild::None,
+                Some(bytes) => FooDataChild::Payload(bytes),
+            },
+        });
+        Foo::new(foo).unwrap()
+    }
+}
+impl From<FooBuilder> for Foo {
+    fn from(builder: FooBuilder) -> Foo {
+        builder.build().into()
+    }
+}
+impl From<Foo> for FooBuilder {
+    fn from(packet: Foo) -> FooBuilder {
+        FooBuilder {
+            a: packet.get_a(),
+            b: packet.get_b(),
+            c: packet.get_c(),
+            d: packet.get_d(),
+            e: packet.get_e().clone(),
+            payload: match &packet.foo.child {
+                FooDataChild::Bar(child) => {
+                    let mut buffer = BytesMut::with_capacity(child.get_total_size());
+                    child.write_to(&mut buffer);
+                    Some(buffer.freeze())
+                }
+                FooDataChild::Payload(bytes) => Some(bytes.clone()),
+                FooDataChild::None => None,
+            },
+        }
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for FooBuilder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let a: u8 = u.int_in_range(0..=0x1)?;
+        let b: Enum16 = u.arbitrary()?;
+        let c: Option<u32> = if a == 1 { Some(u.int_in_range(0..=0xffffff)?) } else { None };
+        let d: Option<Enum16> = if b == Enum16::B { Some(u.arbitrary()?) } else { None };
+        let e: Option<Elem> = if a == 0 { Some(u.arbitrary()?) } else { None };
+        let mut builder = FooBuilder { a, b, c, d, e, payload: None };
+        if matches!((builder.a), (1)) {
+            return Err(arbitrary::Error::IncorrectFormat);
+        }
+        let size = u.arbitrary_len::<u8>()?;
+        builder.payload = Some(Bytes::copy_from_slice(u.bytes(size)?));
+        Ok(builder)
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Foo {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        match u.int_in_range(0..=1usize)? {
+            0 => return Ok(u.arbitrary::<Bar>()?.into()),
+            _ => (),
+        }
+        Ok(u.arbitrary::<FooBuilder>()?.build())
+    }
+}
+
+#[derive(Debug)]
+pub struct BarData {
+    x: u8,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+#[serde(from = "BarBuilder", into = "BarBuilder")]
+pub struct Bar {
+    foo: Arc<FooData>,
+    bar: Arc<BarData>,
+}
+#[derive(Debug, Clone, serde :: Serialize, serde :: Deserialize)]
+pub struct BarBuilder {
+    pub b: Enum16,
+    pub c: Option<u32>,
+    pub d: Option<Enum16>,
+    pub e: Option<Elem>,
+    pub x: u8,
+}
+impl BarData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 1
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Bar".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let x = bytes.get_mut().get_u8();
+        Ok(Self { x })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        buffer.put_u8(self.x);
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        1
+    }
+}
+impl Packet for Bar {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Bar> for Bytes {
+    fn from(packet: Bar) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Bar> for Vec<u8> {
+    fn from(packet: Bar) -> Self {
+        packet.to_vec()
+    }
+}
+impl From<Bar> for Foo {
+    fn from(packet: Bar) -> Foo {
+        Foo::new(packet.foo).unwrap()
+    }
+}
+impl TryFrom<Foo> for Bar {
+    type Error = TryFromError;
+    fn try_from(packet: Foo) -> std::result::Result<Bar, TryFromError> {
+        Bar::new(packet.foo).map_err(TryFromError)
+    }
+}
+impl Bar {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        let bar = match &foo.child {
+            FooDataChild::Bar(value) => value.clone(),
+            _ => return Err("Could not parse data, wrong child type"),
+        };
+        Ok(Self { foo, bar })
+    }
+    pub fn get_a(&self) -> u8 {
+        self.foo.as_ref().a
+    }
+    pub fn get_b(&self) -> Enum16 {
+        self.foo.as_ref().b
+    }
+    pub fn get_c(&self) -> Option<u32> {
+        self.foo.as_ref().c
+    }
+    pub fn get_d(&self) -> Option<Enum16> {
+        self.foo.as_ref().d
+    }
+    pub fn get_e(&self) -> &Option<Elem> {
+        &self.foo.as_ref().e
+    }
+    pub fn get_x(&self) -> u8 {
+        self.bar.as_ref().x
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.bar.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl BarBuilder {
+    pub fn build(self) -> Bar {
+        let bar = Arc::new(BarData { x: self.x });
+        let foo = Arc::new(FooData {
+            a: 1,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            child: FooDataChild::Bar(bar),
+        });
+        Bar::new(foo).unwrap()
+    }
+}
+impl From<BarBuilder> for Foo {
+    fn from(builder: BarBuilder) -> Foo {
+        builder.build().into()
+    }
+}
+impl From<BarBuilder> for Bar {
+    fn from(builder: BarBuilder) -> Bar {
+        builder.build().into()
+    }
+}
+impl From<Bar> for BarBuilder {
+    fn from(packet: Bar) -> BarBuilder {
+        BarBuilder {
+            b: packet.get_b(),
+            c: packet.get_c(),
+            d: packet.get_d(),
+            e: packet.get_e().clone(),
+            x: packet.get_x(),
+        }
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for BarBuilder {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let b: Enum16 = u.arbitrary()?;
+        let x: u8 = u.int_in_range(0..=0xff)?;
+        let c: Option<u32> = Some(u.int_in_range(0..=0xffffff)?);
+        let d: Option<Enum16> = if b == Enum16::B { Some(u.arbitrary()?) } else { None };
+        let e: Option<Elem> = None;
+        Ok(BarBuilder { b, c, d, e, x })
+    }
+}
+impl<'a> arbitrary::Arbitrary<'a> for Bar {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(u.arbitrary::<BarBuilder>()?.build())
+    }
+}
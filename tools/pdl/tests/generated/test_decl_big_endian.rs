@@ -0,0 +1,347 @@
+// @generated rust packets from test
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::cell::Cell;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Packet parsing failed")]
+    InvalidPacketError,
+    #[error("{field} was {value:x}, which is not known")]
+    ConstraintOutOfBounds { field: String, value: u64 },
+    #[error("Got {actual:x}, expected {expected:x}")]
+    InvalidFixedValue { expected: u64, actual: u64 },
+    #[error("when parsing {obj} needed length of {wanted} but got {got}")]
+    InvalidLengthError { obj: String, wanted: usize, got: usize },
+    #[error("array size ({array} bytes) is not a multiple of the element size ({element} bytes)")]
+    InvalidArraySize { array: usize, element: usize },
+    #[error("Due to size restrictions a struct could not be parsed.")]
+    ImpossibleStructError,
+    #[error("when parsing field {obj}.{field}, {value} is not a valid {type_} value")]
+    InvalidEnumValueError { obj: String, field: String, value: u64, type_: String },
+    #[error("when parsing {obj}, checksum was {actual:x} but computed {expected:x}")]
+    InvalidChecksum { obj: String, expected: u64, actual: u64 },
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct TryFromError(&'static str);
+
+pub trait Packet {
+    fn to_bytes(self) -> Bytes;
+    fn to_vec(self) -> Vec<u8>;
+}
+
+#[derive(Debug)]
+pub enum FooDataChild {
+    Bar(Arc<BarData>),
+    Payload(Bytes),
+    None,
+}
+impl FooDataChild {
+    fn get_total_size(&self) -> usize {
+        match self {
+            FooDataChild::Bar(value) => value.get_total_size(),
+            FooDataChild::Payload(bytes) => bytes.len(),
+            FooDataChild::None => 0,
+        }
+    }
+}
+#[derive(Debug)]
+pub enum FooChild {
+    Bar(Bar),
+    Payload(Bytes),
+    None,
+}
+#[derive(Debug)]
+pub struct FooData {
+    a: u8,
+    child: FooDataChild,
+}
+#[derive(Debug, Clone)]
+pub struct Foo {
+    foo: Arc<FooData>,
+}
+#[derive(Debug, Clone)]
+pub struct FooBuilder {
+    pub a: u8,
+    pub payload: Option<Bytes>,
+}
+impl FooData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 1
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 1 {
+            return Err(Error::InvalidLengthError {
+                obj: "Foo".to_string(),
+                wanted: 1,
+                got: bytes.get().remaining(),
+            });
+        }
+        let a = bytes.get_mut().get_u8();
+        let payload = bytes.get();
+        bytes.get_mut().advance(payload.len());
+        let child = match (a) {
+            (1) => {
+                let mut cell = Cell::new(payload);
+                let child_data = BarData::parse(&mut cell)?;
+                if !cell.get().is_empty() {
+                    return Err(Error::InvalidPacketError);
+                }
+                FooDataChild::Bar(Arc::new(child_data))
+            }
+            _ if !payload.is_empty() => FooDataChild::Payload(Bytes::copy_from_slice(payload)),
+            _ => FooDataChild::None,
+        };
+        Ok(Self { a, child })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        buffer.put_u8(self.a);
+        match &self.child {
+            FooDataChild::Bar(child) => child.write_to(buffer),
+            FooDataChild::Payload(payload) => buffer.put_slice(payload),
+            FooDataChild::None => {}
+        }
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        1 + self.child.get_total_size()
+    }
+}
+impl Packet for Foo {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Foo> for Bytes {
+    fn from(packet: Foo) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Foo> for Vec<u8> {
+    fn from(packet: Foo) -> Self {
+        packet.to_vec()
+    }
+}
+impl Foo {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        Ok(Self { foo })
+    }
+    pub fn get_a(&self) -> u8 {
+        self.foo.as_ref().a
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.foo.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl FooBuilder {
+    pub fn build(self) -> Foo {
+        let foo = Arc::new(FooData {
+            a: self.a,
+            child: match self.payload {
+                None => FooDataChild::None,
+                Some(bytes) => FooDataChild::Payload(bytes),
+            },
+        });
+        Foo::new(foo).unwrap()
+    }
+}
+impl From<FooBuilder> for Foo {
+    fn from(builder: FooBuilder) -> Foo {
+        builder.build().into()
+    }
+}
+
+#[derive(Debug)]
+pub struct BarData {
+    x: u16,
+}
+#[derive(Debug, Clone)]
+pub struct Bar {
+    foo: Arc<FooData>,
+    bar: Arc<BarData>,
+}
+#[derive(Debug, Clone)]
+pub struct BarBuilder {
+    pub x: u16,
+}
+impl BarData {
+    fn conforms(bytes: &[u8]) -> bool {
+        bytes.len() >= 2
+    }
+    fn parse(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        if bytes.get().remaining() < 2 {
+            return Err(Error::InvalidLengthError {
+                obj: "Bar".to_string(),
+                wanted: 2,
+                got: bytes.get().remaining(),
+            });
+        }
+        let x = bytes.get_mut().get_u16();
+        Ok(Self { x })
+    }
+    fn write_to(&self, buffer: &mut BytesMut) {
+        buffer.put_u16(self.x);
+    }
+    fn get_total_size(&self) -> usize {
+        self.get_size()
+    }
+    fn get_size(&self) -> usize {
+        2
+    }
+}
+impl Packet for Bar {
+    fn to_bytes(self) -> Bytes {
+        let mut buffer = BytesMut::with_capacity(self.foo.get_size());
+        self.foo.write_to(&mut buffer);
+        buffer.freeze()
+    }
+    fn to_vec(self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+}
+impl From<Bar> for Bytes {
+    fn from(packet: Bar) -> Self {
+        packet.to_bytes()
+    }
+}
+impl From<Bar> for Vec<u8> {
+    fn from(packet: Bar) -> Self {
+        packet.to_vec()
+    }
+}
+impl From<Bar> for Foo {
+    fn from(packet: Bar) -> Foo {
+        Foo::new(packet.foo).unwrap()
+    }
+}
+impl TryFrom<Foo> for Bar {
+    type Error = TryFromError;
+    fn try_from(packet: Foo) -> std::result::Result<Bar, TryFromError> {
+        Bar::new(packet.foo).map_err(TryFromError)
+    }
+}
+impl Bar {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cell = Cell::new(bytes);
+        let packet = Self::parse_inner(&mut cell)?;
+        if !cell.get().is_empty() {
+            return Err(Error::InvalidPacketError);
+        }
+        Ok(packet)
+    }
+    pub(crate) fn parse_inner(mut bytes: &mut Cell<&[u8]>) -> Result<Self> {
+        let data = FooData::parse(&mut bytes)?;
+        Ok(Self::new(Arc::new(data)).unwrap())
+    }
+    fn new(foo: Arc<FooData>) -> std::result::Result<Self, &'static str> {
+        let bar = match &foo.child {
+            FooDataChild::Bar(value) => value.clone(),
+            _ => return Err("Could not parse data, wrong child type"),
+        };
+        Ok(Self { foo, bar })
+    }
+    pub fn get_a(&self) -> u8 {
+        self.foo.as_ref().a
+    }
+    pub fn get_x(&self) -> u16 {
+        self.bar.as_ref().x
+    }
+    pub(crate) fn write_to(&self, buffer: &mut BytesMut) {
+        self.bar.write_to(buffer)
+    }
+    pub fn get_size(&self) -> usize {
+        self.foo.get_size()
+    }
+}
+impl BarBuilder {
+    pub fn build(self) -> Bar {
+        let bar = Arc::new(BarData { x: self.x });
+        let foo = Arc::new(FooData { a: 1, child: FooDataChild::Bar(bar) });
+        Bar::new(foo).unwrap()
+    }
+}
+impl From<BarBuilder> for Foo {
+    fn from(builder: BarBuilder) -> Foo {
+        builder.build().into()
+    }
+}
+impl From<BarBuilder> for Bar {
+    fn from(builder: BarBuilder) -> Bar {
+        builder.build().into()
+    }
+}
+
+#[test]
+fn test_foo_0() {
+    let input: &[u8] = b"\0";
+    let packet = match Foo::parse(input) {
+        Ok(packet) => packet,
+        Err(err) => panic!("{}: could not parse {}: {}", "test:13:15", "Foo", err),
+    };
+    assert_eq!(packet.to_vec(), input, "{}: {} does not round-trip", "test:13:15", "Foo");
+}
+#[test]
+fn test_foo_1() {
+    let input: &[u8] = b"\x02\x03\x04";
+    let packet = match Foo::parse(input) {
+        Ok(packet) => packet,
+        Err(err) => panic!("{}: could not parse {}: {}", "test:14:15", "Foo", err),
+    };
+    assert_eq!(packet.to_vec(), input, "{}: {} does not round-trip", "test:14:15", "Foo");
+}
+
+#[test]
+fn test_bar_0() {
+    let input: &[u8] = b"\x01\x02\x03";
+    let packet = match Foo::parse(input) {
+        Ok(packet) => packet,
+        Err(err) => panic!("{}: could not parse {}: {}", "test:18:15", "Bar", err),
+    };
+    let packet = match Bar::try_from(packet) {
+        Ok(packet) => packet,
+        Err(err) => panic!("{}: could not parse {}: {}", "test:18:15", "Bar", err),
+    };
+    assert_eq!(packet.to_vec(), input, "{}: {} does not round-trip", "test:18:15", "Bar");
+}
+
+#[test]
+fn test_foo_2() {
+    let input: &[u8] = b"\x05\x06";
+    let packet = match Foo::parse(input) {
+        Ok(packet) => packet,
+        Err(err) => panic!("{}: could not parse {}: {}", "test:22:15", "Foo", err),
+    };
+    assert_eq!(packet.to_vec(), input, "{}: {} does not round-trip", "test:22:15", "Foo");
+}